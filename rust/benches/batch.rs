@@ -0,0 +1,34 @@
+//! Single-threaded throughput of the free functions, `BcidGenerator::next`
+//! and `BcidGenerator::next_batch`
+//!
+//! Run with `cargo bench --bench batch`.
+
+use std::time::Instant;
+
+use bcid::{generate_identifier, BcidGenerator};
+
+const COUNT: usize = 500_000;
+
+fn report(name: &str, started: Instant) {
+    let elapsed = started.elapsed();
+    println!("{:<24} {} IDs in {:.2?} ({:.2} million IDs/sec)", name, COUNT, elapsed, COUNT as f64 / elapsed.as_secs_f64() / 1e6);
+}
+
+fn main() {
+    let started = Instant::now();
+    for _ in 0..COUNT {
+        std::hint::black_box(generate_identifier("BNCH", 1, None, false).expect("valid prefix"));
+    }
+    report("generate_identifier", started);
+
+    let mut generator = BcidGenerator::new("BNCH", 1).expect("valid prefix");
+    let started = Instant::now();
+    for _ in 0..COUNT {
+        std::hint::black_box(generator.next());
+    }
+    report("BcidGenerator::next", started);
+
+    let started = Instant::now();
+    std::hint::black_box(generator.next_batch(COUNT));
+    report("BcidGenerator::next_batch", started);
+}
@@ -0,0 +1,46 @@
+//! Throughput of `SharedGenerator` under contention
+//!
+//! Run with `cargo bench --bench shared`; set `BCID_BENCH_THREADS` to change
+//! the number of threads (default: available parallelism).
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use bcid::SharedGenerator;
+
+const IDS_PER_THREAD: usize = 1_000_000;
+
+fn main() {
+    let threads = std::env::var("BCID_BENCH_THREADS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
+    let generator = Arc::new(SharedGenerator::new("BNCH", 1).expect("valid prefix"));
+
+    let started = Instant::now();
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let generator = Arc::clone(&generator);
+            thread::spawn(move || {
+                let mut buf = [0u8; 32];
+                for _ in 0..IDS_PER_THREAD {
+                    std::hint::black_box(generator.next_into(&mut buf));
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().expect("benchmark thread panicked");
+    }
+    let elapsed = started.elapsed();
+
+    let total = threads * IDS_PER_THREAD;
+    println!(
+        "{} threads: {} IDs in {:.2?} ({:.2} million IDs/sec)",
+        threads,
+        total,
+        elapsed,
+        total as f64 / elapsed.as_secs_f64() / 1e6
+    );
+}
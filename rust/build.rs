@@ -0,0 +1,16 @@
+//! Compiles proto/bcid.proto into the gRPC server of `bcid serve`, with the
+//! `grpc` feature
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    {
+        // A vendored protoc, so that building needs no system protobuf compiler
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("protoc is vendored for this platform");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/bcid.proto"], &["proto"])
+            .expect("proto/bcid.proto compiles");
+    }
+}
@@ -0,0 +1,96 @@
+#ifndef BCID_H
+#define BCID_H
+
+/* Generated with cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Components of an identifier, filled by `bcid_decode`
+ */
+typedef struct BcidDecoded {
+  /**
+   * NUL-terminated prefix
+   */
+  char prefix[9];
+  /**
+   * Whether the identifier is random rather than chronological
+   */
+  bool is_random;
+  /**
+   * Format version number (1-5)
+   */
+  uint8_t format;
+  /**
+   * Packed `YYYYMMDDHHmmSScc` timestamp, 0 for random identifiers
+   */
+  uint64_t timestamp;
+  /**
+   * Microseconds since the Unix epoch, 0 for random identifiers
+   */
+  int64_t unix_micros;
+  uint16_t machine_id;
+  /**
+   * 16-bit random value, -1 for random identifiers
+   */
+  int32_t random;
+  /**
+   * NUL-terminated random data following the machine ID, empty for
+   * chronological identifiers
+   */
+  char random_part[65];
+} BcidDecoded;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Generate an identifier with `prefix` and `machine_id`, at `datetime`
+ * (see [`parse_user_datetime`](crate::parse_user_datetime)) unless it is
+ * `NULL`, or fully random if `is_random`
+ *
+ * Returns `NULL` on failure.
+ *
+ * # Safety
+ *
+ * `prefix` and `datetime` must be `NULL` or NUL-terminated strings, and
+ * `error` `NULL` or valid for writes.
+ */
+char *bcid_generate(const char *prefix,
+                    uint16_t machine_id,
+                    const char *datetime,
+                    bool is_random,
+                    char **error);
+
+/**
+ * Decode `identifier` into `*out`
+ *
+ * Returns 0 on success and -1 on failure, leaving `*out` untouched.
+ *
+ * # Safety
+ *
+ * `identifier` must be `NULL` or a NUL-terminated string, `out` `NULL` or
+ * valid for writes, and `error` `NULL` or valid for writes.
+ */
+int32_t bcid_decode(const char *identifier, BcidDecoded *out, char **error);
+
+/**
+ * Release a string returned by this library; `NULL` is ignored
+ *
+ * # Safety
+ *
+ * `string` must be `NULL` or a string returned by this library that has
+ * not been released yet.
+ */
+void bcid_free(char *string);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BCID_H */
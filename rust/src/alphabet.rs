@@ -0,0 +1,158 @@
+use core::fmt;
+
+use crate::base62::{digit_table, DigitTable, SORTABLE_BASE62, SORTABLE_BASE62_DIGITS};
+
+/// Crockford base32 digits (0-9 and A-Z without I, L, O and U)
+const CROCKFORD32: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Base58 digits (base62 without 0, O, I and l), as used by Bitcoin
+const BASE58: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Base62 digits without vowels
+const NO_VOWELS: &[u8] = b"0123456789BCDFGHJKLMNPQRSTVWXYZbcdfghjklmnpqrstvwxyz";
+
+static CROCKFORD32_DIGITS: DigitTable = crockford_table();
+static BASE58_DIGITS: DigitTable = digit_table(BASE58);
+static NO_VOWELS_DIGITS: DigitTable = digit_table(NO_VOWELS);
+
+/// Largest packed `YYYYMMDDHHmmSScc` timestamp a fixed-width field must hold
+const MAX_TIMESTAMP: u64 = 9999999999999999;
+
+/// Digits that encode the fields and padding of an identifier
+///
+/// Identifiers in an alphabet other than [`Alphabet::Base62`] always use the
+/// fixed-width [`Format::V2`](crate::Format::V2) (or, with a
+/// [`Precision`](crate::Precision), [`Format::V3`](crate::Format::V3)) layout, with field widths
+/// scaled to the alphabet's radix, and are always chronological. Every
+/// alphabet lists its digits in ASCII order, so such identifiers still sort
+/// chronologically. Nothing in an identifier records its alphabet: decode
+/// with [`Bcid::parse_with`](crate::Bcid::parse_with) and the alphabet it
+/// was generated with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum Alphabet {
+    /// [`BASE62`](crate::BASE62) for v1 identifiers and [`SORTABLE_BASE62`]
+    /// for v2 identifiers
+    #[default]
+    Base62,
+    /// Crockford base32 (0-9 and A-Z without I, L, O and U), decoded
+    /// case-insensitively with I and L read as 1 and O as 0
+    Crockford32,
+    /// Base58 (base62 without the easily confused 0, O, I and l)
+    Base58,
+    /// Base62 without vowels, so random characters cannot spell words
+    NoVowels,
+}
+
+impl Alphabet {
+    /// Name used on the command line: `base62`, `crockford32`, `base58` or
+    /// `novowels`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Alphabet::Base62 => "base62",
+            Alphabet::Crockford32 => "crockford32",
+            Alphabet::Base58 => "base58",
+            Alphabet::NoVowels => "novowels",
+        }
+    }
+
+    /// The alphabet called `name` (see [`Alphabet::name`])
+    pub fn from_name(name: &str) -> Option<Alphabet> {
+        match name {
+            "base62" => Some(Alphabet::Base62),
+            "crockford32" | "crockford" => Some(Alphabet::Crockford32),
+            "base58" => Some(Alphabet::Base58),
+            "novowels" => Some(Alphabet::NoVowels),
+            _ => None,
+        }
+    }
+
+    /// Digits of the fixed-width layout, in ascending order
+    pub fn chars(&self) -> &'static [u8] {
+        match self {
+            Alphabet::Base62 => SORTABLE_BASE62,
+            Alphabet::Crockford32 => CROCKFORD32,
+            Alphabet::Base58 => BASE58,
+            Alphabet::NoVowels => NO_VOWELS,
+        }
+    }
+
+    /// Number of digits
+    pub fn radix(&self) -> u64 {
+        self.chars().len() as u64
+    }
+
+    /// Whether lowercase and uppercase letters decode to the same digit
+    pub fn is_case_insensitive(&self) -> bool {
+        *self == Alphabet::Crockford32
+    }
+
+    /// Digit values of [`Alphabet::chars`], including any aliases
+    pub(crate) fn digits(&self) -> &'static DigitTable {
+        match self {
+            Alphabet::Base62 => &SORTABLE_BASE62_DIGITS,
+            Alphabet::Crockford32 => &CROCKFORD32_DIGITS,
+            Alphabet::Base58 => &BASE58_DIGITS,
+            Alphabet::NoVowels => &NO_VOWELS_DIGITS,
+        }
+    }
+
+    /// Width of the fixed-width timestamp field
+    pub(crate) fn timestamp_width(&self) -> usize {
+        self.width(MAX_TIMESTAMP)
+    }
+
+    /// Width of the fixed-width machine ID and random value fields
+    pub(crate) fn field_width(&self) -> usize {
+        self.width(u16::MAX as u64)
+    }
+
+    /// Digits needed to write every number up to `max`
+    pub(crate) fn width(&self, max: u64) -> usize {
+        let mut width = 1;
+        let mut limit = self.radix();
+        while limit <= max {
+            limit *= self.radix();
+            width += 1;
+        }
+        width
+    }
+
+    /// Digits needed to write every 128-bit number
+    pub(crate) fn wide_width(&self) -> usize {
+        let radix = self.radix() as u128;
+        let mut width = 1;
+        let mut limit = radix;
+        // The limit overflows once it passes u128::MAX
+        while let Some(next) = limit.checked_mul(radix) {
+            limit = next;
+            width += 1;
+        }
+        width + 1
+    }
+}
+
+impl fmt::Display for Alphabet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Crockford digit values, accepting lowercase letters and the I, L and O
+/// aliases
+const fn crockford_table() -> DigitTable {
+    let mut table = digit_table(CROCKFORD32);
+    let mut digit = 10;
+    while digit < CROCKFORD32.len() {
+        table[CROCKFORD32[digit].to_ascii_lowercase() as usize] = digit as u8;
+        digit += 1;
+    }
+    let aliases = [(b'I', 1), (b'L', 1), (b'O', 0)];
+    let mut i = 0;
+    while i < aliases.len() {
+        table[aliases[i].0 as usize] = aliases[i].1;
+        table[aliases[i].0.to_ascii_lowercase() as usize] = aliases[i].1;
+        i += 1;
+    }
+    table
+}
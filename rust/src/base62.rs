@@ -0,0 +1,128 @@
+use alloc::string::{String, ToString};
+
+use crate::BcidError;
+
+/// Base62 alphabet (a-zA-Z0-9)
+pub const BASE62: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// Base62 alphabet in ASCII order (0-9A-Za-z)
+///
+/// Fixed-width numbers encoded with this alphabet sort lexicographically in
+/// numeric order, which is what makes v2 identifiers sortable.
+pub const SORTABLE_BASE62: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Reverse lookup from a byte to its digit value in an alphabet, with
+/// [`INVALID_DIGIT`] for bytes outside it (including all non-ASCII bytes)
+pub(crate) type DigitTable = [u8; 256];
+
+/// Marks bytes outside the alphabet in a [`DigitTable`]
+pub(crate) const INVALID_DIGIT: u8 = 0xff;
+
+/// Digit values of the [`BASE62`] alphabet
+pub(crate) static BASE62_DIGITS: DigitTable = digit_table(BASE62);
+
+/// Digit values of the [`SORTABLE_BASE62`] alphabet
+pub(crate) static SORTABLE_BASE62_DIGITS: DigitTable = digit_table(SORTABLE_BASE62);
+
+pub(crate) const fn digit_table(alphabet: &[u8]) -> DigitTable {
+    let mut table = [INVALID_DIGIT; 256];
+    let mut digit = 0;
+    while digit < alphabet.len() {
+        table[alphabet[digit] as usize] = digit as u8;
+        digit += 1;
+    }
+    table
+}
+
+/// Convert a number to base62 string
+///
+/// Uses the [`BASE62`] alphabet, most significant digit first.
+pub fn base62_encode(num: u64) -> String {
+    let mut digits = [0u8; 11];
+    let len = encode_into(num, &mut digits);
+    String::from_utf8_lossy(&digits[..len]).to_string()
+}
+
+/// Write `num` as [`BASE62`] digits, most significant first, to the start of
+/// `out`, returning the number of digits (1 to 11)
+///
+/// Panics if `out` is too short.
+pub(crate) fn encode_into(mut num: u64, out: &mut [u8]) -> usize {
+    let mut width = 1;
+    while width < 11 && num >= 62u64.pow(width as u32) {
+        width += 1;
+    }
+    for digit in out[..width].iter_mut().rev() {
+        *digit = BASE62[(num % 62) as usize];
+        num /= 62;
+    }
+    width
+}
+
+/// Convert a base62 string to number
+///
+/// Fails with [`BcidError::InvalidCharacter`] (position relative to `s`) on
+/// the first character outside the [`BASE62`] alphabet, and with
+/// [`BcidError::ValueOutOfRange`] if the number does not fit in a `u64`.
+pub fn base62_decode(s: &str) -> Result<u64, BcidError> {
+    decode_with(&BASE62_DIGITS, 62, s)
+}
+
+/// Encode a number as exactly `out.len()` characters of `alphabet`,
+/// left-padded with the alphabet's zero digit
+///
+/// Digits beyond the width are truncated, so callers must check the range.
+pub(crate) fn encode_fixed(alphabet: &[u8], mut num: u64, out: &mut [u8]) {
+    let base = alphabet.len() as u64;
+    for digit in out.iter_mut().rev() {
+        *digit = alphabet[(num % base) as usize];
+        num /= base;
+    }
+}
+
+/// [`encode_fixed`] for a 128-bit number
+pub(crate) fn encode_fixed_wide(alphabet: &[u8], mut num: u128, out: &mut [u8]) {
+    let base = alphabet.len() as u128;
+    for digit in out.iter_mut().rev() {
+        *digit = alphabet[(num % base) as usize];
+        num /= base;
+    }
+}
+
+/// [`decode_with`] for a number that must fit in 128 bits
+///
+/// The value of [`BcidError::ValueOutOfRange`] saturates at `u64::MAX`.
+pub(crate) fn decode_wide_with(digits: &DigitTable, radix: u64, s: &str) -> Result<u128, BcidError> {
+    let mut num = 0u128;
+    for (position, &byte) in s.as_bytes().iter().enumerate() {
+        let digit = digits[byte as usize];
+        if digit == INVALID_DIGIT {
+            let character = s[position..].chars().next().unwrap_or_default();
+            return Err(BcidError::InvalidCharacter { character, position });
+        }
+        num = num
+            .checked_mul(radix as u128)
+            .and_then(|num| num.checked_add(digit as u128))
+            .ok_or(BcidError::ValueOutOfRange { field: "base62 number", value: u64::try_from(num).unwrap_or(u64::MAX) })?;
+    }
+    Ok(num)
+}
+
+/// Decode a string of base-`radix` digits valued by `digits`, see
+/// [`base62_decode`]
+pub(crate) fn decode_with(digits: &DigitTable, radix: u64, s: &str) -> Result<u64, BcidError> {
+    let mut num = 0u64;
+    for (position, &byte) in s.as_bytes().iter().enumerate() {
+        let digit = digits[byte as usize];
+        if digit == INVALID_DIGIT {
+            // Every earlier byte was ASCII, so this is a character boundary
+            let character = s[position..].chars().next().unwrap_or_default();
+            return Err(BcidError::InvalidCharacter { character, position });
+        }
+        num = num
+            .checked_mul(radix)
+            .and_then(|num| num.checked_add(digit as u64))
+            .ok_or(BcidError::ValueOutOfRange { field: "base62 number", value: num })?;
+    }
+    Ok(num)
+}
@@ -0,0 +1,78 @@
+//! Optional check digit in the final character of an identifier
+//!
+//! The check digit is computed with the Luhn mod N algorithm (N = 62) over
+//! every character but the last and replaces the last one, which is always
+//! random padding or random data. It detects every substitution of one
+//! base62 character for another, which covers the encoded fields and
+//! alphanumeric prefixes, and most transpositions of adjacent characters.
+//!
+//! Characters are mapped to code points by their position in [`BASE62`];
+//! prefix characters outside the alphabet use their byte value modulo 62.
+//! Those share a code point with some other character (`!` with `H`, for
+//! instance), and substituting one for the other goes undetected: a single
+//! base62 check digit cannot tell apart the 93 characters a prefix may hold.
+//! The separator is left out, so the packed and separated forms of an
+//! identifier carry the same check digit.
+//! Whether an identifier carries a check digit is not encoded in it, so
+//! generation and verification have to agree on the mode.
+
+use alloc::string::String;
+
+use crate::{split_prefix, BcidError, BASE62};
+
+/// Size of the check digit alphabet
+const N: u32 = 62;
+
+/// Replace the final character of an identifier with its check digit
+///
+/// Fails if the identifier is not between [`MIN_LENGTH`](crate::MIN_LENGTH)
+/// and [`MAX_LENGTH`](crate::MAX_LENGTH) characters long.
+pub fn append_check_digit(identifier: &str) -> Result<String, BcidError> {
+    let (prefix, fields) = body(identifier)?;
+    let mut checked = String::with_capacity(identifier.len());
+    checked.push_str(&identifier[..identifier.len() - 1]);
+    checked.push(BASE62[check_value(prefix, fields) as usize] as char);
+    Ok(checked)
+}
+
+/// Check that the final character of an identifier is its check digit
+///
+/// Fails with [`BcidError::InvalidCheckDigit`] on a mismatch, or if the
+/// identifier's length is out of range.
+pub fn verify_check_digit(identifier: &str) -> Result<(), BcidError> {
+    let (prefix, fields) = body(identifier)?;
+    let expected = BASE62[check_value(prefix, fields) as usize] as char;
+    let found = identifier[identifier.len() - 1..].chars().next().unwrap_or_default();
+    if found != expected {
+        return Err(BcidError::InvalidCheckDigit { expected, found });
+    }
+    Ok(())
+}
+
+/// The prefix and the encoded fields covered by the check digit
+fn body(identifier: &str) -> Result<(&str, &str), BcidError> {
+    if !identifier.is_ascii() {
+        return Err(BcidError::InvalidIdentifierLength(identifier.chars().count()));
+    }
+    let (prefix, fields) = split_prefix(identifier)?;
+    Ok((prefix, &fields[..fields.len() - 1]))
+}
+
+/// Luhn mod N check value of `prefix` followed by `fields`
+fn check_value(prefix: &str, fields: &str) -> u32 {
+    let mut factor = 2;
+    let mut sum = 0;
+    for byte in prefix.bytes().chain(fields.bytes()).rev() {
+        let addend = factor * code_point(byte);
+        sum += addend / N + addend % N;
+        factor = if factor == 2 { 1 } else { 2 };
+    }
+    (N - sum % N) % N
+}
+
+fn code_point(byte: u8) -> u32 {
+    match BASE62.iter().position(|&c| c == byte) {
+        Some(position) => position as u32,
+        None => byte as u32 % N,
+    }
+}
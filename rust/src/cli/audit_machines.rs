@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::io::{self, BufWriter, Write};
+use std::process;
+use std::time::Duration;
+
+use bcid::{decode_identifier, json, IdentifierType};
+use chrono::{DateTime, Utc};
+use clap::Args;
+
+use super::bulk::identifier_lines;
+use super::{parse_duration, EXIT_FAILURE};
+
+/// Find machine IDs shared by several hosts in a file of identifiers
+///
+/// Reads one chronological identifier per line from FILE (`-` for stdin), in
+/// the order they were generated or stored, and reports machine IDs that look
+/// shared by several hosts:
+///
+///   regressions  an identifier's timestamp is earlier than one read before it
+///                with the same machine ID; one host's generator never goes
+///                back in time, but the interleaved output of hosts whose
+///                clocks differ does
+///   collisions   distinct identifiers with the same prefix, timestamp,
+///                machine ID, sequence and random value, differing only in
+///                their padding
+///
+/// Machine ID 1 is the default, so hosts started without a machine ID share it.
+/// Exits with status 1 if any machine ID looks shared.
+#[derive(Args)]
+#[command(verbatim_doc_comment, after_help = EXAMPLES)]
+pub struct AuditMachinesArgs {
+    /// Ignore timestamps up to this far back, for identifiers stored slightly
+    /// out of order
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "1s")]
+    tolerance: Duration,
+    /// Also report machine IDs that look fine
+    #[arg(long)]
+    all: bool,
+    /// Print the report as JSON Lines
+    #[arg(long)]
+    json: bool,
+    /// File of identifiers, `-` for stdin
+    #[arg(value_name = "FILE")]
+    input: String,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid audit-machines ids.txt
+  psql -Atc 'SELECT id FROM events ORDER BY seq' | bcid audit-machines --tolerance 0s -";
+
+/// Fields that one generator never repeats: prefix, timestamp, sequence and random value
+type Tuple = (String, u64, Option<u32>, Option<u16>);
+
+/// What was seen of one machine ID
+#[derive(Default)]
+struct Machine {
+    count: usize,
+    /// Latest timestamp so far, and its line
+    latest: Option<(DateTime<Utc>, usize)>,
+    regressions: usize,
+    /// Largest step back, the line it was read on and the line it went back from
+    largest_regression: Option<(chrono::Duration, usize, usize)>,
+    /// First identifier and line of each tuple
+    tuples: HashMap<Tuple, (String, usize)>,
+    /// Lines of the first identifier and a later one with the same tuple
+    collisions: Vec<(usize, usize)>,
+}
+
+impl Machine {
+    fn add(&mut self, line: usize, identifier: String, tuple: Tuple, datetime: DateTime<Utc>, tolerance: chrono::Duration) {
+        self.count += 1;
+        match self.latest {
+            Some((latest, latest_line)) if datetime < latest => {
+                let step = latest - datetime;
+                if step > tolerance {
+                    self.regressions += 1;
+                    if self.largest_regression.is_none_or(|(largest, _, _)| step > largest) {
+                        self.largest_regression = Some((step, line, latest_line));
+                    }
+                }
+            }
+            _ => self.latest = Some((datetime, line)),
+        }
+        match self.tuples.get(&tuple) {
+            // The same identifier again is a duplicate row, which `dedupe` reports
+            Some((first, _)) if *first == identifier => {}
+            Some((_, first_line)) => self.collisions.push((*first_line, line)),
+            None => {
+                self.tuples.insert(tuple, (identifier, line));
+            }
+        }
+    }
+
+    fn is_shared(&self) -> bool {
+        self.regressions > 0 || !self.collisions.is_empty()
+    }
+}
+
+pub fn run(args: AuditMachinesArgs) {
+    let (input, with_all, is_json) = (args.input, args.all, args.json);
+    let tolerance = chrono::Duration::from_std(args.tolerance).unwrap_or(chrono::Duration::MAX);
+
+    let mut machines: HashMap<u16, Machine> = HashMap::new();
+    // Lines that are not chronological identifiers
+    let mut skipped = 0;
+    for (line, identifier) in identifier_lines(&input) {
+        let decoded = match decode_identifier(&identifier) {
+            Ok(decoded) if decoded.identifier_type() == IdentifierType::Chronological => decoded,
+            _ => {
+                skipped += 1;
+                continue;
+            }
+        };
+        let (Some(timestamp), Some(datetime)) = (decoded.timestamp(), decoded.datetime()) else {
+            skipped += 1;
+            continue;
+        };
+        let tuple = (decoded.prefix().to_string(), timestamp, decoded.sequence(), decoded.random());
+        let raw = decoded.as_str().to_string();
+        machines.entry(decoded.machine_id()).or_default().add(line, raw, tuple, datetime, tolerance);
+    }
+
+    let mut machine_ids: Vec<&u16> = machines.keys().collect();
+    machine_ids.sort();
+    let shared = machines.values().filter(|machine| machine.is_shared()).count();
+    let mut records = Vec::new();
+    for id in machine_ids {
+        let machine = &machines[id];
+        if !with_all && !machine.is_shared() {
+            continue;
+        }
+        records.push(if is_json { json_record(*id, machine) } else { text_record(*id, machine) });
+    }
+    if !is_json {
+        records.push(format!(
+            "{} machine IDs, {} look shared by several hosts ({} lines skipped: not chronological identifiers)",
+            machines.len(),
+            shared,
+            skipped
+        ));
+    }
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    for record in records {
+        if writeln!(out, "{}", record).is_err() {
+            // Downstream closed the pipe (e.g. `| head`)
+            process::exit(0);
+        }
+    }
+    let _ = out.flush();
+    drop(out);
+
+    if shared > 0 {
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+fn text_record(id: u16, machine: &Machine) -> String {
+    let mut record = format!("Machine ID {}{}: {} identifiers", id, if id == 1 { " (default)" } else { "" }, machine.count);
+    if let Some((step, line, from)) = machine.largest_regression {
+        record.push_str(&format!(
+            ", {} regressions (largest {} back, line {} after line {})",
+            machine.regressions,
+            format_step(step),
+            line,
+            from
+        ));
+    }
+    if let Some((first, line)) = machine.collisions.first() {
+        record.push_str(&format!(", {} collisions (e.g. line {} with line {})", machine.collisions.len(), line, first));
+    }
+    if machine.is_shared() {
+        record.push_str(" - likely shared by several hosts");
+    }
+    record
+}
+
+fn json_record(id: u16, machine: &Machine) -> String {
+    let collisions: Vec<String> =
+        machine.collisions.iter().map(|(first, line)| format!("[{},{}]", first, line)).collect();
+    json::Object::new()
+        .number("machine_id", Some(id))
+        .number("count", Some(machine.count))
+        .number("regressions", Some(machine.regressions))
+        .number("largest_regression_ms", machine.largest_regression.map(|(step, _, _)| step.num_milliseconds()))
+        .number("largest_regression_line", machine.largest_regression.map(|(_, line, _)| line))
+        .raw("collisions", &format!("[{}]", collisions.join(",")))
+        .boolean("shared", machine.is_shared())
+        .finish()
+}
+
+/// A backward step in the largest unit that keeps it readable
+fn format_step(step: chrono::Duration) -> String {
+    match step.num_milliseconds() {
+        ms @ 0..=999 => format!("{}ms", ms),
+        ms @ 1_000..=59_999 => format!("{:.1}s", ms as f64 / 1_000.0),
+        ms @ 60_000..=3_599_999 => format!("{:.1}m", ms as f64 / 60_000.0),
+        ms => format!("{:.1}h", ms as f64 / 3_600_000.0),
+    }
+}
@@ -0,0 +1,116 @@
+use std::hint::black_box;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bcid::{Bcid, BcidGenerator};
+use clap::Args;
+
+use super::bulk::default_jobs;
+use super::{exit_with_error, parse_nonzero_duration, parse_positive};
+
+/// Measure generation and decoding throughput
+///
+/// Measures how many identifiers per second this host generates (chronological,
+/// random and monotonic) and decodes, each workload running on every thread for
+/// DURATION with a generator per thread.
+#[derive(Args)]
+#[command(after_help = EXAMPLES)]
+pub struct BenchArgs {
+    /// How long to run each workload, e.g. 5s or 500ms
+    #[arg(long, value_parser = parse_nonzero_duration, default_value = "1s")]
+    duration: Duration,
+    /// Threads to run each workload on (default: number of CPUs)
+    #[arg(long, value_name = "N", value_parser = parse_positive::<usize>)]
+    threads: Option<usize>,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid bench
+  bcid bench --duration 5s --threads 8";
+
+/// Operations between checks of the clock
+const OPS_PER_CHECK: u64 = 256;
+
+/// Identifiers each thread decodes in turn
+const DECODE_POOL: usize = 1024;
+
+/// Measured workloads
+#[derive(Debug, Clone, Copy)]
+enum Workload {
+    Chronological,
+    Random,
+    Monotonic,
+    Decode,
+}
+
+impl Workload {
+    fn name(&self) -> &'static str {
+        match self {
+            Workload::Chronological => "generate (chronological)",
+            Workload::Random => "generate (random)",
+            Workload::Monotonic => "generate (monotonic)",
+            Workload::Decode => "decode",
+        }
+    }
+}
+
+pub fn run(args: BenchArgs) {
+    let duration = args.duration;
+    let threads = args.threads.unwrap_or_else(default_jobs);
+    let plural = if threads == 1 { "" } else { "s" };
+    println!("{} thread{}, {:.2?} per workload", threads, plural, duration);
+    for workload in [Workload::Chronological, Workload::Random, Workload::Monotonic, Workload::Decode] {
+        let (count, elapsed) = measure(workload, threads, duration);
+        println!(
+            "{:<25} {:>14} IDs/sec",
+            workload.name(),
+            format!("{:.0}", count as f64 / elapsed.as_secs_f64())
+        );
+    }
+}
+
+/// Run `workload` on `threads` threads for `duration`, returning the total
+/// number of identifiers and how long the slowest thread took
+fn measure(workload: Workload, threads: usize, duration: Duration) -> (u64, Duration) {
+    let started = Instant::now();
+    let counts: Vec<u64> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|thread| scope.spawn(move || run_workload(workload, thread as u16, started + duration)))
+            .collect();
+        handles.into_iter().map(|handle| handle.join().expect("benchmark thread panicked")).collect()
+    });
+    (counts.iter().sum(), started.elapsed())
+}
+
+/// Run `workload` until `deadline` with machine ID `machine_id`, returning
+/// the number of identifiers
+fn run_workload(workload: Workload, machine_id: u16, deadline: Instant) -> u64 {
+    let generator = BcidGenerator::new("BNCH", machine_id).unwrap_or_else(|e| exit_with_error(e));
+    let mut generator = match workload {
+        Workload::Random => generator.random(true),
+        Workload::Monotonic => generator.monotonic(true),
+        Workload::Chronological | Workload::Decode => generator,
+    };
+    let pool: Vec<String> = match workload {
+        Workload::Decode => (0..DECODE_POOL).map(|_| generator.next().to_string()).collect(),
+        _ => Vec::new(),
+    };
+
+    let mut count = 0;
+    while Instant::now() < deadline {
+        for i in 0..OPS_PER_CHECK {
+            match workload {
+                Workload::Decode => {
+                    let identifier = &pool[(count + i) as usize % DECODE_POOL];
+                    black_box(Bcid::parse(identifier).unwrap_or_else(|e| exit_with_error(e)));
+                }
+                _ => {
+                    black_box(generator.try_next().unwrap_or_else(|e| exit_with_error(e)));
+                }
+            }
+        }
+        count += OPS_PER_CHECK;
+    }
+    count
+}
@@ -0,0 +1,235 @@
+//! Bulk processing of identifiers read one per line (`bcid decode --input`,
+//! `stats`, `dedupe`, `sort`), and of the keys of `hash --namespace`
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process;
+use std::thread;
+use std::time::Instant;
+
+use bcid::{json, Bcid, BcidError};
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use tracing::{debug, error, info};
+
+use super::decode::{decode_input, decoded_json, DecodeOptions};
+use super::registry::Registry;
+use super::{EXIT_FAILURE, EXIT_INVALID_IDENTIFIER};
+
+/// Record format for bulk decoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// One JSON object per line: the decoded components, or the line number,
+    /// input, error message and reason code
+    Jsonl,
+    /// Comma-separated values with a header row
+    Csv,
+}
+
+/// Lines each job decodes per chunk of input
+const LINES_PER_JOB: usize = 8192;
+
+/// Columns of CSV output, followed by `entity` when decoding with a registry
+const CSV_HEADER: &str =
+    "line,input,prefix,type,format,layout,alphabet,timestamp,iso_datetime,machine_id,random,random_part,raw,reason,error";
+
+/// The default number of decoding threads: one per available CPU
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Decode every non-blank line of `input` (`-` for stdin), printing one
+/// record per line
+///
+/// With a registry, records carry the entity name of their prefix, without
+/// warnings for unregistered prefixes. Input is read in chunks decoded on a
+/// pool of `jobs` threads; records are printed in input order. Lines that fail to decode produce an error
+/// record instead of stopping the run. Exits with status 3 if any line
+/// failed, after reporting the number of failures on stderr.
+pub fn decode_lines(input: &str, options: &DecodeOptions, format: RecordFormat, jobs: usize) {
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if format == RecordFormat::Csv {
+        match options.registry {
+            Some(_) => write_line(&mut out, &format!("{},entity", CSV_HEADER)),
+            None => write_line(&mut out, CSV_HEADER),
+        }
+    }
+    let started = Instant::now();
+    debug!(input, jobs, "Decoding {} on {} thread(s)", input, jobs);
+    let pool = match rayon::ThreadPoolBuilder::new().num_threads(jobs).build() {
+        Ok(pool) => pool,
+        Err(err) => {
+            eprintln!("Error: Cannot start {} decoding thread(s): {}", jobs, err);
+            process::exit(EXIT_FAILURE);
+        }
+    };
+    let (mut total, mut failed) = (0usize, 0usize);
+    let mut lines = identifier_lines(input);
+    loop {
+        // Line numbers and identifiers of the next chunk
+        let chunk: Vec<(usize, String)> = lines.by_ref().take(jobs * LINES_PER_JOB).collect();
+        if chunk.is_empty() {
+            break;
+        }
+
+        for (record, is_ok) in decode_chunk(&chunk, options, format, &pool) {
+            total += 1;
+            failed += usize::from(!is_ok);
+            match options.template {
+                // Templates have no error records, so failures go to stderr
+                Some(_) if !is_ok => error!("{}", record),
+                _ => write_line(&mut out, &record),
+            }
+        }
+    }
+    if out.flush().is_err() {
+        process::exit(0);
+    }
+    drop(out);
+
+    let elapsed = started.elapsed();
+    let duration_us = elapsed.as_micros() as u64;
+    match failed {
+        0 => debug!(total, failed, duration_us, "Decoded {} identifiers in {:.2?}", total, elapsed),
+        _ => info!(total, failed, duration_us, "{} of {} identifiers failed to decode", failed, total),
+    }
+    if failed > 0 {
+        process::exit(EXIT_INVALID_IDENTIFIER);
+    }
+}
+
+/// Open `input` for reading, or stdin for `-`, exiting if it cannot be opened
+fn open_input(input: &str) -> Box<dyn BufRead> {
+    if input == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        match File::open(input) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(err) => read_error(input, err),
+        }
+    }
+}
+
+/// The 1-based line number and trimmed text of every non-blank line of
+/// `input` (`-` for stdin), exiting if it cannot be read
+pub fn identifier_lines(input: &str) -> impl Iterator<Item = (usize, String)> + '_ {
+    open_input(input).split(b'\n').enumerate().filter_map(move |(index, line)| {
+        let line = line.unwrap_or_else(|err| read_error(input, err));
+        // Invalid UTF-8 becomes U+FFFD and is reported as an invalid character
+        let line = String::from_utf8_lossy(&line);
+        let identifier = line.trim();
+        (!identifier.is_empty()).then(|| (index + 1, identifier.to_string()))
+    })
+}
+
+/// Create `output` for writing, or stdout for `-`, exiting if it cannot be
+/// created
+pub fn create_output(output: &str) -> Box<dyn Write> {
+    if output == "-" {
+        Box::new(BufWriter::new(io::stdout().lock()))
+    } else {
+        match File::create(output) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(err) => write_error(output, err),
+        }
+    }
+}
+
+/// Decode `(line number, identifier)` pairs on the threads of `pool`,
+/// returning each record and whether it decoded, in order
+fn decode_chunk(chunk: &[(usize, String)], options: &DecodeOptions, format: RecordFormat, pool: &ThreadPool) -> Vec<(String, bool)> {
+    let decode_line = |(line, identifier): &(usize, String)| -> (String, bool) {
+        let (unwrapped, result) = decode_input(identifier, options);
+        if let Some(template) = &options.template {
+            return match &result {
+                Ok(decoded) => (template.render(unwrapped, decoded), true),
+                Err(err) => (format!("line {}: {}", line, err), false),
+            };
+        }
+        let record = match format {
+            RecordFormat::Jsonl => json_record(*line, identifier, &result, options.registry.as_ref()),
+            RecordFormat::Csv => csv_record(*line, identifier, &result, options.registry.as_ref()),
+        };
+        (record, result.is_ok())
+    };
+    pool.install(|| chunk.par_iter().map(decode_line).collect())
+}
+
+/// A JSON Lines record: the decoded identifier, or the error for a line
+fn json_record(line: usize, input: &str, result: &Result<Bcid, BcidError>, registry: Option<&Registry>) -> String {
+    match result {
+        Ok(decoded) => decoded_json(decoded, registry.map(|registry| registry.entity(decoded.prefix()))),
+        Err(err) => json::Object::new()
+            .number("line", Some(line))
+            .string("input", Some(input))
+            .string("error", Some(&err.to_string()))
+            .string("reason", Some(err.reason()))
+            .finish(),
+    }
+}
+
+/// A CSV record with the columns of [`CSV_HEADER`], and `entity` with a
+/// registry
+fn csv_record(line: usize, input: &str, result: &Result<Bcid, BcidError>, registry: Option<&Registry>) -> String {
+    let mut fields = vec![line.to_string(), input.to_string()];
+    match result {
+        Ok(decoded) => fields.extend([
+            decoded.prefix().to_string(),
+            decoded.identifier_type().to_string(),
+            decoded.format().to_string(),
+            decoded.format().layout().to_string(),
+            decoded.alphabet().to_string(),
+            decoded.timestamp().map(|t| t.to_string()).unwrap_or_default(),
+            decoded
+                .datetime()
+                .map(|dt| dt.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+                .unwrap_or_default(),
+            decoded.machine_id().to_string(),
+            decoded.random().map(|r| r.to_string()).unwrap_or_default(),
+            decoded.random_part().unwrap_or_default().to_string(),
+            decoded.as_str().to_string(),
+            String::new(),
+            String::new(),
+        ]),
+        Err(err) => {
+            fields.extend(std::iter::repeat_n(String::new(), 11));
+            fields.extend([err.reason().to_string(), err.to_string()]);
+        }
+    }
+    if let Some(registry) = registry {
+        let entity = result.as_ref().ok().and_then(|decoded| registry.entity(decoded.prefix()));
+        fields.push(entity.unwrap_or_default().to_string());
+    }
+    fields.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(",")
+}
+
+/// Quote a CSV field if it contains a comma, quote or line break
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn write_line(out: &mut impl Write, record: &str) {
+    if writeln!(out, "{}", record).is_err() {
+        // Downstream closed the pipe (e.g. `| head`)
+        process::exit(0);
+    }
+}
+
+fn read_error(input: &str, err: io::Error) -> ! {
+    eprintln!("Error: Cannot read {}: {}", input, err);
+    process::exit(EXIT_FAILURE);
+}
+
+pub fn write_error(output: &str, err: io::Error) -> ! {
+    if err.kind() == io::ErrorKind::BrokenPipe {
+        // Downstream closed the pipe (e.g. `| head`)
+        process::exit(0);
+    }
+    eprintln!("Error: Cannot write {}: {}", output, err);
+    process::exit(EXIT_FAILURE);
+}
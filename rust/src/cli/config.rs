@@ -0,0 +1,266 @@
+//! Defaults loaded from `~/.config/bcid/config.toml` and the environment
+//!
+//! ```toml
+//! prefix = "USER"
+//! machine_id = 37        # or "auto", "k8s", or a "redis://", "etcd://", "consul://" or "file://" URL to lease one
+//! format = "v2"          # "v1", "v2" (sortable), "v3" (with a precision), "v4" (compact)
+//!                        # or "v5" (bit layout)
+//! precision = "ms"       # timestamp precision: "s", "10ms", "ms" or "us"
+//! environment = "p"     # recorded by generate and required by validate: "p", "s" or "d"
+//! shard = "e1"          # written after the machine ID (implies "v3")
+//! layout = "ts=44,machine=12,seq=10"  # bit layout of v5 identifiers (implies "v5")
+//! alphabet = "base58"    # "base62", "crockford32", "base58" or "novowels"
+//! length = 24            # characters per generated identifier, 24-64
+//! separator = true       # write 4-character prefixes as `USER_...`
+//! safe = true            # re-draw identifiers whose random characters spell a word
+//! clock_regression = "wait"  # "tolerate", "wait" or "error"
+//! sequence_overflow = "error"  # "spin", "borrow" or "error"
+//! output = "json"        # decode output: "text" or "json"
+//! check_digit = true     # append and verify a check digit
+//! registry = "prefixes.toml"  # known prefixes, relative to this file
+//! ```
+//!
+//! Precedence, highest first: command line flags, environment variables
+//! (`BCID_PREFIX`, `BCID_MACHINE_ID`, `BCID_FORMAT`), the configuration file
+//! (`BCID_CONFIG` overrides its path), built-in defaults.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+
+use bcid::{Alphabet, BcidError, BitLayout, ClockRegression, Environment, Format, Precision, SequenceOverflow, MAX_LENGTH, MIN_LENGTH};
+use toml::Value;
+
+use super::generate::MachineIdOption;
+use super::{parse_toml, EXIT_FAILURE, EXIT_USAGE};
+
+/// How decoded identifiers are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown output format '{}' (expected text or json)", value)),
+        }
+    }
+}
+
+/// Settings read from the configuration file
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub prefix: Option<String>,
+    pub machine_id: Option<MachineIdOption>,
+    pub format: Option<Format>,
+    /// Timestamp precision of generated identifiers
+    pub precision: Option<Precision>,
+    /// Environment recorded in generated identifiers, which validate
+    /// requires
+    pub environment: Option<Environment>,
+    /// Shard written after the machine ID of generated identifiers
+    pub shard: Option<String>,
+    /// Bit layout of generated v5 identifiers
+    pub bit_layout: Option<BitLayout>,
+    /// Alphabet for generating and decoding identifiers
+    pub alphabet: Option<Alphabet>,
+    /// Length of generated identifiers
+    pub length: Option<usize>,
+    /// Write a separator after 4-character prefixes of generated identifiers
+    pub separator: bool,
+    /// Generate again identifiers whose random characters spell a denylisted word
+    pub safe: bool,
+    /// What generate does if the clock goes backwards
+    pub clock_regression: Option<ClockRegression>,
+    /// What generate does when the sequence of a millisecond is used up
+    pub sequence_overflow: Option<SequenceOverflow>,
+    pub output: Option<OutputFormat>,
+    /// Generated identifiers end in a check digit, which decode and
+    /// validate verify
+    pub check_digit: bool,
+    /// Prefix registry file for generate and decode
+    pub registry: Option<PathBuf>,
+    /// HMAC key for signed identifiers, from `BCID_SIGNING_KEY` only so it
+    /// stays out of files and process listings
+    pub signing_key: Option<Vec<u8>>,
+    /// Tokens `bcid serve` accepts, from `BCID_AUTH_TOKENS` (comma-separated)
+    /// only for the same reason
+    pub auth_tokens: Vec<String>,
+}
+
+impl Config {
+    /// `$BCID_CONFIG`, else `$XDG_CONFIG_HOME/bcid/config.toml`, falling back
+    /// to `~/.config`
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = env_var("BCID_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("bcid").join("config.toml"))
+    }
+
+    /// Load the configuration file (if it exists) and apply environment
+    /// variable overrides
+    ///
+    /// Exits with an error if the file exists but cannot be read or parsed,
+    /// or if an environment variable holds an invalid value.
+    pub fn load() -> Config {
+        let mut config = Config::load_file();
+        if let Err(err) = config.apply_env() {
+            eprintln!("Error: {}", err);
+            process::exit(EXIT_USAGE);
+        }
+        config
+    }
+
+    fn load_file() -> Config {
+        let Some(path) = Config::path() else {
+            return Config::default();
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Config::default(),
+            Err(err) => {
+                eprintln!("Error: Cannot read {}: {}", path.display(), err);
+                process::exit(EXIT_FAILURE);
+            }
+        };
+        let mut config = Config::parse(&contents).unwrap_or_else(|err| {
+            eprintln!("Error: Invalid configuration in {}: {}", path.display(), err);
+            process::exit(EXIT_USAGE);
+        });
+        // A relative registry path names a file next to the configuration file
+        if let (Some(registry), Some(dir)) = (&mut config.registry, path.parent()) {
+            *registry = dir.join(&registry);
+        }
+        config
+    }
+
+    /// Override settings from `BCID_PREFIX`, `BCID_MACHINE_ID`,
+    /// `BCID_FORMAT` (decode output: `text` or `json`), `BCID_SIGNING_KEY`
+    /// and `BCID_AUTH_TOKENS`
+    pub fn apply_env(&mut self) -> Result<(), String> {
+        if let Some(prefix) = env_var("BCID_PREFIX") {
+            self.prefix = Some(prefix);
+        }
+        if let Some(machine_id) = env_var("BCID_MACHINE_ID") {
+            let machine_id = MachineIdOption::from_spec(&machine_id).map_err(|e| format!("BCID_MACHINE_ID: {}", e))?;
+            self.machine_id = Some(machine_id);
+        }
+        if let Some(output) = env_var("BCID_FORMAT") {
+            self.output = Some(OutputFormat::parse(&output).map_err(|e| format!("BCID_FORMAT: {}", e))?);
+        }
+        if let Some(key) = env_var("BCID_SIGNING_KEY") {
+            self.signing_key = Some(parse_hex_key(&key).map_err(|e| format!("BCID_SIGNING_KEY: {}", e))?);
+        }
+        if let Some(tokens) = env_var("BCID_AUTH_TOKENS") {
+            self.auth_tokens = tokens.split(',').map(str::trim).filter(|token| !token.is_empty()).map(String::from).collect();
+        }
+        Ok(())
+    }
+
+    /// Parse configuration file contents, warning about unknown keys
+    pub fn parse(contents: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+        for entry in parse_toml(contents)? {
+            let invalid = |expected: &str| format!("line {}: `{}` must be {}", entry.line, entry.key, expected);
+            match (entry.key.as_str(), &entry.value) {
+                ("prefix", Value::String(prefix)) => config.prefix = Some(prefix.clone()),
+                ("prefix", _) => return Err(invalid("a string")),
+                ("machine_id", Value::Integer(id)) => {
+                    let id = u16::try_from(*id).map_err(|_| invalid("between 0 and 65535"))?;
+                    config.machine_id = Some(MachineIdOption::Fixed(id));
+                }
+                ("machine_id", Value::String(spec)) => {
+                    config.machine_id = Some(MachineIdOption::from_spec(spec).map_err(|_| invalid("a number, \"auto\" or a coordinator URL"))?);
+                }
+                ("machine_id", _) => return Err(invalid("a number, \"auto\" or a coordinator URL")),
+                ("format", Value::String(format)) => {
+                    let expected = "\"v1\", \"v2\", \"v3\", \"v4\" or \"v5\"";
+                    config.format = Some(Format::parse_version(format).ok_or_else(|| invalid(expected))?);
+                }
+                ("format", _) => return Err(invalid("\"v1\", \"v2\", \"v3\", \"v4\" or \"v5\"")),
+                ("precision", Value::String(precision)) => {
+                    let expected = "\"s\", \"10ms\", \"ms\" or \"us\"";
+                    config.precision = Some(Precision::from_name(precision).ok_or_else(|| invalid(expected))?);
+                }
+                ("precision", _) => return Err(invalid("\"s\", \"10ms\", \"ms\" or \"us\"")),
+                ("environment", Value::String(environment)) => {
+                    let expected = "\"p\", \"s\" or \"d\"";
+                    config.environment = Some(Environment::from_name(environment).ok_or_else(|| invalid(expected))?);
+                }
+                ("environment", _) => return Err(invalid("\"p\", \"s\" or \"d\"")),
+                ("shard", Value::String(shard)) => config.shard = Some(shard.clone()),
+                ("shard", _) => return Err(invalid("a string")),
+                ("layout", Value::String(layout)) => {
+                    config.bit_layout = Some(layout.parse().map_err(|e: BcidError| format!("line {}: {}", entry.line, e))?);
+                }
+                ("layout", _) => return Err(invalid("a bit layout such as \"ts=44,machine=12,seq=10\"")),
+                ("alphabet", Value::String(alphabet)) => {
+                    let expected = "\"base62\", \"crockford32\", \"base58\" or \"novowels\"";
+                    config.alphabet = Some(Alphabet::from_name(alphabet).ok_or_else(|| invalid(expected))?);
+                }
+                ("alphabet", _) => return Err(invalid("\"base62\", \"crockford32\", \"base58\" or \"novowels\"")),
+                ("length", Value::Integer(length)) => {
+                    let length = usize::try_from(*length)
+                        .ok()
+                        .filter(|length| (MIN_LENGTH..=MAX_LENGTH).contains(length))
+                        .ok_or_else(|| invalid(&format!("between {} and {}", MIN_LENGTH, MAX_LENGTH)))?;
+                    config.length = Some(length);
+                }
+                ("length", _) => return Err(invalid(&format!("between {} and {}", MIN_LENGTH, MAX_LENGTH))),
+                ("separator", Value::Boolean(separator)) => config.separator = *separator,
+                ("separator", _) => return Err(invalid("true or false")),
+                ("safe", Value::Boolean(safe)) => config.safe = *safe,
+                ("safe", _) => return Err(invalid("true or false")),
+                ("clock_regression", Value::String(policy)) => {
+                    let expected = "\"tolerate\", \"wait\" or \"error\"";
+                    config.clock_regression = Some(ClockRegression::from_name(policy).ok_or_else(|| invalid(expected))?);
+                }
+                ("clock_regression", _) => return Err(invalid("\"tolerate\", \"wait\" or \"error\"")),
+                ("sequence_overflow", Value::String(policy)) => {
+                    let expected = "\"spin\", \"borrow\" or \"error\"";
+                    config.sequence_overflow = Some(SequenceOverflow::from_name(policy).ok_or_else(|| invalid(expected))?);
+                }
+                ("sequence_overflow", _) => return Err(invalid("\"spin\", \"borrow\" or \"error\"")),
+                ("output", Value::String(output)) => {
+                    config.output = Some(OutputFormat::parse(output).map_err(|_| invalid("\"text\" or \"json\""))?);
+                }
+                ("output", _) => return Err(invalid("\"text\" or \"json\"")),
+                ("check_digit", Value::Boolean(check_digit)) => config.check_digit = *check_digit,
+                ("check_digit", _) => return Err(invalid("true or false")),
+                ("registry", Value::String(registry)) => config.registry = Some(PathBuf::from(registry)),
+                ("registry", _) => return Err(invalid("a path")),
+                (key, _) => eprintln!("Warning: Unknown configuration key '{}' (line {})", key, entry.line),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// A signing or encryption key, as [`parse_hex_key`] reads it (an alias, so
+/// that clap takes a `--key` as one value rather than a list)
+pub type Key = Vec<u8>;
+
+/// Decode a hexadecimal signing key
+pub fn parse_hex_key(hex: &str) -> Result<Vec<u8>, String> {
+    if hex.is_empty() || !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("key must be a non-empty, even number of hexadecimal digits".to_string());
+    }
+    Ok((0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or_default()).collect())
+}
+
+/// A non-empty environment variable
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
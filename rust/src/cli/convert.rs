@@ -0,0 +1,85 @@
+use bcid::{decode_identifier, Bcid};
+use clap::{Args, ValueEnum};
+
+use super::config::Config;
+use super::{exit_with_error, usage_error};
+
+/// Convert identifiers to and from UUIDv7 and ULID
+///
+/// Converts chronological identifiers to and from other time-ordered formats.
+/// The timestamp, machine ID and random value are preserved; the prefix must be
+/// supplied when converting back, and the random padding is re-drawn.
+///
+/// Formats:
+///   uuid7  UUIDv7 (RFC 9562)
+///   ulid   ULID (26 Crockford base32 characters)
+///   typeid TypeID (lowercase prefix, '_' and a UUIDv7 in Crockford base32); the
+///          prefix is taken from the TypeID and uppercased
+///
+/// All three carry a millisecond timestamp, which is truncated to BCID's 10ms
+/// resolution when converting from values that did not originate as BCIDs; their
+/// machine ID and random value are then taken from the random bits.
+#[derive(Args)]
+#[command(
+    verbatim_doc_comment,
+    override_usage = "bcid convert --to FORMAT <IDENTIFIER>\n       bcid convert --from FORMAT -p PREFIX <VALUE>",
+    after_help = EXAMPLES
+)]
+pub struct ConvertArgs {
+    /// Identifier, or value to convert with --from
+    value: String,
+    /// Convert a BCID to FORMAT
+    #[arg(long, value_name = "FORMAT", value_enum, required_unless_present = "from", conflicts_with = "from")]
+    to: Option<Target>,
+    /// Convert a FORMAT value to a BCID
+    #[arg(long, value_name = "FORMAT", value_enum)]
+    from: Option<Target>,
+    /// Prefix of 1-8 characters for --from uuid7/ulid (default: from the
+    /// config file)
+    #[arg(short, long)]
+    prefix: Option<String>,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid convert --to uuid7 TESTjruOLuCzLaabjBBTGfbX87UrE98n
+  bcid convert --from uuid7 -p TEST 019a4a1e-4b9e-7000-8237-7ac6b1f2c3d4
+  bcid convert --to ulid TESTjruOLuCzLaabjBBTGfbX87UrE98n
+  bcid convert --from ulid -p TEST 01JGJFGR4828T4059E8AQJB60J
+  bcid convert --to typeid TESTjruOLuCzLaabjBBTGfbX87UrE98n";
+
+/// Formats identifiers can be converted to and from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Target {
+    #[value(alias = "uuidv7")]
+    Uuid7,
+    Ulid,
+    #[value(name = "typeid")]
+    TypeId,
+}
+
+pub fn run(args: ConvertArgs, config: &Config) {
+    let value = args.value;
+    let prefix = args.prefix.or_else(|| config.prefix.clone());
+    let converted = match (args.to, args.from) {
+        (Some(target), _) => decode_identifier(&value).and_then(|decoded| match target {
+            Target::Uuid7 => decoded.to_uuid7(),
+            Target::Ulid => decoded.to_ulid(),
+            Target::TypeId => decoded.to_typeid(),
+        }),
+        (None, Some(Target::TypeId)) => Bcid::from_typeid(&value).map(|bcid| bcid.to_string()),
+        (None, Some(target)) => {
+            let Some(prefix) = prefix.as_deref() else {
+                usage_error("convert", "Prefix is required when converting to a BCID");
+            };
+            match target {
+                Target::Uuid7 => Bcid::from_uuid7(&value, prefix),
+                Target::Ulid => Bcid::from_ulid(&value, prefix),
+                Target::TypeId => unreachable!("TypeIDs carry their own prefix"),
+            }
+            .map(|bcid| bcid.to_string())
+        }
+        (None, None) => unreachable!("clap requires --to or --from"),
+    };
+    println!("{}", converted.unwrap_or_else(|e| exit_with_error(e)));
+}
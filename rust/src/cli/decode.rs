@@ -0,0 +1,486 @@
+use std::path::PathBuf;
+use std::process;
+
+use bcid::{decrypt_identifier, json, verify_check_digit, Alphabet, Bcid, BcidError, Precision, SEPARATOR};
+use chrono::Utc;
+use clap::Args;
+
+use super::bulk::{decode_lines, default_jobs, RecordFormat};
+use super::config::{parse_hex_key, Config, Key, OutputFormat};
+use super::generate::parse_alphabet;
+use super::log::LogOptions;
+use super::registry::Registry;
+use super::template::Template;
+use super::{exit_with_error, format_age, parse_positive, usage_error, EXIT_INVALID_IDENTIFIER};
+
+/// Decode an identifier into its components
+///
+/// Accepts the packed and separated forms (TESTjqTZ... or TEST_jqTZ...; prefixes
+/// other than 4 characters are always separated) and TypeIDs (e.g.
+/// test_01jgjfgr4828t4059e8aqjb60j). Whitespace, quotes, backticks and brackets
+/// around the identifier and punctuation after it are ignored, so text copied
+/// from a chat message or a sentence decodes as is.
+///
+/// Several identifiers are decoded in turn: each text block starts with an
+/// `Identifier:` line and blocks are separated by blank lines, --json prints an
+/// array, and --template a line each. Identifiers that fail to decode are
+/// reported (as `input`, `error` and `reason` in JSON) without stopping the
+/// rest, and the exit status is 3 if any failed.
+///
+/// With --input, every non-blank line of FILE (`-` for stdin) is decoded and
+/// printed as one JSON object per line, or as CSV with --csv. Lines that fail
+/// to decode print an error record (`line`, `input`, `error` and `reason`)
+/// instead of stopping the run, and the exit status is 3 if any failed.
+///
+/// With a prefix registry (--registry, or `registry` in the config file), the
+/// entity name of the prefix is printed after it (`entity` in JSON and CSV),
+/// and prefixes missing from the registry produce a warning.
+///
+/// Text output is meant for people and may change. For scripts, --porcelain
+/// prints one line per identifier of these tab-separated fields, empty when
+/// absent, which do not change between versions:
+///
+///   input prefix type format layout alphabet timestamp precision iso_datetime
+///   machine_id environment shard bit_layout sequence random random_part raw entity
+#[derive(Args)]
+#[command(
+    verbatim_doc_comment,
+    override_usage = "bcid decode [OPTIONS] <IDENTIFIER>...\n       bcid decode --input FILE [--csv | --template TEMPLATE] [--jobs N] [OPTIONS]",
+    after_help = EXAMPLES
+)]
+pub struct DecodeArgs {
+    /// Identifiers to decode
+    #[arg(value_name = "IDENTIFIER", required_unless_present = "input", conflicts_with = "input")]
+    identifiers: Vec<String>,
+    /// Decode every line of FILE, or stdin for `-`
+    #[arg(long, value_name = "FILE")]
+    input: Option<String>,
+    /// With --input, print CSV with a header row instead of JSON Lines
+    #[arg(long, conflicts_with = "template")]
+    csv: bool,
+    /// With --input, decode on N threads (default: number of CPUs)
+    #[arg(long, value_name = "N", value_parser = parse_positive::<usize>)]
+    jobs: Option<usize>,
+    #[command(flatten)]
+    log: LogOptions,
+    #[command(flatten)]
+    flags: DecodeFlags,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid decode TESTa1b2c3d4e5f6g7h8i9j0k1l2m3n4
+  bcid decode TESTa1b2c3d4e5f6g7h8i9j0k1l2m3n4 --json
+  bcid decode TESTa1b2c3d4e5f6g7h8i9j0k1l2m3n4 USERa1b2c3d4e5f6g7h8i9j0k1l2m3n4 --json
+  bcid decode USERa1b2c3d4e5f6g7h8i9j0k1l2m3n4 --registry prefixes.toml
+  bcid decode TESTa1b2c3d4e5f6g7h8i9j0k1l2m3n4 --porcelain | cut -f 9
+  bcid decode --input ids.txt > decoded.jsonl
+  bcid generate -p TEST -n 1000 | bcid decode --input - --csv
+  bcid decode --input ids.txt --template '{id}\\t{iso_time}\\t{machine_id}'";
+
+/// Flags of `decode`, which `extract` also takes
+#[derive(Args)]
+pub struct DecodeFlags {
+    /// Print decoded components as a JSON object (an array for several identifiers)
+    #[arg(long, overrides_with_all = ["text", "porcelain"])]
+    pub json: bool,
+    /// Print decoded components as text (overrides `output` in the config file)
+    #[arg(long, overrides_with_all = ["json", "porcelain"])]
+    pub text: bool,
+    /// Print decoded components as stable tab-separated fields (see above)
+    #[arg(long, overrides_with_all = ["json", "text"])]
+    pub porcelain: bool,
+    /// Print only the identifier, and no registry warnings
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// Print the identifier through TEMPLATE instead, e.g. '{prefix}-{iso_time}-{machine_id}: {id}',
+    /// with placeholders id (as given), prefix, type, format, timestamp, iso_time,
+    /// machine_id, environment, shard, sequence, random and random_part (empty
+    /// when absent), and \t and \n for tabs and line breaks; with --input, lines
+    /// that fail to decode are reported on stderr
+    #[arg(long, value_parser = Template::parse)]
+    pub template: Option<Template>,
+    /// Verify the check digit in the last character
+    #[arg(long)]
+    pub check_digit: bool,
+    /// Decode identifiers generated with `--alphabet` (base62, crockford32,
+    /// base58 or novowels; default: base62 or from the config file)
+    #[arg(long, value_parser = parse_alphabet)]
+    pub alphabet: Option<Alphabet>,
+    /// Decrypt opaque identifiers (from `bcid generate --opaque`) with this key
+    #[arg(long, value_name = "HEX", value_parser = parse_hex_key)]
+    pub key: Option<Key>,
+    /// Decrypt opaque identifiers with the key in $BCID_SIGNING_KEY
+    #[arg(long)]
+    pub opaque: bool,
+    /// Print the entity name of the prefix from this prefix registry
+    #[arg(long, value_name = "FILE")]
+    pub registry: Option<PathBuf>,
+    /// Require identifiers to be children of IDENTIFIER (from `bcid hash
+    /// --parent`), failing with `not_a_child` otherwise
+    #[arg(long, value_name = "IDENTIFIER", value_parser = parse_parent)]
+    pub parent: Option<Bcid>,
+}
+
+impl DecodeFlags {
+    /// Apply the flags given on the command line over `options`
+    pub fn apply(self, options: &mut DecodeOptions) {
+        if self.json || self.text || self.porcelain {
+            options.is_json = self.json;
+            options.is_porcelain = self.porcelain;
+        }
+        options.is_quiet = self.quiet;
+        options.template = self.template;
+        options.with_check_digit |= self.check_digit;
+        options.alphabet = self.alphabet.unwrap_or(options.alphabet);
+        options.is_opaque = self.opaque || self.key.is_some();
+        options.key = self.key.or(options.key.take());
+        if let Some(registry) = self.registry {
+            options.registry = Some(Registry::load(&registry));
+        }
+        options.parent = self.parent;
+    }
+}
+
+/// Parse a `--parent` identifier
+pub fn parse_parent(value: &str) -> Result<Bcid, String> {
+    Bcid::parse(value).map_err(|e| e.to_string())
+}
+
+/// Quotes and brackets that wrap identifiers copied from chat messages,
+/// Markdown or source code, as opening and closing pairs
+const WRAPPERS: [(char, char); 10] = [
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+    ('\u{201c}', '\u{201d}'),
+    ('\u{2018}', '\u{2019}'),
+    ('\u{ab}', '\u{bb}'),
+    ('<', '>'),
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+];
+
+/// Punctuation that follows identifiers at the end of a sentence or clause
+const PUNCTUATION: [char; 6] = [',', '.', ';', ':', '!', '?'];
+
+/// Options for decoding identifiers
+#[derive(Default)]
+pub struct DecodeOptions {
+    pub is_json: bool,
+    pub with_check_digit: bool,
+    pub alphabet: Alphabet,
+    /// Decrypt opaque identifiers before decoding
+    pub is_opaque: bool,
+    pub key: Option<Vec<u8>>,
+    /// Known prefixes, for entity names
+    pub registry: Option<Registry>,
+    /// Parent that decoded identifiers must be children of
+    pub parent: Option<Bcid>,
+    /// Print identifiers through a template
+    pub template: Option<Template>,
+    /// Print tab-separated fields that do not change between versions
+    pub is_porcelain: bool,
+    /// Print only the identifier, without registry warnings
+    pub is_quiet: bool,
+}
+
+impl DecodeOptions {
+    /// Defaults from the configuration file
+    pub fn from_config(config: &Config) -> Self {
+        DecodeOptions {
+            is_json: config.output == Some(OutputFormat::Json),
+            with_check_digit: config.check_digit,
+            alphabet: config.alphabet.unwrap_or_default(),
+            is_opaque: false,
+            key: config.signing_key.clone(),
+            registry: config.registry.as_deref().map(Registry::load),
+            parent: None,
+            template: None,
+            is_porcelain: false,
+            is_quiet: false,
+        }
+    }
+}
+
+/// What decoded identifiers are printed as, from the most to the least
+/// specific flag
+#[derive(Clone, Copy)]
+enum Output<'a> {
+    Quiet,
+    Template(&'a Template),
+    Porcelain,
+    Json,
+    Text,
+}
+
+impl DecodeOptions {
+    fn output(&self) -> Output<'_> {
+        match (&self.template, self.is_quiet, self.is_porcelain, self.is_json) {
+            (_, true, _, _) => Output::Quiet,
+            (Some(template), _, _, _) => Output::Template(template),
+            (None, _, true, _) => Output::Porcelain,
+            (None, _, _, true) => Output::Json,
+            (None, _, _, false) => Output::Text,
+        }
+    }
+
+    /// The entity name of `prefix` when decoding with a registry (`Some`),
+    /// warning on stderr if it is not registered unless quiet
+    fn entity(&self, prefix: &str) -> Option<Option<&str>> {
+        let registry = self.registry.as_ref()?;
+        Some(if self.is_quiet { registry.entity(prefix) } else { registry.lookup(prefix) })
+    }
+}
+
+pub fn run(args: DecodeArgs, config: &Config) {
+    let mut options = DecodeOptions::from_config(config);
+    args.flags.apply(&mut options);
+    if options.is_opaque && options.key.is_none() {
+        usage_error("decode", "A key is required to decode opaque identifiers (--key or BCID_SIGNING_KEY)");
+    }
+    let record_format = if args.csv { RecordFormat::Csv } else { RecordFormat::Jsonl };
+    match (args.identifiers.as_slice(), args.input) {
+        (_, Some(_)) if options.is_porcelain || options.is_quiet => {
+            usage_error("decode", "--porcelain and --quiet cannot be used with --input (use --csv)")
+        }
+        (_, Some(input)) => {
+            args.log.init();
+            decode_lines(&input, &options, record_format, args.jobs.unwrap_or_else(default_jobs))
+        }
+        (_, None) if args.csv => usage_error("decode", "--csv requires --input"),
+        (_, None) if args.jobs.is_some() => usage_error("decode", "--jobs requires --input"),
+        (_, None) if args.log != LogOptions::default() => usage_error("decode", "--log-format and --log-level require --input"),
+        ([identifier], None) => decode(identifier, &options),
+        (identifiers, None) => decode_many(identifiers, &options),
+    }
+}
+
+/// Verify the check digit and decrypt if asked to, then decode
+fn decode_checked(identifier: &str, options: &DecodeOptions) -> Result<Bcid, BcidError> {
+    if options.with_check_digit {
+        verify_check_digit(identifier)?;
+    }
+    match (&options.key, options.is_opaque) {
+        (Some(key), true) => Bcid::parse(&decrypt_identifier(identifier, key)?),
+        (None, true) => {
+            usage_error("decode", "A key is required to decode opaque identifiers (--key or BCID_SIGNING_KEY)")
+        }
+        (_, false) => Bcid::parse_with(identifier, options.alphabet),
+    }
+}
+
+/// `input` without surrounding whitespace, one pair of quotes or brackets
+/// around it (`"ID"`, `` `ID` ``, `<ID>`) and one punctuation character
+/// after it (`ID.`, `"ID".`), with the number of characters before it
+///
+/// Identifiers always end in a base62 character, so this only removes
+/// characters that cannot belong to one; what is left must still decode.
+pub fn unwrap_input(input: &str) -> (usize, &str) {
+    let trimmed = input.trim();
+    // A sentence ends after the closing quote or bracket, or inside it
+    let (identifier, has_punctuation) = match trimmed.strip_suffix(PUNCTUATION) {
+        Some(inner) => (inner, true),
+        None => (trimmed, false),
+    };
+    let identifier = match WRAPPERS.iter().find_map(|&(open, close)| identifier.strip_prefix(open)?.strip_suffix(close)) {
+        Some(inner) if has_punctuation => inner.trim(),
+        Some(inner) => inner.trim().strip_suffix(PUNCTUATION).unwrap_or(inner.trim()),
+        None => identifier,
+    };
+    // `identifier` is a slice of `input`
+    let start = identifier.as_ptr() as usize - input.as_ptr() as usize;
+    (input[..start].chars().count(), identifier)
+}
+
+/// Decode an identifier or TypeID copied with what [`unwrap_input`] removes,
+/// verifying and decrypting as configured
+///
+/// Returns the unwrapped identifier with the result. Positions in errors
+/// count from the start of `input`.
+pub fn decode_input<'a>(input: &'a str, options: &DecodeOptions) -> (&'a str, Result<Bcid, BcidError>) {
+    let (start, identifier) = unwrap_input(input);
+    // TypeID suffixes start with 0 until the year 3084, and the fields of a
+    // BCID never do (every digit is a reserved version marker)
+    let decoded = match identifier.split_once(SEPARATOR) {
+        Some((_, suffix)) if suffix.starts_with('0') => Bcid::from_typeid(identifier),
+        _ => decode_checked(identifier, options),
+    };
+    let decoded = decoded.and_then(|decoded| match &options.parent {
+        Some(parent) => parent.verify_child(&decoded).map(|_| decoded),
+        None => Ok(decoded),
+    });
+    let decoded = decoded.map_err(|err| match err {
+        BcidError::InvalidCharacter { character, position } => {
+            BcidError::InvalidCharacter { character, position: position + start }
+        }
+        err => err,
+    });
+    (identifier, decoded)
+}
+
+/// Decode one identifier and print its components
+pub fn decode(input: &str, options: &DecodeOptions) {
+    let (identifier, decoded) = decode_input(input, options);
+    let decoded = decoded.unwrap_or_else(|e| exit_with_error(e));
+    print_decoded(identifier, &decoded, options);
+}
+
+/// Decode several identifiers and print the components of each
+///
+/// Text output starts each identifier with an `Identifier:` line and
+/// separates them with blank lines, JSON output is an array, and templates
+/// print a line each. Identifiers that fail to decode are reported (on
+/// stderr, or as an `input`, `error` and `reason` object in the JSON array)
+/// without stopping the rest; the exit status is then 3.
+pub fn decode_many(identifiers: &[String], options: &DecodeOptions) {
+    let output = options.output();
+    let mut records = Vec::new();
+    let mut failed = 0;
+    for (index, input) in identifiers.iter().enumerate() {
+        let (identifier, decoded) = decode_input(input, options);
+        match (decoded, output) {
+            (Ok(decoded), Output::Json) => records.push(decoded_json(&decoded, options.entity(decoded.prefix()))),
+            (Ok(decoded), _) => {
+                if matches!(output, Output::Text) {
+                    if index > 0 {
+                        println!();
+                    }
+                    println!("Identifier: {}", identifier);
+                }
+                print_decoded(identifier, &decoded, options);
+            }
+            (Err(err), Output::Json) => {
+                failed += 1;
+                let record = json::Object::new()
+                    .string("input", Some(identifier))
+                    .string("error", Some(&err.to_string()))
+                    .string("reason", Some(err.reason()))
+                    .finish();
+                records.push(record);
+            }
+            (Err(err), _) => {
+                failed += 1;
+                eprintln!("Error: {}: {}", identifier, err);
+            }
+        }
+    }
+    if matches!(output, Output::Json) {
+        println!("[{}]", records.join(","));
+    }
+    if failed > 0 {
+        process::exit(EXIT_INVALID_IDENTIFIER);
+    }
+}
+
+/// Print the components of `decoded`, as text, JSON, porcelain or through
+/// the template, or only the identifier when quiet
+fn print_decoded(identifier: &str, decoded: &Bcid, options: &DecodeOptions) {
+    let entity = options.entity(decoded.prefix());
+    match options.output() {
+        Output::Quiet => return println!("{}", identifier),
+        Output::Template(template) => return println!("{}", template.render(identifier, decoded)),
+        Output::Porcelain => return println!("{}", porcelain(identifier, decoded, entity.flatten())),
+        Output::Json => return println!("{}", decoded_json(decoded, entity)),
+        Output::Text => {}
+    }
+    match entity.flatten() {
+        Some(entity) => println!("Prefix: {} ({})", decoded.prefix(), entity),
+        None => println!("Prefix: {}", decoded.prefix()),
+    }
+    println!("Type: {}", decoded.identifier_type());
+    println!("Format: {} ({} fields)", decoded.format(), decoded.format().layout());
+    if decoded.alphabet() != Alphabet::Base62 {
+        println!("Alphabet: {}", decoded.alphabet());
+    }
+    println!("Machine ID: {}", decoded.machine_id());
+    if let Some(parent) = &options.parent {
+        println!("Parent: {} (ordinal {})", parent, decoded.machine_id());
+    }
+    if let Some(environment) = decoded.environment() {
+        println!("Environment: {}", environment);
+    }
+    if let Some(shard) = decoded.shard() {
+        println!("Shard: {}", shard);
+    }
+    if let Some(layout) = decoded.bit_layout() {
+        println!("Layout: {}", layout);
+    }
+
+    if let Some(timestamp) = decoded.timestamp() {
+        println!("Timestamp: {}", timestamp);
+    }
+    if let Some(precision) = decoded.precision() {
+        println!("Precision: {}", precision);
+    }
+    if let Some(datetime) = decoded.datetime() {
+        let seconds_format = match decoded.precision() {
+            Some(Precision::Microseconds) => chrono::SecondsFormat::Micros,
+            _ => chrono::SecondsFormat::Millis,
+        };
+        println!(
+            "Datetime: {} ({})",
+            datetime.to_rfc3339_opts(seconds_format, true),
+            format_age(datetime, Utc::now())
+        );
+    }
+    if let Some(sequence) = decoded.sequence() {
+        println!("Sequence: {}", sequence);
+    }
+    if let Some(random) = decoded.random() {
+        println!("Random Value: {}", random);
+    }
+    if let Some(random_part) = decoded.random_part() {
+        println!("Random Part: {}", random_part);
+    }
+}
+
+/// The porcelain form of `decoded`: `input`, `prefix`, `type`, `format`,
+/// `layout`, `alphabet`, `timestamp`, `precision`, `iso_datetime`,
+/// `machine_id`, `environment`, `shard`, `bit_layout`, `sequence`,
+/// `random`, `random_part`, `raw` and `entity`, separated by tabs and empty
+/// when absent
+///
+/// Unlike text output, these fields and their order do not change between
+/// versions, so scripts can split them (e.g. with `cut -f` or `read`).
+pub fn porcelain(input: &str, decoded: &Bcid, entity: Option<&str>) -> String {
+    let seconds_format = match decoded.precision() {
+        Some(Precision::Microseconds) => chrono::SecondsFormat::Micros,
+        _ => chrono::SecondsFormat::Millis,
+    };
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    let fields = [
+        input.to_string(),
+        decoded.prefix().to_string(),
+        decoded.identifier_type().to_string(),
+        decoded.format().to_string(),
+        decoded.format().layout().to_string(),
+        decoded.alphabet().name().to_string(),
+        optional(decoded.timestamp().map(|timestamp| timestamp.to_string())),
+        optional(decoded.precision().map(|precision| precision.name().to_string())),
+        optional(decoded.datetime().map(|datetime| datetime.to_rfc3339_opts(seconds_format, true))),
+        decoded.machine_id().to_string(),
+        optional(decoded.environment().map(|environment| environment.name().to_string())),
+        optional(decoded.shard().map(str::to_string)),
+        optional(decoded.bit_layout().map(|layout| layout.to_string())),
+        optional(decoded.sequence().map(|sequence| sequence.to_string())),
+        optional(decoded.random().map(|random| random.to_string())),
+        optional(decoded.random_part().map(str::to_string)),
+        decoded.as_str().to_string(),
+        // Entity names are free text
+        entity.unwrap_or_default().replace(['\t', '\n', '\r'], " "),
+    ];
+    fields.join("\t")
+}
+
+/// The JSON form of `decoded`, with an `entity` field when decoding with a
+/// registry (`Some`), which is `null` for unregistered prefixes
+pub fn decoded_json(decoded: &Bcid, entity: Option<Option<&str>>) -> String {
+    let json = decoded.to_json();
+    let Some(entity) = entity else {
+        return json;
+    };
+    let entity = json::Object::new().string("entity", entity).finish();
+    format!("{},{}", &json[..json.len() - 1], &entity[1..])
+}
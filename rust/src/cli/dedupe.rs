@@ -0,0 +1,164 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use bcid::{decode_identifier, json, IdentifierType};
+use clap::Args;
+
+use super::bulk::{create_output, identifier_lines, write_error};
+use super::EXIT_FAILURE;
+
+/// Find duplicate identifiers in a file
+///
+/// Reads one identifier per line from FILE (`-` for stdin) and reports every
+/// identifier that appears more than once, with the lines it appears on. With
+/// --output, the first occurrence of each identifier is written out in input
+/// order. Exits with status 1 if any duplicates were found.
+///
+/// With --near, distinct chronological identifiers that share a prefix,
+/// timestamp and machine ID are reported as near-duplicates: they differ only
+/// in their random value and padding, as when the same event was inserted
+/// twice with freshly generated identifiers.
+#[derive(Args)]
+#[command(after_help = EXAMPLES)]
+pub struct DedupeArgs {
+    /// Also report near-duplicates
+    #[arg(long)]
+    near: bool,
+    /// Write the de-duplicated identifiers to FILE (`-` for stdout; the report
+    /// then goes to stderr)
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<String>,
+    /// Print the report as JSON Lines
+    #[arg(long)]
+    json: bool,
+    /// File of identifiers, `-` for stdin
+    #[arg(value_name = "FILE")]
+    input: String,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid dedupe ids.txt
+  bcid dedupe --near -o unique.txt ids.txt";
+
+/// Fields shared by near-duplicate identifiers
+type NearKey = (String, u64, u16);
+
+pub fn run(args: DedupeArgs) {
+    let (input, output, with_near, is_json) = (args.input, args.output, args.near, args.json);
+
+    let mut writer = output.as_deref().map(create_output);
+
+    // Line numbers of every distinct line
+    let mut lines: HashMap<String, Vec<usize>> = HashMap::new();
+    // Distinct identifiers sharing prefix, timestamp and machine ID
+    let mut near: HashMap<NearKey, Vec<(usize, String)>> = HashMap::new();
+    for (line, identifier) in identifier_lines(&input) {
+        match lines.entry(identifier) {
+            Entry::Occupied(mut entry) => entry.get_mut().push(line),
+            Entry::Vacant(entry) => {
+                if let Some(writer) = writer.as_mut() {
+                    if let Err(err) = writeln!(writer, "{}", entry.key()) {
+                        write_error(output.as_deref().unwrap_or("-"), err);
+                    }
+                }
+                if with_near {
+                    if let Some(key) = near_key(entry.key()) {
+                        near.entry(key).or_default().push((line, entry.key().clone()));
+                    }
+                }
+                entry.insert(vec![line]);
+            }
+        }
+    }
+    if let Some(mut writer) = writer {
+        if let Err(err) = writer.flush() {
+            write_error(output.as_deref().unwrap_or("-"), err);
+        }
+    }
+
+    let mut duplicates: Vec<(&String, &Vec<usize>)> = lines.iter().filter(|(_, lines)| lines.len() > 1).collect();
+    duplicates.sort_by_key(|(_, lines)| lines[0]);
+    let mut near_groups: Vec<(&NearKey, &Vec<(usize, String)>)> =
+        near.iter().filter(|(_, identifiers)| identifiers.len() > 1).collect();
+    near_groups.sort_by_key(|(_, identifiers)| identifiers[0].0);
+
+    // Keep stdout for the identifiers when they are written there
+    let mut report: Box<dyn Write> = match output.as_deref() {
+        Some("-") => Box::new(io::stderr().lock()),
+        _ => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let extra: usize = duplicates.iter().map(|(_, lines)| lines.len() - 1).sum();
+    let mut records = Vec::new();
+    for (identifier, lines) in &duplicates {
+        records.push(if is_json {
+            json::Object::new()
+                .string("identifier", Some(identifier))
+                .number("count", Some(lines.len()))
+                .raw("lines", &json_numbers(lines.iter()))
+                .finish()
+        } else {
+            format!("{} appears {} times (lines {})", identifier, lines.len(), join(lines.iter()))
+        });
+    }
+    for ((prefix, timestamp, machine_id), identifiers) in &near_groups {
+        records.push(if is_json {
+            let ids: Vec<&str> = identifiers.iter().map(|(_, id)| id.as_str()).collect();
+            json::Object::new()
+                .string("prefix", Some(prefix))
+                .number("timestamp", Some(timestamp))
+                .number("machine_id", Some(machine_id))
+                .strings("near_duplicates", &ids)
+                .raw("lines", &json_numbers(identifiers.iter().map(|(line, _)| line)))
+                .finish()
+        } else {
+            let ids: Vec<String> =
+                identifiers.iter().map(|(line, id)| format!("{} (line {})", id, line)).collect();
+            format!(
+                "Near-duplicates with prefix {}, timestamp {} and machine ID {}: {}",
+                prefix,
+                timestamp,
+                machine_id,
+                ids.join(", ")
+            )
+        });
+    }
+    if !is_json {
+        let mut summary = format!("{} duplicated identifiers ({} extra lines)", duplicates.len(), extra);
+        if with_near {
+            summary.push_str(&format!(", {} groups of near-duplicates", near_groups.len()));
+        }
+        records.push(summary);
+    }
+    for record in records {
+        if writeln!(report, "{}", record).is_err() {
+            // Downstream closed the pipe (e.g. `| head`)
+            process::exit(0);
+        }
+    }
+    let _ = report.flush();
+    drop(report);
+
+    if !duplicates.is_empty() || !near_groups.is_empty() {
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+/// Prefix, timestamp and machine ID of a chronological identifier
+fn near_key(identifier: &str) -> Option<NearKey> {
+    let decoded = decode_identifier(identifier).ok()?;
+    if decoded.identifier_type() != IdentifierType::Chronological {
+        return None;
+    }
+    Some((decoded.prefix().to_string(), decoded.timestamp()?, decoded.machine_id()))
+}
+
+fn join<'a>(numbers: impl Iterator<Item = &'a usize>) -> String {
+    numbers.map(|n| n.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn json_numbers<'a>(numbers: impl Iterator<Item = &'a usize>) -> String {
+    format!("[{}]", numbers.map(|n| n.to_string()).collect::<Vec<_>>().join(","))
+}
@@ -0,0 +1,90 @@
+use std::process;
+
+use bcid::{decode_identifier, json, Bcid};
+use chrono::SecondsFormat;
+use clap::Args;
+
+use super::{exit_with_error, EXIT_INVALID_IDENTIFIER};
+
+/// Show the time elapsed between two identifiers
+///
+/// Prints the time elapsed from the first identifier's embedded timestamp to
+/// the second's, negative if the second is older. Timestamps have 10ms
+/// resolution, so identifiers from the same tick show no difference.
+///
+/// Random identifiers carry no timestamp: each one is reported on stderr and
+/// the exit status is 3.
+#[derive(Args)]
+#[command(after_help = EXAMPLES)]
+pub struct DiffArgs {
+    /// The identifier to measure from
+    first: String,
+    /// The identifier to measure to
+    second: String,
+    /// Print the result as a JSON object, with a null elapsed time if either
+    /// identifier is random
+    #[arg(long)]
+    json: bool,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid diff TESTjqTZNAWSyaabkXENIJ3mg9rTtkWk TESTjqTZOgUkSaabhZMFUZ3TaCnPMeBO";
+
+pub fn run(args: DiffArgs) {
+    let identifiers = [args.first, args.second];
+    let decoded: Vec<Bcid> =
+        identifiers.iter().map(|id| decode_identifier(id).unwrap_or_else(|e| exit_with_error(e))).collect();
+    let (first, second) = (&decoded[0], &decoded[1]);
+    let elapsed_ms = first
+        .datetime()
+        .zip(second.datetime())
+        .map(|(from, to)| to.signed_duration_since(from).num_milliseconds());
+    let random: Vec<&str> = decoded.iter().filter(|id| id.datetime().is_none()).map(Bcid::as_str).collect();
+
+    let iso = |id: &Bcid| id.datetime().map(|dt| dt.to_rfc3339_opts(SecondsFormat::Millis, true));
+    if args.json {
+        let json = json::Object::new()
+            .string("first", Some(first.as_str()))
+            .string("first_datetime", iso(first).as_deref())
+            .string("second", Some(second.as_str()))
+            .string("second_datetime", iso(second).as_deref())
+            .number("elapsed_ms", elapsed_ms)
+            .string("elapsed", elapsed_ms.map(format_elapsed).as_deref())
+            .strings("random", &random)
+            .finish();
+        println!("{}", json);
+    } else if let Some(elapsed_ms) = elapsed_ms {
+        println!("From: {} ({})", iso(first).unwrap_or_default(), first);
+        println!("To: {} ({})", iso(second).unwrap_or_default(), second);
+        let note = match elapsed_ms {
+            0 => " (same 10ms tick)",
+            ms if ms < 0 => " (the second identifier is older)",
+            _ => "",
+        };
+        println!("Elapsed: {}{}", format_elapsed(elapsed_ms), note);
+    }
+
+    if !random.is_empty() {
+        for id in &random {
+            eprintln!("Error: {} is a random identifier and carries no timestamp", id);
+        }
+        process::exit(EXIT_INVALID_IDENTIFIER);
+    }
+}
+
+/// A signed duration in milliseconds as e.g. `+1d 2h 3m 4.560s`, omitting
+/// leading zero units
+fn format_elapsed(ms: i64) -> String {
+    let sign = if ms < 0 { '-' } else { '+' };
+    let ms = ms.unsigned_abs();
+    let units = [(ms / 86_400_000, "d"), (ms / 3_600_000 % 24, "h"), (ms / 60_000 % 60, "m")];
+    let mut out = sign.to_string();
+    for (i, (amount, unit)) in units.iter().enumerate() {
+        if *amount > 0 || units[..i].iter().any(|(a, _)| *a > 0) {
+            out.push_str(&format!("{}{} ", amount, unit));
+        }
+    }
+    out.push_str(&format!("{}.{:03}s", ms / 1000 % 60, ms % 1000));
+    out
+}
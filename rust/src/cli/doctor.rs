@@ -0,0 +1,279 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bcid::{derive_machine_id, kubernetes_machine_id, MachineIdLease, MachineIdSource, DEFAULT_LENGTH};
+use chrono::{TimeZone, Utc};
+use clap::Args;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use super::config::Config;
+use super::generate::{redact_url, MachineIdOption};
+use super::EXIT_FAILURE;
+
+/// Check the clock, entropy and machine ID of this host
+///
+/// Checks that this host can generate identifiers safely, printing one line
+/// per check (`ok`, `warn` or `fail`), and exits with status 1 if any check
+/// fails:
+///
+///   clock        the system clock is set, and keeps pace with the monotonic
+///                clock (a stepped clock makes timestamps jump)
+///   ntp          with --ntp, the system clock's offset from an NTP server
+///   entropy      the operating system's random number generator works
+///   machine_id   where the machine ID comes from, warning about the default
+///                of 1 that hosts without one share; a coordinator (redis://,
+///                etcd://, consul:// or file://) is checked by leasing an ID and giving
+///                it back
+///   length       configured identifiers shorter than 32 characters, which have
+///                less random padding to tell identifiers apart
+#[derive(Args)]
+#[command(verbatim_doc_comment, after_help = EXAMPLES)]
+pub struct DoctorArgs {
+    /// Machine ID to check, or `auto` (default: from BCID_MACHINE_ID or the
+    /// config file, else 1)
+    #[arg(short, long, value_parser = MachineIdOption::from_spec)]
+    machine_id: Option<MachineIdOption>,
+    /// Compare the clock with an NTP server (host or host:port)
+    #[arg(long, value_name = "SERVER")]
+    ntp: Option<String>,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid doctor
+  bcid doctor -m auto --ntp pool.ntp.org";
+
+/// How long the clock is watched against the monotonic clock
+const CLOCK_SAMPLE: Duration = Duration::from_millis(100);
+
+/// Drift between the system and monotonic clocks over [`CLOCK_SAMPLE`]
+/// worth a warning, in microseconds
+const CLOCK_DRIFT_WARN: i64 = 10_000;
+
+/// Clock offsets from the NTP server worth a warning and a failure, in
+/// microseconds
+const NTP_OFFSET_WARN: i64 = 100_000;
+const NTP_OFFSET_FAIL: i64 = 1_000_000;
+
+/// How long to wait for the NTP server
+const NTP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Seconds from the NTP epoch (1900) to the Unix epoch
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+/// Outcome of a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Prints check results and remembers whether any failed
+#[derive(Default)]
+struct Report {
+    failed: bool,
+}
+
+impl Report {
+    fn print(&mut self, status: Status, check: &str, message: &str) {
+        let status = match status {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => {
+                self.failed = true;
+                "fail"
+            }
+        };
+        println!("{:<5} {:<11} {}", status, check, message);
+    }
+}
+
+pub fn run(args: DoctorArgs, config: &Config) {
+    let mut report = Report::default();
+    check_clock(&mut report);
+    if let Some(server) = &args.ntp {
+        check_ntp(&mut report, server);
+    }
+    check_entropy(&mut report);
+    let (machine_id, source) = match (args.machine_id, &config.machine_id) {
+        (Some(machine_id), _) => (machine_id, Some("from the command line")),
+        (None, Some(machine_id)) => (machine_id.clone(), Some("from BCID_MACHINE_ID or the config file")),
+        (None, None) => (MachineIdOption::default(), None),
+    };
+    check_machine_id(&mut report, &machine_id, source);
+    if let Some(length) = config.length.filter(|length| *length < DEFAULT_LENGTH) {
+        report.print(
+            Status::Warn,
+            "length",
+            &format!("{}-character identifiers have {} fewer random characters than the default", length, DEFAULT_LENGTH - length),
+        );
+    }
+    if report.failed {
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+/// The system clock is set and advances with the monotonic clock
+fn check_clock(report: &mut Report) {
+    let start = Utc::now();
+    let monotonic_start = Instant::now();
+    thread::sleep(CLOCK_SAMPLE);
+    let wall = (Utc::now() - start).num_microseconds().unwrap_or(i64::MAX);
+    let monotonic = monotonic_start.elapsed().as_micros() as i64;
+
+    let earliest = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+    let now = start.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    let drift = wall - monotonic;
+    let (status, message) = if start < earliest {
+        (Status::Fail, format!("system time {} is before 2020; the clock is not set", now))
+    } else if wall < 0 {
+        (Status::Fail, format!("system time went backwards by {} while checking", micros(-wall)))
+    } else if drift.abs() > CLOCK_DRIFT_WARN {
+        (
+            Status::Warn,
+            format!("system time {} moved {} against {} of the monotonic clock; it is being stepped or slewed", now, micros(wall), micros(monotonic)),
+        )
+    } else {
+        (Status::Ok, format!("system time {}, in step with the monotonic clock", now))
+    };
+    report.print(status, "clock", &message);
+}
+
+/// The system clock agrees with an NTP server
+fn check_ntp(report: &mut Report, server: &str) {
+    let (status, message) = match ntp_offset(server) {
+        Ok(offset) if offset.abs() > NTP_OFFSET_FAIL => {
+            (Status::Fail, format!("the clock is {} {} {}", micros(offset.abs()), direction(offset), server))
+        }
+        Ok(offset) if offset.abs() > NTP_OFFSET_WARN => {
+            (Status::Warn, format!("the clock is {} {} {}", micros(offset.abs()), direction(offset), server))
+        }
+        Ok(offset) => (Status::Ok, format!("the clock is within {} of {}", micros(offset.abs()), server)),
+        Err(err) => (Status::Warn, format!("could not query {}: {}", server, err)),
+    };
+    report.print(status, "ntp", &message);
+}
+
+/// Whether a clock `offset` from the server is ahead or behind
+fn direction(offset: i64) -> &'static str {
+    if offset < 0 {
+        "ahead of"
+    } else {
+        "behind"
+    }
+}
+
+/// Microseconds the server's clock is ahead of the system clock, from one
+/// SNTP request, assuming the network delay is the same both ways
+fn ntp_offset(server: &str) -> io::Result<i64> {
+    let server = match server.parse::<IpAddr>() {
+        Ok(ip) => SocketAddr::new(ip, 123).to_string(),
+        Err(_) if server.contains(':') => server.to_string(),
+        Err(_) => format!("{}:123", server),
+    };
+    let address = server
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address"))?;
+    let socket = UdpSocket::bind(if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" })?;
+    socket.set_read_timeout(Some(NTP_TIMEOUT))?;
+    socket.connect(address)?;
+
+    let mut packet = [0u8; 48];
+    // No leap second warning, version 4, client mode
+    packet[0] = 0x23;
+    let sent = Utc::now();
+    socket.send(&packet)?;
+    if socket.recv(&mut packet)? < packet.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "short response"));
+    }
+    let received = Utc::now();
+
+    // The server's transmit timestamp: seconds since 1900 and a binary fraction
+    let seconds = u32::from_be_bytes(packet[40..44].try_into().unwrap()) as i64;
+    let fraction = u32::from_be_bytes(packet[44..48].try_into().unwrap()) as i64;
+    if seconds == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the server is not synchronized"));
+    }
+    let server_micros = (seconds - NTP_UNIX_OFFSET) * 1_000_000 + ((fraction * 1_000_000) >> 32);
+    let midpoint = sent + (received - sent) / 2;
+    Ok(server_micros - midpoint.timestamp_micros())
+}
+
+/// The operating system's random number generator returns data
+fn check_entropy(report: &mut Report) {
+    let (status, message) = match entropy_failure() {
+        Some(failure) => (Status::Fail, failure),
+        None => (Status::Ok, "the operating system's random number generator works".to_string()),
+    };
+    report.print(status, "entropy", &message);
+}
+
+/// Why the operating system's random number generator cannot be used, if
+/// it fails or returns only zeros
+pub fn entropy_failure() -> Option<String> {
+    let mut bytes = [0u8; 32];
+    match OsRng.try_fill_bytes(&mut bytes) {
+        Err(err) => Some(format!("the operating system's random number generator failed: {}", err)),
+        Ok(()) if bytes.iter().all(|byte| *byte == 0) => {
+            Some("the operating system's random number generator returned only zeros".to_string())
+        }
+        Ok(()) => None,
+    }
+}
+
+/// Where the machine ID comes from (`None` for the default), and whether
+/// other hosts are likely to share it
+fn check_machine_id(report: &mut Report, machine_id: &MachineIdOption, source: Option<&str>) {
+    let (status, message) = match machine_id {
+        MachineIdOption::Fixed(1) if source.is_none() => (
+            Status::Warn,
+            "1 (default); every host without a machine ID uses 1, so identifiers from two such hosts in the same tick can collide (set -m, BCID_MACHINE_ID or `machine_id`)".to_string(),
+        ),
+        MachineIdOption::Fixed(id) => (Status::Ok, format!("{} ({})", id, source.unwrap_or("default"))),
+        MachineIdOption::Auto => match derive_machine_id() {
+            Ok(derived) => (
+                Status::Ok,
+                format!(
+                    "{} (derived from {} {:?}); derived IDs of different hosts collide 1 time in 65536",
+                    derived.id, derived.source, derived.input
+                ),
+            ),
+            Err(err) => (Status::Fail, format!("auto: {}", err)),
+        },
+        MachineIdOption::Kubernetes => match kubernetes_machine_id() {
+            Ok(derived) if derived.source == MachineIdSource::StatefulSetOrdinal => (
+                Status::Ok,
+                format!("{} (ordinal of {} {:?}); distinct within the StatefulSet", derived.id, derived.source, derived.input),
+            ),
+            Ok(derived) => (
+                Status::Ok,
+                format!(
+                    "{} (derived from {} {:?}); derived IDs of different pods collide 1 time in 65536",
+                    derived.id, derived.source, derived.input
+                ),
+            ),
+            Err(err) => (Status::Fail, format!("k8s: {}", err)),
+        },
+        // Lease and give back an ID to check the coordinator works
+        MachineIdOption::Leased(url) => match MachineIdLease::acquire(url) {
+            Ok(lease) => (Status::Ok, format!("leased from {} (e.g. {}); each process gets its own", redact_url(url), lease.id())),
+            Err(err) => (Status::Fail, format!("{}: {}", redact_url(url), err)),
+        },
+    };
+    report.print(status, "machine_id", &message);
+}
+
+/// `value` microseconds, in the largest unit that keeps it readable
+fn micros(value: i64) -> String {
+    match value {
+        0..=999 => format!("{}us", value),
+        1_000..=999_999 => format!("{:.1}ms", value as f64 / 1_000.0),
+        _ => format!("{:.2}s", value as f64 / 1_000_000.0),
+    }
+}
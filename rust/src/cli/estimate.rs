@@ -0,0 +1,264 @@
+use std::time::Duration;
+
+use bcid::{Bcid, BcidGenerator, Format, IdentifierType, Precision};
+use clap::Args;
+
+use super::config::Config;
+use super::generate::{GenerateOptions, LayoutFlags};
+use super::{exit_with_error, parse_nonzero_duration, parse_positive, parse_rate};
+
+/// Estimate collision probabilities for a generation rate
+///
+/// Estimates how likely identifiers of the configured layout are to collide:
+/// two identifiers collide only if they share a machine ID and a timestamp tick
+/// and their random bits match, so the estimate follows from the identifiers
+/// each machine ID generates per tick and the random bits each one carries
+/// (the birthday bound, with identifiers arriving at random).
+#[derive(Args)]
+#[command(
+    override_usage = "bcid estimate --rate RATE [--machines N] [--window DURATION] [LAYOUT OPTIONS]",
+    after_help = EXAMPLES,
+    mut_arg("prefix", |arg| arg.help("Prefix, whose length leaves less room for padding (default: TEST)"))
+)]
+pub struct EstimateArgs {
+    /// Identifiers generated across all machines, e.g. 50000/s, 120/ms or
+    /// 1000000/h (a bare number is per second)
+    #[arg(long, value_parser = parse_rate)]
+    rate: f64,
+    /// Machines generating them, each with its own machine ID
+    #[arg(long, value_name = "N", value_parser = parse_positive::<u64>, default_value_t = 1)]
+    machines: u64,
+    /// Period to give the probability of a collision for, e.g. 10ms or 365d
+    #[arg(long, value_name = "DURATION", value_parser = parse_window, default_value = "1d")]
+    window: (String, Duration),
+    #[command(flatten, next_help_heading = "Layout options, as for generate (default: from the config file)")]
+    layout: LayoutFlags,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid estimate --rate 50000/s --machines 20 --window 10ms
+  bcid estimate --rate 1000000/s --machines 200 --window 365d --precision ms";
+
+/// Parse a `--window`, keeping the duration as given for the report
+fn parse_window(value: &str) -> Result<(String, Duration), String> {
+    parse_nonzero_duration(value).map(|duration| (value.to_string(), duration))
+}
+
+/// Seconds in a year, for mean times between collisions
+const YEAR_SECONDS: f64 = 365.0 * 86400.0;
+
+/// Machine IDs that derived (`-m auto`) machine IDs are spread over
+const DERIVED_MACHINE_IDS: f64 = 65536.0;
+
+pub fn run(args: EstimateArgs, config: &Config) {
+    let mut options = GenerateOptions::from_config(config);
+    args.layout.apply(&mut options);
+    let (rate, machines, window) = (args.rate, args.machines, args.window);
+
+    let sample = sample(&options).unwrap_or_else(|e| exit_with_error(e));
+    let (bits, source) = random_bits(&sample);
+    let tick = tick(&sample);
+    let layout = sample.bit_layout();
+    let capacity = layout.map_or(DERIVED_MACHINE_IDS, |layout| 2f64.powi(layout.machine_bits() as i32));
+    let is_shared = machines as f64 > capacity;
+    let protection = match (options.is_monotonic, layout) {
+        _ if is_shared || tick.is_none() => None,
+        (true, _) => Some("monotonic generation increments the random value"),
+        (false, Some(layout)) if layout.sequence_bits() > 0 => Some("the sequence numbers identifiers within each millisecond"),
+        _ => None,
+    };
+
+    // Machines sharing a machine ID generate as one
+    let machine_ids = (machines as f64).min(capacity);
+    let per_second = rate / machine_ids;
+    let space = 2f64.powf(bits);
+    let window_seconds = window.1.as_secs_f64();
+    // With Poisson arrivals, n identifiers on average hold n²/2 pairs on average
+    let pairs = |identifiers: f64| identifiers * identifiers / 2.0;
+    let (expected, mean_time) = match tick {
+        _ if protection.is_some() => (0.0, f64::INFINITY),
+        None => (machine_ids * pairs(per_second * window_seconds) / space, (2.0 * space / machine_ids).sqrt() / per_second),
+        Some(tick) => {
+            let tick = tick.as_secs_f64();
+            let per_tick = machine_ids * pairs(per_second * tick) / space;
+            let expected = match window_seconds < tick {
+                true => machine_ids * pairs(per_second * window_seconds) / space,
+                false => per_tick * window_seconds / tick,
+            };
+            (expected, tick / per_tick)
+        }
+    };
+
+    print_line("layout", &describe_layout(&sample));
+    print_line("entropy", &format!("{:.1} bits: {}", bits, source));
+    match tick {
+        Some(tick) => {
+            let per_tick = per_second * tick.as_secs_f64();
+            print_line(
+                "rate",
+                &format!("{}/s per machine ID: {} per {} tick", number(per_second), number(per_tick), tick_name(tick)),
+            );
+            let message = match protection {
+                Some(reason) => format!("none: {}", reason),
+                None => format!(
+                    "{} probability that identifiers of one machine ID in one tick collide",
+                    probability(-(-pairs(per_tick) / space).exp_m1())
+                ),
+            };
+            print_line("per tick", &message);
+        }
+        None => print_line("rate", &format!("{}/s per machine ID", number(per_second))),
+    }
+    print_line(
+        "window",
+        &format!("{} probability of a collision in {}", probability(-(-expected).exp_m1()), window.0),
+    );
+    print_line("mean time", &mean_time_between(mean_time));
+    if is_shared {
+        print_line(
+            "machine IDs",
+            &format!(
+                "{} machines share {} machine IDs, so their identifiers can collide across machines (counted as one machine per ID)",
+                machines, capacity
+            ),
+        );
+    } else if machines > 1 && capacity == DERIVED_MACHINE_IDS {
+        let machines = machines as f64;
+        let clashes = -(-machines * (machines - 1.0) / 2.0 / DERIVED_MACHINE_IDS).exp_m1();
+        print_line(
+            "machine IDs",
+            &format!(
+                "{} machines need distinct machine IDs (of {}); derived ones (-m auto) clash with probability {}",
+                machines,
+                capacity,
+                probability(clashes)
+            ),
+        );
+    }
+}
+
+/// An identifier of the layout `options` configure
+fn sample(options: &GenerateOptions) -> Result<Bcid, bcid::BcidError> {
+    let mut generator = BcidGenerator::new(options.prefix.as_deref().unwrap_or("TEST"), 0)?
+        .format(options.format)
+        .precision(options.precision)
+        .alphabet(options.alphabet)
+        .random(options.is_random)
+        .monotonic(options.is_monotonic);
+    if let Some(length) = options.length {
+        generator = generator.length(length)?;
+    }
+    if let Some(shard) = &options.shard {
+        generator = generator.shard(shard);
+    }
+    if let Some(environment) = options.environment {
+        generator = generator.environment(environment);
+    }
+    if let Some(layout) = options.bit_layout {
+        generator = generator.bit_layout(layout)?;
+    }
+    generator.try_next()
+}
+
+/// Random bits telling apart identifiers with the same machine ID and tick,
+/// and where they come from
+pub fn random_bits(sample: &Bcid) -> (f64, String) {
+    let char_bits = (sample.alphabet().radix() as f64).log2();
+    let fields = sample.fields();
+    let start = |name: &str| fields.iter().find(|field| field.name == name).map(|field| field.start);
+    let end = sample.as_str().len();
+    match (sample.identifier_type(), sample.bit_layout()) {
+        (IdentifierType::Random, _) => {
+            let random = end - start("random").unwrap_or(end);
+            (random as f64 * char_bits, format!("{} random characters", random))
+        }
+        (_, Some(layout)) => {
+            let padding = end - start("padding").unwrap_or(end);
+            (
+                layout.random_bits() as f64 + padding as f64 * char_bits,
+                format!("the {}-bit random field and {} padding characters", layout.random_bits(), padding),
+            )
+        }
+        _ => {
+            // v1 writes the random value in as few digits as it needs, so
+            // count the padding after the widest one
+            let random_width = (16.0 / char_bits).ceil() as usize;
+            let padding = end - start("random").unwrap_or(end) - random_width;
+            (16.0 + padding as f64 * char_bits, format!("the 16-bit random value and {} padding characters", padding))
+        }
+    }
+}
+
+/// How long identifiers share a timestamp, or `None` for random identifiers
+fn tick(sample: &Bcid) -> Option<Duration> {
+    if sample.identifier_type() == IdentifierType::Random {
+        return None;
+    }
+    if sample.format() == Format::V5 {
+        return Some(Duration::from_millis(1));
+    }
+    Some(match sample.precision().unwrap_or_default() {
+        Precision::Seconds => Duration::from_secs(1),
+        Precision::Centiseconds => Duration::from_millis(10),
+        Precision::Milliseconds => Duration::from_millis(1),
+        Precision::Microseconds => Duration::from_micros(1),
+    })
+}
+
+/// Name of a tick, e.g. `10ms`
+fn tick_name(tick: Duration) -> String {
+    match tick.as_micros() {
+        1_000_000 => "1s".to_string(),
+        micros if micros >= 1000 => format!("{}ms", micros / 1000),
+        micros => format!("{}us", micros),
+    }
+}
+
+/// The layout of `sample`, e.g. `v3, ms precision, base62, 32 characters`
+fn describe_layout(sample: &Bcid) -> String {
+    let kind = match (sample.identifier_type(), sample.bit_layout()) {
+        (IdentifierType::Random, _) => "random".to_string(),
+        (_, Some(layout)) => format!("{} ({})", sample.format(), layout),
+        _ => format!("{}, {} precision", sample.format(), sample.precision().unwrap_or_default()),
+    };
+    format!("{}, {}, {} characters", kind, sample.alphabet(), sample.as_str().len())
+}
+
+fn print_line(label: &str, message: &str) {
+    println!("{:<12} {}", label, message);
+}
+
+/// `value` with up to 3 decimals below 100 and none above
+fn number(value: f64) -> String {
+    if value >= 100.0 {
+        return format!("{:.0}", value);
+    }
+    let value = format!("{:.3}", value);
+    value.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// `value` as a percentage, or in scientific notation when tiny
+fn probability(value: f64) -> String {
+    match value {
+        0.0 => "0".to_string(),
+        _ if value < 0.001 => format!("{:.1e}", value),
+        _ => format!("{:.2}%", value * 100.0),
+    }
+}
+
+/// How often collisions happen, given the mean time between them in seconds
+fn mean_time_between(seconds: f64) -> String {
+    let (amount, unit) = match seconds {
+        _ if seconds.is_infinite() => return "never".to_string(),
+        _ if seconds < 60.0 => (seconds, "seconds"),
+        _ if seconds < 3600.0 => (seconds / 60.0, "minutes"),
+        _ if seconds < 86400.0 => (seconds / 3600.0, "hours"),
+        _ if seconds < YEAR_SECONDS => (seconds / 86400.0, "days"),
+        _ => (seconds / YEAR_SECONDS, "years"),
+    };
+    match amount {
+        _ if amount >= 1e6 => format!("one collision every {:.1e} {}", amount, unit),
+        _ => format!("one collision every {} {}", number(amount), unit),
+    }
+}
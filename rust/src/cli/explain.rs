@@ -0,0 +1,62 @@
+use bcid::{Alphabet, Bcid};
+use clap::Args;
+
+use super::estimate::random_bits;
+use super::generate::parse_alphabet;
+use super::inspect::field_value;
+use super::template::iso_time;
+use super::config::Config;
+use super::exit_with_error;
+
+/// Mark each component under the identifier, with its decoded value
+///
+/// Prints the identifier with each component's characters marked below it,
+/// and the component's name and decoded value alongside, followed by the random
+/// bits that tell it apart from identifiers of the same machine ID and tick, e.g.
+///
+///   TESTjqTZNAWSyaabkXENIJ3mg9rTtkWk
+///   ^^^^                              prefix       TEST
+///       ^^^^^^^^^                     timestamp    2024-01-01T00:00:00.000Z (2024010100000000)
+///                ^^^                  machine_id   1
+///                   ^^^               random       41508
+///                      ^^^^^^^^^^^^^  padding      (random padding)
+///                                     entropy      93.4 random bits, from the 16-bit random value and 13 padding characters
+#[derive(Args)]
+#[command(verbatim_doc_comment, after_help = EXAMPLES)]
+pub struct ExplainArgs {
+    /// Identifier to explain
+    identifier: String,
+    /// Alphabet the identifier was generated with (default: base62 or from
+    /// the config file)
+    #[arg(long, value_parser = parse_alphabet)]
+    alphabet: Option<Alphabet>,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid explain TESTjqTZNAWSyaabkXENIJ3mg9rTtkWk
+  bcid explain $(bcid generate -p TEST --shard e1 --environment s)";
+
+pub fn run(args: ExplainArgs, config: &Config) {
+    let identifier = args.identifier;
+    let alphabet = args.alphabet.or(config.alphabet).unwrap_or_default();
+
+    let decoded = Bcid::parse_with(&identifier, alphabet).unwrap_or_else(|e| exit_with_error(e));
+    let width = decoded.as_str().len();
+    println!("{}", decoded);
+    for field in decoded.fields() {
+        let value = match field.name {
+            "version" => format!("{} ({} fields)", decoded.format(), decoded.format().layout()),
+            "timestamp" => match iso_time(&decoded) {
+                Some(iso_time) => format!("{} ({})", iso_time, field_value(&decoded, &field)),
+                None => field_value(&decoded, &field),
+            },
+            "separator" => "_".to_string(),
+            _ => field_value(&decoded, &field),
+        };
+        let marks = format!("{}{}", " ".repeat(field.start), "^".repeat(field.end - field.start));
+        println!("{:<width$}  {:<11}  {}", marks, field.name, value, width = width);
+    }
+    let (bits, source) = random_bits(&decoded);
+    println!("{:<width$}  {:<11}  {:.1} random bits, from {}", "", "entropy", bits, source, width = width);
+}
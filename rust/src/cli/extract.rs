@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use bcid::{json, Bcid};
+use clap::Args;
+
+use super::bulk::{identifier_lines, write_line};
+use super::config::Config;
+use super::decode::{decode_input, decoded_json, porcelain, DecodeFlags, DecodeOptions};
+use super::{usage_error, EXIT_FAILURE};
+
+/// Find identifiers in log files and other text
+///
+/// Finds identifiers in free text, e.g. log files and stack traces, and prints
+/// each one on its own line in the order found. Reads every FILE in turn, or
+/// stdin if none is given (or for `-`).
+///
+/// A hit is a word of letters, digits and underscores that decodes: a packed or
+/// separated identifier (TESTjqTZ..., usr_jqTZ...) or a TypeID, wherever it
+/// stands in the line (`id=TESTjqTZ...,` or `"TESTjqTZ..."`). Random identifiers
+/// are any 24 to 64 characters of base62 after a prefix, so other random tokens
+/// can look like one; restrict the prefixes with --prefix or --known to avoid
+/// that. Prefixes with characters other than letters and digits are only found
+/// with --prefix. Exits with status 1 if no identifier was found; with --quiet
+/// nothing is printed and the exit status is 0 as soon as one is found.
+#[derive(Args)]
+#[command(
+    after_help = EXAMPLES,
+    mut_arg("json", |arg| arg.help("Print each identifier decoded, as JSON Lines with its `file` (`-` for stdin), `line` and `input`")),
+    mut_arg("text", |arg| arg.hide(true)),
+    mut_arg("porcelain", |arg| arg.help("Print each identifier decoded, as the stable tab-separated fields of `bcid decode --porcelain`, after its file and line number")),
+    mut_arg("template", |arg| arg.help("Print each identifier through TEMPLATE (see `bcid help decode`)")),
+    mut_arg("quiet", |arg| arg.help("Print nothing; only the exit status tells whether one was found")),
+    mut_arg("alphabet", |arg| arg.help("Find identifiers generated with `--alphabet` (see `bcid help decode`)")),
+    mut_arg("registry", |arg| arg.help("Prefix registry for --known, and for the `entity` of --json")),
+    mut_arg("parent", |arg| arg.help("Only find children of IDENTIFIER (from `bcid hash --parent`)")),
+    mut_arg("check_digit", |arg| arg.help("Only find identifiers whose last character is a valid check digit")),
+    mut_arg("key", |arg| arg.help("Find opaque identifiers and decrypt them with this key")),
+    mut_arg("opaque", |arg| arg.help("Find opaque identifiers and decrypt them with the key in $BCID_SIGNING_KEY"))
+)]
+pub struct ExtractArgs {
+    /// Only find identifiers with this prefix (repeatable)
+    #[arg(short, long = "prefix", value_name = "PREFIX")]
+    prefixes: Vec<String>,
+    /// Only find identifiers whose prefix is in the prefix registry
+    /// (--registry, or `registry` in the config file)
+    #[arg(long)]
+    known: bool,
+    /// Print each identifier only the first time it is found
+    #[arg(short, long)]
+    unique: bool,
+    /// Print the line number (and file name, with several files) before each
+    /// identifier
+    #[arg(short = 'n', long)]
+    line_number: bool,
+    #[command(flatten)]
+    flags: DecodeFlags,
+    /// Files to search (default: stdin)
+    #[arg(value_name = "FILE")]
+    inputs: Vec<String>,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid extract app.log
+  kubectl logs deploy/api | bcid extract -p ORDR -p USER --unique
+  bcid extract --json -n error.log | jq -r '.iso_datetime'
+  bcid extract --template '{iso_time} {id}' trace.txt";
+
+/// Bytes of the words that identifiers are found in
+fn is_word_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+pub fn run(args: ExtractArgs, config: &Config) {
+    let mut options = DecodeOptions::from_config(config);
+    args.flags.apply(&mut options);
+    let mut prefixes = args.prefixes;
+    let mut inputs = args.inputs;
+    if options.is_opaque && options.key.is_none() {
+        usage_error("extract", "A key is required to decode opaque identifiers (--key or BCID_SIGNING_KEY)");
+    }
+    if args.known {
+        let Some(registry) = &options.registry else {
+            usage_error("extract", "--known requires a prefix registry (--registry, or `registry` in the config file)");
+        };
+        prefixes.extend(registry.prefixes().map(str::to_string));
+    }
+    if inputs.is_empty() {
+        inputs.push("-".to_string());
+    }
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut seen = HashSet::new();
+    let mut found = 0usize;
+    for input in &inputs {
+        for (line, text) in identifier_lines(input) {
+            for (identifier, decoded) in find_identifiers(&text, &prefixes, &options) {
+                if args.unique && !seen.insert(identifier.to_string()) {
+                    continue;
+                }
+                found += 1;
+                if options.is_quiet {
+                    process::exit(0);
+                }
+                let record = if let Some(template) = &options.template {
+                    template.render(identifier, &decoded)
+                } else if options.is_porcelain {
+                    let entity = options.registry.as_ref().and_then(|registry| registry.entity(decoded.prefix()));
+                    format!("{}\t{}\t{}", input, line, porcelain(identifier, &decoded, entity))
+                } else if options.is_json {
+                    let location = json::Object::new()
+                        .string("file", Some(input))
+                        .number("line", Some(line))
+                        .string("input", Some(identifier))
+                        .finish();
+                    let entity = options.registry.as_ref().map(|registry| registry.entity(decoded.prefix()));
+                    format!("{},{}", &location[..location.len() - 1], &decoded_json(&decoded, entity)[1..])
+                } else {
+                    identifier.to_string()
+                };
+                match (args.line_number && !options.is_json && !options.is_porcelain, inputs.len()) {
+                    (false, _) => write_line(&mut out, &record),
+                    (true, 1) => write_line(&mut out, &format!("{}:{}", line, record)),
+                    (true, _) => write_line(&mut out, &format!("{}:{}:{}", input, line, record)),
+                }
+            }
+        }
+    }
+    let _ = out.flush();
+    drop(out);
+    if found == 0 {
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+/// The identifiers in `text` with their decoded components, in order
+///
+/// Without `prefixes`, each word is tried whole and then from after each
+/// underscore in it, so `order_usr_jqTZ...` finds `usr_jqTZ...`. With
+/// `prefixes`, each occurrence of one that starts a word is tried up to the
+/// end of the word following it.
+fn find_identifiers<'a>(text: &'a str, prefixes: &[String], options: &DecodeOptions) -> Vec<(&'a str, Bcid)> {
+    let bytes = text.as_bytes();
+    let word_end = |start: usize| start + bytes[start..].iter().take_while(|&&byte| is_word_byte(byte)).count();
+    let decode = |start: usize, end: usize| decode_input(&text[start..end], options).1.ok().map(|decoded| (start, end, decoded));
+
+    let mut hits = Vec::new();
+    if prefixes.is_empty() {
+        let mut start = 0;
+        while start < bytes.len() {
+            if !is_word_byte(bytes[start]) {
+                start += 1;
+                continue;
+            }
+            let end = word_end(start);
+            let starts = std::iter::once(start).chain((start..end).filter(|&i| bytes[i] == b'_').map(|i| i + 1));
+            hits.extend(starts.filter(|&from| from < end).find_map(|from| decode(from, end)));
+            start = end;
+        }
+    } else {
+        for prefix in prefixes {
+            for (start, _) in text.match_indices(prefix.as_str()) {
+                if start > 0 && bytes[start - 1].is_ascii_alphanumeric() {
+                    continue;
+                }
+                let end = word_end(start + prefix.len());
+                hits.extend(decode(start, end).filter(|(_, _, decoded)| decoded.prefix() == prefix));
+            }
+        }
+        // Prefixes can overlap, e.g. `ORD` and `ORDR`; keep the first hit at
+        // each position
+        hits.sort_by_key(|&(start, end, _)| (start, usize::MAX - end));
+        let mut last_end = 0;
+        hits.retain(|&(start, end, _)| {
+            let is_new = start >= last_end;
+            last_end = last_end.max(end);
+            is_new
+        });
+    }
+    hits.into_iter().map(|(start, end, decoded)| (&text[start..end], decoded)).collect()
+}
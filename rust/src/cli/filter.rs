@@ -0,0 +1,92 @@
+use std::io::{self, BufWriter, Write};
+
+use bcid::{decode_identifier, Bcid};
+use chrono::{DateTime, Utc};
+use clap::Args;
+
+use super::bulk::{identifier_lines, write_line};
+use super::{parse_datetime, usage_error};
+
+/// Select identifiers by time window, prefix or machine ID
+///
+/// Reads one identifier per line from FILE (default: stdin) and prints those
+/// that match every given condition, in input order, as they are read.
+///
+/// Random identifiers carry no timestamp and never match --after or --before.
+/// Lines that fail to decode never match; their number is reported on stderr.
+#[derive(Args)]
+#[command(after_help = EXAMPLES)]
+pub struct FilterArgs {
+    /// Created at or after DATETIME
+    #[arg(long, value_name = "DATETIME", value_parser = parse_datetime)]
+    after: Option<DateTime<Utc>>,
+    /// Created before DATETIME
+    #[arg(long, value_name = "DATETIME", value_parser = parse_datetime)]
+    before: Option<DateTime<Utc>>,
+    /// With this prefix (repeatable)
+    #[arg(short, long = "prefix", value_name = "PREFIX")]
+    prefixes: Vec<String>,
+    /// With this machine ID (repeatable)
+    #[arg(short, long = "machine", value_name = "ID")]
+    machine_ids: Vec<u16>,
+    /// File of identifiers (default: stdin)
+    #[arg(value_name = "FILE")]
+    input: Option<String>,
+}
+
+const EXAMPLES: &str = "\
+Date/time formats: 2023-12-25T10:30:00, 2023-12-25 10:30:00, 2023-12-25 (UTC)
+
+Examples:
+  bcid filter --after 2024-06-01 --before 2024-06-02 --machine 37 < ids.txt
+  bcid filter -p USER -p TEAM ids.txt";
+
+/// Conditions an identifier must meet to be printed
+#[derive(Default)]
+struct Filter {
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+    prefixes: Vec<String>,
+    machine_ids: Vec<u16>,
+}
+
+impl Filter {
+    fn matches(&self, decoded: &Bcid) -> bool {
+        let in_window = match (decoded.datetime(), self.after.is_some() || self.before.is_some()) {
+            (_, false) => true,
+            (None, true) => false,
+            (Some(datetime), true) => {
+                self.after.is_none_or(|after| datetime >= after) && self.before.is_none_or(|before| datetime < before)
+            }
+        };
+        in_window
+            && (self.prefixes.is_empty() || self.prefixes.iter().any(|prefix| prefix == decoded.prefix()))
+            && (self.machine_ids.is_empty() || self.machine_ids.contains(&decoded.machine_id()))
+    }
+}
+
+pub fn run(args: FilterArgs) {
+    let filter = Filter { after: args.after, before: args.before, prefixes: args.prefixes, machine_ids: args.machine_ids };
+    let input = args.input;
+    if let (Some(after), Some(before)) = (filter.after, filter.before) {
+        if before <= after {
+            usage_error("filter", "--before must be after --after");
+        }
+    }
+
+    let input = input.unwrap_or_else(|| "-".to_string());
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut invalid = 0usize;
+    for (_, identifier) in identifier_lines(&input) {
+        match decode_identifier(&identifier) {
+            Ok(decoded) if filter.matches(&decoded) => write_line(&mut out, &identifier),
+            Ok(_) => {}
+            Err(_) => invalid += 1,
+        }
+    }
+    let _ = out.flush();
+    drop(out);
+    if invalid > 0 {
+        eprintln!("{} lines could not be decoded and were skipped", invalid);
+    }
+}
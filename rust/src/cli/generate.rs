@@ -0,0 +1,1093 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+
+use bcid::{
+    append_check_digit, derive_machine_id, encrypt_identifier, json, kubernetes_machine_id, parse_user_datetime, sign_identifier, Alphabet, Bcid, BcidError,
+    BcidGenerator, BitLayout, ClockRegression, Environment, Format, MachineIdLease, Precision, SequenceOverflow, DEFAULT_LENGTH, DEFAULT_SIGNATURE_LENGTH, MAX_LENGTH, MAX_SIGNATURE_LENGTH, MIN_LENGTH,
+};
+use clap::{Args, ValueEnum};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use super::bulk::{csv_field, identifier_lines};
+use super::config::{parse_hex_key, Config, Key};
+use super::registry::Registry;
+use super::state;
+use super::template::{iso_time, Template};
+use super::{exit_status, exit_with_error, parse_positive, usage_error, EXIT_USAGE};
+
+/// Generate identifiers
+#[derive(Args)]
+#[command(override_usage = "bcid generate -p PREFIX [OPTIONS]\n       bcid generate --stdin [OPTIONS]", after_help = EXAMPLES)]
+pub struct GenerateArgs {
+    #[command(flatten)]
+    pub flags: GenerateFlags,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid generate -p TEST
+  bcid generate -p TEST -m 2 -t '2023-12-25T10:30:00'
+  bcid generate -p TEST -t '2 hours ago'
+  bcid generate -p TEST -r
+  bcid generate -p TEST -m auto -v
+  bcid generate -p TEST -n 10000 --monotonic
+  bcid generate -p TEST --state-file /var/lib/bcid/state
+  bcid generate -p TEST --sortable
+  bcid generate -p TEST --format-version 2
+  bcid generate -p TEST --precision us
+  bcid generate -p TEST --format-version 4 --precision ms
+  bcid generate -p TEST --shard e1
+  bcid generate -p TEST --environment s
+  bcid generate -p TEST --layout ts=44,machine=12,seq=10,rand=62
+  bcid generate -p TEST --format typeid
+  bcid generate -p TEST --alphabet crockford32
+  bcid generate -p TEST --safe
+  bcid generate -p TEST --length 24
+  bcid generate -p user
+  bcid generate -p TEST --separator
+  bcid generate -p USER --registry prefixes.toml
+  bcid generate -p TEST -t 2024-01-01 --seed 42 -n 3
+  bcid generate -p TEST -n 3 --template '{id}\\t{iso_time}'
+  bcid generate -p TEST -n 1000 --output csv > batch.csv
+  bcid generate -p TEST -n 5000 --output sql --table entities --column id > seed.sql
+  printf 'USER\\nORDR,7,2024-01-01T12:00:00\\n' | bcid generate --stdin
+  BCID_SIGNING_KEY=00112233445566778899aabbccddeeff bcid generate -p TEST --sign";
+
+/// Flags that shape identifiers, shared by `generate` and `estimate`
+#[derive(Args)]
+pub struct LayoutFlags {
+    /// Prefix of 1-8 printable ASCII characters other than `_` (required to
+    /// generate); prefixes other than 4 characters are followed by a `_`
+    /// separator
+    #[arg(short, long)]
+    pub prefix: Option<String>,
+    /// Generate fully random identifiers (non-chronological)
+    #[arg(short, long)]
+    pub random: bool,
+    /// Guarantee strictly increasing identifiers within this process
+    #[arg(long)]
+    pub monotonic: bool,
+    /// Same as --format-version 2
+    #[arg(long, conflicts_with = "format_version")]
+    pub sortable: bool,
+    /// Layout of chronological identifiers: 1 (default), 2, whose fields are
+    /// fixed-width and whose string order is chronological, 3, which is 2
+    /// with a recorded timestamp precision, 4, which is 3 with a compact
+    /// timestamp and more random padding, or 5, which packs the fields into
+    /// a configurable bit layout
+    #[arg(long, value_name = "VERSION", value_parser = parse_format_version)]
+    pub format_version: Option<Format>,
+    /// Timestamp precision: s, 10ms (default), ms or us; other than 10ms
+    /// implies --format-version 3
+    #[arg(long, value_parser = parse_precision)]
+    pub precision: Option<Precision>,
+    /// Record the environment after the machine ID: p (production), s
+    /// (staging) or d (development); implies --format-version 3
+    #[arg(long, value_name = "ENV", value_parser = parse_environment)]
+    pub environment: Option<Environment>,
+    /// Shard or region written after the machine ID: 1 or 2 letters or
+    /// digits, not starting with 0; implies --format-version 3
+    #[arg(long)]
+    pub shard: Option<String>,
+    /// Bits of the v5 fields, e.g. ts=44,machine=12,seq=10,rand=62:
+    /// timestamp (ms since 1970, 42-61), machine ID (0-16), sequence within
+    /// a millisecond (0-32) and random value (the rest of 128); implies
+    /// --format-version 5
+    #[arg(long, value_name = "SPEC", value_parser = parse_bit_layout)]
+    pub layout: Option<BitLayout>,
+    /// Digits of chronological identifiers: base62 (default), crockford32,
+    /// base58 or novowels; other than base62 implies --format-version 2, and
+    /// decoding needs the same --alphabet
+    #[arg(long, value_parser = parse_alphabet)]
+    pub alphabet: Option<Alphabet>,
+    /// Identifier length with the prefix and separator, 24-64 (default: 32,
+    /// or up to 35 for v5 with prefixes over 5 characters), and at least 21
+    /// plus the length of prefixes over 4 characters; the random padding (or
+    /// the random data of random identifiers) grows or shrinks to fit
+    #[arg(long, value_name = "N", value_parser = parse_length)]
+    pub length: Option<usize>,
+}
+
+impl LayoutFlags {
+    /// Apply the flags given on the command line over `options`
+    pub fn apply(self, options: &mut GenerateOptions) {
+        let flags = [
+            (self.prefix.is_some(), "Prefix"),
+            (self.random, "Random flag"),
+            (self.monotonic, "Monotonic flag"),
+            (self.sortable, "Sortable flag"),
+            (self.format_version.is_some(), "Format version"),
+            (self.precision.is_some(), "Precision"),
+            (self.environment.is_some(), "Environment"),
+            (self.shard.is_some(), "Shard"),
+            (self.layout.is_some(), "Bit layout"),
+            (self.alphabet.is_some(), "Alphabet"),
+            (self.length.is_some(), "Length"),
+        ];
+        options.set_flags.extend(flags.iter().filter(|(is_set, _)| *is_set).map(|(_, description)| *description));
+        options.prefix = self.prefix.or(options.prefix.take());
+        options.is_random |= self.random;
+        options.is_monotonic |= self.monotonic;
+        if self.sortable {
+            options.format = Format::V2;
+        }
+        options.format = self.format_version.unwrap_or(options.format);
+        options.precision = self.precision.unwrap_or(options.precision);
+        options.environment = self.environment.or(options.environment);
+        options.shard = self.shard.or(options.shard.take());
+        options.bit_layout = self.layout.or(options.bit_layout);
+        options.alphabet = self.alphabet.unwrap_or(options.alphabet);
+        options.length = self.length.or(options.length);
+    }
+}
+
+/// Flags of `generate`, which the flag-style invocation (`bcid -p TEST`)
+/// also takes
+#[derive(Args)]
+pub struct GenerateFlags {
+    #[command(flatten)]
+    pub layout: LayoutFlags,
+    /// 16-bit machine identifier (0-65535, default: 1), `auto` to derive one
+    /// from the cloud instance ID, MAC address or hostname, `k8s` to use the
+    /// StatefulSet pod ordinal or a hash of the pod UID, or a redis://,
+    /// etcd://, consul:// or file:// URL to lease a unique one
+    #[arg(short, long, value_parser = MachineIdOption::from_spec)]
+    pub machine_id: Option<MachineIdOption>,
+    /// RFC 3339 date/time (UTC unless it has an offset), or Unix timestamp in
+    /// seconds (@1703500200 or 1703500200) or milliseconds (1703500200123),
+    /// or relative time ('2 hours ago', yesterday, +30m) (default: current
+    /// time, ignored with -r)
+    #[arg(short, long, value_name = "DATETIME")]
+    pub time: Option<String>,
+    /// If the clock goes backwards while generating: `tolerate` (default),
+    /// `wait` until it catches up, or `error`
+    #[arg(long, value_name = "POLICY", value_parser = parse_clock_regression)]
+    pub clock_regression: Option<ClockRegression>,
+    /// Save the monotonic sequence to FILE and continue from it on the next
+    /// run, so restarts never re-issue an identifier (implies --monotonic)
+    #[arg(long, value_name = "FILE")]
+    pub state_file: Option<PathBuf>,
+    /// When a layout's sequence is used up within a millisecond: `spin` until
+    /// the next one (default; `borrow` with -t), `borrow` it ahead of the
+    /// clock, or `error`
+    #[arg(long, value_name = "POLICY", value_parser = parse_sequence_overflow)]
+    pub sequence_overflow: Option<SequenceOverflow>,
+    /// Generate again any identifier whose random characters spell an
+    /// offensive word (see also --alphabet novowels)
+    #[arg(long)]
+    pub safe: bool,
+    /// Replace the last character with a check digit
+    #[arg(long)]
+    pub check_digit: bool,
+    /// Encrypt the timestamp and machine ID (decode with `bcid decode --key`)
+    #[arg(long)]
+    pub opaque: bool,
+    /// Replace the last characters with an HMAC-SHA256 signature
+    #[arg(long)]
+    pub sign: bool,
+    /// Key for --opaque and --sign (default: $BCID_SIGNING_KEY)
+    #[arg(long, value_name = "HEX", value_parser = parse_hex_key)]
+    pub key: Option<Key>,
+    /// Signature characters, 1-12, after the first 16 characters that follow
+    /// the prefix (default: 8)
+    #[arg(long, value_name = "N", value_parser = parse_signature_length)]
+    pub signature_length: Option<usize>,
+    /// Write a `_` separator after 4-character prefixes too (e.g.
+    /// TEST_jqTZNAWSyaab..., one character longer than --length)
+    #[arg(long)]
+    pub separator: bool,
+    /// Output `bcid` (default) or `typeid` (e.g. test_01jgjfgr4828t4059e8aqjb60j)
+    #[arg(long, value_enum)]
+    pub format: Option<IdentifierSyntax>,
+    /// Print `text` (default), or `csv` or `tsv` rows of id, prefix,
+    /// timestamp_iso, machine_id and random (the random part of random
+    /// identifiers) after a header row, `jsonl` objects of the identifier and
+    /// its decoded components, or `sql` INSERT statements of up to 1000 rows
+    /// or a PostgreSQL `copy` block for --table
+    #[arg(long, value_name = "FORMAT", value_enum)]
+    pub output: Option<GeneratedOutput>,
+    /// Table of `sql` and `copy` output, written as given
+    #[arg(long)]
+    pub table: Option<String>,
+    /// Column of `sql` and `copy` output (default: id)
+    #[arg(long)]
+    pub column: Option<String>,
+    /// Print each identifier through TEMPLATE, e.g. '{prefix}-{iso_time}-{machine_id}: {id}',
+    /// with placeholders id, prefix, type, format, timestamp, iso_time,
+    /// machine_id, environment, shard, sequence, random and random_part
+    /// (empty when absent), and \t and \n for tabs and line breaks
+    #[arg(long, value_parser = Template::parse)]
+    pub template: Option<Template>,
+    /// Warn if the prefix is not listed in this prefix registry (default:
+    /// `registry` in the config file)
+    #[arg(long, value_name = "FILE")]
+    pub registry: Option<PathBuf>,
+    /// Seed the random components for reproducible output (combine with -t);
+    /// never use seeded identifiers where they must be unguessable
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Number of identifiers to generate, one per line (default: 1)
+    #[arg(short = 'n', long, value_parser = parse_positive::<usize>)]
+    pub count: Option<usize>,
+    /// Generate one identifier per line of stdin, which is a prefix or a
+    /// `prefix,machine_id,datetime` record (empty or missing fields default
+    /// to -m and -t), printed and flushed line by line
+    #[arg(long)]
+    pub stdin: bool,
+    /// Report how the machine ID was chosen on stderr
+    #[arg(short, long)]
+    pub verbose: bool,
+    /// Print nothing but the identifiers: no warnings on stderr
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+/// How identifiers are written (`--format`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum IdentifierSyntax {
+    Bcid,
+    Typeid,
+}
+
+/// Identifiers generated at a time with `-n`
+const BATCH_SIZE: usize = 4096;
+
+/// How generated identifiers are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum GeneratedOutput {
+    /// One identifier per line
+    #[default]
+    Text,
+    /// Comma-separated [`GENERATED_HEADER`] columns, after a header row
+    Csv,
+    /// Tab-separated [`GENERATED_HEADER`] columns, after a header row
+    Tsv,
+    /// One JSON object per line: `id` as printed, then the decoded
+    /// components
+    Jsonl,
+    /// `INSERT` statements of up to [`SQL_BATCH_ROWS`] rows each
+    Sql,
+    /// A PostgreSQL `COPY ... FROM stdin` block
+    Copy,
+}
+
+/// Columns of CSV and TSV output
+const GENERATED_HEADER: [&str; 5] = ["id", "prefix", "timestamp_iso", "machine_id", "random"];
+
+/// Rows per `INSERT` statement of SQL output
+const SQL_BATCH_ROWS: usize = 1000;
+
+impl GeneratedOutput {
+    /// Whether the output goes into a table (`--table` and `--column`)
+    fn is_sql(&self) -> bool {
+        matches!(self, GeneratedOutput::Sql | GeneratedOutput::Copy)
+    }
+}
+
+/// Formats generated identifiers as `--output` and `--template` ask,
+/// keeping track of the open `INSERT` statement of SQL output
+struct Records<'a> {
+    output: GeneratedOutput,
+    template: Option<&'a Template>,
+    table: &'a str,
+    column: &'a str,
+    /// Rows written to the open `INSERT` statement
+    rows: usize,
+}
+
+impl Records<'_> {
+    /// Text to print before the first identifier
+    fn header(&self) -> Option<String> {
+        match self.output {
+            GeneratedOutput::Csv => Some(format!("{}\n", GENERATED_HEADER.join(","))),
+            GeneratedOutput::Tsv => Some(format!("{}\n", GENERATED_HEADER.join("\t"))),
+            GeneratedOutput::Copy => Some(format!("COPY {} ({}) FROM stdin;\n", self.table, self.column)),
+            GeneratedOutput::Text | GeneratedOutput::Jsonl | GeneratedOutput::Sql => None,
+        }
+    }
+
+    /// Text to print for `id` (as printed) and the fields of `generated`
+    ///
+    /// SQL rows are written with the separator before them, so that the
+    /// statement can be ended after the last one.
+    fn record(&mut self, id: String, generated: &Bcid) -> String {
+        let fields = || {
+            [
+                id.clone(),
+                generated.prefix().to_string(),
+                iso_time(generated).unwrap_or_default(),
+                generated.machine_id().to_string(),
+                match generated.random() {
+                    Some(random) => random.to_string(),
+                    None => generated.random_part().unwrap_or_default().to_string(),
+                },
+            ]
+        };
+        match self.output {
+            GeneratedOutput::Text => match self.template {
+                Some(template) => format!("{}\n", template.render(&id, generated)),
+                None => format!("{}\n", id),
+            },
+            GeneratedOutput::Csv => format!("{}\n", fields().map(|field| csv_field(&field)).join(",")),
+            GeneratedOutput::Tsv => format!("{}\n", fields().join("\t")),
+            GeneratedOutput::Jsonl => {
+                let id = json::Object::new().string("id", Some(&id)).finish();
+                let decoded = generated.to_json();
+                format!("{},{}\n", &id[..id.len() - 1], &decoded[1..])
+            }
+            GeneratedOutput::Copy => format!("{}\n", id.replace('\\', "\\\\")),
+            GeneratedOutput::Sql => {
+                let separator = match self.rows {
+                    0 => format!("INSERT INTO {} ({}) VALUES\n", self.table, self.column),
+                    _ => ",\n".to_string(),
+                };
+                self.rows += 1;
+                let row = format!("{}  ('{}')", separator, id.replace('\'', "''"));
+                if self.rows < SQL_BATCH_ROWS {
+                    return row;
+                }
+                self.rows = 0;
+                format!("{};\n", row)
+            }
+        }
+    }
+
+    /// Text to print after the last identifier
+    fn footer(&self) -> Option<String> {
+        match self.output {
+            GeneratedOutput::Sql if self.rows > 0 => Some(";\n".to_string()),
+            GeneratedOutput::Copy => Some("\\.\n".to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// URL schemes of the coordinators machine IDs can be leased from
+const COORDINATOR_SCHEMES: [&str; 4] = ["redis://", "etcd://", "consul://", "file://"];
+
+/// How the machine ID is chosen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MachineIdOption {
+    Fixed(u16),
+    /// Derived from the host (`-m auto`)
+    Auto,
+    /// Derived from the Kubernetes pod (`-m k8s`)
+    Kubernetes,
+    /// Leased from a coordinator (`-m redis://HOST:PORT`, `etcd://...`, `consul://...` or `file://DIR`)
+    Leased(String),
+}
+
+impl MachineIdOption {
+    /// Parse a machine ID number, `auto` or a coordinator URL
+    pub fn from_spec(value: &str) -> Result<Self, String> {
+        match value {
+            "auto" => Ok(MachineIdOption::Auto),
+            "k8s" => Ok(MachineIdOption::Kubernetes),
+            url if COORDINATOR_SCHEMES.iter().any(|scheme| url.starts_with(scheme)) => {
+                Ok(MachineIdOption::Leased(url.to_string()))
+            }
+            _ => value.parse().map(MachineIdOption::Fixed).map_err(|_| {
+                "Invalid machine ID - must be a number between 0 and 65535, 'auto', 'k8s' or a redis://, etcd://, consul:// or file:// URL"
+                    .to_string()
+            }),
+        }
+    }
+
+    /// Resolve to a concrete machine ID, reporting the source when verbose
+    ///
+    /// A leased machine ID comes with its lease, which must be kept until the
+    /// ID is no longer used; dropping it gives the ID back.
+    pub fn resolve(&self, verbose: bool) -> (u16, Option<MachineIdLease>) {
+        match self {
+            MachineIdOption::Fixed(id) => {
+                if verbose {
+                    eprintln!("Machine ID: {} (source: command line)", id);
+                }
+                (*id, None)
+            }
+            MachineIdOption::Auto | MachineIdOption::Kubernetes => {
+                let derived = match self {
+                    MachineIdOption::Auto => derive_machine_id(),
+                    _ => kubernetes_machine_id(),
+                }
+                .unwrap_or_else(|e| exit_with_error(e));
+                if verbose {
+                    eprintln!("Machine ID: {} (source: {} {:?})", derived.id, derived.source, derived.input);
+                }
+                (derived.id, None)
+            }
+            MachineIdOption::Leased(url) => {
+                let lease = MachineIdLease::acquire(url).unwrap_or_else(|e| exit_with_error(e));
+                if verbose {
+                    eprintln!("Machine ID: {} (source: leased from {})", lease.id(), redact_url(url));
+                }
+                (lease.id(), Some(lease))
+            }
+        }
+    }
+}
+
+/// `url` without the password it may carry
+pub fn redact_url(url: &str) -> String {
+    match url.split_once("://") {
+        Some((scheme, rest)) => match rest.rsplit_once('@') {
+            Some((_, host)) => format!("{}://***@{}", scheme, host),
+            None => url.to_string(),
+        },
+        None => url.to_string(),
+    }
+}
+
+/// Parse a `--format-version` value (`1`, `2`, `v1` or `v2`)
+pub fn parse_format_version(value: &str) -> Result<Format, String> {
+    Format::parse_version(value).ok_or_else(|| format!("Invalid format version '{}' - must be 1, 2, 3, 4 or 5", value))
+}
+
+/// Parse a `--layout` value
+pub fn parse_bit_layout(value: &str) -> Result<BitLayout, String> {
+    value.parse().map_err(|e| format!("{}", e))
+}
+
+/// Parse a `--precision` value
+pub fn parse_precision(value: &str) -> Result<Precision, String> {
+    Precision::from_name(value).ok_or_else(|| format!("Invalid precision '{}' - must be s, 10ms, ms or us", value))
+}
+
+/// Parse an `--environment` value
+pub fn parse_environment(value: &str) -> Result<Environment, String> {
+    Environment::from_name(value).ok_or_else(|| {
+        format!("Invalid environment '{}' - must be p (production), s (staging) or d (development)", value)
+    })
+}
+
+/// Parse an `--alphabet` value
+pub fn parse_alphabet(value: &str) -> Result<Alphabet, String> {
+    Alphabet::from_name(value)
+        .ok_or_else(|| format!("Invalid alphabet '{}' - must be base62, crockford32, base58 or novowels", value))
+}
+
+/// Parse a `--clock-regression` value
+pub fn parse_clock_regression(value: &str) -> Result<ClockRegression, String> {
+    ClockRegression::from_name(value)
+        .ok_or_else(|| format!("Invalid clock regression policy '{}' - must be tolerate, wait or error", value))
+}
+
+/// Parse a `--sequence-overflow` value
+pub fn parse_sequence_overflow(value: &str) -> Result<SequenceOverflow, String> {
+    SequenceOverflow::from_name(value)
+        .ok_or_else(|| format!("Invalid sequence overflow policy '{}' - must be spin, borrow or error", value))
+}
+
+/// Parse a `--length` value
+pub fn parse_length(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|length| (MIN_LENGTH..=MAX_LENGTH).contains(length))
+        .ok_or_else(|| format!("must be between {} and {}", MIN_LENGTH, MAX_LENGTH))
+}
+
+/// Parse a `--signature-length` value
+pub fn parse_signature_length(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|length| (1..=MAX_SIGNATURE_LENGTH).contains(length))
+        .ok_or_else(|| format!("must be between 1 and {}", MAX_SIGNATURE_LENGTH))
+}
+
+/// Options for generating identifiers
+pub struct GenerateOptions {
+    pub prefix: Option<String>,
+    pub machine_id: MachineIdOption,
+    pub user_datetime: Option<String>,
+    pub is_random: bool,
+    pub is_monotonic: bool,
+    /// Generate again identifiers whose random characters spell a denylisted word
+    pub is_safe: bool,
+    /// What to do if the clock goes backwards
+    pub clock_regression: ClockRegression,
+    /// What to do when the sequence of a millisecond is used up
+    pub sequence_overflow: SequenceOverflow,
+    /// File the generator state is saved to and resumed from
+    pub state_file: Option<PathBuf>,
+    pub format: Format,
+    /// Timestamp precision, which is not 10ms only in the v3 layout
+    pub precision: Precision,
+    /// Environment recorded after the machine ID, which implies the v3
+    /// layout unless it is v4
+    pub environment: Option<Environment>,
+    /// Shard written after the environment, which implies the v3 layout
+    /// unless it is v4
+    pub shard: Option<String>,
+    /// Bit layout, which implies the v5 layout
+    pub bit_layout: Option<BitLayout>,
+    pub alphabet: Alphabet,
+    /// Characters per identifier, or `None` for the default of the layout
+    pub length: Option<usize>,
+    /// Write a separator after 4-character prefixes
+    pub is_separated: bool,
+    /// Render identifiers as TypeIDs
+    pub is_typeid: bool,
+    /// Print identifiers through a template
+    pub template: Option<Template>,
+    /// Print identifiers as text, CSV, TSV or SQL
+    pub output: GeneratedOutput,
+    /// Table of SQL output
+    pub table: Option<String>,
+    /// Column of SQL output
+    pub column: String,
+    /// End identifiers in a check digit
+    pub with_check_digit: bool,
+    /// Encrypt the structured fields
+    pub is_opaque: bool,
+    pub is_signed: bool,
+    pub signing_key: Option<Vec<u8>>,
+    pub signature_length: usize,
+    /// Known prefixes, to warn about unknown ones
+    pub registry: Option<Registry>,
+    /// Seed for a deterministic RNG
+    pub seed: Option<u64>,
+    pub count: usize,
+    /// Read a prefix, or prefix, machine ID and datetime, per line of stdin
+    pub is_stdin: bool,
+    pub is_verbose: bool,
+    /// Print nothing but the identifiers, without warnings
+    pub is_quiet: bool,
+    /// Descriptions of the flags given on the command line, for "ignored
+    /// when decoding" warnings
+    pub set_flags: Vec<&'static str>,
+}
+
+impl Default for MachineIdOption {
+    fn default() -> Self {
+        MachineIdOption::Fixed(1)
+    }
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions {
+            prefix: None,
+            machine_id: MachineIdOption::default(),
+            user_datetime: None,
+            is_random: false,
+            is_monotonic: false,
+            is_safe: false,
+            clock_regression: ClockRegression::default(),
+            sequence_overflow: SequenceOverflow::default(),
+            state_file: None,
+            format: Format::V1,
+            precision: Precision::default(),
+            environment: None,
+            shard: None,
+            bit_layout: None,
+            alphabet: Alphabet::Base62,
+            length: None,
+            is_separated: false,
+            is_typeid: false,
+            template: None,
+            output: GeneratedOutput::default(),
+            table: None,
+            column: "id".to_string(),
+            with_check_digit: false,
+            is_opaque: false,
+            is_signed: false,
+            signing_key: None,
+            signature_length: DEFAULT_SIGNATURE_LENGTH,
+            registry: None,
+            seed: None,
+            count: 1,
+            is_stdin: false,
+            is_verbose: false,
+            is_quiet: false,
+            set_flags: Vec::new(),
+        }
+    }
+}
+
+impl GenerateOptions {
+    /// Defaults from the configuration file
+    pub fn from_config(config: &Config) -> Self {
+        GenerateOptions {
+            prefix: config.prefix.clone(),
+            machine_id: config.machine_id.clone().unwrap_or_default(),
+            clock_regression: config.clock_regression.unwrap_or_default(),
+            sequence_overflow: config.sequence_overflow.unwrap_or_default(),
+            format: config.format.unwrap_or_default(),
+            precision: config.precision.unwrap_or_default(),
+            environment: config.environment,
+            shard: config.shard.clone(),
+            bit_layout: config.bit_layout,
+            alphabet: config.alphabet.unwrap_or_default(),
+            length: config.length,
+            is_separated: config.separator,
+            is_safe: config.safe,
+            with_check_digit: config.check_digit,
+            signing_key: config.signing_key.clone(),
+            registry: config.registry.as_deref().map(Registry::load),
+            ..GenerateOptions::default()
+        }
+    }
+}
+
+impl GenerateFlags {
+    /// Apply the flags given on the command line over `options`
+    pub fn apply(self, options: &mut GenerateOptions) {
+        self.layout.apply(options);
+        let flags = [
+            (self.machine_id.is_some(), "Machine ID"),
+            (self.time.is_some(), "User datetime"),
+            (self.clock_regression.is_some(), "Clock regression"),
+            (self.state_file.is_some(), "State file"),
+            (self.sequence_overflow.is_some(), "Sequence overflow"),
+            (self.safe, "Safe flag"),
+            (self.check_digit, "Check digit flag"),
+            (self.opaque, "Opaque flag"),
+            (self.sign, "Sign flag"),
+            (self.key.is_some(), "Key"),
+            (self.signature_length.is_some(), "Signature length"),
+            (self.separator, "Separator flag"),
+            (self.format.is_some(), "Output format"),
+            (self.output.is_some(), "Output"),
+            (self.table.is_some(), "Table"),
+            (self.column.is_some(), "Column"),
+            (self.template.is_some(), "Template"),
+            (self.registry.is_some(), "Registry"),
+            (self.seed.is_some(), "Seed"),
+            (self.count.is_some(), "Count"),
+            (self.stdin, "Stdin flag"),
+            (self.verbose, "Verbose flag"),
+            (self.quiet, "Quiet flag"),
+        ];
+        options.set_flags.extend(flags.iter().filter(|(is_set, _)| *is_set).map(|(_, description)| *description));
+        if let Some(machine_id) = self.machine_id {
+            options.machine_id = machine_id;
+        }
+        options.user_datetime = self.time;
+        options.clock_regression = self.clock_regression.unwrap_or(options.clock_regression);
+        options.state_file = self.state_file;
+        options.sequence_overflow = self.sequence_overflow.unwrap_or(options.sequence_overflow);
+        options.is_safe |= self.safe;
+        options.with_check_digit |= self.check_digit;
+        options.is_opaque = self.opaque;
+        options.is_signed = self.sign;
+        options.signing_key = self.key.or(options.signing_key.take());
+        options.signature_length = self.signature_length.unwrap_or(options.signature_length);
+        options.is_separated |= self.separator;
+        options.is_typeid = self.format == Some(IdentifierSyntax::Typeid);
+        options.output = self.output.unwrap_or_default();
+        options.table = self.table;
+        if let Some(column) = self.column {
+            options.column = column;
+        }
+        options.template = self.template;
+        if let Some(registry) = self.registry {
+            options.registry = Some(Registry::load(&registry));
+        }
+        options.seed = self.seed;
+        options.count = self.count.unwrap_or(options.count);
+        options.is_stdin = self.stdin;
+        options.is_verbose = self.verbose;
+        options.is_quiet = self.quiet;
+    }
+}
+
+pub fn run(args: GenerateArgs, config: &Config) {
+    let mut options = GenerateOptions::from_config(config);
+    args.flags.apply(&mut options);
+    if options.prefix.is_none() && !options.is_stdin {
+        usage_error("generate", "Prefix is required when generating an identifier");
+    }
+    generate(options);
+}
+
+/// Generate `options.count` identifiers, one per line
+pub fn generate(mut options: GenerateOptions) {
+    let prefix = options.prefix.as_deref().unwrap_or_default();
+    let user_datetime = options.user_datetime.as_deref();
+
+    if options.is_stdin {
+        if options.set_flags.contains(&"Prefix") {
+            usage_error("generate", "--stdin cannot be used with --prefix, as each line names its prefix");
+        }
+        if options.set_flags.contains(&"Count") {
+            usage_error("generate", "--stdin cannot be used with --count, as each line generates one identifier");
+        }
+        if options.state_file.is_some() {
+            usage_error("generate", "--stdin cannot be used with a state file");
+        }
+    }
+    if options.template.is_some() && options.output != GeneratedOutput::Text {
+        usage_error("generate", "--output cannot be used with --template");
+    }
+    if options.output.is_sql() && options.table.is_none() {
+        usage_error("generate", "--output sql and copy require --table");
+    }
+    if !options.output.is_sql() && (options.set_flags.contains(&"Table") || options.set_flags.contains(&"Column")) {
+        usage_error("generate", "--table and --column require --output sql or copy");
+    }
+    if options.is_random && options.is_monotonic {
+        usage_error("generate", "Random identifiers cannot be monotonic");
+    }
+    if options.is_quiet && options.is_verbose {
+        usage_error("generate", "--quiet cannot be used with --verbose");
+    }
+    if options.is_quiet {
+        // The registry is only used to warn about unknown prefixes
+        options.registry = None;
+    }
+    if options.state_file.is_some() {
+        if options.is_random {
+            usage_error("generate", "Random identifiers cannot use a state file");
+        }
+        options.is_monotonic = true;
+    }
+    if options.is_random && options.format != Format::V1 {
+        if options.set_flags.contains(&"Sortable flag") || options.set_flags.contains(&"Format version") {
+            usage_error("generate", &format!("Random identifiers cannot use the {} layout", options.format));
+        }
+        // A configured default layout only applies to chronological identifiers
+        options.format = Format::V1;
+    }
+    if options.bit_layout.is_some() {
+        let explicit_format = options.set_flags.contains(&"Sortable flag") || options.set_flags.contains(&"Format version");
+        let conflict = if options.is_random {
+            Some("random identifiers")
+        } else if options.format != Format::V5 && explicit_format {
+            Some("layouts other than v5")
+        } else if options.set_flags.contains(&"Precision") {
+            Some("a precision, as v5 timestamps are in milliseconds")
+        } else if options.is_typeid {
+            Some("TypeIDs")
+        } else if options.with_check_digit || options.is_opaque || options.is_signed {
+            Some("check digits, opaque or signed identifiers")
+        } else {
+            None
+        };
+        match conflict {
+            Some(conflict) if options.set_flags.contains(&"Bit layout") => {
+                usage_error("generate", &format!("A bit layout cannot be used with {}", conflict))
+            }
+            // A configured default bit layout only applies where it can
+            Some(_) => options.bit_layout = None,
+            None => options.format = Format::V5,
+        }
+    }
+    // A fixed time never reaches the next millisecond
+    if user_datetime.is_some() && options.sequence_overflow == SequenceOverflow::Spin {
+        if options.set_flags.contains(&"Sequence overflow") {
+            usage_error("generate", "The spin sequence overflow policy cannot be used with a fixed time (-t)");
+        }
+        options.sequence_overflow = SequenceOverflow::Borrow;
+    }
+    if options.precision != Precision::default() {
+        let precision = options.precision;
+        let conflict = if options.is_random {
+            Some("random identifiers")
+        } else if options.format == Format::V5 {
+            Some("the v5 layout")
+        } else if !matches!(options.format, Format::V3 | Format::V4) && options.set_flags.contains(&"Format version") {
+            Some("the v1 and v2 layouts")
+        } else if options.is_typeid {
+            Some("TypeIDs")
+        } else if options.with_check_digit || options.is_opaque || options.is_signed {
+            Some("check digits, opaque or signed identifiers")
+        } else {
+            None
+        };
+        match conflict {
+            Some(conflict) if options.set_flags.contains(&"Precision") => {
+                usage_error("generate", &format!("The {} precision cannot be used with {}", precision, conflict))
+            }
+            // A configured default precision only applies where it can
+            Some(_) => options.precision = Precision::default(),
+            None if options.format != Format::V4 => options.format = Format::V3,
+            None => {}
+        }
+    }
+    if options.shard.is_some() || options.environment.is_some() {
+        let conflict = if options.is_random {
+            Some("random identifiers")
+        } else if options.format == Format::V5 {
+            Some("the v5 layout")
+        } else if !matches!(options.format, Format::V3 | Format::V4) && options.set_flags.contains(&"Format version") {
+            Some("the v1 and v2 layouts")
+        } else if options.is_typeid {
+            Some("TypeIDs")
+        } else if options.with_check_digit || options.is_opaque || options.is_signed {
+            Some("check digits, opaque or signed identifiers")
+        } else {
+            None
+        };
+        match conflict {
+            Some(conflict) if options.set_flags.contains(&"Shard") => {
+                usage_error("generate", &format!("A shard cannot be used with {}", conflict))
+            }
+            Some(conflict) if options.set_flags.contains(&"Environment") => {
+                usage_error("generate", &format!("An environment cannot be used with {}", conflict))
+            }
+            // A configured default shard or environment only applies where it can
+            Some(_) => {
+                options.shard = None;
+                options.environment = None;
+            }
+            None if options.format != Format::V4 => options.format = Format::V3,
+            None => {}
+        }
+    }
+    if options.alphabet != Alphabet::Base62 {
+        let alphabet = options.alphabet;
+        let conflict = if options.is_random {
+            Some("random identifiers")
+        } else if options.format == Format::V1 && options.set_flags.contains(&"Format version") {
+            Some("the v1 layout")
+        } else if options.is_typeid {
+            Some("TypeIDs")
+        } else if options.with_check_digit || options.is_opaque || options.is_signed {
+            Some("check digits, opaque or signed identifiers")
+        } else {
+            None
+        };
+        match conflict {
+            Some(conflict) if options.set_flags.contains(&"Alphabet") => {
+                usage_error("generate", &format!("The {} alphabet cannot be used with {}", alphabet, conflict))
+            }
+            // A configured default alphabet only applies where it can
+            Some(_) => options.alphabet = Alphabet::Base62,
+            None if options.format == Format::V1 => options.format = Format::V2,
+            None => {}
+        }
+    }
+    if options.is_typeid && options.length.is_some_and(|length| length != DEFAULT_LENGTH) {
+        if options.set_flags.contains(&"Length") {
+            usage_error("generate", "TypeIDs have a fixed length");
+        }
+        // A configured length only applies to BCID output
+        options.length = None;
+    }
+    if options.is_typeid && options.is_separated {
+        if options.set_flags.contains(&"Separator flag") {
+            usage_error("generate", "TypeIDs always have a separator");
+        }
+        // A configured separator only applies to BCID output
+        options.is_separated = false;
+    }
+    if options.is_random && options.is_typeid {
+        usage_error("generate", "Random identifiers cannot be rendered as TypeIDs");
+    }
+    if options.is_typeid && options.with_check_digit {
+        if options.set_flags.contains(&"Check digit flag") {
+            usage_error("generate", "TypeIDs cannot carry a check digit");
+        }
+        // A configured check digit only applies to BCID output
+        options.with_check_digit = false;
+    }
+    let key = match (options.is_signed || options.is_opaque, &options.signing_key) {
+        (false, _) => None,
+        (true, Some(key)) => Some(key.as_slice()),
+        (true, None) => usage_error("generate", "A key is required to sign or encrypt identifiers (--key or BCID_SIGNING_KEY)"),
+    };
+    let opaque_key = key.filter(|_| options.is_opaque);
+    let signing_key = key.filter(|_| options.is_signed);
+    if opaque_key.is_some() && options.is_typeid {
+        usage_error("generate", "TypeIDs cannot be opaque");
+    }
+    if signing_key.is_some() {
+        if options.is_typeid {
+            usage_error("generate", "TypeIDs cannot be signed");
+        }
+        if options.with_check_digit {
+            usage_error("generate", "Signed identifiers cannot carry a check digit");
+        }
+        // The signature must not overlap the fields, which end 16 characters after the prefix
+        let fields_start = if prefix.len() == 4 || options.is_stdin { 4 } else { prefix.len() + 1 };
+        let length = options.length.unwrap_or(DEFAULT_LENGTH);
+        let max = length.saturating_sub(fields_start + 16);
+        if options.signature_length > max {
+            usage_error("generate", &format!(
+                "Signature length {} does not fit {}-character identifiers with this prefix (at most {})",
+                options.signature_length, length, max
+            ));
+        }
+    }
+    if options.is_random && user_datetime.is_some() && !options.is_quiet {
+        eprintln!("Warning: Time parameter is ignored when generating random identifiers");
+    }
+
+    // Held until generation ends
+    let (machine_id, _lease) = options.machine_id.resolve(options.is_verbose);
+    let new_generator = |prefix: &str, machine_id: u16, user_datetime: Option<&str>| {
+        let generator = match options.seed {
+            Some(seed) => BcidGenerator::with_rng(prefix, machine_id, StdRng::seed_from_u64(seed)),
+            None => BcidGenerator::new(prefix, machine_id),
+        };
+        let mut generator = generator?
+            .format(options.format)
+            .precision(options.precision)
+            .alphabet(options.alphabet)
+            .random(options.is_random)
+            .monotonic(options.is_monotonic)
+            .safe(options.is_safe)
+            .clock_regression(options.clock_regression)
+            .sequence_overflow(options.sequence_overflow);
+        if let Some(length) = options.length {
+            generator = generator.length(length)?;
+        }
+        if let Some(shard) = &options.shard {
+            generator = generator.shard(shard);
+        }
+        if let Some(environment) = options.environment {
+            generator = generator.environment(environment);
+        }
+        if let Some(layout) = options.bit_layout {
+            generator = generator.bit_layout(layout)?;
+        }
+        if let Some(datetime) = user_datetime.filter(|_| !options.is_random) {
+            let datetime = parse_user_datetime(datetime)?;
+            generator = generator.clock(move || datetime);
+        }
+        Ok(generator)
+    };
+    let render = |generated: &Bcid| {
+        let identifier = if options.is_separated { generated.to_separated() } else { generated.to_string() };
+        let identifier = match opaque_key {
+            Some(key) => encrypt_identifier(&identifier, key)?,
+            None => identifier,
+        };
+        let identifier = match (options.is_typeid, options.with_check_digit) {
+            (true, _) => Bcid::parse(&identifier)?.to_typeid()?,
+            (false, true) => append_check_digit(&identifier)?,
+            (false, false) => identifier,
+        };
+        match signing_key {
+            Some(key) => sign_identifier(&identifier, key, options.signature_length),
+            None => Ok(identifier),
+        }
+    };
+    let mut records = Records {
+        output: options.output,
+        template: options.template.as_ref(),
+        table: options.table.as_deref().unwrap_or_default(),
+        column: &options.column,
+        rows: 0,
+    };
+    if options.is_stdin {
+        generate_lines(machine_id, user_datetime, options.registry.as_ref(), new_generator, render, &mut records);
+        return;
+    }
+
+    let mut generator = new_generator(prefix, machine_id, user_datetime).unwrap_or_else(|e| exit_with_error(e));
+    if let Some(state_file) = &options.state_file {
+        generator = generator.resume(state::load(state_file));
+    }
+    if let Some(registry) = &options.registry {
+        registry.lookup(prefix);
+    }
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    write_text(&mut out, records.header());
+    let mut remaining = options.count;
+    while remaining > 0 {
+        let batch = generator.try_next_batch(remaining.min(BATCH_SIZE)).unwrap_or_else(|e| exit_with_error(e));
+        remaining -= batch.len();
+        // Saved before printing, so that no printed identifier can be issued again
+        if let Some(state_file) = &options.state_file {
+            state::save(state_file, &generator.state());
+        }
+        for generated in batch {
+            let identifier = render(&generated).unwrap_or_else(|e| exit_with_error(e));
+            write_text(&mut out, Some(records.record(identifier, &generated)));
+        }
+    }
+    write_text(&mut out, records.footer());
+    if out.flush().is_err() {
+        process::exit(0);
+    }
+}
+
+/// Print `text`, if any, exiting quietly if downstream closed the pipe
+/// (e.g. `| head`)
+fn write_text(out: &mut impl Write, text: Option<String>) {
+    if let Some(text) = text {
+        if out.write_all(text.as_bytes()).is_err() {
+            process::exit(0);
+        }
+    }
+}
+
+/// Generate one identifier for each non-blank line of stdin, which is a
+/// prefix or a `prefix,machine_id,datetime` record whose empty or missing
+/// fields default to `machine_id` and `user_datetime`
+///
+/// Lines at the default time share a generator per prefix and machine ID,
+/// so `--monotonic` orders them; lines with their own datetime are
+/// generated independently. Each identifier is flushed as soon as it is
+/// printed, for programs that wait for it before writing the next line.
+/// Exits at the first line that cannot be generated.
+fn generate_lines(
+    machine_id: u16,
+    user_datetime: Option<&str>,
+    registry: Option<&Registry>,
+    new_generator: impl Fn(&str, u16, Option<&str>) -> Result<BcidGenerator, BcidError>,
+    render: impl Fn(&Bcid) -> Result<String, BcidError>,
+    records: &mut Records,
+) {
+    let mut generators = HashMap::new();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    write_text(&mut out, records.header());
+    for (line, record) in identifier_lines("-") {
+        let mut generate = |prefix: &str, machine_id: u16, datetime: Option<&str>| {
+            if let Some(registry) = registry {
+                registry.lookup(prefix);
+            }
+            let generated = match datetime {
+                Some(datetime) => new_generator(prefix, machine_id, Some(datetime))?.try_next()?,
+                None => match generators.entry((prefix.to_string(), machine_id)) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(new_generator(prefix, machine_id, user_datetime)?),
+                }
+                .try_next()?,
+            };
+            Ok(records.record(render(&generated)?, &generated))
+        };
+        // Malformed records are invalid arguments, like the flags they default to
+        let identifier = parse_record(&record, machine_id)
+            .map_err(|e| (e, EXIT_USAGE))
+            .and_then(|(prefix, machine_id, datetime)| {
+                generate(prefix, machine_id, datetime).map_err(|e: BcidError| (e.to_string(), exit_status(&e)))
+            });
+        let text = identifier.unwrap_or_else(|(e, status)| {
+            eprintln!("Error: line {}: {}", line, e);
+            process::exit(status);
+        });
+        write_text(&mut out, Some(text));
+        if out.flush().is_err() {
+            process::exit(0);
+        }
+    }
+    write_text(&mut out, records.footer());
+}
+
+/// The prefix, machine ID and datetime (if given) of a `--stdin` line
+fn parse_record(record: &str, default_machine_id: u16) -> Result<(&str, u16, Option<&str>), String> {
+    let mut fields = record.split(',').map(str::trim);
+    let prefix = fields.next().unwrap_or_default();
+    let machine_id = match fields.next() {
+        None | Some("") => default_machine_id,
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("Invalid machine ID '{}' - must be a number between 0 and 65535", value))?,
+    };
+    let datetime = fields.next().filter(|datetime| !datetime.is_empty());
+    if fields.next().is_some() {
+        return Err("Too many fields - expected prefix,machine_id,datetime".to_string());
+    }
+    Ok((prefix, machine_id, datetime))
+}
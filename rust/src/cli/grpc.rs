@@ -0,0 +1,249 @@
+//! gRPC `BcidService` of `bcid serve --grpc-port`, built with the `grpc`
+//! feature (see `proto/bcid.proto`)
+//!
+//! The RPCs share the settings, monotonic generation, tokens, rate limits
+//! and metrics of the HTTP server. Invalid requests fail with
+//! `INVALID_ARGUMENT` and a `reason: message` status message, with the
+//! reason codes of HTTP errors; calls without a valid token (`authorization:
+//! Bearer TOKEN` or `x-api-key: TOKEN` metadata) fail with `UNAUTHENTICATED`,
+//! calls over the rate limit with `RESOURCE_EXHAUSTED`, and generation while
+//! the lease on the machine ID is lost with `UNAVAILABLE`. Batches are
+//! generated and sent [`BATCH_CHUNK`] identifiers at a time.
+
+// The service trait returns tonic's `Status`, however large
+#![allow(clippy::result_large_err)]
+
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+use bcid::{Bcid, IdentifierType};
+use chrono::SecondsFormat;
+use tokio_stream::wrappers::TcpListenerStream;
+use tokio_stream::Stream;
+use tonic::{Request, Response, Status};
+use tracing::{debug, info};
+
+use super::serve::{authenticate, charge, GenerateParams, Generation, RequestError, Server, BATCH_CHUNK, POLL_INTERVAL, SHUTDOWN};
+
+/// Messages and service generated from `proto/bcid.proto` by build.rs
+pub mod proto {
+    tonic::include_proto!("bcid.v1");
+}
+
+use proto::bcid_service_server::{BcidService, BcidServiceServer};
+use proto::{DecodeRequest, DecodeResponse, GenerateBatchRequest, GenerateRequest, GenerateResponse, GenerateStreamRequest};
+
+/// Identifiers sent by a batch RPC
+type IdStream = Pin<Box<dyn Stream<Item = Result<GenerateResponse, Status>> + Send>>;
+
+/// Serve the gRPC service on a TCP address until a shutdown signal arrives,
+/// over TLS if given the certificate, key and client CA files
+pub fn serve(bind: &str, port: u16, server: Arc<Server>, tls: Option<(&Path, &Path, Option<&Path>)>) -> io::Result<()> {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind((bind, port)).await?;
+        let mut builder = tonic::transport::Server::builder();
+        #[cfg(feature = "tls")]
+        if let Some((cert, key, client_ca)) = tls {
+            builder = builder.tls_config(super::tls::grpc_config(cert, key, client_ca)?).map_err(io::Error::other)?;
+        }
+        #[cfg(not(feature = "tls"))]
+        let _ = tls;
+        let address = listener.local_addr()?.to_string();
+        info!(address, "Listening for gRPC on {}", address);
+        let shutdown = async {
+            while !SHUTDOWN.load(Ordering::SeqCst) {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        };
+        builder
+            .add_service(BcidServiceServer::new(Service { server }))
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), shutdown)
+            .await
+            .map_err(io::Error::other)
+    })
+}
+
+/// The `BcidService` implementation
+struct Service {
+    server: Arc<Server>,
+}
+
+impl Service {
+    /// Check the token of a call and charge `cost` identifiers to the
+    /// client's rate limit
+    fn admit<T>(&self, request: &Request<T>, cost: usize) -> Result<(), Status> {
+        let metadata = request.metadata();
+        let token = match metadata.get("authorization") {
+            Some(value) => value.to_str().ok().and_then(|value| value.strip_prefix("Bearer ")).map(str::trim),
+            None => metadata.get("x-api-key").and_then(|value| value.to_str().ok()),
+        };
+        let peer = request.remote_addr().map(|address| address.ip().to_string()).unwrap_or_default();
+        let Some(client) = authenticate(&self.server, token, &peer) else {
+            return Err(Status::unauthenticated("unauthorized: A valid token is required (authorization: Bearer TOKEN or x-api-key: TOKEN)"));
+        };
+        charge(&self.server, &client, cost).map_err(|wait| {
+            Status::resource_exhausted(format!("rate_limited: Rate limit exceeded; retry in {:.1}s", wait.as_secs_f64()))
+        })
+    }
+
+    /// Time an RPC for the request metrics and log it at debug level
+    fn record<T>(&self, rpc: &'static str, started: Instant, result: &Result<T, Status>) {
+        let elapsed = started.elapsed();
+        self.server.metrics.observe(rpc, elapsed);
+        let status = match result {
+            Ok(_) => "OK".to_string(),
+            Err(status) => format!("{:?}", status.code()),
+        };
+        debug!(rpc, status, duration_us = elapsed.as_micros() as u64, "{} {}", rpc, status);
+    }
+
+    /// Identifiers a batch RPC costs: its count if it is valid, otherwise one,
+    /// as it fails without generating anything
+    fn cost(&self, prefix: &str, count: u64, random: bool, format: i32, max_count: usize) -> usize {
+        let count = count.to_string();
+        let Ok(params) = generate_params(prefix, Some(&count), random, format) else {
+            return 1;
+        };
+        params.validate(&self.server, max_count).map_or(1, |generation| generation.count)
+    }
+
+    /// Generate the identifiers of a batch RPC lazily, a chunk at a time,
+    /// allowing at most `max_count`
+    fn stream(&self, prefix: &str, count: u64, random: bool, format: i32, max_count: usize) -> Result<IdStream, Status> {
+        let count = count.to_string();
+        let params = generate_params(prefix, Some(&count), random, format)?;
+        let generation = params.validate(&self.server, max_count).map_err(rejected)?;
+        let (prefix, is_random, format) = (generation.prefix.to_string(), generation.is_random, generation.format);
+        let server = Arc::clone(&self.server);
+        let mut remaining = generation.count;
+        let chunks = std::iter::from_fn(move || {
+            if remaining == 0 {
+                return None;
+            }
+            let count = remaining.min(BATCH_CHUNK);
+            let generation = Generation { prefix: &prefix, count, is_random, format };
+            match generation.generate(count, &server) {
+                Ok(ids) => {
+                    remaining -= count;
+                    Some(ids.into_iter().map(|id| Ok(GenerateResponse { id })).collect::<Vec<_>>())
+                }
+                // The stream ends with the error
+                Err(err) => {
+                    remaining = 0;
+                    Some(vec![Err(rejected(err))])
+                }
+            }
+        });
+        Ok(Box::pin(tokio_stream::iter(chunks.flatten())))
+    }
+}
+
+#[tonic::async_trait]
+impl BcidService for Service {
+    type GenerateBatchStream = IdStream;
+    type GenerateStreamStream = IdStream;
+
+    async fn generate(&self, request: Request<GenerateRequest>) -> Result<Response<GenerateResponse>, Status> {
+        let started = Instant::now();
+        let result = self.admit(&request, 1).and_then(|_| {
+            let request = request.get_ref();
+            let params = generate_params(&request.prefix, None, request.random, request.format)?;
+            let generation = params.validate(&self.server, 1).map_err(rejected)?;
+            let mut ids = generation.generate(1, &self.server).map_err(rejected)?;
+            Ok(Response::new(GenerateResponse { id: ids.remove(0) }))
+        });
+        self.record("/bcid.v1.BcidService/Generate", started, &result);
+        result
+    }
+
+    async fn generate_batch(&self, request: Request<GenerateBatchRequest>) -> Result<Response<IdStream>, Status> {
+        let started = Instant::now();
+        let max_count = self.server.max_count;
+        let batch = request.get_ref();
+        let cost = self.cost(&batch.prefix, batch.count.into(), batch.random, batch.format, max_count);
+        let result = self.admit(&request, cost).and_then(|_| {
+            let request = request.get_ref();
+            self.stream(&request.prefix, request.count.into(), request.random, request.format, max_count)
+                .map(Response::new)
+        });
+        self.record("/bcid.v1.BcidService/GenerateBatch", started, &result);
+        result
+    }
+
+    async fn generate_stream(&self, request: Request<GenerateStreamRequest>) -> Result<Response<IdStream>, Status> {
+        let started = Instant::now();
+        let max_count = self.server.max_batch_count;
+        let batch = request.get_ref();
+        let cost = self.cost(&batch.prefix, batch.count, batch.random, batch.format, max_count);
+        let result = self.admit(&request, cost).and_then(|_| {
+            let request = request.get_ref();
+            self.stream(&request.prefix, request.count, request.random, request.format, max_count).map(Response::new)
+        });
+        self.record("/bcid.v1.BcidService/GenerateStream", started, &result);
+        result
+    }
+
+    async fn decode(&self, request: Request<DecodeRequest>) -> Result<Response<DecodeResponse>, Status> {
+        let started = Instant::now();
+        let result = self.admit(&request, 1).and_then(|_| {
+            let decoded = self.server.decode(&request.get_ref().id).map_err(|err| rejected(err.into()))?;
+            Ok(Response::new(decode_response(&decoded)))
+        });
+        self.record("/bcid.v1.BcidService/Decode", started, &result);
+        result
+    }
+}
+
+/// The parameters of a generation RPC, as `POST /v1/ids` would get them
+fn generate_params<'a>(prefix: &'a str, count: Option<&'a str>, random: bool, format: i32) -> Result<GenerateParams<'a>, Status> {
+    let format = match proto::Format::try_from(format) {
+        Ok(proto::Format::Unspecified) => None,
+        Ok(proto::Format::V1) => Some("v1"),
+        Ok(proto::Format::V2) => Some("v2"),
+        Ok(proto::Format::V3) => Some("v3"),
+        Ok(proto::Format::V4) => Some("v4"),
+        Ok(proto::Format::V5) => Some("v5"),
+        Err(_) => return Err(rejected(RequestError::new("invalid_format", "format must be v1, v2, v3, v4 or v5"))),
+    };
+    Ok(GenerateParams {
+        prefix: Some(prefix).filter(|prefix| !prefix.is_empty()),
+        count,
+        random: random.then_some("true"),
+        format,
+    })
+}
+
+/// The components of a decoded identifier
+fn decode_response(decoded: &Bcid) -> DecodeResponse {
+    let identifier_type = match decoded.identifier_type() {
+        IdentifierType::Chronological => proto::IdentifierType::Chronological,
+        IdentifierType::Random => proto::IdentifierType::Random,
+    };
+    DecodeResponse {
+        prefix: decoded.prefix().to_string(),
+        r#type: identifier_type.into(),
+        // The values of the proto enum are the version numbers
+        format: decoded.format().version().into(),
+        machine_id: decoded.machine_id().into(),
+        timestamp: decoded.timestamp(),
+        iso_datetime: decoded.datetime().map(|datetime| datetime.to_rfc3339_opts(SecondsFormat::Millis, true)),
+        random: decoded.random().map(u32::from),
+        random_part: decoded.random_part().map(str::to_string),
+        raw: decoded.as_str().to_string(),
+    }
+}
+
+/// `INVALID_ARGUMENT`, or `UNAVAILABLE` for what HTTP answers with 503,
+/// with the reason code and message of a rejected request
+fn rejected(err: RequestError) -> Status {
+    let message = format!("{}: {}", err.reason, err.message);
+    match err.status() {
+        503 => Status::unavailable(message),
+        _ => Status::invalid_argument(message),
+    }
+}
@@ -0,0 +1,92 @@
+use std::fs;
+use std::io::{self, Read};
+use std::process;
+
+use bcid::Bcid;
+use clap::Args;
+
+use super::bulk::identifier_lines;
+use super::config::Config;
+use super::{exit_with_error, usage_error, EXIT_FAILURE};
+
+/// Derive identifiers from file contents, keys in a namespace or a parent
+///
+/// Prints the identifier of the contents of FILE: a random identifier with
+/// machine ID 0 whose random data is derived from the SHA-256 hash of the
+/// contents, so the same contents always give the same identifier.
+///
+/// With --namespace, each non-blank line of FILE is a key (e.g. a legacy
+/// customer number) instead, and the identifier of each key in the namespace is
+/// printed on its own line, derived from the HMAC-SHA256 of the key keyed with
+/// the namespace.
+///
+/// With --parent, the child of the parent identifier with the ordinal is
+/// printed instead (its machine ID is the ordinal), which `bcid decode
+/// --parent` verifies.
+#[derive(Args)]
+#[command(
+    override_usage = "bcid hash -p PREFIX [--namespace NAMESPACE] [--input FILE]\n       bcid hash -p PREFIX --parent IDENTIFIER --ordinal N",
+    after_help = EXAMPLES
+)]
+pub struct HashArgs {
+    /// Prefix of 1-8 characters (default: from the config file)
+    #[arg(short, long)]
+    prefix: Option<String>,
+    /// Map each line to the identifier of that key in NAMESPACE
+    #[arg(long)]
+    namespace: Option<String>,
+    /// File to hash (default: `-` for stdin)
+    #[arg(long, value_name = "FILE", default_value = "-", hide_default_value = true)]
+    input: String,
+    /// Derive the child of IDENTIFIER with --ordinal
+    #[arg(long, value_name = "IDENTIFIER")]
+    parent: Option<String>,
+    /// Ordinal of the child, 0-65535
+    #[arg(long, value_name = "N")]
+    ordinal: Option<u16>,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid hash -p DOCU --input file.bin
+  echo hello | bcid hash -p DOCU
+  bcid hash -p CUST --namespace legacy-crm --input customer-numbers.txt
+  bcid hash -p LINE --parent ORDRjqTZNAWSyaabkXENIJ3mg9rTtkWk --ordinal 3";
+
+pub fn run(args: HashArgs, config: &Config) {
+    let Some(prefix) = args.prefix.or_else(|| config.prefix.clone()) else {
+        usage_error("hash", "Missing prefix argument");
+    };
+    let input = args.input;
+    match (args.parent, args.ordinal) {
+        (Some(_), _) if args.namespace.is_some() => usage_error("hash", "--parent cannot be used with --namespace"),
+        (Some(parent), Some(ordinal)) => {
+            let parent = Bcid::parse(&parent).unwrap_or_else(|e| exit_with_error(e));
+            println!("{}", parent.child(&prefix, ordinal).unwrap_or_else(|e| exit_with_error(e)));
+            return;
+        }
+        (Some(_), None) => usage_error("hash", "--parent requires --ordinal"),
+        (None, Some(_)) => usage_error("hash", "--ordinal requires --parent"),
+        (None, None) => {}
+    }
+    if let Some(namespace) = args.namespace {
+        for (_, key) in identifier_lines(&input) {
+            let id = Bcid::from_key(&prefix, &namespace, key.as_bytes()).unwrap_or_else(|e| exit_with_error(e));
+            println!("{}", id);
+        }
+        return;
+    }
+
+    let contents = match input.as_str() {
+        "-" => {
+            let mut contents = Vec::new();
+            io::stdin().read_to_end(&mut contents).map(|_| contents)
+        }
+        path => fs::read(path),
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("Error: Cannot read {}: {}", input, err);
+        process::exit(EXIT_FAILURE);
+    });
+    println!("{}", Bcid::from_content(&prefix, &contents).unwrap_or_else(|e| exit_with_error(e)));
+}
@@ -0,0 +1,266 @@
+//! Minimal HTTP/1.1 request parsing and response writing for `bcid serve`
+
+use std::io::{self, BufRead, Write};
+
+/// Largest request head (request line and headers) accepted
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// Largest request body accepted
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// A parsed HTTP request
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    /// Percent-decoded path without the query string
+    pub path: String,
+    /// Percent-decoded query parameters, in order
+    pub query: Vec<(String, String)>,
+    /// Headers with lowercase names
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// First header named `name` (lowercase)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Whether the client asked to keep the connection open
+    pub fn keep_alive(&self) -> bool {
+        !self.header("connection").is_some_and(|value| value.eq_ignore_ascii_case("close"))
+    }
+}
+
+/// Why a request could not be read
+#[derive(Debug)]
+pub enum ReadError {
+    /// Reading from the connection failed or timed out
+    Io(io::Error),
+    /// The request cannot be served: answer with this response, then close
+    /// the connection, as the rest of the request may still be unread
+    Rejected(Response),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::InvalidData => invalid(&err.to_string()),
+            _ => ReadError::Io(err),
+        }
+    }
+}
+
+/// Read one request, or `None` if the client closed the connection
+///
+/// Bodies must be sent with `Content-Length`: requests with a
+/// `Transfer-Encoding` are rejected with 411, and bodies over 1 MiB with 413.
+pub fn read_request<R: BufRead>(reader: &mut R) -> Result<Option<Request>, ReadError> {
+    let mut head_bytes = 0;
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    head_bytes += line.len();
+
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid("malformed request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(invalid("unsupported HTTP version"));
+    }
+    let method = method.to_string();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = percent_decode(path);
+    let query = parse_query(query);
+
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Err(invalid("unexpected end of headers"));
+        }
+        head_bytes += header.len();
+        if head_bytes > MAX_HEAD_BYTES {
+            return Err(invalid("request head too large"));
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':').ok_or_else(|| invalid("malformed header"))?;
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+
+    let mut request = Request { method, path, query, headers, body: Vec::new() };
+    if request.header("transfer-encoding").is_some() {
+        let message = "Transfer-Encoding is not supported; send the body with Content-Length";
+        return Err(ReadError::Rejected(Response::error(411, "length_required", message)));
+    }
+    let mut lengths = request.headers.iter().filter(|(name, _)| name == "content-length").map(|(_, value)| value);
+    let length: usize = match (lengths.next(), lengths.next()) {
+        (Some(_), Some(_)) => return Err(invalid("more than one content-length")),
+        (Some(length), None) => length.parse().map_err(|_| invalid("invalid content-length"))?,
+        (None, _) => 0,
+    };
+    if length > MAX_BODY_BYTES {
+        let message = format!("request body is larger than {} bytes", MAX_BODY_BYTES);
+        return Err(ReadError::Rejected(Response::error(413, "payload_too_large", &message)));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)?;
+    Ok(Some(request))
+}
+
+/// An HTTP response
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(status: u16, body: String) -> Self {
+        Response { status, content_type: "application/json", headers: Vec::new(), body: body.into_bytes() }
+    }
+
+    /// A plain-text body of `content_type`
+    pub fn text(status: u16, content_type: &'static str, body: String) -> Self {
+        Response { status, content_type, headers: Vec::new(), body: body.into_bytes() }
+    }
+
+    /// A JSON error body: `{"error": message, "reason": reason}`
+    pub fn error(status: u16, reason: &str, message: &str) -> Self {
+        let body = bcid::json::Object::new()
+            .string("error", Some(message))
+            .string("reason", Some(reason))
+            .finish();
+        Response::json(status, body)
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Write the status line, headers and body
+    pub fn write_to<W: Write>(&self, out: &mut W, keep_alive: bool) -> io::Result<()> {
+        let length = format!("Content-Length: {}\r\n", self.body.len());
+        write_head(out, self.status, self.content_type, &self.headers, &length, keep_alive)?;
+        out.write_all(&self.body)?;
+        out.flush()
+    }
+}
+
+/// A response body sent with chunked transfer encoding as it is produced,
+/// so large responses are never held in memory at once
+pub struct ChunkedBody<'a, W: Write> {
+    out: &'a mut W,
+}
+
+impl<'a, W: Write> ChunkedBody<'a, W> {
+    /// Write the status line and headers of a chunked response
+    pub fn start(out: &'a mut W, status: u16, content_type: &'static str, keep_alive: bool) -> io::Result<Self> {
+        write_head(out, status, content_type, &[], "Transfer-Encoding: chunked\r\n", keep_alive)?;
+        Ok(ChunkedBody { out })
+    }
+
+    /// Send `data` to the client as one chunk
+    pub fn write_chunk(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        write!(self.out, "{:x}\r\n", data.len())?;
+        self.out.write_all(data)?;
+        self.out.write_all(b"\r\n")?;
+        self.out.flush()
+    }
+
+    /// End the body with the terminating empty chunk
+    pub fn finish(self) -> io::Result<()> {
+        self.out.write_all(b"0\r\n\r\n")?;
+        self.out.flush()
+    }
+}
+
+/// Write a status line and headers, `framing` being the Content-Length or
+/// Transfer-Encoding header line
+fn write_head<W: Write>(
+    out: &mut W,
+    status: u16,
+    content_type: &str,
+    headers: &[(&'static str, String)],
+    framing: &str,
+    keep_alive: bool,
+) -> io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\n{}Connection: {}\r\n",
+        status,
+        reason_phrase(status),
+        content_type,
+        framing,
+        if keep_alive { "keep-alive" } else { "close" }
+    );
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    out.write_all(head.as_bytes())
+}
+
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+/// A malformed request, answered with 400
+fn invalid(message: &str) -> ReadError {
+    ReadError::Rejected(Response::error(400, "bad_request", message))
+}
+
+/// Split `a=1&b=2` into percent-decoded pairs
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(&key.replace('+', " ")), percent_decode(&value.replace('+', " ")))
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes, leaving malformed escapes as they are
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
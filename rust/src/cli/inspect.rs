@@ -0,0 +1,68 @@
+use bcid::{Alphabet, Bcid, Field};
+use clap::Args;
+
+use super::generate::parse_alphabet;
+use super::config::Config;
+use super::exit_with_error;
+
+/// Show the character range of each encoded component
+///
+/// Lists the character range and encoded text of each component.
+#[derive(Args)]
+pub struct InspectArgs {
+    /// Identifier to inspect
+    identifier: String,
+    /// Alphabet the identifier was generated with (default: base62 or from
+    /// the config file)
+    #[arg(long, value_parser = parse_alphabet)]
+    alphabet: Option<Alphabet>,
+}
+
+pub fn run(args: InspectArgs, config: &Config) {
+    let identifier = args.identifier;
+    let alphabet = args.alphabet.or(config.alphabet).unwrap_or_default();
+
+    let decoded = Bcid::parse_with(&identifier, alphabet).unwrap_or_else(|e| exit_with_error(e));
+    println!("Identifier: {}", decoded);
+    println!("Type: {}", decoded.identifier_type());
+    println!("Format: {} ({} fields)", decoded.format(), decoded.format().layout());
+    if decoded.alphabet() != Alphabet::Base62 {
+        println!("Alphabet: {}", decoded.alphabet());
+    }
+    for field in decoded.fields() {
+        println!(
+            "  {:>2}..{:<2}  {:<11}  {:<25}  {}",
+            field.start,
+            field.end,
+            field.name,
+            &decoded.as_str()[field.start..field.end],
+            field_value(&decoded, &field)
+        );
+    }
+}
+
+/// The decoded value of `field` of `decoded`, or a description of what it
+/// holds
+pub fn field_value(decoded: &Bcid, field: &Field) -> String {
+    match field.name {
+        "prefix" => decoded.prefix().to_string(),
+        "version" => decoded.format().version().to_string(),
+        "precision" => decoded.precision().map(|p| p.to_string()).unwrap_or_default(),
+        "timestamp" => decoded.timestamp().map(|t| t.to_string()).unwrap_or_default(),
+        "machine_id" => decoded.machine_id().to_string(),
+        "environment" => decoded.environment().map(|e| e.to_string()).unwrap_or_default(),
+        "shard" => decoded.shard().unwrap_or_default().to_string(),
+        "layout" => decoded.bit_layout().map(|layout| layout.to_string()).unwrap_or_default(),
+        "bits" => format!(
+            "timestamp {}, machine ID {}, sequence {}, random {}",
+            decoded.timestamp().unwrap_or_default(),
+            decoded.machine_id(),
+            decoded.sequence().unwrap_or_default(),
+            decoded.random().unwrap_or_default()
+        ),
+        "random" => decoded.random().map(|r| r.to_string()).unwrap_or_else(|| "(random data)".to_string()),
+        "separator" => String::new(),
+        "marker" => "random".to_string(),
+        _ => "(random padding)".to_string(),
+    }
+}
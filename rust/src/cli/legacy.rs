@@ -0,0 +1,111 @@
+//! The original flag-style invocation (`bcid -p TEST`, `bcid -d ID`)
+
+use bcid::Bcid;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+
+use super::bulk::identifier_lines;
+use super::decode::{self, parse_parent, DecodeOptions};
+use super::generate::{self, GenerateFlags, GenerateOptions};
+use super::config::{Config, OutputFormat};
+
+/// The generate flags, and `-d` with the decode output flags
+#[derive(Parser)]
+#[command(name = "bcid", override_usage = "bcid -p PREFIX [OPTIONS]\n       bcid -d IDENTIFIER [-d IDENTIFIER...] [OPTIONS]")]
+pub struct LegacyArgs {
+    /// Decode IDENTIFIER instead of generating (repeatable; `-` decodes the
+    /// whitespace-separated identifiers on stdin)
+    #[arg(short = 'd', value_name = "IDENTIFIER")]
+    decode: Vec<String>,
+    /// Print decoded components as a JSON object (an array for several identifiers)
+    #[arg(long, overrides_with_all = ["text", "porcelain"])]
+    json: bool,
+    /// Print decoded components as text (overrides `output` in the config file)
+    #[arg(long, overrides_with_all = ["json", "porcelain"])]
+    text: bool,
+    /// Print decoded components as stable tab-separated fields
+    #[arg(long, overrides_with_all = ["json", "text"])]
+    porcelain: bool,
+    /// Require decoded identifiers to be children of IDENTIFIER
+    #[arg(long, value_name = "IDENTIFIER", value_parser = parse_parent)]
+    parent: Option<Bcid>,
+    #[command(flatten)]
+    generate: GenerateFlags,
+}
+
+pub fn run(args: LegacyArgs, config: &Config) {
+    let mut generate_options = GenerateOptions::from_config(config);
+    let mut decode_options = DecodeOptions::from_config(config);
+    let decode_ids = args.decode;
+    if args.json || args.text || args.porcelain {
+        decode_options.is_json = args.json;
+        decode_options.is_porcelain = args.porcelain;
+    }
+    decode_options.parent = args.parent;
+    args.generate.apply(&mut generate_options);
+
+    if !decode_ids.is_empty() {
+        // Flags shared with decoding are parsed as generate flags: the key also
+        // decrypts, and the alphabet is the one the identifiers were generated with
+        let is_set = |flag| generate_options.set_flags.contains(&flag);
+        if is_set("Key") {
+            decode_options.key = generate_options.signing_key.clone();
+            decode_options.is_opaque = true;
+        }
+        if is_set("Alphabet") {
+            decode_options.alphabet = generate_options.alphabet;
+        }
+        if is_set("Check digit flag") {
+            decode_options.with_check_digit = true;
+        }
+        decode_options.is_opaque |= generate_options.is_opaque;
+        decode_options.template = generate_options.template.clone();
+        decode_options.is_quiet = generate_options.is_quiet;
+        if is_set("Registry") {
+            decode_options.registry = generate_options.registry.clone();
+        }
+        let is_shared = |flag: &&str| {
+            matches!(*flag, "Key" | "Alphabet" | "Check digit flag" | "Opaque flag" | "Template" | "Quiet flag" | "Registry")
+        };
+        for flag in generate_options.set_flags.iter().filter(|flag| !is_shared(flag) && !decode_options.is_quiet) {
+            eprintln!("Warning: {} is ignored when decoding", flag);
+        }
+        match decode_ids.as_slice() {
+            [id] if id != "-" => decode::decode(id, &decode_options),
+            _ => {
+                let mut identifiers = Vec::new();
+                for id in decode_ids {
+                    if id != "-" {
+                        identifiers.push(id);
+                        continue;
+                    }
+                    // Whitespace-separated identifiers, e.g. `echo $ID | bcid -d -`
+                    let before = identifiers.len();
+                    for (_, line) in identifier_lines("-") {
+                        identifiers.extend(line.split_whitespace().map(str::to_string));
+                    }
+                    if identifiers.len() == before {
+                        usage_error("No identifiers on stdin");
+                    }
+                }
+                decode::decode_many(&identifiers, &decode_options);
+            }
+        }
+    } else {
+        if generate_options.prefix.is_none() {
+            usage_error("Prefix is required when generating an identifier");
+        }
+        if decode_options.is_json && config.output != Some(OutputFormat::Json) && !generate_options.is_quiet {
+            eprintln!("Warning: JSON flag is ignored when generating");
+        }
+        if decode_options.is_porcelain && !generate_options.is_quiet {
+            eprintln!("Warning: Porcelain flag is ignored when generating");
+        }
+        generate::generate(generate_options);
+    }
+}
+
+/// Report an argument error with the flag-style usage and exit
+fn usage_error(message: &str) -> ! {
+    LegacyArgs::command().error(ErrorKind::ValueValidation, message).exit()
+}
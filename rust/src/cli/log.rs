@@ -0,0 +1,121 @@
+//! Diagnostic logging on stderr for `bcid serve` and bulk decoding, with
+//! `tracing` events written as plain text or one JSON object per line
+//! (`--log-format json`)
+//!
+//! JSON events carry `timestamp`, `level` and `message`, followed by the
+//! event's own fields, e.g.
+//!
+//! ```text
+//! {"timestamp":"2026-10-16T09:12:03.517204Z","level":"DEBUG","message":"POST /v1/ids 200","method":"POST","path":"/v1/ids","status":200,"duration_us":84}
+//! ```
+
+use std::fmt;
+use std::io;
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use clap::{Args, ValueEnum};
+
+/// How events are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LogFormat {
+    /// The message alone, with `Error: ` or `Warning: ` before errors and
+    /// warnings
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// `--log-format` and `--log-level`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Args)]
+pub struct LogOptions {
+    /// Write logs on stderr as `text` (default) or `json`, one object per
+    /// line with timestamp, level, message and fields
+    #[arg(long = "log-format", value_name = "FORMAT", value_enum, default_value_t = LogFormat::Text, hide_default_value = true)]
+    pub format: LogFormat,
+    /// Least severe level logged: error, warn, info (default) or debug
+    #[arg(long = "log-level", value_name = "LEVEL", value_parser = parse_level, default_value = "info", hide_default_value = true)]
+    pub level: Level,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions { format: LogFormat::Text, level: Level::INFO }
+    }
+}
+
+/// Parse a `--log-level` value
+fn parse_level(value: &str) -> Result<Level, String> {
+    match value {
+        "error" => Ok(Level::ERROR),
+        "warn" => Ok(Level::WARN),
+        "info" => Ok(Level::INFO),
+        "debug" => Ok(Level::DEBUG),
+        _ => Err("must be error, warn, info or debug".to_string()),
+    }
+}
+
+impl LogOptions {
+    /// Install the subscriber writing every event from now on
+    pub fn init(self) {
+        // Only bcid's own events; those of the gRPC stack are not for users
+        let targets = Targets::new().with_target("bcid", self.level);
+        let builder = tracing_subscriber::fmt().with_writer(io::stderr).with_max_level(self.level);
+        let _ = match self.format {
+            LogFormat::Text => builder.event_format(TextFormat).finish().with(targets).try_init(),
+            LogFormat::Json => builder
+                .json()
+                .flatten_event(true)
+                .with_target(false)
+                .with_current_span(false)
+                .finish()
+                .with(targets)
+                .try_init(),
+        };
+    }
+}
+
+/// Plain-text events: the message alone, after `Error: ` or `Warning: `
+struct TextFormat;
+
+impl<S, N> FormatEvent<S, N> for TextFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, _: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let mut message = Message::default();
+        event.record(&mut message);
+        match *event.metadata().level() {
+            Level::ERROR => writeln!(writer, "Error: {}", message.0),
+            Level::WARN => writeln!(writer, "Warning: {}", message.0),
+            _ => writeln!(writer, "{}", message.0),
+        }
+    }
+}
+
+/// The `message` field of an event, ignoring the others
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = value.to_string();
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
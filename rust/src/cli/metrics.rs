@@ -0,0 +1,150 @@
+//! Prometheus metrics for `bcid serve`, exposed at `GET /metrics` in the
+//! text exposition format
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Content type of the text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds of the request latency histogram buckets, in seconds
+const LATENCY_BUCKETS: [f64; 12] = [0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0];
+
+/// Counters and histograms shared by every connection
+#[derive(Default)]
+pub struct Metrics {
+    /// Identifiers generated, by prefix
+    generated: Mutex<BTreeMap<String, u64>>,
+    /// Failed decodes, by reason code
+    decode_errors: Mutex<BTreeMap<&'static str, u64>>,
+    /// Requests refused by authentication or rate limiting, and connections
+    /// over the limit, by reason code
+    rejected: Mutex<BTreeMap<&'static str, u64>>,
+    /// Request latencies, by endpoint
+    latencies: Mutex<BTreeMap<&'static str, Histogram>>,
+    clock_regressions: AtomicU64,
+    sequence_overflows: AtomicU64,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations at or below each of [`LATENCY_BUCKETS`]
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Metrics {
+    pub fn generated(&self, prefix: &str, count: usize) {
+        let mut generated = self.generated.lock().unwrap_or_else(|e| e.into_inner());
+        *generated.entry(prefix.to_string()).or_default() += count as u64;
+    }
+
+    pub fn decode_error(&self, reason: &'static str) {
+        let mut decode_errors = self.decode_errors.lock().unwrap_or_else(|e| e.into_inner());
+        *decode_errors.entry(reason).or_default() += 1;
+    }
+
+    pub fn rejected(&self, reason: &'static str) {
+        let mut rejected = self.rejected.lock().unwrap_or_else(|e| e.into_inner());
+        *rejected.entry(reason).or_default() += 1;
+    }
+
+    /// Record how long a request to `endpoint` took
+    pub fn observe(&self, endpoint: &'static str, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let mut latencies = self.latencies.lock().unwrap_or_else(|e| e.into_inner());
+        let histogram = latencies.entry(endpoint).or_default();
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        histogram.count += 1;
+        histogram.sum += seconds;
+    }
+
+    /// Count clock readings that were earlier than the latest one
+    pub fn clock_regressions(&self, count: u64) {
+        self.clock_regressions.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Count times a tick ran out of random values, or a millisecond out of
+    /// sequence numbers, so identifiers were timestamped ahead of the clock
+    pub fn sequence_overflows(&self, count: u64) {
+        self.sequence_overflows.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// The metrics in the text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let generated = self.generated.lock().unwrap_or_else(|e| e.into_inner());
+        header(&mut out, "bcid_ids_generated_total", "counter", "Identifiers generated, by prefix");
+        for (prefix, count) in generated.iter() {
+            let _ = writeln!(out, "bcid_ids_generated_total{{prefix=\"{}\"}} {}", escape(prefix), count);
+        }
+        drop(generated);
+
+        let decode_errors = self.decode_errors.lock().unwrap_or_else(|e| e.into_inner());
+        header(&mut out, "bcid_decode_errors_total", "counter", "Identifiers that failed to decode, by reason");
+        for (reason, count) in decode_errors.iter() {
+            let _ = writeln!(out, "bcid_decode_errors_total{{reason=\"{}\"}} {}", reason, count);
+        }
+        drop(decode_errors);
+
+        let rejected = self.rejected.lock().unwrap_or_else(|e| e.into_inner());
+        header(
+            &mut out,
+            "bcid_requests_rejected_total",
+            "counter",
+            "Requests refused by authentication or rate limiting, and connections over the limit, by reason",
+        );
+        for (reason, count) in rejected.iter() {
+            let _ = writeln!(out, "bcid_requests_rejected_total{{reason=\"{}\"}} {}", reason, count);
+        }
+        drop(rejected);
+
+        let latencies = self.latencies.lock().unwrap_or_else(|e| e.into_inner());
+        header(&mut out, "bcid_request_duration_seconds", "histogram", "Time taken to answer requests, by endpoint");
+        for (endpoint, histogram) in latencies.iter() {
+            let name = "bcid_request_duration_seconds";
+            for (count, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(out, "{}_bucket{{endpoint=\"{}\",le=\"{}\"}} {}", name, endpoint, bound, count);
+            }
+            let _ = writeln!(out, "{}_bucket{{endpoint=\"{}\",le=\"+Inf\"}} {}", name, endpoint, histogram.count);
+            let _ = writeln!(out, "{}_sum{{endpoint=\"{}\"}} {}", name, endpoint, histogram.sum);
+            let _ = writeln!(out, "{}_count{{endpoint=\"{}\"}} {}", name, endpoint, histogram.count);
+        }
+        drop(latencies);
+
+        header(
+            &mut out,
+            "bcid_clock_regressions_total",
+            "counter",
+            "Clock readings earlier than the latest one, e.g. after NTP stepped the clock back",
+        );
+        let _ = writeln!(out, "bcid_clock_regressions_total {}", self.clock_regressions.load(Ordering::Relaxed));
+        header(
+            &mut out,
+            "bcid_sequence_overflows_total",
+            "counter",
+            "Times a tick ran out of random values, or a millisecond out of sequence numbers, so identifiers were timestamped ahead of the clock",
+        );
+        let _ = writeln!(out, "bcid_sequence_overflows_total {}", self.sequence_overflows.load(Ordering::Relaxed));
+        out
+    }
+}
+
+/// The `# HELP` and `# TYPE` lines of a metric
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// `value` escaped for a label value
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -0,0 +1,262 @@
+//! Command line front end: the clap subcommands, and the parsing and output
+//! helpers they share
+
+pub mod audit_machines;
+pub mod bench;
+pub mod bulk;
+pub mod config;
+pub mod convert;
+pub mod dedupe;
+pub mod diff;
+pub mod doctor;
+pub mod estimate;
+pub mod explain;
+pub mod extract;
+pub mod filter;
+pub mod decode;
+pub mod generate;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod hash;
+pub mod http;
+pub mod inspect;
+pub mod range;
+pub mod ratelimit;
+pub mod legacy;
+pub mod log;
+pub mod metrics;
+pub mod openapi;
+pub mod registry;
+pub mod selftest;
+pub mod serve;
+#[cfg(unix)]
+pub mod socket;
+pub mod sort;
+pub mod state;
+pub mod stats;
+pub mod template;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod validate;
+pub mod verify;
+
+use std::collections::BTreeMap;
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+
+use bcid::{parse_user_datetime, BcidError};
+use chrono::{DateTime, Utc};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+
+/// Base62 Chronological Identifiers: generate, decode and check them
+#[derive(Parser)]
+#[command(name = "bcid", version, after_help = AFTER_HELP)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    Generate(generate::GenerateArgs),
+    Decode(decode::DecodeArgs),
+    Validate(validate::ValidateArgs),
+    Verify(verify::VerifyArgs),
+    Inspect(inspect::InspectArgs),
+    Explain(explain::ExplainArgs),
+    Convert(convert::ConvertArgs),
+    Range(range::RangeArgs),
+    Diff(diff::DiffArgs),
+    Extract(extract::ExtractArgs),
+    Hash(hash::HashArgs),
+    Serve(serve::ServeArgs),
+    Doctor(doctor::DoctorArgs),
+    Bench(bench::BenchArgs),
+    Estimate(estimate::EstimateArgs),
+    Stats(stats::StatsArgs),
+    Dedupe(dedupe::DedupeArgs),
+    Sort(sort::SortArgs),
+    Filter(filter::FilterArgs),
+    AuditMachines(audit_machines::AuditMachinesArgs),
+    Selftest(selftest::SelftestArgs),
+}
+
+const AFTER_HELP: &str = "\
+The original flag-style invocation is still supported:
+  bcid -p PREFIX [-m MACHINE_ID] [-t DATETIME] [-r | --monotonic] [--sortable] [-n COUNT]
+  bcid -d IDENTIFIER [-d IDENTIFIER...] [--json | --porcelain | -q]    (`-d -` decodes the identifiers on stdin)
+
+Exit status:
+  0  Success
+  1  Failure, e.g. a file cannot be read, a check fails or nothing was found
+  2  Invalid arguments, configuration or environment variables
+  3  Invalid identifier (validate, verify, decode, convert, ...)
+  4  Clock error: the clock moved backwards or a sequence was used up
+  5  Machine ID unavailable, e.g. the lease coordinator cannot be reached
+
+Defaults for the prefix, machine ID, format and decode output can be set in
+~/.config/bcid/config.toml (or the file named by BCID_CONFIG) and overridden
+with BCID_PREFIX, BCID_MACHINE_ID and BCID_FORMAT (text or json); command
+line flags take precedence over both. BCID_SIGNING_KEY holds the key for
+signed identifiers.";
+
+/// Exit status of failures without a more specific one below
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit status of invalid arguments, configuration or environment variables
+pub const EXIT_USAGE: i32 = 2;
+/// Exit status of identifiers that are malformed or fail a check
+pub const EXIT_INVALID_IDENTIFIER: i32 = 3;
+/// Exit status of a clock that moved backwards or a used-up sequence
+pub const EXIT_CLOCK: i32 = 4;
+/// Exit status of a machine ID that cannot be derived or leased
+pub const EXIT_COORDINATOR: i32 = 5;
+
+/// The exit status for a library error
+pub fn exit_status(err: &BcidError) -> i32 {
+    match err {
+        BcidError::InvalidPrefixLength(_)
+        | BcidError::InvalidPrefixCharacter(_)
+        | BcidError::InvalidDatetime(_)
+        | BcidError::InvalidShard(_)
+        | BcidError::InvalidLayout(_)
+        | BcidError::LayoutDoesNotFit { .. } => EXIT_USAGE,
+        BcidError::InvalidIdentifierLength(_)
+        | BcidError::InvalidCharacter { .. }
+        | BcidError::InvalidTimestamp(_)
+        | BcidError::ValueOutOfRange { .. }
+        | BcidError::UnknownFormat(_)
+        | BcidError::UnknownType
+        | BcidError::InvalidCheckDigit { .. }
+        | BcidError::InvalidSignature
+        | BcidError::InvalidUuid(_)
+        | BcidError::InvalidUlid(_)
+        | BcidError::InvalidTypeId(_)
+        | BcidError::NotChronological
+        | BcidError::UnexpectedPrefix { .. }
+        | BcidError::NotAChild
+        | BcidError::WrongEnvironment { .. } => EXIT_INVALID_IDENTIFIER,
+        BcidError::ClockMovedBackwards { .. } | BcidError::SequenceOverflow { .. } => EXIT_CLOCK,
+        BcidError::MachineIdUnavailable(_) => EXIT_COORDINATOR,
+    }
+}
+
+/// Report an argument error with the usage of `command` (a subcommand, or
+/// `""` for none) and exit with [`EXIT_USAGE`]
+///
+/// For the checks clap cannot express: conflicts with the configuration
+/// file, or between values rather than flags.
+pub fn usage_error(command: &str, message: &str) -> ! {
+    let mut cli = Cli::command();
+    cli.build();
+    match cli.find_subcommand_mut(command) {
+        Some(subcommand) => subcommand.error(ErrorKind::ValueValidation, message).exit(),
+        None => cli.error(ErrorKind::ValueValidation, message).exit(),
+    }
+}
+
+/// Report a library error and exit with its status
+pub fn exit_with_error(err: BcidError) -> ! {
+    eprintln!("Error: {}", err);
+    process::exit(exit_status(&err));
+}
+
+/// Parse a duration such as `5s`, `500ms`, `10us`, `2m`, `1h` or `30d`
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration '{}' - must be a number followed by us, ms, s, m, h or d", value);
+    let unit_start = value.find(|c: char| !c.is_ascii_digit() && c != '.').ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(unit_start);
+    let amount: f64 = amount.parse().map_err(|_| invalid())?;
+    let unit_seconds = match unit {
+        "us" => 1e-6,
+        "ms" => 1e-3,
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        "d" => 86400.0,
+        _ => return Err(invalid()),
+    };
+    Duration::try_from_secs_f64(amount * unit_seconds).map_err(|_| invalid())
+}
+
+/// Parse a duration like [`parse_duration`], refusing a zero one
+pub fn parse_nonzero_duration(value: &str) -> Result<Duration, String> {
+    match parse_duration(value)? {
+        duration if duration.is_zero() => Err("Invalid duration - must be longer than 0".to_string()),
+        duration => Ok(duration),
+    }
+}
+
+/// Parse a rate such as `50000/s`, `120/ms` or `50000` (per second), in
+/// events per second
+pub fn parse_rate(value: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid rate '{}' - must be a positive number per us, ms, s, m, h or d (e.g. 50000/s)", value);
+    let (count, per) = match value.split_once('/') {
+        Some((count, per)) if per.starts_with(|c: char| c.is_ascii_digit()) => (count, parse_duration(per)),
+        Some((count, per)) => (count, parse_duration(&format!("1{}", per))),
+        None => (value, Ok(Duration::from_secs(1))),
+    };
+    let count: f64 = count.parse().map_err(|_| invalid())?;
+    let per = per.map_err(|_| invalid())?;
+    if !count.is_finite() || count <= 0.0 || per.is_zero() {
+        return Err(invalid());
+    }
+    Ok(count / per.as_secs_f64())
+}
+
+/// Parse a date/time as `parse_user_datetime` reads it, for a flag's value
+pub fn parse_datetime(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_user_datetime(value).map_err(|e| e.to_string())
+}
+
+/// Parse a count, thread number or limit of at least 1
+pub fn parse_positive<T: FromStr + PartialOrd + Default>(value: &str) -> Result<T, String> {
+    value.parse().ok().filter(|value| *value > T::default()).ok_or_else(|| "must be a positive number".to_string())
+}
+
+/// A top-level `key = value` entry of a TOML file (configuration, prefix
+/// registry or generator state), with the line number of its value
+pub struct TomlEntry {
+    pub key: String,
+    pub value: toml::Value,
+    pub line: usize,
+}
+
+/// Parse a TOML document into its top-level entries, in order, reporting
+/// syntax errors as `line N: message`
+pub fn parse_toml(contents: &str) -> Result<Vec<TomlEntry>, String> {
+    let line = |offset: usize| contents[..offset].matches('\n').count() + 1;
+    let table: BTreeMap<String, toml::Spanned<toml::Value>> = toml::from_str(contents).map_err(|err| match err.span() {
+        Some(span) => format!("line {}: {}", line(span.start), err.message().trim_end()),
+        None => err.message().trim_end().to_string(),
+    })?;
+    let mut entries: Vec<TomlEntry> = table
+        .into_iter()
+        .map(|(key, value)| TomlEntry { key, line: line(value.span().start), value: value.into_inner() })
+        .collect();
+    entries.sort_by_key(|entry| entry.line);
+    Ok(entries)
+}
+
+/// Describe how long ago (or how far in the future) `datetime` is, e.g. "3 days ago"
+pub fn format_age(datetime: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let delta = now.signed_duration_since(datetime);
+    let seconds = delta.num_seconds().unsigned_abs();
+    let (amount, unit) = match seconds {
+        0..=59 => (seconds, "second"),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86399 => (seconds / 3600, "hour"),
+        86400..=2_591_999 => (seconds / 86400, "day"),
+        2_592_000..=31_535_999 => (seconds / 2_592_000, "month"),
+        _ => (seconds / 31_536_000, "year"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    if seconds == 0 {
+        "just now".to_string()
+    } else if delta.num_seconds() > 0 {
+        format!("{} {}{} ago", amount, unit, plural)
+    } else {
+        format!("in {} {}{}", amount, unit, plural)
+    }
+}
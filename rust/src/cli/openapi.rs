@@ -0,0 +1,255 @@
+//! OpenAPI 3 description of `bcid serve`, served at `/openapi.json`
+//!
+//! Operations come from the `#[utoipa::path]` attributes on the handlers in
+//! [`super::serve`]. The server writes its JSON with [`bcid::json`], so the
+//! types below only describe the bodies; none is built or parsed.
+
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+
+use utoipa::openapi::schema::{OneOfBuilder, Schema};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{OpenApi as Document, Ref, RefOr};
+use utoipa::{IntoParams, Modify, OpenApi, PartialSchema, ToResponse, ToSchema};
+
+use super::serve;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "bcid serve",
+        description = "Centralized generation and decoding of BCIDs (Base62 Chronological Identifiers). \
+                       Chronological identifiers are monotonic across all requests to one server. \
+                       Errors carry the same reason codes as `bcid validate`."
+    ),
+    servers((url = "http://127.0.0.1:8080")),
+    paths(
+        serve::generate_ids,
+        serve::post_batch,
+        serve::decode_id,
+        serve::health,
+        serve::readiness,
+        serve::prometheus_metrics,
+        serve::openapi_document
+    ),
+    components(responses(BadRequest, Unauthorized, RateLimited, MachineIdUnavailable)),
+    modifiers(&Security, &Healthz),
+    security(("bearer" = []), ("apiKey" = []))
+)]
+struct ApiDoc;
+
+/// The document as pretty-printed JSON
+pub fn document() -> String {
+    let mut openapi = ApiDoc::openapi();
+    // utoipa fills the license in from Cargo.toml, which has none
+    openapi.info.license = None;
+    openapi.to_pretty_json().expect("the OpenAPI document serializes")
+}
+
+/// The bearer token and `X-API-Key` schemes
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, openapi: &mut Document) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        let mut bearer = HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build();
+        bearer.description = Some("Required when the server is started with tokens".to_string());
+        components.add_security_scheme("bearer", SecurityScheme::Http(bearer));
+        let api_key = ApiKeyValue::with_description("X-API-Key", "Alternative to the bearer token");
+        components.add_security_scheme("apiKey", SecurityScheme::ApiKey(ApiKey::Header(api_key)));
+    }
+}
+
+/// `/healthz`, answered by the same handler as `/health`
+struct Healthz;
+
+impl Modify for Healthz {
+    fn modify(&self, openapi: &mut Document) {
+        let Some(mut item) = openapi.paths.paths.get("/health").cloned() else {
+            return;
+        };
+        if let Some(operation) = &mut item.get {
+            operation.operation_id = Some("healthz".to_string());
+            operation.summary = Some("Liveness check (same as /health)".to_string());
+        }
+        openapi.paths.paths.insert("/healthz".to_string(), item);
+    }
+}
+
+/// Parameters of `POST /v1/ids`, which `POST /v1/ids/batch` also takes in
+/// its body
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GenerateQuery {
+    /// Identifier prefix; required unless the server has a default prefix
+    #[param(min_length = 1, max_length = 8)]
+    prefix: Option<String>,
+    /// Number of identifiers, between 1 and the server's --max-count
+    #[param(minimum = 1, default = 1)]
+    count: Option<u64>,
+    /// Generate fully random identifiers instead of chronological ones
+    #[param(default = false)]
+    random: Option<bool>,
+    /// Identifier layout; the server's default when omitted
+    format: Option<Format>,
+}
+
+/// Identifier layout, by version with or without the `v`
+#[derive(ToSchema)]
+pub enum Format {
+    #[schema(rename = "v1")]
+    V1,
+    #[schema(rename = "v2")]
+    V2,
+    #[schema(rename = "v3")]
+    V3,
+    #[schema(rename = "v4")]
+    V4,
+    #[schema(rename = "v5")]
+    V5,
+    #[schema(rename = "1")]
+    One,
+    #[schema(rename = "2")]
+    Two,
+    #[schema(rename = "3")]
+    Three,
+    #[schema(rename = "4")]
+    Four,
+    #[schema(rename = "5")]
+    Five,
+}
+
+/// The identifiers of `POST /v1/ids`, in generation order
+#[derive(ToSchema)]
+pub struct Ids {
+    ids: Vec<String>,
+}
+
+/// Body of `POST /v1/ids/batch`
+#[derive(ToSchema)]
+pub struct BatchRequest {
+    /// Identifier prefix; required unless the server has a default prefix
+    #[schema(min_length = 1, max_length = 8)]
+    prefix: Option<String>,
+    /// Number of identifiers, between 1 and the server's --max-batch-count
+    #[schema(minimum = 1, default = 1)]
+    count: Option<u64>,
+    #[schema(default = false)]
+    random: Option<bool>,
+    format: Option<Format>,
+}
+
+/// One identifier of a streamed batch
+#[derive(ToSchema)]
+pub struct BatchLine {
+    id: String,
+}
+
+/// A line of a streamed batch: an identifier, or the error that ended it
+pub struct BatchRecord;
+
+impl PartialSchema for BatchRecord {
+    fn schema() -> RefOr<Schema> {
+        let one_of = OneOfBuilder::new().item(Ref::from_schema_name("BatchLine")).item(Ref::from_schema_name("Error"));
+        RefOr::T(Schema::OneOf(one_of.build()))
+    }
+}
+
+impl ToSchema for BatchRecord {
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        schemas.push((BatchLine::name().into(), BatchLine::schema()));
+        schemas.push((Error::name().into(), Error::schema()));
+    }
+}
+
+/// The components of an identifier, as `bcid decode --json`
+#[derive(ToSchema)]
+pub struct Decoded {
+    prefix: String,
+    #[schema(rename = "type")]
+    identifier_type: IdentifierType,
+    format: String,
+    layout: String,
+    alphabet: String,
+    /// Packed YYYYMMDDHHmmSScc timestamp; chronological identifiers only
+    timestamp: Option<i64>,
+    precision: Option<String>,
+    #[schema(format = DateTime)]
+    iso_datetime: Option<String>,
+    #[schema(maximum = 65535)]
+    machine_id: u16,
+    environment: Option<String>,
+    shard: Option<String>,
+    bit_layout: Option<String>,
+    sequence: Option<u64>,
+    /// Random value; chronological identifiers only
+    random: Option<u64>,
+    /// Random data after the machine ID; random identifiers only
+    random_part: Option<String>,
+    raw: String,
+}
+
+#[derive(ToSchema)]
+#[schema(rename_all = "snake_case")]
+pub enum IdentifierType {
+    Chronological,
+    Random,
+}
+
+/// An error and its reason code
+#[derive(ToSchema)]
+pub struct Error {
+    /// Human-readable message
+    error: String,
+    /// Machine-readable reason code, as reported by `bcid validate`
+    reason: String,
+}
+
+/// Body of the liveness checks
+#[derive(ToSchema)]
+pub struct Health {
+    status: HealthStatus,
+}
+
+#[derive(ToSchema)]
+#[schema(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+}
+
+/// Body of `GET /readyz`
+#[derive(ToSchema)]
+pub struct Readiness {
+    status: ReadinessStatus,
+    /// `ok`, or why the check failed, for `clock`, `entropy`, and
+    /// `state_file` and `machine_id` when the server has a state file or a
+    /// leased machine ID
+    checks: BTreeMap<String, String>,
+}
+
+#[derive(ToSchema)]
+#[schema(rename_all = "snake_case")]
+pub enum ReadinessStatus {
+    Ready,
+    NotReady,
+}
+
+/// Invalid parameters or identifier
+#[derive(ToResponse)]
+pub struct BadRequest(Error);
+
+/// The server requires a token and none valid was given (reason
+/// `unauthorized`)
+#[derive(ToResponse)]
+pub struct Unauthorized(Error);
+
+/// The client is over its rate limit (reason `rate_limited`)
+#[derive(ToResponse)]
+#[response(headers(("Retry-After" = u64, description = "Seconds until the request would be allowed")))]
+pub struct RateLimited(Error);
+
+/// The lease on the server's machine ID has been lost, so it generates no
+/// identifiers until a renewal succeeds (reason `machine_id_unavailable`)
+#[derive(ToResponse)]
+pub struct MachineIdUnavailable(Error);
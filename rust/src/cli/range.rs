@@ -0,0 +1,62 @@
+use bcid::{json, Bcid};
+use chrono::{DateTime, Duration, Utc};
+use clap::Args;
+
+use super::config::Config;
+use super::{exit_with_error, parse_datetime, usage_error};
+
+/// Print the smallest and largest identifiers for a time window
+///
+/// Prints the smallest and largest possible v2 identifiers with PREFIX created
+/// from FROM up to, but not including, TO, one per line, for range scans such as
+/// `WHERE id BETWEEN $1 AND $2`. The database must compare identifiers bytewise
+/// (e.g. the `C` collation in PostgreSQL).
+///
+/// Only v2 identifiers (`generate --format-version 2`) sort by time; v1
+/// identifiers fall inside or outside the range arbitrarily.
+#[derive(Args)]
+#[command(after_help = EXAMPLES)]
+pub struct RangeArgs {
+    /// Prefix of 1-8 characters (default: from the config file)
+    #[arg(short, long)]
+    prefix: Option<String>,
+    /// Start of the window (inclusive)
+    #[arg(long, value_name = "DATETIME", value_parser = parse_datetime)]
+    from: DateTime<Utc>,
+    /// End of the window (exclusive; default: now)
+    #[arg(long, value_name = "DATETIME", value_parser = parse_datetime)]
+    to: Option<DateTime<Utc>>,
+    /// Print {"min": ..., "max": ...}
+    #[arg(long)]
+    json: bool,
+}
+
+const EXAMPLES: &str = "\
+Date/time formats: 2023-12-25T10:30:00, 2023-12-25 10:30:00, 2023-12-25 (UTC)
+
+Examples:
+  bcid range -p TEST --from 2024-01-01 --to 2024-01-02";
+
+pub fn run(args: RangeArgs, config: &Config) {
+    let Some(prefix) = args.prefix.or_else(|| config.prefix.clone()) else {
+        usage_error("range", "Missing prefix argument");
+    };
+    let from = args.from;
+    let to = args.to.unwrap_or_else(Utc::now);
+    if to <= from {
+        usage_error("range", "--to must be after --from");
+    }
+
+    let min = Bcid::min_for(&prefix, from).unwrap_or_else(|e| exit_with_error(e));
+    // The last 10ms tick that starts before `to`
+    let max = Bcid::max_for(&prefix, to - Duration::nanoseconds(1)).unwrap_or_else(|e| exit_with_error(e));
+    if args.json {
+        println!(
+            "{}",
+            json::Object::new().string("min", Some(min.as_str())).string("max", Some(max.as_str())).finish()
+        );
+    } else {
+        println!("{}", min);
+        println!("{}", max);
+    }
+}
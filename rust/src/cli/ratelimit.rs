@@ -0,0 +1,60 @@
+//! Per-client token buckets for `bcid serve --rate-limit`
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Clients tracked before full buckets, then the least recently refilled
+/// one, are forgotten
+const MAX_CLIENTS: usize = 10_000;
+
+/// Token buckets that refill at `rate` tokens per second up to `burst`,
+/// one per client
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: f64) -> Self {
+        RateLimiter { rate, burst, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    /// Take `cost` tokens from the bucket of `client`, or say how long
+    /// until it holds enough
+    pub fn take(&self, client: &str, cost: f64) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_CLIENTS && !buckets.contains_key(client) {
+            // A client whose bucket has refilled is the same as a new one
+            buckets.retain(|_, bucket| bucket.refilled(now, self.rate, self.burst) < self.burst);
+        }
+        if buckets.len() >= MAX_CLIENTS && !buckets.contains_key(client) {
+            let oldest = buckets.iter().min_by_key(|(_, bucket)| bucket.updated).map(|(client, _)| client.clone());
+            if let Some(oldest) = oldest {
+                buckets.remove(&oldest);
+            }
+        }
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket { tokens: self.burst, updated: now });
+        bucket.tokens = bucket.refilled(now, self.rate, self.burst);
+        bucket.updated = now;
+        if bucket.tokens < cost {
+            return Err(Duration::from_secs_f64((cost - bucket.tokens) / self.rate));
+        }
+        bucket.tokens -= cost;
+        Ok(())
+    }
+}
+
+impl Bucket {
+    /// Tokens held at `now`
+    fn refilled(&self, now: Instant, rate: f64, burst: f64) -> f64 {
+        (self.tokens + now.duration_since(self.updated).as_secs_f64() * rate).min(burst)
+    }
+}
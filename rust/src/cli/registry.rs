@@ -0,0 +1,90 @@
+//! Registry of known prefixes and the entities they identify, read from a
+//! `prefixes.toml` file
+//!
+//! ```toml
+//! USER = "user accounts service"
+//! ORDR = "orders"
+//! "inv" = "invoices"
+//! ```
+//!
+//! With a registry (`--registry FILE`, or `registry` in the configuration
+//! file), `generate` and `decode` warn about prefixes it does not list, and
+//! `decode` prints the entity name of the ones it does.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use bcid::{BcidError, MAX_PREFIX_LENGTH, MIN_PREFIX_LENGTH, SEPARATOR};
+use toml::Value;
+
+use super::{parse_toml, EXIT_FAILURE, EXIT_USAGE};
+
+/// Known prefixes and their entity names
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    path: PathBuf,
+    entities: BTreeMap<String, String>,
+}
+
+impl Registry {
+    /// Load the registry in `path`, exiting with an error if it cannot be
+    /// read or parsed
+    pub fn load(path: &Path) -> Registry {
+        let contents = fs::read_to_string(path).unwrap_or_else(|err| {
+            eprintln!("Error: Cannot read {}: {}", path.display(), err);
+            process::exit(EXIT_FAILURE);
+        });
+        let entities = Registry::parse(&contents).unwrap_or_else(|err| {
+            eprintln!("Error: Invalid prefix registry in {}: {}", path.display(), err);
+            process::exit(EXIT_USAGE);
+        });
+        Registry { path: path.to_path_buf(), entities }
+    }
+
+    /// Parse registry file contents into entity names by prefix
+    pub fn parse(contents: &str) -> Result<BTreeMap<String, String>, String> {
+        let mut entities = BTreeMap::new();
+        for entry in parse_toml(contents)? {
+            let Value::String(entity) = entry.value else {
+                return Err(format!("line {}: `{}` must be a string naming the entity", entry.line, entry.key));
+            };
+            check_prefix(&entry.key).map_err(|e| format!("line {}: {}", entry.line, e))?;
+            entities.insert(entry.key, entity);
+        }
+        Ok(entities)
+    }
+
+    /// The registered prefixes
+    pub fn prefixes(&self) -> impl Iterator<Item = &str> {
+        self.entities.keys().map(String::as_str)
+    }
+
+    /// The entity name registered for `prefix`
+    pub fn entity(&self, prefix: &str) -> Option<&str> {
+        self.entities.get(prefix).map(String::as_str)
+    }
+
+    /// The entity name registered for `prefix`, warning on stderr if there
+    /// is none
+    pub fn lookup(&self, prefix: &str) -> Option<&str> {
+        let entity = self.entity(prefix);
+        if entity.is_none() {
+            eprintln!("Warning: Prefix '{}' is not in the registry {}", prefix, self.path.display());
+        }
+        entity
+    }
+}
+
+/// Check that a registry key could be the prefix of an identifier
+fn check_prefix(prefix: &str) -> Result<(), BcidError> {
+    let len = prefix.chars().count();
+    if !(MIN_PREFIX_LENGTH..=MAX_PREFIX_LENGTH).contains(&len) {
+        return Err(BcidError::InvalidPrefixLength(len));
+    }
+    match prefix.chars().find(|&c| !c.is_ascii_graphic() || c == SEPARATOR) {
+        Some(c) => Err(BcidError::InvalidPrefixCharacter(c)),
+        None => Ok(()),
+    }
+}
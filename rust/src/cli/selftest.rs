@@ -0,0 +1,74 @@
+use std::collections::HashSet;
+use std::process;
+
+use bcid::{json, selftest};
+use clap::Args;
+
+use super::{parse_positive, EXIT_FAILURE};
+
+/// Check that generated identifiers decode to what they were encoded from
+///
+/// Generates COUNT identifiers across every generator mode (random, v1 to v5,
+/// every alphabet and precision, environment and shard tags, v5 layouts,
+/// monotonic and safe generation), decodes each one again and checks that every
+/// component comes back as it was encoded: prefix, type, format, alphabet,
+/// precision, timestamp, machine ID, tags, layout, sequence number, random value
+/// and length. Prints each component that does not round-trip and exits with
+/// status 1 if any does, so it verifies an installed binary.
+#[derive(Args)]
+#[command(after_help = EXAMPLES)]
+pub struct SelftestArgs {
+    /// Number of identifiers to check
+    #[arg(short = 'n', long, value_parser = parse_positive::<usize>, default_value_t = 10_000)]
+    count: usize,
+    /// Print the failures and a summary as JSON Lines
+    #[arg(long)]
+    json: bool,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid selftest
+  bcid selftest -n 1000000";
+
+pub fn run(args: SelftestArgs) {
+    let (count, is_json) = (args.count, args.json);
+
+    let report = selftest(count);
+    for failure in &report.failures {
+        if is_json {
+            let record = json::Object::new()
+                .string("identifier", Some(failure.identifier.as_str()).filter(|identifier| !identifier.is_empty()))
+                .string("mode", Some(&failure.mode))
+                .string("component", Some(failure.component))
+                .string("expected", Some(&failure.expected))
+                .string("found", Some(&failure.found))
+                .finish();
+            println!("{}", record);
+        } else {
+            println!("fail  {}", failure);
+        }
+    }
+    if is_json {
+        let summary = json::Object::new()
+            .number("checked", Some(report.checked))
+            .number("modes", Some(report.modes))
+            .number("failures", Some(report.failures.len()))
+            .boolean("ok", report.is_ok())
+            .finish();
+        println!("{}", summary);
+    } else if report.is_ok() {
+        println!("ok    {} identifiers in {} modes round-trip", report.checked, report.modes);
+    } else {
+        println!(
+            "fail  {} of {} identifiers in {} modes have components that do not round-trip",
+            report.failures.iter().map(|failure| &failure.identifier).collect::<HashSet<_>>().len(),
+            report.checked,
+            report.modes
+        );
+    }
+
+    if !report.is_ok() {
+        process::exit(EXIT_FAILURE);
+    }
+}
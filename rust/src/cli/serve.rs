@@ -0,0 +1,1130 @@
+//! `bcid serve`: a long-running HTTP server for centralized ID generation
+//!
+//! ```text
+//! POST /v1/ids?prefix=TEST&count=100   -> {"ids":["TEST...", ...]}
+//! POST /v1/ids/batch {"count":100000}  -> {"id":"TEST..."} per line, streamed
+//! GET  /v1/decode/{id}                 -> decoded components (as `bcid decode --json`)
+//! GET  /health, /healthz              -> {"status":"ok"}
+//! GET  /readyz                         -> {"status":"ready","checks":{...}}, or 503 if not
+//! GET  /metrics                        -> Prometheus metrics (see [`super::metrics`])
+//! GET  /openapi.json                   -> OpenAPI 3 description of these endpoints
+//! ```
+//!
+//! Errors are returned as `{"error": message, "reason": code}` with a 4xx
+//! status, or 503 `machine_id_unavailable` while the lease on a leased
+//! machine ID is lost. With tokens configured, every endpoint but the probes and
+//! `/openapi.json` requires one, and `--rate-limit` gives each client (its token, or its address
+//! without tokens) a token bucket charged one per identifier. With `--unix PATH` the server listens on a Unix domain socket
+//! instead and speaks the line protocol described in [`super::socket`].
+//! With `--tls-cert` and `--tls-key` (the `tls` feature) it serves HTTPS,
+//! and `--tls-client-ca` also requires client certificates.
+//! `--grpc-port` (the `grpc` feature) serves the gRPC service of
+//! [`super::grpc`] alongside, with the same settings.
+//! SIGINT and SIGTERM stop accepting new connections and wait for in-flight
+//! requests to finish.
+
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bcid::{
+    append_check_digit, decode_identifier, json, BcidGenerator,
+    verify_check_digit, Bcid, BcidError, Format, GeneratorState, MachineIdLease, MAX_PREFIX_LENGTH, MIN_PREFIX_LENGTH,
+};
+use chrono::Utc;
+use clap::Args;
+use tracing::{debug, error, info, warn, Level};
+
+use super::config::Config;
+use super::doctor::entropy_failure;
+use super::generate::{parse_format_version, MachineIdOption};
+use super::http::{self, ChunkedBody, ReadError, Request, Response};
+use super::log::LogOptions;
+use super::metrics::{self, Metrics};
+use super::openapi::{self, BadRequest, BatchRecord, BatchRequest, Decoded, GenerateQuery, Health, Ids, MachineIdUnavailable, RateLimited, Readiness, Unauthorized};
+use super::ratelimit::RateLimiter;
+use super::{parse_duration, parse_positive, parse_rate, state, usage_error, EXIT_FAILURE};
+
+/// Run an HTTP server that generates and decodes identifiers
+#[derive(Args)]
+#[command(after_help = NOTES)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1")]
+    bind: String,
+    /// Port to listen on
+    #[arg(long, default_value_t = 8080)]
+    port: u16,
+    /// Listen on a Unix domain socket with the line protocol instead of HTTP
+    #[arg(long = "unix", value_name = "PATH")]
+    unix_socket: Option<PathBuf>,
+    /// Also serve the gRPC BcidService of proto/bcid.proto on PORT (needs a
+    /// build with the `grpc` feature)
+    #[arg(long, value_name = "PORT")]
+    grpc_port: Option<u16>,
+    /// Serve HTTPS with the PEM certificate chain in FILE (needs --tls-key and
+    /// a build with the `tls` feature); the TLS options apply to gRPC too
+    #[arg(long, value_name = "FILE")]
+    tls_cert: Option<PathBuf>,
+    /// PEM private key of --tls-cert
+    #[arg(long, value_name = "FILE")]
+    tls_key: Option<PathBuf>,
+    /// Require client certificates issued by the PEM CA certificates in FILE
+    /// (mTLS); other clients fail the handshake
+    #[arg(long, value_name = "FILE")]
+    tls_client_ca: Option<PathBuf>,
+    /// Prefix used when a request does not give one
+    #[arg(short, long)]
+    prefix: Option<String>,
+    /// 16-bit machine identifier (0-65535, default: 1), `auto` to derive one
+    /// from the host, `k8s` to derive one from the Kubernetes pod, or a
+    /// redis://, etcd://, consul:// or file:// URL to lease a unique one while
+    /// running
+    #[arg(short, long, value_parser = MachineIdOption::from_spec)]
+    machine_id: Option<MachineIdOption>,
+    /// Layout of generated identifiers unless a request gives `format`: 1
+    /// (default), 2, 3, 4 or 5
+    #[arg(long, value_name = "VERSION", value_parser = parse_format_version)]
+    format_version: Option<Format>,
+    /// Same as --format-version 2
+    #[arg(long, conflicts_with = "format_version")]
+    sortable: bool,
+    /// End generated identifiers in a check digit and verify it when decoding
+    #[arg(long)]
+    check_digit: bool,
+    /// Save the latest clock reading to FILE on each readiness check and at
+    /// shutdown, and compare the clock with it on startup
+    #[arg(long, value_name = "FILE")]
+    state_file: Option<PathBuf>,
+    /// How far the clock may read behind its latest reading before /readyz
+    /// fails
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "1s")]
+    clock_tolerance: Duration,
+    /// Largest number of identifiers per request
+    #[arg(long, value_name = "COUNT", value_parser = parse_positive::<usize>, default_value_t = 10000)]
+    max_count: usize,
+    /// Largest number of connections served at once; further connections
+    /// are answered with 503 `too_many_connections` and closed
+    #[arg(long, value_name = "N", value_parser = parse_positive::<usize>, default_value_t = 256)]
+    max_connections: usize,
+    /// Largest number of identifiers per streamed batch request
+    #[arg(long, value_name = "COUNT", value_parser = parse_positive::<usize>, default_value_t = 1_000_000)]
+    max_batch_count: usize,
+    /// Require this token (`Authorization: Bearer TOKEN` or `X-API-Key:
+    /// TOKEN`) on every endpoint but the health checks and /openapi.json; may
+    /// be repeated, and replaces tokens from BCID_AUTH_TOKENS
+    #[arg(long = "auth-token", value_name = "TOKEN")]
+    auth_tokens: Vec<String>,
+    /// Read tokens from FILE, one per line (# starts a comment), keeping them
+    /// out of process listings
+    #[arg(long, value_name = "FILE")]
+    auth_token_file: Option<PathBuf>,
+    /// Identifiers each client may generate, e.g. 1000/s or 100000/h; decodes
+    /// and invalid requests count as one. A client is its token, or its
+    /// address without tokens
+    #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+    rate_limit: Option<f64>,
+    /// Identifiers a client may take at once (default: one second of
+    /// --rate-limit, and at least --max-count)
+    #[arg(long, value_name = "N", value_parser = parse_positive::<usize>, requires = "rate_limit")]
+    rate_burst: Option<usize>,
+    #[command(flatten)]
+    log: LogOptions,
+    /// Same as --log-level debug
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+const NOTES: &str = "\
+Endpoints:
+  POST /v1/ids?prefix=TEST&count=100   Generate identifiers; also accepts
+                                       `random=true` and `format=v1`..`v5`
+  POST /v1/ids/batch                   Generate a large batch, taking the same parameters
+                                       as a JSON body ({\"prefix\":\"TEST\",\"count\":100000})
+                                       and streaming one {\"id\":...} object per line
+  GET  /v1/decode/{id}                 Decode an identifier
+  GET  /health, /healthz              Liveness check
+  GET  /readyz                         Readiness check: fails with 503 if the clock reads
+                                       earlier than --clock-tolerance behind its latest
+                                       reading, the random number generator fails, the
+                                       state file cannot be written, or the machine ID
+                                       lease has been lost
+  GET  /metrics                        Prometheus metrics: identifiers generated per
+                                       prefix, decode errors, request latencies, clock
+                                       regressions and sequence overflows
+  GET  /openapi.json                   OpenAPI 3 description of these endpoints
+
+Unix socket protocol (one request and one response per line):
+  GENERATE prefix=TEST count=100       OK <id> <id> ...   (also random=true, format=v1..v5)
+  DECODE <id>                          OK <json>
+  PING                                 OK
+Errors are answered with `ERR <reason> <message>`.
+
+Requests without a valid token are answered with 401 `unauthorized`, and
+requests over the rate limit with 429 `rate_limited` and a Retry-After header.
+The Unix socket relies on its file permissions instead.
+
+While the lease on a leased machine ID is lost, every generation request
+fails with 503 `machine_id_unavailable` (`ERR machine_id_unavailable` on the
+Unix socket), until a renewal succeeds again.
+
+gRPC calls take the same parameters, tokens (`authorization` or `x-api-key`
+metadata) and rate limits; errors are INVALID_ARGUMENT, UNAUTHENTICATED,
+RESOURCE_EXHAUSTED or UNAVAILABLE with `reason: message` as the status
+message.
+
+Chronological identifiers are monotonic across all requests to the server.
+At most --max-connections connections are served at once; further ones are
+answered with 503 `too_many_connections` (`ERR too_many_connections` on the
+Unix socket) and closed. Connections idle for 5 seconds are closed.
+Bodies need Content-Length and at most 1 MiB: requests with
+Transfer-Encoding are answered with 411 `length_required`, and larger bodies
+with 413 `payload_too_large`.
+SIGINT or SIGTERM stops the server once in-flight requests have completed.
+
+Examples:
+  bcid serve --port 8080 -m auto
+  BCID_AUTH_TOKENS=s3cr3t bcid serve --bind 0.0.0.0 --rate-limit 1000/s
+  bcid serve --unix /run/bcid.sock
+  bcid serve --port 8080 --grpc-port 50051
+  bcid serve --bind 0.0.0.0 --tls-cert server.pem --tls-key server.key --tls-client-ca clients.pem
+  curl -X POST 'http://127.0.0.1:8080/v1/ids?prefix=TEST&count=3'
+  curl -X POST -d '{\"prefix\":\"TEST\",\"count\":100000}' http://127.0.0.1:8080/v1/ids/batch
+  curl http://127.0.0.1:8080/v1/decode/TESTa1b2c3d4e5f6g7h8i9j0k1l2m3n4";
+
+
+/// How often the accept loop checks for a shutdown request
+pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long an idle keep-alive connection is held open
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Identifiers generated and sent per chunk of a streamed batch
+pub const BATCH_CHUNK: usize = 1000;
+
+/// Content type of streamed batches: one JSON object per line
+const NDJSON: &str = "application/x-ndjson";
+
+/// Set by the signal handler to request a graceful shutdown
+pub static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// Options for `bcid serve`
+pub struct ServeOptions {
+    pub bind: String,
+    pub port: u16,
+    pub unix_socket: Option<PathBuf>,
+    pub grpc_port: Option<u16>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// CA certificates that client certificates must be issued by
+    pub tls_client_ca: Option<PathBuf>,
+    pub prefix: Option<String>,
+    pub machine_id: MachineIdOption,
+    pub format: Format,
+    pub max_count: usize,
+    pub max_batch_count: usize,
+    pub max_connections: usize,
+    pub with_check_digit: bool,
+    pub state_file: Option<PathBuf>,
+    pub clock_tolerance: Duration,
+    pub is_verbose: bool,
+    pub log: LogOptions,
+    /// Tokens clients must present, none to allow any client
+    pub auth_tokens: Vec<String>,
+    /// Identifiers per second each client may generate
+    pub rate_limit: Option<f64>,
+    /// Identifiers a client may take at once
+    pub rate_burst: Option<usize>,
+}
+
+impl ServeOptions {
+    /// Defaults from the configuration file
+    pub fn from_config(config: &Config) -> Self {
+        ServeOptions {
+            bind: "127.0.0.1".to_string(),
+            port: 8080,
+            unix_socket: None,
+            grpc_port: None,
+            tls_cert: None,
+            tls_key: None,
+            tls_client_ca: None,
+            prefix: config.prefix.clone(),
+            machine_id: config.machine_id.clone().unwrap_or_default(),
+            format: config.format.unwrap_or_default(),
+            max_count: 10000,
+            max_batch_count: 1_000_000,
+            max_connections: 256,
+            with_check_digit: config.check_digit,
+            state_file: None,
+            clock_tolerance: Duration::from_secs(1),
+            is_verbose: false,
+            log: LogOptions::default(),
+            auth_tokens: config.auth_tokens.clone(),
+            rate_limit: None,
+            rate_burst: None,
+        }
+    }
+
+    /// Apply the flags given on the command line over these options
+    pub fn apply(&mut self, args: ServeArgs) {
+        self.bind = args.bind;
+        self.port = args.port;
+        self.unix_socket = args.unix_socket;
+        self.grpc_port = args.grpc_port;
+        self.tls_cert = args.tls_cert;
+        self.tls_key = args.tls_key;
+        self.tls_client_ca = args.tls_client_ca;
+        self.prefix = args.prefix.or(self.prefix.take());
+        if let Some(machine_id) = args.machine_id {
+            self.machine_id = machine_id;
+        }
+        if args.sortable {
+            self.format = Format::V2;
+        } else if let Some(format) = args.format_version {
+            self.format = format;
+        }
+        self.with_check_digit |= args.check_digit;
+        self.state_file = args.state_file;
+        self.clock_tolerance = args.clock_tolerance;
+        self.max_count = args.max_count;
+        self.max_batch_count = args.max_batch_count;
+        self.max_connections = args.max_connections;
+        let mut auth_tokens = args.auth_tokens;
+        if let Some(path) = args.auth_token_file {
+            let contents = std::fs::read_to_string(&path).unwrap_or_else(|err| {
+                eprintln!("Error: Cannot read {}: {}", path.display(), err);
+                std::process::exit(EXIT_FAILURE);
+            });
+            let tokens = contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+            auth_tokens.extend(tokens.map(String::from));
+        }
+        if !auth_tokens.is_empty() {
+            self.auth_tokens = auth_tokens;
+        }
+        self.rate_limit = args.rate_limit;
+        self.rate_burst = args.rate_burst;
+        self.is_verbose = args.verbose;
+        self.log = args.log;
+        if args.verbose {
+            self.log.level = Level::DEBUG;
+        }
+    }
+}
+
+/// Settings shared by every connection
+pub struct Server {
+    pub prefix: Option<String>,
+    pub machine_id: u16,
+    /// Lease on the machine ID, released when the server is dropped
+    pub machine_id_lease: Option<MachineIdLease>,
+    pub format: Format,
+    pub max_count: usize,
+    pub max_batch_count: usize,
+    /// Connections served at once, beyond which new ones are refused
+    pub max_connections: usize,
+    pub with_check_digit: bool,
+    /// Monotonic sequence and latest clock reading shared by every request
+    pub generator_state: Mutex<GeneratorState>,
+    /// File the latest clock reading is saved to
+    pub state_file: Option<PathBuf>,
+    /// How far the clock may regress before the server is not ready
+    pub clock_tolerance: Duration,
+    pub auth_tokens: Vec<String>,
+    pub rate_limiter: Option<RateLimiter>,
+    pub metrics: Metrics,
+    /// TLS configuration, if the server speaks HTTPS
+    #[cfg(feature = "tls")]
+    pub tls: Option<Arc<rustls::ServerConfig>>,
+}
+
+impl Server {
+    /// The generator state shared by every request, locked
+    pub fn generator_state(&self) -> MutexGuard<'_, GeneratorState> {
+        self.generator_state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Why the machine ID must not be used, if its lease has been lost
+    pub fn lease_failure(&self) -> Option<String> {
+        let lease = self.machine_id_lease.as_ref()?;
+        (!lease.is_held()).then(|| format!("the lease on machine ID {} has been lost", lease.id()))
+    }
+
+    /// Decode an identifier, verifying its check digit if enabled
+    pub fn decode(&self, identifier: &str) -> Result<Bcid, BcidError> {
+        let decoded = match self.with_check_digit {
+            true => verify_check_digit(identifier).and_then(|_| decode_identifier(identifier)),
+            false => decode_identifier(identifier),
+        };
+        if let Err(err) = &decoded {
+            self.metrics.decode_error(err.reason());
+        }
+        decoded
+    }
+}
+
+/// Parameters of a generation request, as given by the client
+#[derive(Default)]
+pub struct GenerateParams<'a> {
+    pub prefix: Option<&'a str>,
+    pub count: Option<&'a str>,
+    pub random: Option<&'a str>,
+    pub format: Option<&'a str>,
+}
+
+impl<'a> GenerateParams<'a> {
+    /// Collect the known parameters from `key=value` pairs
+    pub fn from_pairs(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut params = GenerateParams::default();
+        for (key, value) in pairs {
+            let slot = match key {
+                "prefix" => &mut params.prefix,
+                "count" => &mut params.count,
+                "random" => &mut params.random,
+                "format" => &mut params.format,
+                _ => continue,
+            };
+            slot.get_or_insert(value);
+        }
+        params
+    }
+}
+
+/// A rejected request: a machine-readable reason code and a message
+pub struct RequestError {
+    pub reason: &'static str,
+    pub message: String,
+}
+
+impl RequestError {
+    pub fn new(reason: &'static str, message: impl Into<String>) -> Self {
+        RequestError { reason, message: message.into() }
+    }
+
+    /// HTTP status of the error: 503 while the machine ID may not be used,
+    /// otherwise 400
+    pub fn status(&self) -> u16 {
+        match self.reason {
+            "machine_id_unavailable" => 503,
+            _ => 400,
+        }
+    }
+}
+
+impl From<BcidError> for RequestError {
+    fn from(err: BcidError) -> Self {
+        RequestError { reason: err.reason(), message: err.to_string() }
+    }
+}
+
+pub fn run(args: ServeArgs, config: &Config) {
+    let mut options = ServeOptions::from_config(config);
+    options.apply(args);
+    let rate_burst = match (options.rate_limit, options.rate_burst) {
+        (_, Some(burst)) if burst < options.max_count => {
+            usage_error("serve", "--rate-burst must be at least --max-count, or the largest requests could never be served")
+        }
+        (_, Some(burst)) => burst as f64,
+        (rate, None) => rate.unwrap_or_default().max(options.max_count as f64),
+    };
+    match (&options.tls_cert, &options.tls_key) {
+        (Some(_), None) => usage_error("serve", "--tls-cert requires --tls-key"),
+        (None, Some(_)) => usage_error("serve", "--tls-key requires --tls-cert"),
+        (None, None) if options.tls_client_ca.is_some() => usage_error("serve", "--tls-client-ca requires --tls-cert"),
+        (Some(_), Some(_)) if options.unix_socket.is_some() => usage_error("serve", "--tls-cert cannot be used with --unix"),
+        (Some(_), Some(_)) if cfg!(not(feature = "tls")) => {
+            usage_error("serve", "--tls-cert requires bcid to be built with the `tls` feature")
+        }
+        _ => {}
+    }
+    if options.grpc_port.is_some() && cfg!(not(feature = "grpc")) {
+        usage_error("serve", "--grpc-port requires bcid to be built with the `grpc` feature");
+    }
+    if let Some(prefix) = &options.prefix {
+        let len = prefix.chars().count();
+        if !(MIN_PREFIX_LENGTH..=MAX_PREFIX_LENGTH).contains(&len) {
+            usage_error("serve", &BcidError::InvalidPrefixLength(len).to_string());
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    let tls = match (&options.tls_cert, &options.tls_key) {
+        (Some(cert), Some(key)) => match super::tls::server_config(cert, key, options.tls_client_ca.as_deref()) {
+            Ok(config) => Some(config),
+            Err(message) => {
+                eprintln!("Error: {}", message);
+                std::process::exit(EXIT_FAILURE);
+            }
+        },
+        _ => None,
+    };
+
+    options.log.init();
+    let (machine_id, machine_id_lease) = options.machine_id.resolve(options.is_verbose);
+    let server = Server {
+        prefix: options.prefix,
+        machine_id,
+        machine_id_lease,
+        format: options.format,
+        max_count: options.max_count,
+        max_batch_count: options.max_batch_count,
+        max_connections: options.max_connections,
+        with_check_digit: options.with_check_digit,
+        generator_state: Mutex::new(GeneratorState::default()),
+        state_file: options.state_file,
+        clock_tolerance: options.clock_tolerance,
+        auth_tokens: options.auth_tokens,
+        rate_limiter: options.rate_limit.map(|rate| RateLimiter::new(rate, rate_burst)),
+        metrics: Metrics::default(),
+        #[cfg(feature = "tls")]
+        tls,
+    };
+    if let Some(path) = &server.state_file {
+        // Regressions are measured from the reading saved before a restart
+        server.generator_state().latest_reading = state::load(path).latest_reading;
+        if let Some(failure) = clock_failure(&server) {
+            warn!(check = "clock", "Not ready: {}", failure);
+        }
+    }
+    let server = Arc::new(server);
+    #[cfg(feature = "grpc")]
+    let grpc = options.grpc_port.map(|port| {
+        let (bind, server) = (options.bind.clone(), Arc::clone(&server));
+        let tls = options.tls_cert.clone().zip(options.tls_key.clone()).map(|files| (files, options.tls_client_ca.clone()));
+        thread::spawn(move || {
+            let tls = tls.as_ref().map(|((cert, key), client_ca)| (cert.as_path(), key.as_path(), client_ca.as_deref()));
+            let result = super::grpc::serve(&bind, port, server, tls);
+            // Stop the HTTP server too if gRPC cannot be served
+            SHUTDOWN.store(true, Ordering::SeqCst);
+            result
+        })
+    });
+    let result = match &options.unix_socket {
+        Some(path) => serve_unix(path, Arc::clone(&server)),
+        None => serve(&options.bind, options.port, Arc::clone(&server)),
+    };
+    #[cfg(feature = "grpc")]
+    let result = match grpc {
+        Some(grpc) => {
+            SHUTDOWN.store(true, Ordering::SeqCst);
+            result.and(grpc.join().unwrap_or(Ok(())))
+        }
+        None => result,
+    };
+    let result = result.and_then(|_| match &server.state_file {
+        Some(path) => save_clock(path, &server),
+        None => Ok(()),
+    });
+    if let Err(err) = result {
+        error!("{}", err);
+        std::process::exit(EXIT_FAILURE);
+    }
+}
+
+/// Serve HTTP on a TCP address
+fn serve(bind: &str, port: u16, server: Arc<Server>) -> io::Result<()> {
+    let listener = TcpListener::bind((bind, port))?;
+    // Non-blocking so the loop can notice a shutdown request between connections
+    listener.set_nonblocking(true)?;
+    install_signal_handlers();
+    #[cfg(feature = "tls")]
+    let scheme = if server.tls.is_some() { "https" } else { "http" };
+    #[cfg(not(feature = "tls"))]
+    let scheme = "http";
+    let address = format!("{}://{}", scheme, listener.local_addr()?);
+    info!(address, "Listening on {}", address);
+    accept_loop(|| listener.accept().map(|(stream, _)| stream), server, handle_connection, refuse_connection);
+    Ok(())
+}
+
+/// Serve the line protocol on a Unix domain socket, removing it on shutdown
+#[cfg(unix)]
+fn serve_unix(path: &std::path::Path, server: Arc<Server>) -> io::Result<()> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    // A socket file left behind by a server that did not shut down cleanly
+    if path.exists() && UnixStream::connect(path).is_err() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    listener.set_nonblocking(true)?;
+    install_signal_handlers();
+    let address = path.display().to_string();
+    info!(address, "Listening on {}", address);
+    let (handle, refuse) = (super::socket::handle_connection, super::socket::refuse_connection);
+    accept_loop(|| listener.accept().map(|(stream, _)| stream), server, handle, refuse);
+    drop(listener);
+    std::fs::remove_file(path)
+}
+
+#[cfg(not(unix))]
+fn serve_unix(_path: &std::path::Path, _server: Arc<Server>) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Unix domain sockets are not supported on this platform"))
+}
+
+/// Accept connections until a shutdown signal arrives, handling each on its
+/// own thread, then wait for in-flight connections to finish
+///
+/// Connections beyond `--max-connections` are passed to `refuse` instead.
+fn accept_loop<S: Send + 'static>(
+    mut accept: impl FnMut() -> io::Result<S>,
+    server: Arc<Server>,
+    handle: fn(S, &Server),
+    refuse: fn(S, &Server),
+) {
+    let mut workers: Vec<thread::JoinHandle<()>> = Vec::new();
+    while !SHUTDOWN.load(Ordering::SeqCst) {
+        match accept() {
+            Ok(stream) => {
+                workers.retain(|worker| !worker.is_finished());
+                if workers.len() >= server.max_connections {
+                    server.metrics.rejected("too_many_connections");
+                    refuse(stream, &server);
+                    continue;
+                }
+                let server = Arc::clone(&server);
+                workers.push(thread::spawn(move || handle(stream, &server)));
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => warn!(error = %err, "failed to accept connection: {}", err),
+        }
+    }
+
+    let connections = workers.iter().filter(|w| !w.is_finished()).count();
+    info!(connections, "Shutting down, waiting for {} connection(s)", connections);
+    for worker in workers {
+        let _ = worker.join();
+    }
+}
+
+/// Serve requests on one connection, after a TLS handshake if the server
+/// speaks HTTPS
+fn handle_connection(stream: TcpStream, server: &Server) {
+    if stream.set_nonblocking(false).is_err() || stream.set_read_timeout(Some(IDLE_TIMEOUT)).is_err() {
+        return;
+    }
+    let peer = stream.peer_addr().map(|address| address.ip().to_string()).unwrap_or_default();
+    #[cfg(feature = "tls")]
+    if let Some(config) = &server.tls {
+        match super::tls::accept(config, stream) {
+            Ok(mut stream) => {
+                serve_requests(&mut stream, &peer, server);
+                super::tls::close(&mut stream);
+            }
+            Err(err) => debug!(peer, error = %err, "TLS handshake with {} failed: {}", peer, err),
+        }
+        return;
+    }
+    let mut stream = stream;
+    serve_requests(&mut stream, &peer, server);
+}
+
+/// Answer a connection over the limit with 503 and close it; over TLS it is
+/// closed without an answer, as the handshake would hold up the accept loop
+fn refuse_connection(mut stream: TcpStream, server: &Server) {
+    #[cfg(feature = "tls")]
+    if server.tls.is_some() {
+        return;
+    }
+    let message = format!("the server is already serving {} connections", server.max_connections);
+    if stream.set_nonblocking(false).is_ok() && stream.set_write_timeout(Some(POLL_INTERVAL)).is_ok() {
+        let _ = Response::error(503, "too_many_connections", &message).write_to(&mut stream, false);
+    }
+}
+
+/// Serve requests until the client closes the connection, it goes idle, or
+/// the server shuts down
+fn serve_requests<S: Read + Write>(stream: &mut S, peer: &str, server: &Server) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let request = match http::read_request(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => return,
+            Err(ReadError::Io(err)) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return,
+            Err(ReadError::Io(err)) => {
+                debug!(peer, error = %err, "Reading a request from {} failed: {}", peer, err);
+                return;
+            }
+            Err(ReadError::Rejected(response)) => {
+                let _ = response.write_to(reader.get_mut(), false);
+                return;
+            }
+        };
+        // Responses bypass the buffer, which only holds data read ahead
+        let writer = reader.get_mut();
+        let started = Instant::now();
+        let keep_alive = request.keep_alive() && !SHUTDOWN.load(Ordering::SeqCst);
+        let response = match admit(&request, server, peer) {
+            Ok(()) if request.path == "/v1/ids/batch" && request.method == "POST" => {
+                post_batch(&request, server, writer, keep_alive)
+            }
+            Ok(()) => Ok(route(&request, server)),
+            Err(response) => Ok(response),
+        };
+        // A streamed batch has already been written; anything else is written now
+        let (status, written) = match response {
+            Ok(response) => (response.status, response.write_to(writer, keep_alive)),
+            Err(streamed) => (200, streamed),
+        };
+        let elapsed = started.elapsed();
+        server.metrics.observe(endpoint(&request.path), elapsed);
+        debug!(
+            method = request.method,
+            path = request.path,
+            status,
+            duration_us = elapsed.as_micros() as u64,
+            "{} {} {}",
+            request.method,
+            request.path,
+            status
+        );
+        if written.is_err() || !keep_alive {
+            return;
+        }
+    }
+}
+
+/// Check the token of a request, unless it is a health check or for
+/// `/openapi.json`, and charge requests to `/v1/` endpoints to the client's
+/// rate limit: the count of a valid generation request, otherwise one
+fn admit(request: &Request, server: &Server, peer: &str) -> Result<(), Response> {
+    if matches!(request.path.as_str(), "/health" | "/healthz" | "/readyz" | "/openapi.json") {
+        return Ok(());
+    }
+    let token = match request.header("authorization") {
+        Some(value) => value.strip_prefix("Bearer ").map(str::trim),
+        None => request.header("x-api-key"),
+    };
+    let Some(client) = authenticate(server, token, peer) else {
+        let message = "A valid token is required (Authorization: Bearer TOKEN or X-API-Key: TOKEN)";
+        return Err(Response::error(401, "unauthorized", message).with_header("WWW-Authenticate", "Bearer".to_string()));
+    };
+    if server.rate_limiter.is_none() || !request.path.starts_with("/v1/") {
+        return Ok(());
+    }
+    let cost = match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/v1/ids") => generation_cost(&request.query, server, server.max_count),
+        ("POST", "/v1/ids/batch") => {
+            generation_cost(&request_params(request).unwrap_or_default(), server, server.max_batch_count)
+        }
+        _ => 1,
+    };
+    charge(server, &client, cost).map_err(|wait| {
+        let message = format!("Rate limit exceeded; retry in {:.1}s", wait.as_secs_f64());
+        Response::error(429, "rate_limited", &message).with_header("Retry-After", wait.as_secs_f64().ceil().to_string())
+    })
+}
+
+/// Identifiers a generation request with the parameters `pairs` costs: its
+/// count if it is valid, otherwise one, as it is answered without generating
+/// anything
+fn generation_cost(pairs: &[(String, String)], server: &Server, max_count: usize) -> usize {
+    let params = GenerateParams::from_pairs(pairs.iter().map(|(key, value)| (key.as_str(), value.as_str())));
+    params.validate(server, max_count).map_or(1, |generation| generation.count)
+}
+
+/// The client a request is charged to: its token, or its address (`peer`)
+/// when no tokens are configured; `None`, counted as a rejection, if tokens
+/// are configured and `token` is not one of them
+pub fn authenticate(server: &Server, token: Option<&str>, peer: &str) -> Option<String> {
+    if server.auth_tokens.is_empty() {
+        return Some(peer.to_string());
+    }
+    let index = token.and_then(|token| server.auth_tokens.iter().position(|known| tokens_match(known, token)));
+    if index.is_none() {
+        server.metrics.rejected("unauthorized");
+    }
+    index.map(|index| format!("token {}", index))
+}
+
+/// Charge `cost` identifiers to the rate limit of `client`, returning how
+/// long to wait if it is exceeded
+pub fn charge(server: &Server, client: &str, cost: usize) -> Result<(), Duration> {
+    let Some(limiter) = &server.rate_limiter else {
+        return Ok(());
+    };
+    limiter.take(client, cost as f64).inspect_err(|_| server.metrics.rejected("rate_limited"))
+}
+
+/// Compare a token in time that does not depend on where they differ
+fn tokens_match(known: &str, given: &str) -> bool {
+    known.len() == given.len() && known.bytes().zip(given.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Dispatch a request to its endpoint
+fn route(request: &Request, server: &Server) -> Response {
+    let method = request.method.as_str();
+    match request.path.as_str() {
+        "/v1/ids" => match method {
+            "POST" => generate_ids(request, server),
+            _ => method_not_allowed("POST"),
+        },
+        // POST requests are streamed by `handle_connection`
+        "/v1/ids/batch" => method_not_allowed("POST"),
+        "/health" | "/healthz" => match method {
+            "GET" => health(),
+            _ => method_not_allowed("GET"),
+        },
+        "/readyz" => match method {
+            "GET" => readiness(server),
+            _ => method_not_allowed("GET"),
+        },
+        "/metrics" => match method {
+            "GET" => prometheus_metrics(server),
+            _ => method_not_allowed("GET"),
+        },
+        "/openapi.json" => match method {
+            "GET" => openapi_document(),
+            _ => method_not_allowed("GET"),
+        },
+        path => match path.strip_prefix("/v1/decode/") {
+            Some(identifier) if method == "GET" => decode_id(identifier, server),
+            Some(_) => method_not_allowed("GET"),
+            None => Response::error(404, "not_found", &format!("No endpoint at {}", path)),
+        },
+    }
+}
+
+/// The endpoint of `path`, to label request metrics without one series per
+/// identifier decoded
+fn endpoint(path: &str) -> &'static str {
+    match path {
+        "/v1/ids" => "/v1/ids",
+        "/v1/ids/batch" => "/v1/ids/batch",
+        "/health" => "/health",
+        "/healthz" => "/healthz",
+        "/readyz" => "/readyz",
+        "/metrics" => "/metrics",
+        "/openapi.json" => "/openapi.json",
+        path if path.starts_with("/v1/decode/") => "/v1/decode",
+        _ => "other",
+    }
+}
+
+/// `POST /v1/ids`
+#[utoipa::path(
+    post,
+    path = "/v1/ids",
+    operation_id = "generateIds",
+    summary = "Generate identifiers",
+    params(GenerateQuery),
+    responses(
+        (status = 200, description = "The generated identifiers, in generation order", body = Ids),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 429, response = RateLimited),
+        (status = 503, response = MachineIdUnavailable)
+    )
+)]
+fn generate_ids(request: &Request, server: &Server) -> Response {
+    let params = GenerateParams::from_pairs(request.query.iter().map(|(key, value)| (key.as_str(), value.as_str())));
+    match generate_batch(&params, server) {
+        Ok(ids) => Response::json(200, json::Object::new().strings("ids", &ids).finish()),
+        Err(err) => Response::error(err.status(), err.reason, &err.message),
+    }
+}
+
+/// Generate the identifiers asked for by `params`
+pub fn generate_batch(params: &GenerateParams, server: &Server) -> Result<Vec<String>, RequestError> {
+    let generation = params.validate(server, server.max_count)?;
+    generation.generate(generation.count, server)
+}
+
+/// A validated generation request
+pub struct Generation<'a> {
+    pub prefix: &'a str,
+    pub count: usize,
+    pub is_random: bool,
+    pub format: Format,
+}
+
+impl<'a> GenerateParams<'a> {
+    /// Check the parameters, allowing at most `max_count` identifiers, and
+    /// that the machine ID may be used
+    pub fn validate(&self, server: &'a Server, max_count: usize) -> Result<Generation<'a>, RequestError> {
+        let Some(prefix) = self.prefix.or(server.prefix.as_deref()) else {
+            return Err(RequestError::new("missing_prefix", "The prefix parameter is required"));
+        };
+        // Fails on an invalid prefix, before anything is charged for it
+        BcidGenerator::new(prefix, server.machine_id)?;
+        let count = match self.count.map(str::parse::<usize>) {
+            None => 1,
+            Some(Ok(count)) if (1..=max_count).contains(&count) => count,
+            Some(_) => {
+                let message = format!("count must be a number between 1 and {}", max_count);
+                return Err(RequestError::new("invalid_count", message));
+            }
+        };
+        let is_random = match self.random {
+            None | Some("false") | Some("0") => false,
+            Some("true") | Some("1") | Some("") => true,
+            Some(_) => return Err(RequestError::new("invalid_random", "random must be true or false")),
+        };
+        let format = match self.format {
+            None => server.format,
+            Some(format) => Format::parse_version(format)
+                .ok_or_else(|| RequestError::new("invalid_format", "format must be v1, v2, v3, v4 or v5"))?,
+        };
+        if is_random && self.format.is_some() && format != Format::V1 {
+            return Err(RequestError::new("invalid_format", "Random identifiers cannot use the sortable layout"));
+        }
+        if let Some(failure) = server.lease_failure() {
+            return Err(BcidError::MachineIdUnavailable(failure).into());
+        }
+        Ok(Generation { prefix, count, is_random, format })
+    }
+}
+
+impl Generation<'_> {
+    /// Generate `count` identifiers with these settings, failing if the
+    /// lease on the machine ID has been lost since the request was validated
+    pub fn generate(&self, count: usize, server: &Server) -> Result<Vec<String>, RequestError> {
+        if let Some(failure) = server.lease_failure() {
+            return Err(BcidError::MachineIdUnavailable(failure).into());
+        }
+        let generator = BcidGenerator::new(self.prefix, server.machine_id)?;
+        let ids = match self.is_random {
+            true => generator.random(true).try_next_batch(count)?,
+            false => {
+                // Held while generating, so identifiers increase across requests
+                let mut state = server.generator_state();
+                let mut generator = generator.format(self.format).monotonic(true).resume(*state);
+                let ids = generator.try_next_batch(count)?;
+                *state = generator.state();
+                drop(state);
+                server.metrics.clock_regressions(generator.clock_regressions());
+                server.metrics.sequence_overflows(generator.sequence_overflows());
+                ids
+            }
+        };
+        let ids: Vec<String> = ids.into_iter().map(String::from).collect();
+        server.metrics.generated(self.prefix, ids.len());
+        match server.with_check_digit {
+            true => ids.iter().map(|identifier| append_check_digit(identifier).map_err(RequestError::from)).collect(),
+            false => Ok(ids),
+        }
+    }
+}
+
+/// The parameters of a request: the fields of its JSON body, if it has one,
+/// followed by its query parameters
+fn request_params(request: &Request) -> Result<Vec<(String, String)>, RequestError> {
+    let mut pairs = match request.body.iter().all(u8::is_ascii_whitespace) {
+        true => Vec::new(),
+        false => std::str::from_utf8(&request.body)
+            .map_err(|_| "body is not UTF-8".to_string())
+            .and_then(json::parse_object)
+            .map_err(|err| RequestError::new("bad_request", format!("Invalid JSON body: {}", err)))?,
+    };
+    pairs.extend(request.query.iter().cloned());
+    Ok(pairs)
+}
+
+/// `POST /v1/ids/batch`: a 400 response for invalid parameters, or the
+/// result of streaming the batch
+#[utoipa::path(
+    post,
+    path = "/v1/ids/batch",
+    operation_id = "generateBatch",
+    summary = "Generate a large batch of identifiers, streamed one per line",
+    request_body = BatchRequest,
+    responses(
+        (
+            status = 200,
+            description = "Newline-delimited JSON, sent with chunked transfer encoding: one `{\"id\": ...}` object per \
+                           identifier. If generation fails part-way, the last line is an Error object instead.",
+            body = BatchRecord,
+            content_type = "application/x-ndjson"
+        ),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 429, response = RateLimited),
+        (status = 503, response = MachineIdUnavailable)
+    )
+)]
+fn post_batch<W: Write>(request: &Request, server: &Server, out: &mut W, keep_alive: bool) -> Result<Response, io::Result<()>> {
+    let pairs = match request_params(request) {
+        Ok(pairs) => pairs,
+        Err(err) => return Ok(Response::error(400, err.reason, &err.message)),
+    };
+    let params = GenerateParams::from_pairs(pairs.iter().map(|(key, value)| (key.as_str(), value.as_str())));
+    match params.validate(server, server.max_batch_count) {
+        Ok(generation) => Err(stream_batch(&generation, server, out, keep_alive)),
+        Err(err) => Ok(Response::error(err.status(), err.reason, &err.message)),
+    }
+}
+
+/// Stream `{"id": ...}` lines, generating and sending
+/// them a chunk at a time so that neither side holds the whole batch
+fn stream_batch<W: Write>(generation: &Generation, server: &Server, out: &mut W, keep_alive: bool) -> io::Result<()> {
+    let mut body = ChunkedBody::start(out, 200, NDJSON, keep_alive)?;
+    let mut remaining = generation.count;
+    while remaining > 0 {
+        let count = remaining.min(BATCH_CHUNK);
+        let mut chunk = String::new();
+        match generation.generate(count, server) {
+            Ok(ids) => {
+                for identifier in &ids {
+                    chunk.push_str(&json::Object::new().string("id", Some(identifier)).finish());
+                    chunk.push('\n');
+                }
+            }
+            Err(err) => {
+                // The status is already sent, so the error ends the stream as its last line
+                let line = json::Object::new().string("error", Some(&err.message)).string("reason", Some(err.reason));
+                body.write_chunk(format!("{}\n", line.finish()).as_bytes())?;
+                return body.finish();
+            }
+        }
+        body.write_chunk(chunk.as_bytes())?;
+        remaining -= count;
+    }
+    body.finish()
+}
+
+/// `GET /v1/decode/{id}`
+#[utoipa::path(
+    get,
+    path = "/v1/decode/{id}",
+    operation_id = "decodeId",
+    summary = "Decode an identifier into its components",
+    params(("id" = String, Path, description = "The identifier to decode")),
+    responses(
+        (status = 200, description = "The decoded components, as `bcid decode --json`", body = Decoded),
+        (status = 400, response = BadRequest),
+        (status = 401, response = Unauthorized),
+        (status = 429, response = RateLimited)
+    )
+)]
+fn decode_id(identifier: &str, server: &Server) -> Response {
+    match server.decode(identifier) {
+        Ok(decoded) => Response::json(200, decoded.to_json()),
+        Err(err) => bcid_error(&err),
+    }
+}
+
+/// `GET /health` and `GET /healthz`
+#[utoipa::path(
+    get,
+    path = "/health",
+    operation_id = "health",
+    summary = "Liveness check",
+    security(()),
+    responses((status = 200, description = "The server is running", body = Health))
+)]
+fn health() -> Response {
+    Response::json(200, json::Object::new().string("status", Some("ok")).finish())
+}
+
+/// `GET /metrics`
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    operation_id = "metrics",
+    summary = "Prometheus metrics",
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text format", body = String, content_type = "text/plain"),
+        (status = 401, response = Unauthorized)
+    )
+)]
+fn prometheus_metrics(server: &Server) -> Response {
+    Response::text(200, metrics::CONTENT_TYPE, server.metrics.render())
+}
+
+/// `GET /openapi.json`: the description of these endpoints, for generating
+/// client SDKs
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    operation_id = "openapi",
+    summary = "This document",
+    security(()),
+    responses((status = 200, description = "The OpenAPI 3.1 description of the server", body = Object))
+)]
+fn openapi_document() -> Response {
+    Response::json(200, openapi::document())
+}
+
+/// `GET /readyz`: 200 if every check passes, otherwise 503, so that load
+/// balancers stop routing to a generator that might issue colliding
+/// identifiers
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    operation_id = "readyz",
+    summary = "Readiness check",
+    description = "Fails if the clock reads more than --clock-tolerance behind its latest reading, the operating \
+                   system's random number generator fails, the --state-file cannot be written, or the machine ID \
+                   lease has been lost.",
+    security(()),
+    responses(
+        (status = 200, description = "Every check passed", body = Readiness),
+        (status = 503, description = "A check failed; its entry in `checks` says why", body = Readiness)
+    )
+)]
+fn readiness(server: &Server) -> Response {
+    let mut checks = vec![("clock", clock_failure(server)), ("entropy", entropy_failure())];
+    if server.machine_id_lease.is_some() {
+        checks.push(("machine_id", server.lease_failure()));
+    }
+    if let Some(path) = &server.state_file {
+        let failure = save_clock(path, server).err().map(|err| format!("cannot write {}: {}", path.display(), err));
+        checks.push(("state_file", failure));
+    }
+    let is_ready = checks.iter().all(|(_, failure)| failure.is_none());
+    let checks = checks
+        .iter()
+        .fold(json::Object::new(), |object, (name, failure)| object.string(name, Some(failure.as_deref().unwrap_or("ok"))));
+    let status = if is_ready { "ready" } else { "not_ready" };
+    let body = json::Object::new().string("status", Some(status)).raw("checks", &checks.finish()).finish();
+    Response::json(if is_ready { 200 } else { 503 }, body)
+}
+
+/// How far the clock reads behind its latest reading, if that is more
+/// than the tolerance
+fn clock_failure(server: &Server) -> Option<String> {
+    let latest = state::unpack_clock(server.generator_state().latest_reading)?;
+    let behind = latest - Utc::now().timestamp_micros();
+    let behind = Duration::from_micros(u64::try_from(behind).ok()?);
+    (behind > server.clock_tolerance).then(|| {
+        format!("the clock reads {:.3}s behind its latest reading (tolerance {:?})", behind.as_secs_f64(), server.clock_tolerance)
+    })
+}
+
+/// Save the latest clock reading to the state file
+fn save_clock(path: &Path, server: &Server) -> io::Result<()> {
+    let latest = server.generator_state().latest_reading.max(state::pack_clock(Utc::now().timestamp_micros()));
+    let state = GeneratorState { latest_reading: latest, ..GeneratorState::default() };
+    state::write(path, &state)
+}
+
+/// A 400 response carrying a library error and its reason code
+fn bcid_error(err: &BcidError) -> Response {
+    Response::error(400, err.reason(), &err.to_string())
+}
+
+fn method_not_allowed(allowed: &'static str) -> Response {
+    Response::error(405, "method_not_allowed", &format!("Use {}", allowed)).with_header("Allow", allowed.to_string())
+}
+
+/// Request a graceful shutdown on SIGINT and SIGTERM
+#[cfg(unix)]
+fn install_signal_handlers() {
+    extern "C" fn request_shutdown(_signal: libc::c_int) {
+        SHUTDOWN.store(true, Ordering::SeqCst);
+    }
+    let handler = request_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+/// Without signal handling the process is simply terminated
+#[cfg(not(unix))]
+fn install_signal_handlers() {}
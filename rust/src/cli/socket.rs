@@ -0,0 +1,122 @@
+//! Line protocol spoken by `bcid serve --unix` on a Unix domain socket
+//!
+//! Each request is one line, answered by one line, so clients can pipeline
+//! requests over a persistent connection:
+//!
+//! ```text
+//! GENERATE prefix=TEST count=3       -> OK TEST... TEST... TEST...
+//! GENERATE prefix=TEST random=true   -> OK TEST...
+//! DECODE TEST...                     -> OK {"prefix":"TEST",...}
+//! PING                               -> OK
+//! ```
+//!
+//! `GENERATE` takes the same parameters as `POST /v1/ids`. Failures are
+//! answered with `ERR <reason> <message>`, using the same reason codes.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use tracing::debug;
+
+use super::serve::{generate_batch, GenerateParams, RequestError, Server, IDLE_TIMEOUT, POLL_INTERVAL, SHUTDOWN};
+
+/// Longest request line accepted
+const MAX_LINE_BYTES: usize = 4096;
+
+/// Answer requests on one connection until the client closes it, it goes
+/// idle, or the server shuts down
+pub fn handle_connection(stream: UnixStream, server: &Server) {
+    // Wake up periodically to notice a shutdown while the client is idle
+    if stream.set_nonblocking(false).is_err() || stream.set_read_timeout(Some(POLL_INTERVAL)).is_err() {
+        return;
+    }
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    // When the client last sent anything, and how much of the line it had
+    let mut last_read = (Instant::now(), 0);
+    loop {
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => return,
+            Ok(_) if line.ends_with(b"\n") => {}
+            // End of stream without a trailing newline
+            Ok(_) => {}
+            Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                if SHUTDOWN.load(Ordering::SeqCst) {
+                    return;
+                }
+                if line.len() > last_read.1 {
+                    last_read = (Instant::now(), line.len());
+                } else if last_read.0.elapsed() >= IDLE_TIMEOUT {
+                    return;
+                }
+                if line.len() <= MAX_LINE_BYTES {
+                    continue;
+                }
+            }
+            Err(_) => return,
+        }
+
+        let response = if line.len() > MAX_LINE_BYTES {
+            Err(RequestError::new("bad_request", "request line too long"))
+        } else {
+            let request = String::from_utf8_lossy(&line);
+            let request = request.trim();
+            let started = Instant::now();
+            let response = respond(request, server);
+            let elapsed = started.elapsed();
+            server.metrics.observe(command(request), elapsed);
+            let status = response.as_ref().map_or_else(|err| err.reason, |_| "ok");
+            debug!(request, status, duration_us = elapsed.as_micros() as u64, "{} {}", request, status);
+            response
+        };
+        let written = match response {
+            Ok(body) if body.is_empty() => writer.write_all(b"OK\n"),
+            Ok(body) => writeln!(writer, "OK {}", body),
+            Err(err) => writeln!(writer, "ERR {} {}", err.reason, err.message),
+        };
+        if written.is_err() || line.len() > MAX_LINE_BYTES {
+            return;
+        }
+        line.clear();
+        last_read = (Instant::now(), 0);
+    }
+}
+
+/// Answer a connection over the limit with an error line and close it
+pub fn refuse_connection(mut stream: UnixStream, server: &Server) {
+    if stream.set_nonblocking(false).is_ok() && stream.set_write_timeout(Some(POLL_INTERVAL)).is_ok() {
+        let _ = writeln!(stream, "ERR too_many_connections the server is already serving {} connections", server.max_connections);
+    }
+}
+
+/// The command of a request line, to label request metrics
+fn command(request: &str) -> &'static str {
+    match request.split_whitespace().next().unwrap_or_default() {
+        "GENERATE" => "GENERATE",
+        "DECODE" => "DECODE",
+        "PING" => "PING",
+        _ => "other",
+    }
+}
+
+/// Run one request line, returning the body of the `OK` response
+fn respond(request: &str, server: &Server) -> Result<String, RequestError> {
+    let mut words = request.split_whitespace();
+    match words.next().unwrap_or_default() {
+        "GENERATE" => {
+            let params = GenerateParams::from_pairs(words.map(|word| word.split_once('=').unwrap_or((word, ""))));
+            generate_batch(&params, server).map(|ids| ids.join(" "))
+        }
+        "DECODE" => match (words.next(), words.next()) {
+            (Some(identifier), None) => Ok(server.decode(identifier)?.to_json()),
+            _ => Err(RequestError::new("bad_request", "DECODE takes one identifier")),
+        },
+        "PING" => Ok(String::new()),
+        command => Err(RequestError::new("unknown_command", format!("Unknown command '{}'", command))),
+    }
+}
@@ -0,0 +1,106 @@
+//! Generator state saved between runs of `bcid generate --state-file`
+//!
+//! ```toml
+//! timestamp = 2024010100000000   # last issued timestamp and random value
+//! random = 1234
+//! micros = 4500                  # after the timestamp, at precisions finer than 10ms
+//! clock = 2024010100000000       # latest clock reading
+//! ```
+//!
+//! Only one process at a time may use a state file.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use bcid::GeneratorState;
+use chrono::{DateTime, NaiveDateTime};
+use toml::Value;
+
+use super::{parse_toml, EXIT_FAILURE};
+
+/// Load the state saved in `path`, or the initial state if there is none
+///
+/// Exits with an error if the file exists but cannot be read or parsed.
+pub fn load(path: &Path) -> GeneratorState {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return GeneratorState::default(),
+        Err(err) => {
+            eprintln!("Error: Cannot read {}: {}", path.display(), err);
+            process::exit(EXIT_FAILURE);
+        }
+    };
+    parse(&contents).unwrap_or_else(|err| {
+        eprintln!("Error: Invalid state file {}: {}", path.display(), err);
+        process::exit(EXIT_FAILURE);
+    })
+}
+
+/// Parse state file contents
+pub fn parse(contents: &str) -> Result<GeneratorState, String> {
+    let (mut timestamp, mut random, mut micros, mut clock) = (None, None, 0, 0);
+    for entry in parse_toml(contents)? {
+        let invalid = || format!("line {}: invalid `{}`", entry.line, entry.key);
+        match (entry.key.as_str(), &entry.value) {
+            ("timestamp", Value::Integer(value)) => timestamp = Some(u64::try_from(*value).map_err(|_| invalid())?),
+            ("random", Value::Integer(value)) => random = Some(u16::try_from(*value).map_err(|_| invalid())?),
+            ("micros", Value::Integer(value)) => micros = u16::try_from(*value).map_err(|_| invalid())?,
+            ("clock", Value::Integer(value)) => clock = u64::try_from(*value).map_err(|_| invalid())?,
+            _ => return Err(invalid()),
+        }
+    }
+    let last_issued = match (timestamp, random) {
+        (Some(timestamp), Some(random)) => Some((timestamp, random)),
+        (None, None) => None,
+        _ => return Err("`timestamp` and `random` must be given together".to_string()),
+    };
+    Ok(GeneratorState { last_issued, last_issued_micros: micros, latest_reading: clock })
+}
+
+/// Save `state` to `path`, replacing it atomically so that a crash never
+/// leaves a partly written file
+///
+/// Exits with an error if the file cannot be written.
+pub fn save(path: &Path, state: &GeneratorState) {
+    if let Err(err) = write(path, state) {
+        eprintln!("Error: Cannot write {}: {}", path.display(), err);
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+/// Save `state` to `path` like [`save`], returning any error
+pub fn write(path: &Path, state: &GeneratorState) -> io::Result<()> {
+    let mut contents = String::new();
+    if let Some((timestamp, random)) = state.last_issued {
+        contents.push_str(&format!("timestamp = {}\nrandom = {}\n", timestamp, random));
+        if state.last_issued_micros > 0 {
+            contents.push_str(&format!("micros = {}\n", state.last_issued_micros));
+        }
+    }
+    contents.push_str(&format!("clock = {}\n", state.latest_reading));
+
+    let mut temporary = PathBuf::from(path);
+    temporary.as_mut_os_string().push(".tmp");
+    File::create(&temporary)
+        .and_then(|mut file| file.write_all(contents.as_bytes()).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&temporary, path))
+}
+
+/// Pack a clock reading in microseconds since the Unix epoch as a
+/// `YYYYMMDDHHmmSScc` timestamp, as the `clock` entry stores it
+pub fn pack_clock(micros: i64) -> u64 {
+    let Some(datetime) = DateTime::from_timestamp_micros(micros) else {
+        return 0;
+    };
+    let packed = datetime.format("%Y%m%d%H%M%S").to_string().parse::<u64>().unwrap_or_default();
+    packed * 100 + u64::from(datetime.timestamp_subsec_millis() / 10)
+}
+
+/// The clock reading of a packed `clock` entry, in microseconds since the
+/// Unix epoch, or `None` for no reading
+pub fn unpack_clock(packed: u64) -> Option<i64> {
+    let datetime = NaiveDateTime::parse_from_str(&(packed / 100).to_string(), "%Y%m%d%H%M%S").ok()?;
+    Some(datetime.and_utc().timestamp_micros() + (packed % 100) as i64 * 10_000)
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use bcid::{decode_identifier, json};
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::{Args, ValueEnum};
+
+use super::bulk::identifier_lines;
+
+/// Summarize a file of identifiers
+///
+/// Reads one identifier per line from FILE (`-` for stdin) and reports how many
+/// there are of each prefix, type, format and machine ID, a histogram of the
+/// embedded timestamps of chronological identifiers, the reasons invalid lines
+/// failed to decode, and identifiers that appear more than once.
+#[derive(Args)]
+#[command(after_help = EXAMPLES)]
+pub struct StatsArgs {
+    /// Histogram bucket
+    #[arg(long = "by", value_name = "UNIT", value_enum, default_value_t = Bucket::Day)]
+    bucket: Bucket,
+    /// Show the N most common entries of each table, 0 for all
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    top: usize,
+    /// Print the report as a JSON object
+    #[arg(long)]
+    json: bool,
+    /// File of identifiers, `-` for stdin
+    #[arg(value_name = "FILE")]
+    input: String,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  bcid stats ids.txt
+  psql -Atc 'SELECT id FROM orders' | bcid stats --by hour -";
+
+/// Width of the longest histogram bar
+const BAR_WIDTH: usize = 40;
+
+/// Histogram bucket size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Bucket {
+    Day,
+    Hour,
+}
+
+impl Bucket {
+    /// Label of the bucket containing `datetime`, which sorts chronologically
+    fn label(&self, datetime: DateTime<Utc>) -> String {
+        match self {
+            Bucket::Day => datetime.format("%Y-%m-%d").to_string(),
+            Bucket::Hour => datetime.format("%Y-%m-%dT%H:00").to_string(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Bucket::Day => "day",
+            Bucket::Hour => "hour",
+        }
+    }
+}
+
+/// Counts gathered over every line of the input
+#[derive(Default)]
+struct Stats {
+    total: usize,
+    invalid: usize,
+    prefixes: HashMap<String, usize>,
+    types: HashMap<&'static str, usize>,
+    formats: HashMap<String, usize>,
+    machine_ids: HashMap<u16, usize>,
+    reasons: HashMap<&'static str, usize>,
+    histogram: HashMap<String, usize>,
+    earliest: Option<DateTime<Utc>>,
+    latest: Option<DateTime<Utc>>,
+    /// Occurrences of every distinct line
+    seen: HashMap<String, usize>,
+}
+
+impl Stats {
+    fn add(&mut self, identifier: String, bucket: Bucket) {
+        self.total += 1;
+        match decode_identifier(&identifier) {
+            Ok(decoded) => {
+                *self.prefixes.entry(decoded.prefix().to_string()).or_default() += 1;
+                *self.types.entry(decoded.identifier_type().as_str()).or_default() += 1;
+                *self.formats.entry(decoded.format().to_string()).or_default() += 1;
+                *self.machine_ids.entry(decoded.machine_id()).or_default() += 1;
+                if let Some(datetime) = decoded.datetime() {
+                    *self.histogram.entry(bucket.label(datetime)).or_default() += 1;
+                    self.earliest = Some(self.earliest.map_or(datetime, |earliest| earliest.min(datetime)));
+                    self.latest = Some(self.latest.map_or(datetime, |latest| latest.max(datetime)));
+                }
+            }
+            Err(err) => {
+                self.invalid += 1;
+                *self.reasons.entry(err.reason()).or_default() += 1;
+            }
+        }
+        *self.seen.entry(identifier).or_default() += 1;
+    }
+
+    /// Identifiers that appear more than once, with their occurrence counts
+    fn duplicates(&self) -> HashMap<&str, usize> {
+        self.seen.iter().filter(|(_, &count)| count > 1).map(|(id, &count)| (id.as_str(), count)).collect()
+    }
+}
+
+pub fn run(args: StatsArgs) {
+    let (input, bucket, top, is_json) = (args.input, args.bucket, args.top, args.json);
+
+    let mut stats = Stats::default();
+    for (_, identifier) in identifier_lines(&input) {
+        stats.add(identifier, bucket);
+    }
+    if is_json {
+        println!("{}", report_json(&stats, bucket));
+    } else {
+        print_report(&stats, bucket, top);
+    }
+}
+
+/// Entries sorted by descending count, then by key, limited to `top` (0 for all)
+fn most_common<K: Ord + Clone + Hash>(counts: &HashMap<K, usize>, top: usize) -> Vec<(K, usize)> {
+    let mut entries: Vec<(K, usize)> = counts.iter().map(|(key, &count)| (key.clone(), count)).collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    if top > 0 {
+        entries.truncate(top);
+    }
+    entries
+}
+
+fn print_table<K: Ord + Clone + Hash + ToString>(title: &str, counts: &HashMap<K, usize>, top: usize) {
+    if counts.is_empty() {
+        return;
+    }
+    let entries = most_common(counts, top);
+    let width = entries.iter().map(|(key, _)| key.to_string().len()).max().unwrap_or(0);
+    println!();
+    if entries.len() < counts.len() {
+        println!("{} (top {} of {}):", title, entries.len(), counts.len());
+    } else {
+        println!("{}:", title);
+    }
+    for (key, count) in entries {
+        println!("  {:width$}  {}", key.to_string(), count, width = width);
+    }
+}
+
+fn print_report(stats: &Stats, bucket: Bucket, top: usize) {
+    println!("Identifiers: {} ({} valid, {} invalid)", stats.total, stats.total - stats.invalid, stats.invalid);
+    if let (Some(earliest), Some(latest)) = (stats.earliest, stats.latest) {
+        println!("Earliest: {}", earliest.to_rfc3339_opts(SecondsFormat::Millis, true));
+        println!("Latest: {}", latest.to_rfc3339_opts(SecondsFormat::Millis, true));
+    }
+    print_table("Prefixes", &stats.prefixes, top);
+    print_table("Types", &stats.types, top);
+    print_table("Formats", &stats.formats, top);
+    print_table("Machine IDs", &stats.machine_ids, top);
+    print_table("Invalid", &stats.reasons, top);
+
+    if !stats.histogram.is_empty() {
+        let mut buckets: Vec<(&String, &usize)> = stats.histogram.iter().collect();
+        buckets.sort();
+        let max = buckets.iter().map(|(_, &count)| count).max().unwrap_or(1);
+        println!();
+        println!("By {}:", bucket.name());
+        for (label, &count) in buckets {
+            let bar = "#".repeat((count * BAR_WIDTH).div_ceil(max));
+            println!("  {}  {:>8}  {}", label, count, bar);
+        }
+    }
+
+    let duplicates = stats.duplicates();
+    let extra: usize = duplicates.values().map(|count| count - 1).sum();
+    println!();
+    if duplicates.is_empty() {
+        println!("Duplicates: none");
+    } else {
+        println!("Duplicates: {} identifiers appear more than once ({} extra copies)", duplicates.len(), extra);
+        for (identifier, count) in most_common(&duplicates, top) {
+            println!("  {}  {}", identifier, count);
+        }
+    }
+}
+
+/// A JSON object mapping each key to its count
+fn counts_json<K: Ord + Clone + Hash + ToString>(counts: &HashMap<K, usize>) -> String {
+    most_common(counts, 0)
+        .into_iter()
+        .fold(json::Object::new(), |object, (key, count)| object.number(&key.to_string(), Some(count)))
+        .finish()
+}
+
+fn report_json(stats: &Stats, bucket: Bucket) -> String {
+    let mut histogram: Vec<(&String, &usize)> = stats.histogram.iter().collect();
+    histogram.sort();
+    let histogram =
+        histogram.into_iter().fold(json::Object::new(), |object, (label, count)| object.number(label, Some(count)));
+    let datetime = |dt: Option<DateTime<Utc>>| dt.map(|dt| dt.to_rfc3339_opts(SecondsFormat::Millis, true));
+    json::Object::new()
+        .number("total", Some(stats.total))
+        .number("valid", Some(stats.total - stats.invalid))
+        .number("invalid", Some(stats.invalid))
+        .string("earliest", datetime(stats.earliest).as_deref())
+        .string("latest", datetime(stats.latest).as_deref())
+        .raw("prefixes", &counts_json(&stats.prefixes))
+        .raw("types", &counts_json(&stats.types))
+        .raw("formats", &counts_json(&stats.formats))
+        .raw("machine_ids", &counts_json(&stats.machine_ids))
+        .raw("invalid_reasons", &counts_json(&stats.reasons))
+        .string("bucket", Some(bucket.name()))
+        .raw("histogram", &histogram.finish())
+        .raw("duplicates", &counts_json(&stats.duplicates()))
+        .finish()
+}
@@ -0,0 +1,145 @@
+//! Output templates (`--template`) for `generate` and `decode`
+
+use bcid::{Bcid, Precision};
+
+/// Placeholder names, for usage and error messages
+pub const PLACEHOLDERS: &str =
+    "id, prefix, type, format, timestamp, iso_time, machine_id, environment, shard, sequence, random, random_part";
+
+/// A parsed `--template`: literal text and `{placeholder}`s for the fields
+/// of an identifier, which are empty when the identifier has no such field
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Placeholder {
+    /// The identifier as printed or given
+    Id,
+    Prefix,
+    Type,
+    Format,
+    Timestamp,
+    IsoTime,
+    MachineId,
+    Environment,
+    Shard,
+    Sequence,
+    Random,
+    RandomPart,
+}
+
+impl Placeholder {
+    fn from_name(name: &str) -> Option<Placeholder> {
+        Some(match name {
+            "id" => Placeholder::Id,
+            "prefix" => Placeholder::Prefix,
+            "type" => Placeholder::Type,
+            "format" => Placeholder::Format,
+            "timestamp" => Placeholder::Timestamp,
+            "iso_time" => Placeholder::IsoTime,
+            "machine_id" => Placeholder::MachineId,
+            "environment" => Placeholder::Environment,
+            "shard" => Placeholder::Shard,
+            "sequence" => Placeholder::Sequence,
+            "random" => Placeholder::Random,
+            "random_part" => Placeholder::RandomPart,
+            _ => return None,
+        })
+    }
+}
+
+impl Template {
+    /// Parse a template, in which `\t`, `\n` and `\\` are escapes for a
+    /// tab, a line break and a backslash, and `{{` and `}}` for braces
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => literal.push(match chars.next() {
+                    Some('t') => '\t',
+                    Some('n') => '\n',
+                    Some('\\') => '\\',
+                    Some(other) => return Err(format!("Invalid escape '\\{}' in template - use \\t, \\n or \\\\", other)),
+                    None => return Err("Template ends in a backslash - use \\\\ for one".to_string()),
+                }),
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let Some((name, rest)) = chars.as_str().split_once('}') else {
+                        return Err("Unclosed '{' in template - use {{ for a brace".to_string());
+                    };
+                    let placeholder = Placeholder::from_name(name)
+                        .ok_or_else(|| format!("Unknown placeholder {{{}}} in template - must be one of {}", name, PLACEHOLDERS))?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(placeholder));
+                    chars = rest.chars();
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template { parts })
+    }
+
+    /// The template filled in with `id` (the identifier as printed or given)
+    /// and the fields of `decoded`
+    pub fn render(&self, id: &str, decoded: &Bcid) -> String {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Placeholder(placeholder) => rendered.push_str(&field(*placeholder, id, decoded)),
+            }
+        }
+        rendered
+    }
+}
+
+/// The RFC 3339 date/time of a chronological identifier, in milliseconds
+/// or microseconds as its precision needs
+pub fn iso_time(decoded: &Bcid) -> Option<String> {
+    let seconds_format = match decoded.precision() {
+        Some(Precision::Microseconds) => chrono::SecondsFormat::Micros,
+        _ => chrono::SecondsFormat::Millis,
+    };
+    decoded.datetime().map(|datetime| datetime.to_rfc3339_opts(seconds_format, true))
+}
+
+/// The value of `placeholder`, or an empty string
+fn field(placeholder: Placeholder, id: &str, decoded: &Bcid) -> String {
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    match placeholder {
+        Placeholder::Id => id.to_string(),
+        Placeholder::Prefix => decoded.prefix().to_string(),
+        Placeholder::Type => decoded.identifier_type().to_string(),
+        Placeholder::Format => decoded.format().to_string(),
+        Placeholder::Timestamp => optional(decoded.timestamp().map(|timestamp| timestamp.to_string())),
+        Placeholder::IsoTime => optional(iso_time(decoded)),
+        Placeholder::MachineId => decoded.machine_id().to_string(),
+        Placeholder::Environment => optional(decoded.environment().map(|environment| environment.to_string())),
+        Placeholder::Shard => decoded.shard().unwrap_or_default().to_string(),
+        Placeholder::Sequence => optional(decoded.sequence().map(|sequence| sequence.to_string())),
+        Placeholder::Random => optional(decoded.random().map(|random| random.to_string())),
+        Placeholder::RandomPart => decoded.random_part().unwrap_or_default().to_string(),
+    }
+}
@@ -0,0 +1,91 @@
+//! TLS for `bcid serve`, built with the `tls` feature
+//!
+//! The certificate chain and private key are read from PEM files. With a
+//! client CA, every client must present a certificate issued by it (mTLS);
+//! the handshake fails for any other, before a request is read.
+
+use std::io;
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::crypto::ring;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+
+/// A TLS connection to a client
+pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+/// Build the server configuration from a certificate chain and private key,
+/// requiring client certificates issued by `client_ca` if given
+pub fn server_config(cert: &Path, key: &Path, client_ca: Option<&Path>) -> Result<Arc<ServerConfig>, String> {
+    // Chosen explicitly, since other features may enable a second provider
+    let provider = Arc::new(ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .map_err(|err| err.to_string())?;
+    let builder = match client_ca {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(path)? {
+                roots.add(cert).map_err(|err| format!("Invalid CA certificate in {}: {}", path.display(), err))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(|err| format!("Invalid client CA {}: {}", path.display(), err))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let key = PrivateKeyDer::from_pem_file(key).map_err(|err| format!("Cannot read private key {}: {}", key.display(), err))?;
+    let mut config = builder
+        .with_single_cert(read_certs(cert)?, key)
+        .map_err(|err| format!("Invalid certificate {}: {}", cert.display(), err))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Every certificate in a PEM file, failing if there is none
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| format!("Cannot read certificates from {}: {}", path.display(), err))?;
+    match certs.is_empty() {
+        true => Err(format!("No certificates in {}", path.display())),
+        false => Ok(certs),
+    }
+}
+
+/// The same certificates for the gRPC server, whose TLS comes from tonic
+#[cfg(feature = "grpc")]
+pub fn grpc_config(cert: &Path, key: &Path, client_ca: Option<&Path>) -> io::Result<tonic::transport::ServerTlsConfig> {
+    use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+
+    // tonic builds its configuration with the process-wide provider
+    let _ = ring::default_provider().install_default();
+    let identity = Identity::from_pem(std::fs::read(cert)?, std::fs::read(key)?);
+    let config = ServerTlsConfig::new().identity(identity);
+    Ok(match client_ca {
+        Some(path) => config.client_ca_root(Certificate::from_pem(std::fs::read(path)?)),
+        None => config,
+    })
+}
+
+/// Complete the handshake with a client, which fails if it presents no
+/// acceptable certificate when one is required
+pub fn accept(config: &Arc<ServerConfig>, mut stream: TcpStream) -> io::Result<TlsStream> {
+    let mut connection = ServerConnection::new(Arc::clone(config)).map_err(io::Error::other)?;
+    while connection.is_handshaking() {
+        connection.complete_io(&mut stream)?;
+    }
+    Ok(StreamOwned::new(connection, stream))
+}
+
+/// Tell the client that no more data follows, so a truncated response can
+/// be told apart from a complete one
+pub fn close(stream: &mut TlsStream) {
+    stream.conn.send_close_notify();
+    let _ = stream.conn.complete_io(&mut stream.sock);
+}
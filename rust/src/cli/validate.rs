@@ -0,0 +1,126 @@
+use std::process;
+
+use bcid::{json, verify_check_digit, Alphabet, Bcid, BcidError, Environment};
+use clap::Args;
+
+use super::config::Config;
+use super::generate::{parse_alphabet, parse_environment};
+use super::EXIT_INVALID_IDENTIFIER;
+
+/// Check whether an identifier is well-formed
+///
+/// Checks an identifier without decoding it. Prints `valid`, or `invalid`
+/// followed by a tab-separated reason code and message, and exits with
+/// status 0 if the identifier is well-formed and 3 otherwise (2 for invalid
+/// arguments; see `bcid --help`). With --quiet
+/// nothing is printed; with --porcelain the message is left out, so the output
+/// is `valid` or `invalid<TAB>REASON` in every version.
+///
+/// Reason codes:
+///   invalid_length      not between 24 and 64 characters
+///   invalid_character   a character outside the base62 alphabet
+///   invalid_timestamp   the timestamp is not a valid date/time
+///   value_out_of_range  a field does not fit its range
+///   unknown_format      unknown version marker
+///   unknown_type        fits neither the chronological nor the random layout
+///   invalid_check_digit the last character is not the check digit (with --check-digit)
+///   wrong_environment   not generated in the environment (with --environment)
+#[derive(Args)]
+#[command(verbatim_doc_comment)]
+pub struct ValidateArgs {
+    /// Identifier to check
+    identifier: String,
+    #[command(flatten)]
+    output: ReportFlags,
+    /// Also verify the check digit in the last character
+    #[arg(long)]
+    check_digit: bool,
+    /// Also require the identifier to record the environment p (production),
+    /// s (staging) or d (development) (default: from the config file)
+    #[arg(long, value_name = "ENV", value_parser = parse_environment)]
+    environment: Option<Environment>,
+    /// Alphabet the identifier was generated with (default: base62 or from
+    /// the config file)
+    #[arg(long, value_parser = parse_alphabet)]
+    alphabet: Option<Alphabet>,
+}
+
+pub fn run(args: ValidateArgs, config: &Config) {
+    let identifier = args.identifier;
+    let with_check_digit = args.check_digit || config.check_digit;
+    let alphabet = args.alphabet.or(config.alphabet).unwrap_or_default();
+    let environment = args.environment.or(config.environment);
+
+    let result = Bcid::parse_with(&identifier, alphabet)
+        .and_then(|decoded| match environment {
+            Some(environment) => decoded.check_environment(environment),
+            None => Ok(()),
+        })
+        .and_then(|_| match with_check_digit {
+            true => verify_check_digit(&identifier),
+            false => Ok(()),
+        });
+    report(result, args.output.output());
+}
+
+/// `--json`, `--porcelain` and `--quiet`, of which the last given applies
+#[derive(Args)]
+pub struct ReportFlags {
+    /// Print the result as a JSON object
+    #[arg(long, overrides_with_all = ["porcelain", "quiet"])]
+    json: bool,
+    /// Print `valid`, or `invalid` and the reason code, tab-separated
+    #[arg(long, overrides_with_all = ["json", "quiet"])]
+    porcelain: bool,
+    /// Print nothing; only the exit status tells the result
+    #[arg(short, long, overrides_with_all = ["json", "porcelain"])]
+    quiet: bool,
+}
+
+impl ReportFlags {
+    /// The output the flags ask for
+    pub fn output(&self) -> ReportOutput {
+        match (self.json, self.porcelain, self.quiet) {
+            (true, _, _) => ReportOutput::Json,
+            (_, true, _) => ReportOutput::Porcelain,
+            (_, _, true) => ReportOutput::Quiet,
+            _ => ReportOutput::Text,
+        }
+    }
+}
+
+/// How a validation result is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportOutput {
+    /// `valid`, or `invalid`, the reason code and the message
+    Text,
+    /// A JSON object of `valid`, `reason` and `message`
+    Json,
+    /// `valid`, or `invalid` and the reason code, whose format never changes
+    Porcelain,
+    /// Nothing but the exit status
+    Quiet,
+}
+
+/// Print a validation result and exit with status 3 if it failed
+pub fn report(result: Result<(), BcidError>, output: ReportOutput) {
+    match (&result, output) {
+        (_, ReportOutput::Quiet) => {}
+        (Ok(()), ReportOutput::Text | ReportOutput::Porcelain) => println!("valid"),
+        (Err(err), ReportOutput::Text) => println!("invalid\t{}\t{}", err.reason(), err),
+        (Err(err), ReportOutput::Porcelain) => println!("invalid\t{}", err.reason()),
+        (_, ReportOutput::Json) => {
+            let err = result.as_ref().err();
+            let message = err.map(|e| e.to_string());
+            let json = json::Object::new()
+                .boolean("valid", err.is_none())
+                .string("reason", err.map(|e| e.reason()))
+                .string("message", message.as_deref())
+                .finish();
+            println!("{}", json);
+        }
+    }
+    if result.is_err() {
+        process::exit(EXIT_INVALID_IDENTIFIER);
+    }
+}
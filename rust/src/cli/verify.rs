@@ -0,0 +1,49 @@
+use bcid::{decrypt_identifier, verify_signature, Bcid, DEFAULT_SIGNATURE_LENGTH};
+use clap::Args;
+
+use super::config::{parse_hex_key, Config, Key};
+use super::generate::parse_signature_length;
+use super::usage_error;
+use super::validate::{report, ReportFlags};
+
+/// Check the signature of a signed identifier
+///
+/// Checks that an identifier is well-formed and that its trailing signature
+/// (from `bcid generate --sign`) matches. Output and exit status are as for
+/// `bcid validate`, with the reason code `invalid_signature` for a mismatch.
+#[derive(Args)]
+#[command(after_help = EXAMPLES)]
+pub struct VerifyArgs {
+    /// Identifier to check
+    identifier: String,
+    /// Signing key (default: $BCID_SIGNING_KEY)
+    #[arg(long, value_name = "HEX", value_parser = parse_hex_key)]
+    key: Option<Key>,
+    /// Signature characters, 1-12 (default: 8)
+    #[arg(long, value_name = "N", value_parser = parse_signature_length, default_value_t = DEFAULT_SIGNATURE_LENGTH, hide_default_value = true)]
+    signature_length: usize,
+    /// The identifier is opaque (`generate --opaque --sign`); check its
+    /// decrypted form
+    #[arg(long)]
+    opaque: bool,
+    #[command(flatten)]
+    output: ReportFlags,
+}
+
+const EXAMPLES: &str = "\
+Examples:
+  BCID_SIGNING_KEY=00112233445566778899aabbccddeeff bcid verify TESTjruOLuXxWaabpKphWLxfb4ugO58C";
+
+pub fn run(args: VerifyArgs, config: &Config) {
+    let identifier = args.identifier;
+    let Some(key) = args.key.or_else(|| config.signing_key.clone()) else {
+        usage_error("verify", "A key is required to verify identifiers (--key or BCID_SIGNING_KEY)");
+    };
+
+    let result = match args.opaque {
+        true => decrypt_identifier(&identifier, &key).and_then(|plain| Bcid::validate(&plain)),
+        false => Bcid::validate(&identifier),
+    }
+    .and_then(|()| verify_signature(&identifier, &key, args.signature_length));
+    report(result, args.output.output());
+}
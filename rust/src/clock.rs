@@ -0,0 +1,96 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Source of the current time for [`BcidGenerator`](crate::BcidGenerator)
+/// and [`SharedGenerator`](crate::SharedGenerator)
+///
+/// Implemented by [`SystemClock`], [`MockClock`] and every
+/// `Fn() -> DateTime<Utc>` closure, such as `move || fixed_time`.
+pub trait Clock: Send + Sync {
+    /// The current time
+    fn now(&self) -> DateTime<Utc>;
+}
+
+impl<F: Fn() -> DateTime<Utc> + Send + Sync> Clock for F {
+    fn now(&self) -> DateTime<Utc> {
+        self()
+    }
+}
+
+/// The system clock, which generators use unless given another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests of time
+/// progression, clock regression and bursts within one 10ms window
+///
+/// Clones share the same time, so a test keeps one clone and hands another
+/// to the generator:
+///
+/// ```
+/// use bcid::{BcidGenerator, MockClock};
+/// use chrono::Duration;
+///
+/// let clock = MockClock::new(bcid::parse_user_datetime("2024-01-01")?);
+/// let mut generator = BcidGenerator::new("TEST", 1)?.monotonic(true).clock(clock.clone());
+///
+/// // A burst: the clock stands still, so every identifier has the same timestamp
+/// let burst = generator.next_batch(1000);
+/// assert!(burst.iter().all(|id| id.timestamp() == burst[0].timestamp()));
+///
+/// clock.advance(Duration::seconds(1));
+/// assert!(generator.next().timestamp() > burst[0].timestamp());
+///
+/// // NTP steps the clock back
+/// clock.advance(Duration::seconds(-5));
+/// # Ok::<(), bcid::BcidError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    /// The current time, and how far it moves after each reading
+    state: Arc<Mutex<(DateTime<Utc>, Duration)>>,
+}
+
+impl MockClock {
+    /// A clock standing still at `start`
+    pub fn new(start: DateTime<Utc>) -> Self {
+        MockClock { state: Arc::new(Mutex::new((start, Duration::zero()))) }
+    }
+
+    /// A clock that starts at `start` and moves forward by `step` after
+    /// every reading
+    pub fn stepping(start: DateTime<Utc>, step: Duration) -> Self {
+        MockClock { state: Arc::new(Mutex::new((start, step))) }
+    }
+
+    /// Jump to `now`, forwards or backwards
+    pub fn set(&self, now: DateTime<Utc>) {
+        self.lock().0 = now;
+    }
+
+    /// Move the clock by `by`, which is negative to move it back
+    pub fn advance(&self, by: Duration) {
+        let mut state = self.lock();
+        state.0 += by;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, (DateTime<Utc>, Duration)> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        let mut state = self.lock();
+        let (now, step) = *state;
+        state.0 = now + step;
+        now
+    }
+}
@@ -0,0 +1,121 @@
+//! Content-addressed, namespaced and child identifiers
+//!
+//! [`Bcid::from_content`] derives an identifier from the SHA-256 hash of a
+//! payload, [`Bcid::from_key`] from the HMAC-SHA256 of a business key keyed
+//! with a namespace, and [`Bcid::child`] from a parent identifier and an
+//! ordinal, instead of the clock and the operating system's randomness, so
+//! the same input always yields the same identifier. Derived identifiers
+//! are random identifiers, with machine ID 0 or the ordinal of a child:
+//! their random data is drawn from SHA-256 in counter mode over the hash,
+//! and decodes like any other.
+
+use crate::signature::{hmac_sha256, sha256};
+use crate::{encode_random, validate_prefix, Bcid, BcidError, EntropySource};
+
+impl Bcid {
+    /// The identifier of `content` with `prefix`: a random identifier with
+    /// machine ID 0 whose random data is derived from the SHA-256 hash of
+    /// `content`, e.g. to deduplicate re-ingested documents
+    ///
+    /// Fails if the prefix is not 1 to 8 printable ASCII characters other
+    /// than `_`.
+    ///
+    /// ```
+    /// let id = bcid::Bcid::from_content("DOCU", b"hello")?;
+    /// assert_eq!(id, bcid::Bcid::from_content("DOCU", b"hello")?);
+    /// assert_ne!(id, bcid::Bcid::from_content("DOCU", b"hello!")?);
+    /// assert_eq!(id.identifier_type(), bcid::IdentifierType::Random);
+    /// # Ok::<(), bcid::BcidError>(())
+    /// ```
+    pub fn from_content(prefix: &str, content: &[u8]) -> Result<Bcid, BcidError> {
+        derive_identifier(prefix, 0, sha256(content))
+    }
+
+    /// The identifier of `key` in `namespace` with `prefix`, like a UUIDv5:
+    /// a random identifier with machine ID 0 whose random data is derived
+    /// from the HMAC-SHA256 of `key` keyed with `namespace`, e.g. to map
+    /// legacy customer numbers to the same new identifiers on every run of
+    /// a migration
+    ///
+    /// The same key gives unrelated identifiers in different namespaces,
+    /// unrelated to the identifier of the same bytes as
+    /// [content](Bcid::from_content) too. Fails if the prefix is not 1 to 8
+    /// printable ASCII characters other than `_`.
+    ///
+    /// ```
+    /// let id = bcid::Bcid::from_key("CUST", "legacy-crm", b"10042")?;
+    /// assert_eq!(id, bcid::Bcid::from_key("CUST", "legacy-crm", b"10042")?);
+    /// assert_ne!(id, bcid::Bcid::from_key("CUST", "billing", b"10042")?);
+    /// # Ok::<(), bcid::BcidError>(())
+    /// ```
+    pub fn from_key(prefix: &str, namespace: &str, key: &[u8]) -> Result<Bcid, BcidError> {
+        derive_identifier(prefix, 0, hmac_sha256(namespace.as_bytes(), key))
+    }
+
+    /// The child of this identifier with `prefix` and `ordinal`, e.g. the
+    /// third line item of an order: a random identifier whose machine ID is
+    /// `ordinal` and whose random data is derived from the SHA-256 hash of
+    /// this identifier, `prefix` and `ordinal`
+    ///
+    /// The same parent, prefix and ordinal always give the same child, and
+    /// [`verify_child`](Self::verify_child) proves the relationship without
+    /// looking it up. Fails if the prefix is not 1 to 8 printable ASCII
+    /// characters other than `_`.
+    ///
+    /// ```
+    /// let order = bcid::Bcid::parse("ORDRjqTZNAWSyaabkXENIJ3mg9rTtkWk")?;
+    /// let line = order.child("LINE", 3)?;
+    /// assert_eq!(line.machine_id(), 3);
+    /// assert_eq!(line, order.child("LINE", 3)?);
+    /// assert!(order.verify_child(&line).is_ok());
+    /// assert!(order.verify_child(&order.child("LINE", 4)?).is_ok());
+    /// assert!(bcid::Bcid::parse("ORDRjqTZNAWSyaabkXENIJ3mg9rTtkWl")?.verify_child(&line).is_err());
+    /// # Ok::<(), bcid::BcidError>(())
+    /// ```
+    pub fn child(&self, prefix: &str, ordinal: u16) -> Result<Bcid, BcidError> {
+        let seed = [self.as_str().as_bytes(), &[0], prefix.as_bytes(), &[0], &ordinal.to_be_bytes()].concat();
+        derive_identifier(prefix, ordinal, sha256(&seed))
+    }
+
+    /// Fail with [`BcidError::NotAChild`] unless `child` is the
+    /// [`child`](Self::child) of this identifier with its prefix and
+    /// ordinal (its machine ID)
+    pub fn verify_child(&self, child: &Bcid) -> Result<(), BcidError> {
+        match self.child(child.prefix(), child.machine_id()) {
+            Ok(expected) if expected.as_str() == child.as_str() => Ok(()),
+            _ => Err(BcidError::NotAChild),
+        }
+    }
+}
+
+/// The random identifier with `machine_id` whose random data is drawn from
+/// SHA-256 in counter mode over `seed`
+fn derive_identifier(prefix: &str, machine_id: u16, seed: [u8; 32]) -> Result<Bcid, BcidError> {
+    validate_prefix(prefix)?;
+    let mut stream = HashStream { seed, counter: 0, block: [0; 32], used: 32 };
+    Bcid::try_from(encode_random(prefix, machine_id, &mut stream))
+}
+
+/// SHA-256 of the seed followed by a block counter, as an endless byte
+/// stream that is the same for the same seed
+struct HashStream {
+    seed: [u8; 32],
+    counter: u64,
+    block: [u8; 32],
+    /// Bytes of `block` already returned
+    used: usize,
+}
+
+impl EntropySource for HashStream {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest {
+            if self.used == self.block.len() {
+                self.block = sha256(&[&self.seed[..], &self.counter.to_be_bytes()].concat());
+                self.counter += 1;
+                self.used = 0;
+            }
+            *byte = self.block[self.used];
+            self.used += 1;
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Sources of randomness for the random value, padding and random data
+//!
+//! The free generation functions draw from the operating system's CSPRNG
+//! ([`OsRng`]). Any [`rand::RngCore`] is an [`EntropySource`], so callers can
+//! inject their own generator with [`generate_identifier_with`](crate::generate_identifier_with).
+
+#[cfg(feature = "std")]
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+/// A source of random bytes
+pub trait EntropySource {
+    /// Fill `dest` with random bytes
+    fn fill_bytes(&mut self, dest: &mut [u8]);
+}
+
+impl<R: RngCore + ?Sized> EntropySource for R {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RngCore::fill_bytes(self, dest)
+    }
+}
+
+/// The default entropy source, the operating system's CSPRNG
+#[cfg(feature = "std")]
+pub(crate) fn os_entropy() -> OsRng {
+    OsRng
+}
+
+/// A uniformly distributed 16-bit value
+pub(crate) fn random_u16(entropy: &mut dyn EntropySource) -> u16 {
+    let mut bytes = [0u8; 2];
+    entropy.fill_bytes(&mut bytes);
+    u16::from_be_bytes(bytes)
+}
+
+/// Smallest random value that the v1 layout can delimit
+///
+/// The decoder reads as many digits of a v1 random value as fit in 16 bits,
+/// so a smaller value absorbs the start of the padding. Generated random
+/// values are drawn from here up, so that they round-trip in every format.
+pub(crate) const MIN_RANDOM_VALUE: u16 = 1058;
+
+/// A random value uniformly distributed from [`MIN_RANDOM_VALUE`] up
+pub(crate) fn random_value(entropy: &mut dyn EntropySource) -> u16 {
+    loop {
+        let value = random_u16(entropy);
+        if value >= MIN_RANDOM_VALUE {
+            return value;
+        }
+    }
+}
+
+/// A uniformly distributed 128-bit value
+pub(crate) fn random_u128(entropy: &mut dyn EntropySource) -> u128 {
+    let mut bytes = [0u8; 16];
+    entropy.fill_bytes(&mut bytes);
+    u128::from_be_bytes(bytes)
+}
+
+/// Fill `out` with characters drawn uniformly from `alphabet` (at most 64
+/// characters)
+///
+/// Each character takes the low bits of a random byte (6 for base62),
+/// rejecting values past the end of the alphabet, so there is no modulo bias.
+pub(crate) fn fill_random_chars(entropy: &mut dyn EntropySource, alphabet: &[u8], out: &mut [u8]) {
+    let mask = (alphabet.len().next_power_of_two() - 1) as u8;
+    let mut filled = 0;
+    let mut buffer = [0u8; 32];
+    while filled < out.len() {
+        // 1/32 of base62 draws are rejected, so a little slack avoids most refills
+        let remaining = out.len() - filled;
+        let len = (remaining + remaining / 16 + 2).min(buffer.len());
+        entropy.fill_bytes(&mut buffer[..len]);
+        for &byte in &buffer[..len] {
+            let index = (byte & mask) as usize;
+            if index < alphabet.len() && filled < out.len() {
+                out[filled] = alphabet[index];
+                filled += 1;
+            }
+        }
+    }
+}
+
+/// Bytes drawn from the underlying source at once by [`BufferedEntropy`]
+#[cfg(feature = "std")]
+const BUFFER_SIZE: usize = 1024;
+
+/// An entropy source that draws from another in bulk and hands out the
+/// bytes in order, so each identifier costs no call into the underlying RNG
+#[cfg(feature = "std")]
+pub(crate) struct BufferedEntropy {
+    source: Box<dyn EntropySource + Send>,
+    buffer: Box<[u8; BUFFER_SIZE]>,
+    /// Next unused byte of `buffer`
+    position: usize,
+}
+
+#[cfg(feature = "std")]
+impl BufferedEntropy {
+    pub(crate) fn new(source: Box<dyn EntropySource + Send>) -> Self {
+        BufferedEntropy { source, buffer: Box::new([0; BUFFER_SIZE]), position: BUFFER_SIZE }
+    }
+}
+
+#[cfg(feature = "std")]
+impl EntropySource for BufferedEntropy {
+    fn fill_bytes(&mut self, mut dest: &mut [u8]) {
+        while !dest.is_empty() {
+            if self.position == BUFFER_SIZE {
+                self.source.fill_bytes(&mut self.buffer[..]);
+                self.position = 0;
+            }
+            let len = dest.len().min(BUFFER_SIZE - self.position);
+            dest[..len].copy_from_slice(&self.buffer[self.position..self.position + len]);
+            self.position += len;
+            dest = &mut dest[len..];
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use core::fmt;
+
+use crate::Alphabet;
+
+/// Deployment environment recorded in chronological identifiers
+///
+/// Tagged identifiers use the [`Format::V3`](crate::Format::V3) layout (or
+/// [`Format::V4`](crate::Format::V4)), so a pasted identifier shows where
+/// it was generated, and services can reject identifiers from other
+/// environments with [`Bcid::check_environment`](crate::Bcid::check_environment).
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// let mut generator = bcid::BcidGenerator::new("TEST", 1)?.environment(bcid::Environment::Staging);
+/// let id = generator.next();
+/// assert_eq!(id.environment(), Some(bcid::Environment::Staging));
+/// assert!(id.check_environment(bcid::Environment::Production).is_err());
+/// # }
+/// # Ok::<(), bcid::BcidError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Environment {
+    /// Production, marked `p`
+    Production,
+    /// Staging, marked `s`
+    Staging,
+    /// Development, marked `d`
+    Development,
+}
+
+impl Environment {
+    /// Name used in output: `production`, `staging` or `development`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Environment::Production => "production",
+            Environment::Staging => "staging",
+            Environment::Development => "development",
+        }
+    }
+
+    /// The environment called `name` (see [`Environment::name`]) or marked
+    /// by `name` (`p`, `s` or `d`)
+    pub fn from_name(name: &str) -> Option<Environment> {
+        match name {
+            "p" | "production" => Some(Environment::Production),
+            "s" | "staging" => Some(Environment::Staging),
+            "d" | "development" => Some(Environment::Development),
+            _ => None,
+        }
+    }
+
+    /// The character recording this environment: `p`, `s` or `d`, in
+    /// uppercase for alphabets whose digits are uppercase (Crockford base32)
+    pub(crate) fn marker(&self, alphabet: Alphabet) -> u8 {
+        let marker = match self {
+            Environment::Production => b'p',
+            Environment::Staging => b's',
+            Environment::Development => b'd',
+        };
+        match alphabet.chars().contains(&marker) {
+            true => marker,
+            false => marker.to_ascii_uppercase(),
+        }
+    }
+
+    /// The environment recorded by `marker` in `alphabet` (see
+    /// [`Environment::marker`])
+    pub(crate) fn from_marker(marker: u8, alphabet: Alphabet) -> Option<Environment> {
+        [Environment::Production, Environment::Staging, Environment::Development]
+            .into_iter()
+            .find(|environment| environment.marker(alphabet) == marker)
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,193 @@
+use alloc::string::String;
+use core::fmt;
+
+use crate::{Environment, MAX_LENGTH, MAX_PREFIX_LENGTH, MIN_LENGTH, MIN_PREFIX_LENGTH, SEPARATOR};
+
+/// Errors returned by BCID generation and decoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BcidError {
+    /// The prefix is not between [`MIN_PREFIX_LENGTH`](crate::MIN_PREFIX_LENGTH)
+    /// and [`MAX_PREFIX_LENGTH`](crate::MAX_PREFIX_LENGTH) characters long
+    InvalidPrefixLength(usize),
+    /// The prefix contains a character other than printable ASCII, or the
+    /// separator
+    InvalidPrefixCharacter(char),
+    /// A user-supplied date/time could not be parsed
+    InvalidDatetime(String),
+    /// The identifier (or a configured identifier length) is not between
+    /// [`MIN_LENGTH`](crate::MIN_LENGTH) and [`MAX_LENGTH`](crate::MAX_LENGTH)
+    /// characters long, leaving out a separator after a 4-character prefix,
+    /// or is too short for its prefix and the fields of its format; holds
+    /// the length of the input
+    InvalidIdentifierLength(usize),
+    /// The identifier contains a character that cannot appear in a BCID
+    InvalidCharacter { character: char, position: usize },
+    /// The timestamp field does not hold a valid date/time
+    InvalidTimestamp(u64),
+    /// A decoded field does not fit its expected range
+    ValueOutOfRange { field: &'static str, value: u64 },
+    /// The version marker does not name a known format
+    UnknownFormat(char),
+    /// The identifier fits neither the chronological nor the random layout
+    UnknownType,
+    /// The final character does not match the check digit of the others
+    InvalidCheckDigit { expected: char, found: char },
+    /// The trailing signature does not match the rest of the identifier
+    InvalidSignature,
+    /// No machine ID could be derived or allocated
+    MachineIdUnavailable(String),
+    /// A UUID could not be parsed, or is not a UUIDv7
+    InvalidUuid(String),
+    /// A ULID could not be parsed
+    InvalidUlid(String),
+    /// A TypeID could not be parsed, or the prefix cannot be written as one
+    InvalidTypeId(String),
+    /// The operation needs a chronological identifier, but got a random one
+    NotChronological,
+    /// The identifier has a different prefix than its typed ID (see
+    /// [`define_bcid!`](crate::define_bcid!)) requires
+    UnexpectedPrefix { expected: &'static str, found: String },
+    /// A generator's clock reads earlier than it did before (see [`ClockRegression::Error`](crate::ClockRegression::Error)); both
+    /// are packed `YYYYMMDDHHmmSScc` timestamps
+    ClockMovedBackwards { latest: u64, now: u64 },
+    /// A generator's fields need longer identifiers than its configured
+    /// length, e.g. microsecond [`Precision`](crate::Precision) with
+    /// Crockford base32 at [`MIN_LENGTH`](crate::MIN_LENGTH)
+    LayoutDoesNotFit { needed: usize, length: usize },
+    /// A generator used up the sequence of a millisecond (see
+    /// [`SequenceOverflow::Error`](crate::SequenceOverflow::Error)); the
+    /// packed `YYYYMMDDHHmmSScc` timestamp is truncated to 10ms
+    SequenceOverflow { timestamp: u64, max: u32 },
+    /// A shard is not 1 or 2 digits of the alphabet, or the layout has no
+    /// shard field (see [`BcidGenerator::shard`](crate::BcidGenerator::shard))
+    InvalidShard(String),
+    /// An identifier is not the child of the given parent (see
+    /// [`Bcid::verify_child`](crate::Bcid::verify_child))
+    NotAChild,
+    /// An identifier was not generated in the expected environment (see
+    /// [`Bcid::check_environment`](crate::Bcid::check_environment))
+    WrongEnvironment { expected: Environment, found: Option<Environment> },
+    /// A [`BitLayout`](crate::BitLayout) could not be parsed, or its fields
+    /// do not fit their ranges or 128 bits
+    InvalidLayout(String),
+}
+
+impl BcidError {
+    /// Shift the position of an [`BcidError::InvalidCharacter`], or the
+    /// length of an [`BcidError::InvalidIdentifierLength`], by `offset`, for
+    /// errors from decoding the end of a larger identifier
+    pub(crate) fn offset(self, offset: usize) -> Self {
+        match self {
+            BcidError::InvalidCharacter { character, position } => {
+                BcidError::InvalidCharacter { character, position: position + offset }
+            }
+            BcidError::InvalidIdentifierLength(length) => BcidError::InvalidIdentifierLength(length + offset),
+            err => err,
+        }
+    }
+
+    /// Stable, machine-readable reason code, e.g. `invalid_length`
+    pub fn reason(&self) -> &'static str {
+        match self {
+            BcidError::InvalidPrefixLength(_) => "invalid_prefix_length",
+            BcidError::InvalidPrefixCharacter(_) => "invalid_prefix_character",
+            BcidError::InvalidDatetime(_) => "invalid_datetime",
+            BcidError::InvalidIdentifierLength(_) => "invalid_length",
+            BcidError::InvalidCharacter { .. } => "invalid_character",
+            BcidError::InvalidTimestamp(_) => "invalid_timestamp",
+            BcidError::ValueOutOfRange { .. } => "value_out_of_range",
+            BcidError::UnknownFormat(_) => "unknown_format",
+            BcidError::UnknownType => "unknown_type",
+            BcidError::InvalidCheckDigit { .. } => "invalid_check_digit",
+            BcidError::InvalidSignature => "invalid_signature",
+            BcidError::MachineIdUnavailable(_) => "machine_id_unavailable",
+            BcidError::InvalidUuid(_) => "invalid_uuid",
+            BcidError::InvalidUlid(_) => "invalid_ulid",
+            BcidError::InvalidTypeId(_) => "invalid_typeid",
+            BcidError::NotChronological => "not_chronological",
+            BcidError::UnexpectedPrefix { .. } => "unexpected_prefix",
+            BcidError::ClockMovedBackwards { .. } => "clock_moved_backwards",
+            BcidError::LayoutDoesNotFit { .. } => "layout_does_not_fit",
+            BcidError::SequenceOverflow { .. } => "sequence_overflow",
+            BcidError::InvalidShard(_) => "invalid_shard",
+            BcidError::NotAChild => "not_a_child",
+            BcidError::WrongEnvironment { .. } => "wrong_environment",
+            BcidError::InvalidLayout(_) => "invalid_layout",
+        }
+    }
+}
+
+impl fmt::Display for BcidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BcidError::InvalidPrefixLength(len) => {
+                write!(f, "Prefix must be between {} and {} characters long (got {})", MIN_PREFIX_LENGTH, MAX_PREFIX_LENGTH, len)
+            }
+            BcidError::InvalidPrefixCharacter(character) => {
+                write!(f, "Invalid prefix character {:?} (prefixes are printable ASCII without {:?})", character, SEPARATOR)
+            }
+            BcidError::InvalidDatetime(input) => write!(
+                f,
+                "Invalid date format: {}. Use ISO 8601 format (e.g., '2023-12-25T10:30:00'), a Unix timestamp (e.g., '@1703500200') or a relative time (e.g., '2 hours ago')",
+                input
+            ),
+            BcidError::InvalidIdentifierLength(len) if (MIN_LENGTH..=MAX_LENGTH).contains(len) => {
+                write!(f, "Identifier is too short for its prefix and the fields of its format (got {} characters)", len)
+            }
+            BcidError::InvalidIdentifierLength(len) => {
+                write!(f, "Identifier must be between {} and {} characters long (got {})", MIN_LENGTH, MAX_LENGTH, len)
+            }
+            BcidError::InvalidCharacter { character, position } => {
+                write!(f, "Invalid character {:?} at position {}", character, position)
+            }
+            BcidError::InvalidTimestamp(timestamp) => {
+                write!(f, "Invalid timestamp {}", timestamp)
+            }
+            BcidError::ValueOutOfRange { field, value } => {
+                write!(f, "Invalid {} {}: out of range", field, value)
+            }
+            BcidError::UnknownFormat(marker) => {
+                write!(f, "Unknown format version marker {:?}", marker)
+            }
+            BcidError::UnknownType => {
+                f.write_str("Identifier fits neither the chronological nor the random layout")
+            }
+            BcidError::InvalidCheckDigit { expected, found } => {
+                write!(f, "Invalid check digit {:?} (expected {:?})", found, expected)
+            }
+            BcidError::InvalidSignature => f.write_str("Signature does not match"),
+            BcidError::MachineIdUnavailable(reason) => {
+                write!(f, "Machine ID unavailable: {}", reason)
+            }
+            BcidError::InvalidUuid(reason) => write!(f, "Invalid UUID: {}", reason),
+            BcidError::InvalidUlid(reason) => write!(f, "Invalid ULID: {}", reason),
+            BcidError::InvalidTypeId(reason) => write!(f, "Invalid TypeID: {}", reason),
+            BcidError::NotChronological => {
+                f.write_str("Random identifiers carry no timestamp and cannot be converted")
+            }
+            BcidError::UnexpectedPrefix { expected, found } => {
+                write!(f, "Unexpected prefix {:?} (expected {:?})", found, expected)
+            }
+            BcidError::ClockMovedBackwards { latest, now } => {
+                write!(f, "Clock moved backwards: {} is before the previous reading {}", now, latest)
+            }
+            BcidError::LayoutDoesNotFit { needed, length } => {
+                write!(f, "Identifier length {} is too short for the prefix and fields of this layout (at least {})", length, needed)
+            }
+            BcidError::SequenceOverflow { timestamp, max } => {
+                write!(f, "Sequence overflow: more than {} identifiers in the millisecond at {}", *max as u64 + 1, timestamp)
+            }
+            BcidError::InvalidShard(reason) => write!(f, "Invalid shard: {}", reason),
+            BcidError::NotAChild => write!(f, "Not a child of the given parent"),
+            BcidError::WrongEnvironment { expected, found: Some(found) } => {
+                write!(f, "Wrong environment: {} (expected {})", found, expected)
+            }
+            BcidError::WrongEnvironment { expected, found: None } => {
+                write!(f, "Wrong environment: none recorded (expected {})", expected)
+            }
+            BcidError::InvalidLayout(reason) => write!(f, "Invalid bit layout: {}", reason),
+        }
+    }
+}
+
+impl core::error::Error for BcidError {}
@@ -0,0 +1,476 @@
+#[cfg(feature = "std")]
+use alloc::format;
+use core::fmt;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+use crate::base62::{decode_with, encode_fixed};
+use crate::entropy::fill_random_chars;
+use crate::{
+    min_length, pack_timestamp, unpack_timestamp, validate_prefix, write_prefix, Alphabet, Bcid, BcidError, EntropySource,
+    Precision, MAX_ENCODED_LENGTH, MAX_LENGTH,
+};
+#[cfg(feature = "std")]
+use crate::Environment;
+
+/// Version marker placed right after the prefix of v2 identifiers
+///
+/// v1 identifiers always have a letter at this position, so every digit is
+/// reserved as the version marker of a layout after v1.
+pub(crate) const V2_MARKER: u8 = b'2';
+
+/// Version marker of v3 identifiers
+pub(crate) const V3_MARKER: u8 = b'3';
+
+/// Version marker of v4 identifiers
+pub(crate) const V4_MARKER: u8 = b'4';
+
+/// Version marker of v5 identifiers
+pub(crate) const V5_MARKER: u8 = b'5';
+
+/// Marker placed right after the prefix of random identifiers, before the
+/// machine ID
+///
+/// Without it, the machine ID and random data of a random identifier can
+/// read as a v1 timestamp (always, for machine IDs 558-619 and
+/// 34596-38439). Random identifiers generated before the marker are still
+/// told apart by whether their first characters decode to a plausible year.
+pub(crate) const RANDOM_MARKER: u8 = b'0';
+
+/// Identifier layout
+///
+/// Each layout after v1 starts with a version digit right after the prefix,
+/// and decoding dispatches on it. More layouts may be added, so matches
+/// outside this crate need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum Format {
+    /// Original layout: variable-width timestamp and random value encoded
+    /// with [`BASE62`](crate::BASE62)
+    ///
+    /// Nothing marks where the random value ends, so the decoder reads as
+    /// many digits as fit in 16 bits: a random value below 1058 absorbs the
+    /// start of the padding (e.g. 12 followed by padding `Qv` decodes as
+    /// 48753). Generated random values are never that small, but use
+    /// [`Format::V2`] when identifiers from other implementations must
+    /// round-trip too.
+    #[default]
+    V1,
+    /// Sortable layout: the `2` marker followed by fixed-width timestamp (9),
+    /// machine ID (3) and random value (3) fields encoded with
+    /// [`SORTABLE_BASE62`](crate::SORTABLE_BASE62), then random padding
+    ///
+    /// Other [`Alphabet`]s use this layout with wider fields where their
+    /// radix needs them (e.g. 11, 4 and 4 characters in Crockford base32).
+    ///
+    /// Every field decodes exactly as it was encoded, and identifiers with
+    /// the same prefix sort lexicographically in chronological order. Only
+    /// chronological identifiers use this layout.
+    V2,
+    /// v2 with a recorded timestamp precision: the `3` marker, a
+    /// [`Precision`] character (`s`, `c`, `m` or `u`), then the v2 fields
+    /// with a timestamp field sized for the precision
+    ///
+    /// An uppercase precision character marks 3 characters of tags between
+    /// the machine ID and the random value, in v3 and v4 alike: an
+    /// [`Environment`](crate::Environment) and a 2-character shard (see
+    /// [`BcidGenerator::shard`](crate::BcidGenerator::shard)), each the
+    /// zero digit of the alphabet when absent.
+    ///
+    /// With [`SORTABLE_BASE62`](crate::SORTABLE_BASE62) the timestamp takes
+    /// 8 characters at second precision, 9 at 10ms, 10 at millisecond and 12
+    /// at microsecond precision; identifiers with the same prefix and
+    /// precision sort chronologically.
+    V3,
+    /// v3 with a compact timestamp: the `4` marker and a [`Precision`]
+    /// character, then the time as a count of precision steps since
+    /// 0000-01-01, and the machine ID, random value and padding as in v2
+    ///
+    /// The count is 1 character shorter than the v3 timestamp at second,
+    /// 10ms and millisecond precision and 2 shorter at microsecond
+    /// precision, and the freed characters become random padding.
+    /// Identifiers with the same prefix and precision sort chronologically.
+    ///
+    /// The epoch is fixed so that decoding needs nothing but the identifier,
+    /// and covers every year the library accepts. It is not configurable:
+    /// recording another epoch would take a character, and a field 3
+    /// characters shorter than v3 would only span about 29 years at second
+    /// precision, 18 at 10ms, 111 at millisecond and 429 at microsecond
+    /// precision in base62.
+    V4,
+    /// Configurable bit allocation: the `5` marker and three characters
+    /// recording the timestamp, machine ID and sequence bits of a
+    /// [`BitLayout`](crate::BitLayout), then its 128 bits as one fixed-width
+    /// number (22 characters of [`SORTABLE_BASE62`](crate::SORTABLE_BASE62))
+    /// and random padding
+    ///
+    /// The timestamp counts milliseconds since the Unix epoch. Identifiers
+    /// with the same prefix and layout sort chronologically.
+    V5,
+}
+
+impl Format {
+    /// Format version number
+    pub fn version(&self) -> u8 {
+        match self {
+            Format::V1 => 1,
+            Format::V2 => 2,
+            Format::V3 => 3,
+            Format::V4 => 4,
+            Format::V5 => 5,
+        }
+    }
+
+    /// The layout with version number `version`
+    pub fn from_version(version: u8) -> Option<Format> {
+        match version {
+            1 => Some(Format::V1),
+            2 => Some(Format::V2),
+            3 => Some(Format::V3),
+            4 => Some(Format::V4),
+            5 => Some(Format::V5),
+            _ => None,
+        }
+    }
+
+    /// Parse a version number, optionally written with a `v` (`2` or `v2`)
+    pub fn parse_version(value: &str) -> Option<Format> {
+        let digits = value.strip_prefix('v').unwrap_or(value);
+        digits.parse().ok().and_then(Format::from_version)
+    }
+
+    /// The character after the prefix that marks this layout (none for v1)
+    pub fn marker(&self) -> Option<char> {
+        match self {
+            Format::V1 => None,
+            Format::V2 => Some(V2_MARKER as char),
+            Format::V3 => Some(V3_MARKER as char),
+            Format::V4 => Some(V4_MARKER as char),
+            Format::V5 => Some(V5_MARKER as char),
+        }
+    }
+
+    /// Whether every field has a fixed width, so that decoding recovers
+    /// exactly the encoded values
+    pub fn has_fixed_width_fields(&self) -> bool {
+        match self {
+            Format::V1 => false,
+            Format::V2 | Format::V3 | Format::V4 | Format::V5 => true,
+        }
+    }
+
+    /// `fixed-width` or `variable-width`, as reported by `decode`
+    pub fn layout(&self) -> &'static str {
+        if self.has_fixed_width_fields() {
+            "fixed-width"
+        } else {
+            "variable-width"
+        }
+    }
+
+    /// Detect the layout of an identifier from the marker at the start of
+    /// its encoded fields, [`Format::V1`] for random identifiers
+    ///
+    /// Fails for digits other than a known version marker or the random
+    /// identifier marker.
+    pub(crate) fn detect(fields: &str) -> Result<Format, BcidError> {
+        match fields.as_bytes().first() {
+            Some(&V2_MARKER) => Ok(Format::V2),
+            Some(&V3_MARKER) => Ok(Format::V3),
+            Some(&V4_MARKER) => Ok(Format::V4),
+            Some(&V5_MARKER) => Ok(Format::V5),
+            Some(&RANDOM_MARKER) => Ok(Format::V1),
+            Some(&marker) if marker.is_ascii_digit() => Err(BcidError::UnknownFormat(marker as char)),
+            _ => Ok(Format::V1),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.version())
+    }
+}
+
+impl Bcid {
+    /// The smallest v2 identifier with `prefix` in the 10ms tick containing
+    /// `datetime`
+    ///
+    /// v2 identifiers sort by time, so with [`Bcid::max_for`] this bounds a
+    /// range scan over a time window, e.g. `WHERE id BETWEEN $1 AND $2`
+    /// (under a bytewise collation such as `C`). v1 identifiers do not sort
+    /// by time and fall inside or outside such a range arbitrarily. This bound
+    /// is the shortest length the prefix allows (see [`min_length`]) and the
+    /// one from [`Bcid::max_for`] [`MAX_LENGTH`] long, so the range covers
+    /// identifiers of every length.
+    ///
+    /// Fails if the prefix is not 1 to 8 printable ASCII characters other
+    /// than `_`, or `datetime` is outside years 0-9999.
+    pub fn min_for(prefix: &str, datetime: DateTime<Utc>) -> Result<Bcid, BcidError> {
+        v2_bound(prefix, datetime, 0, b'0', min_length(prefix.len()))
+    }
+
+    /// The largest v2 identifier with `prefix` in the 10ms tick containing
+    /// `datetime` (see [`Bcid::min_for`])
+    pub fn max_for(prefix: &str, datetime: DateTime<Utc>) -> Result<Bcid, BcidError> {
+        v2_bound(prefix, datetime, u16::MAX, b'z', MAX_LENGTH)
+    }
+}
+
+/// The v2 identifier of `length` characters with every field after the
+/// timestamp set to `field` and padding of `padding` characters
+fn v2_bound(prefix: &str, datetime: DateTime<Utc>, field: u16, padding: u8, length: usize) -> Result<Bcid, BcidError> {
+    validate_prefix(prefix)?;
+    let timestamp = pack_timestamp(&datetime);
+    if unpack_timestamp(timestamp).is_none() {
+        return Err(BcidError::InvalidTimestamp(timestamp));
+    }
+    let mut out = [padding; MAX_ENCODED_LENGTH];
+    let out = &mut out[..length];
+    FixedLayout::v2(Alphabet::Base62).encode_fields(out, prefix, (timestamp, 0), field, field);
+    Bcid::parse(core::str::from_utf8(out).expect("identifiers are ASCII"))
+}
+
+/// The fixed-width fields of a v2, v3 or v4 identifier (v5 identifiers pack
+/// theirs with a [`BitLayout`](crate::BitLayout))
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FixedLayout {
+    pub(crate) alphabet: Alphabet,
+    format: Format,
+    /// Timestamp precision, always 10ms for v2
+    precision: Precision,
+    /// Encoded tags after the machine ID (v3 and v4 only, see
+    /// [`encode_tags`]); decoding only uses whether there are any
+    tags: Option<[u8; TAGS_WIDTH]>,
+}
+
+/// Offsets of the fields of a fixed-width identifier, from the start of its
+/// encoded fields
+struct Offsets {
+    timestamp: usize,
+    /// Start of the part of a packed timestamp finer than 10ms
+    sub_tick: usize,
+    machine: usize,
+    tags: usize,
+    random: usize,
+    padding: usize,
+}
+
+impl FixedLayout {
+    /// The v2 layout in `alphabet`
+    pub(crate) fn v2(alphabet: Alphabet) -> FixedLayout {
+        FixedLayout { alphabet, format: Format::V2, precision: Precision::Centiseconds, tags: None }
+    }
+
+    /// The layout of chronological identifiers in `format` and `alphabet`,
+    /// or `None` for v1 and v5
+    pub(crate) fn of(format: Format, alphabet: Alphabet, precision: Precision) -> Option<FixedLayout> {
+        match format {
+            Format::V1 if alphabet == Alphabet::Base62 => None,
+            Format::V5 => None,
+            Format::V1 | Format::V2 => Some(FixedLayout::v2(alphabet)),
+            Format::V3 | Format::V4 => Some(FixedLayout { alphabet, format, precision, tags: None }),
+        }
+    }
+
+    /// This v3 or v4 layout with encoded tags (see [`encode_tags`])
+    pub(crate) fn with_tags(self, tags: Option<[u8; TAGS_WIDTH]>) -> FixedLayout {
+        FixedLayout { tags, ..self }
+    }
+
+    /// Offset of the tags from the start of the encoded fields
+    pub(crate) fn tags_offset(&self) -> Option<usize> {
+        self.tags.map(|_| self.offsets().tags)
+    }
+
+    /// Characters from the marker to the end of the random value
+    #[cfg(feature = "std")]
+    pub(crate) fn fields_len(&self) -> usize {
+        self.offsets().padding
+    }
+
+    /// Encoded widths of the whole timestamp and of the machine ID and
+    /// random value
+    pub(crate) fn widths(&self) -> (usize, usize) {
+        let offsets = self.offsets();
+        (offsets.machine - offsets.timestamp, self.alphabet.field_width())
+    }
+
+    fn offsets(&self) -> Offsets {
+        let alphabet = self.alphabet;
+        let (timestamp, tick_width, sub_tick_width) = match (self.format, self.precision) {
+            (Format::V4, precision) => (2, alphabet.width(MAX_EPOCH_MICROS / precision.unit_micros()), 0),
+            (Format::V3, Precision::Seconds) => (2, alphabet.width(MAX_SECONDS), 0),
+            (Format::V3, precision) => match precision.sub_tick().1 {
+                0 => (2, alphabet.timestamp_width(), 0),
+                max => (2, alphabet.timestamp_width(), alphabet.width(max as u64)),
+            },
+            _ => (1, alphabet.timestamp_width(), 0),
+        };
+        let sub_tick = timestamp + tick_width;
+        let machine = sub_tick + sub_tick_width;
+        let tags = machine + alphabet.field_width();
+        let random = tags + self.tags.map_or(0, |tags| tags.len());
+        Offsets { timestamp, sub_tick, machine, tags, random, padding: random + alphabet.field_width() }
+    }
+
+    /// Encode a chronological identifier from its components into all of
+    /// `out`: the prefix, the fields and random padding
+    pub(crate) fn encode_into(
+        &self,
+        out: &mut [u8],
+        prefix: &str,
+        time: (u64, u16),
+        machine_id: u16,
+        random_value: u16,
+        entropy: &mut dyn EntropySource,
+    ) {
+        let padding_start = self.encode_fields(out, prefix, time, machine_id, random_value);
+
+        // Fill the rest with random padding
+        fill_random_chars(entropy, self.alphabet.chars(), &mut out[padding_start..]);
+    }
+
+    /// Encode the prefix, markers and fields, leaving the padding
+    /// untouched, and return where the padding starts
+    ///
+    /// `time` is a packed timestamp and the microseconds after it.
+    fn encode_fields(&self, out: &mut [u8], prefix: &str, time: (u64, u16), machine_id: u16, random_value: u16) -> usize {
+        let offsets = self.offsets();
+        let chars = self.alphabet.chars();
+        let start = write_prefix(out, prefix);
+        let fields = &mut out[start..];
+        fields[0] = self.format.marker().expect("fixed-width layouts have a marker") as u8;
+        if offsets.timestamp > 1 {
+            fields[1] = match self.tags {
+                Some(_) => self.precision.marker().to_ascii_uppercase(),
+                None => self.precision.marker(),
+            };
+        }
+        let (timestamp, sub_tick) = match (self.format, self.precision) {
+            (Format::V4, precision) => (epoch_micros(time) / precision.unit_micros(), 0),
+            (Format::V3, Precision::Seconds) => (time.0 / 100, 0),
+            (_, precision) => (time.0, time.1 / precision.sub_tick().0),
+        };
+        encode_fixed(chars, timestamp, &mut fields[offsets.timestamp..offsets.sub_tick]);
+        encode_fixed(chars, sub_tick as u64, &mut fields[offsets.sub_tick..offsets.machine]);
+        encode_fixed(chars, machine_id as u64, &mut fields[offsets.machine..offsets.tags]);
+        if let Some(tags) = self.tags {
+            fields[offsets.tags..offsets.random].copy_from_slice(&tags);
+        }
+        encode_fixed(chars, random_value as u64, &mut fields[offsets.random..offsets.padding]);
+        start + offsets.padding
+    }
+
+    /// Decode the `(timestamp, micros, machine_id, random)` fields from the
+    /// encoded fields of an identifier, whose markers must match this layout
+    ///
+    /// `timestamp` is packed, and `micros` are the microseconds after it.
+    /// Fails with [`BcidError::InvalidIdentifierLength`] if the fields are
+    /// too short for this layout.
+    pub(crate) fn decode(&self, fields: &str) -> Result<(u64, u16, u16, u16), BcidError> {
+        let offsets = self.offsets();
+        if fields.len() < offsets.padding {
+            return Err(BcidError::InvalidIdentifierLength(fields.len()));
+        }
+        let alphabet = self.alphabet;
+        let timestamp = decode_field(fields, alphabet, offsets.timestamp, offsets.sub_tick)?;
+        let sub_tick = decode_field(fields, alphabet, offsets.sub_tick, offsets.machine)?;
+        let machine_id = decode_field(fields, alphabet, offsets.machine, offsets.tags)?;
+        let random = decode_field(fields, alphabet, offsets.random, offsets.padding)?;
+
+        let (step, max) = self.precision.sub_tick();
+        let (timestamp, micros) = match (self.format, self.precision) {
+            (Format::V4, precision) => timestamp
+                .checked_mul(precision.unit_micros())
+                .filter(|&micros| micros <= MAX_EPOCH_MICROS)
+                .map(from_epoch_micros)
+                .ok_or(BcidError::ValueOutOfRange { field: "timestamp", value: timestamp })?,
+            (Format::V3, Precision::Seconds) => (timestamp.saturating_mul(100), 0),
+            _ if sub_tick > max as u64 => return Err(BcidError::ValueOutOfRange { field: "timestamp", value: sub_tick }),
+            _ => (timestamp, sub_tick as u16 * step),
+        };
+        if unpack_timestamp(timestamp).is_none() {
+            return Err(BcidError::InvalidTimestamp(timestamp));
+        }
+        let machine_id = u16::try_from(machine_id)
+            .map_err(|_| BcidError::ValueOutOfRange { field: "machine ID", value: machine_id })?;
+        let random = u16::try_from(random)
+            .map_err(|_| BcidError::ValueOutOfRange { field: "random value", value: random })?;
+        Ok((timestamp, micros, machine_id, random))
+    }
+}
+
+/// Number of marker characters at the start of the fields of an identifier
+/// whose first field character is `marker`: the version digit, the
+/// precision of v3 and v4 identifiers, and the layout of v5 identifiers
+pub(crate) fn markers_len(marker: u8) -> usize {
+    match marker {
+        V3_MARKER | V4_MARKER => 2,
+        V5_MARKER => 4,
+        _ => 1,
+    }
+}
+
+/// Characters of the tags: the environment, then the shard
+pub(crate) const TAGS_WIDTH: usize = 3;
+
+/// Encode the tags of a v3 or v4 identifier in `alphabet`, checking that a
+/// shard is 1 or 2 of its digits, not starting with its zero digit
+///
+/// An absent environment or shard is written as zero digits, and a
+/// 1-character shard is padded with one.
+#[cfg(feature = "std")]
+pub(crate) fn encode_tags(
+    environment: Option<Environment>,
+    shard: Option<&str>,
+    alphabet: Alphabet,
+) -> Result<[u8; TAGS_WIDTH], BcidError> {
+    let chars = alphabet.chars();
+    let mut tags = [chars[0]; TAGS_WIDTH];
+    if let Some(environment) = environment {
+        tags[0] = environment.marker(alphabet);
+    }
+    if let Some(shard) = shard {
+        let bytes = shard.as_bytes();
+        if !(1..TAGS_WIDTH).contains(&bytes.len()) || bytes[0] == chars[0] || !bytes.iter().all(|byte| chars.contains(byte)) {
+            return Err(BcidError::InvalidShard(format!(
+                "{:?} is not 1 or 2 {} digits without a leading {}",
+                shard, alphabet, chars[0] as char
+            )));
+        }
+        tags[TAGS_WIDTH - bytes.len()..].copy_from_slice(bytes);
+    }
+    Ok(tags)
+}
+
+/// Largest packed timestamp in whole seconds (`YYYYMMDDHHmmSS`)
+const MAX_SECONDS: u64 = 99999999999999;
+
+/// Microseconds from the v4 epoch (0000-01-01) to the end of year 9999
+const MAX_EPOCH_MICROS: u64 = 315_569_519_999_999_999;
+
+/// The start of year 0, from which v4 identifiers count time
+fn epoch() -> DateTime<Utc> {
+    NaiveDate::from_ymd_opt(0, 1, 1).expect("valid date").and_time(NaiveTime::MIN).and_utc()
+}
+
+/// Microseconds since the v4 epoch of a packed timestamp and the
+/// microseconds after it
+fn epoch_micros((timestamp, micros): (u64, u16)) -> u64 {
+    let datetime = unpack_timestamp(timestamp).unwrap_or_else(epoch);
+    let since = (datetime - epoch()).num_microseconds().unwrap_or_default();
+    u64::try_from(since).unwrap_or_default() + micros as u64
+}
+
+/// The packed timestamp and the microseconds after it of a time since the
+/// v4 epoch
+fn from_epoch_micros(micros: u64) -> (u64, u16) {
+    let datetime = epoch() + chrono::Duration::microseconds(micros as i64);
+    Precision::Microseconds.pack(&datetime)
+}
+
+/// Decode the fixed-width field from `start` to `end`
+fn decode_field(fields: &str, alphabet: Alphabet, start: usize, end: usize) -> Result<u64, BcidError> {
+    decode_with(alphabet.digits(), alphabet.radix(), &fields[start..end]).map_err(|e| e.offset(start))
+}
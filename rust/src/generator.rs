@@ -0,0 +1,954 @@
+use std::thread;
+use std::time::Duration;
+
+use rand::rngs::OsRng;
+
+use crate::entropy::{random_value, BufferedEntropy};
+use crate::format::{encode_tags, FixedLayout};
+use crate::layout::unix_millis;
+use crate::monotonic::{next_monotonic, next_sequence};
+use crate::safe::has_avoidable_word;
+use crate::{
+    encode_chronological_into, encode_random_into, fields_start, identifier_string, validate_length, validate_prefix,
+    Alphabet, Bcid, BcidError, BitLayout, Clock, EntropySource, Environment, Format, Precision, Prefix, SystemClock, DEFAULT_LENGTH,
+    MAX_ENCODED_LENGTH, MIN_FIELDS_LENGTH,
+};
+
+/// Identifiers generated between clock reads in [`BcidGenerator::next_batch`]
+const CLOCK_READ_INTERVAL: usize = 256;
+
+/// How often [`ClockRegression::Wait`] rereads the clock
+const CLOCK_WAIT_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Most draws of one identifier in [`BcidGenerator::safe`] mode
+const MAX_SAFE_DRAWS: usize = 16;
+
+/// What a [`BcidGenerator`] does when its clock reads earlier than it did
+/// before, e.g. after NTP steps the system clock back
+///
+/// ```
+/// use bcid::{BcidError, BcidGenerator, ClockRegression, MockClock};
+///
+/// let clock = MockClock::new(bcid::parse_user_datetime("2024-01-02")?);
+/// let mut generator = BcidGenerator::new("TEST", 1)?
+///     .clock_regression(ClockRegression::Error)
+///     .clock(clock.clone());
+/// generator.try_next()?;
+/// clock.set(bcid::parse_user_datetime("2024-01-01")?);
+/// assert!(matches!(generator.try_next(), Err(BcidError::ClockMovedBackwards { .. })));
+/// # Ok::<(), bcid::BcidError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ClockRegression {
+    /// Use the earlier time, so new identifiers sort before ones already
+    /// issued (in [`monotonic`](BcidGenerator::monotonic) mode the last
+    /// timestamp is reused instead)
+    #[default]
+    Tolerate,
+    /// Sleep until the clock catches up with its latest reading, as
+    /// Snowflake generators do; a clock stepped back by hours blocks for
+    /// hours
+    Wait,
+    /// Fail with [`BcidError::ClockMovedBackwards`]
+    Error,
+}
+
+/// The sequence state of a [`BcidGenerator`], to save before a process
+/// exits and [`resume`](BcidGenerator::resume) after it restarts
+///
+/// A resumed monotonic generator continues after the last identifier issued
+/// before the restart, even if the clock now reads earlier, and the
+/// [`ClockRegression`] policy compares the clock with its reading from before
+/// the restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct GeneratorState {
+    /// Packed timestamp and random value of the last identifier issued in
+    /// monotonic mode
+    pub last_issued: Option<(u64, u16)>,
+    /// Microseconds after the timestamp of `last_issued`, at a
+    /// [`Precision`] finer than 10ms
+    pub last_issued_micros: u16,
+    /// Latest packed timestamp read from the clock
+    pub latest_reading: u64,
+}
+
+impl ClockRegression {
+    /// Name used on the command line: `tolerate`, `wait` or `error`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClockRegression::Tolerate => "tolerate",
+            ClockRegression::Wait => "wait",
+            ClockRegression::Error => "error",
+        }
+    }
+
+    /// The policy called `name` (see [`ClockRegression::name`])
+    pub fn from_name(name: &str) -> Option<ClockRegression> {
+        match name {
+            "tolerate" => Some(ClockRegression::Tolerate),
+            "wait" => Some(ClockRegression::Wait),
+            "error" => Some(ClockRegression::Error),
+            _ => None,
+        }
+    }
+}
+
+/// What a [`BcidGenerator`] does when a [`BitLayout`] with sequence bits has
+/// used up the sequence of the current millisecond
+///
+/// ```
+/// use bcid::{BcidError, BcidGenerator, MockClock, SequenceOverflow};
+///
+/// let clock = MockClock::new(bcid::parse_user_datetime("2024-01-01")?);
+/// let mut generator = BcidGenerator::new("TEST", 0)?
+///     .bit_layout("ts=44,seq=1".parse()?)?
+///     .sequence_overflow(SequenceOverflow::Error)
+///     .clock(clock);
+/// assert_eq!(generator.try_next()?.sequence(), Some(0));
+/// assert_eq!(generator.try_next()?.sequence(), Some(1));
+/// assert!(matches!(generator.try_next(), Err(BcidError::SequenceOverflow { .. })));
+/// # Ok::<(), bcid::BcidError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SequenceOverflow {
+    /// Spin until the clock reaches the next millisecond, as Snowflake
+    /// generators do; a clock that never advances (e.g. a
+    /// [`MockClock`](crate::MockClock)) blocks forever
+    #[default]
+    Spin,
+    /// Take the next millisecond before the clock reaches it, so timestamps
+    /// can run ahead of the clock while bursts last
+    Borrow,
+    /// Fail with [`BcidError::SequenceOverflow`]
+    Error,
+}
+
+impl SequenceOverflow {
+    /// Name used on the command line: `spin`, `borrow` or `error`
+    pub fn name(&self) -> &'static str {
+        match self {
+            SequenceOverflow::Spin => "spin",
+            SequenceOverflow::Borrow => "borrow",
+            SequenceOverflow::Error => "error",
+        }
+    }
+
+    /// The policy called `name` (see [`SequenceOverflow::name`])
+    pub fn from_name(name: &str) -> Option<SequenceOverflow> {
+        match name {
+            "spin" => Some(SequenceOverflow::Spin),
+            "borrow" => Some(SequenceOverflow::Borrow),
+            "error" => Some(SequenceOverflow::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Generates identifiers with a fixed prefix and machine ID
+///
+/// The prefix is validated once, and the generator owns its clock, random
+/// source and monotonic sequence state. Randomness is drawn from the source
+/// in 1 KiB blocks rather than once per identifier. Configure it
+/// builder-style:
+///
+/// ```
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// let mut generator = bcid::BcidGenerator::new("TEST", 1)?.format(bcid::Format::V2).monotonic(true);
+/// let first = generator.next();
+/// let batch = generator.next_batch(100);
+/// assert!(batch.iter().all(|id| id.as_str() > first.as_str()));
+///
+/// // Generators are endless iterators
+/// let ids: Vec<String> = generator.by_ref().take(3).map(String::from).collect();
+/// assert_eq!(ids.len(), 3);
+///
+/// // A seeded RNG and a fixed clock give reproducible identifiers
+/// let at = bcid::parse_user_datetime("2024-01-01")?;
+/// let mut a = bcid::BcidGenerator::with_rng("TEST", 1, StdRng::seed_from_u64(42))?.clock(move || at);
+/// let mut b = bcid::BcidGenerator::with_rng("TEST", 1, StdRng::seed_from_u64(42))?.clock(move || at);
+/// assert_eq!(a.next(), b.next());
+/// # Ok::<(), bcid::BcidError>(())
+/// ```
+pub struct BcidGenerator {
+    prefix: String,
+    machine_id: u16,
+    format: Format,
+    alphabet: Alphabet,
+    precision: Precision,
+    /// Environment written after the machine ID
+    environment: Option<Environment>,
+    /// Shard written after the environment
+    shard: Option<String>,
+    /// Bit allocation of v5 identifiers
+    bit_layout: BitLayout,
+    /// Configured length, or `None` for [`DEFAULT_LENGTH`] unless the
+    /// fields need more
+    length: Option<usize>,
+    is_random: bool,
+    is_monotonic: bool,
+    /// Draw again identifiers whose random characters spell a denylisted word
+    is_safe: bool,
+    clock_regression: ClockRegression,
+    sequence_overflow: SequenceOverflow,
+    clock: Box<dyn Clock>,
+    entropy: BufferedEntropy,
+    /// Last time (packed timestamp and the microseconds after it) and
+    /// random value issued in monotonic mode
+    last_issued: Option<((u64, u16), u16)>,
+    /// Last millisecond and sequence number of a v5 layout with sequence
+    /// bits
+    last_sequence: Option<(u64, u32)>,
+    /// Latest packed timestamp read from the clock, to detect it going
+    /// backwards
+    latest_reading: u64,
+    clock_regressions: u64,
+    sequence_overflows: u64,
+    /// Components of the last chronological identifier, for
+    /// [`selftest`](crate::selftest) to compare with what decoding finds
+    drawn: Option<Drawn>,
+}
+
+/// The components a chronological identifier was encoded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Drawn {
+    /// Packed timestamp and the microseconds after it
+    pub(crate) time: (u64, u16),
+    /// Milliseconds since the Unix epoch of a v5 identifier, which can be
+    /// later than `time` once the sequence of a millisecond is used up
+    pub(crate) millis: Option<u64>,
+    pub(crate) random_value: u16,
+    /// Sequence number of a v5 layout with sequence bits
+    pub(crate) sequence: Option<u32>,
+}
+
+impl BcidGenerator {
+    /// A generator drawing randomness from the operating system
+    ///
+    /// Fails if the prefix is not 1 to 8 printable ASCII characters other
+    /// than `_`.
+    pub fn new(prefix: &str, machine_id: u16) -> Result<Self, BcidError> {
+        Self::with_rng(prefix, machine_id, OsRng)
+    }
+
+    /// A generator for a prefix checked at compile time, drawing randomness
+    /// from the operating system
+    pub fn from_prefix(prefix: Prefix, machine_id: u16) -> Self {
+        Self::new(prefix.as_str(), machine_id).expect("prefixes are valid")
+    }
+
+    /// A generator drawing randomness from `entropy`
+    ///
+    /// With a seeded RNG and a fixed [`clock`](Self::clock) the output is
+    /// reproducible, which suits test fixtures and golden files. Never use a
+    /// seeded RNG for identifiers that must be unguessable.
+    pub fn with_rng<E: EntropySource + Send + 'static>(prefix: &str, machine_id: u16, entropy: E) -> Result<Self, BcidError> {
+        validate_prefix(prefix)?;
+        Ok(BcidGenerator {
+            prefix: prefix.to_string(),
+            machine_id,
+            format: Format::V1,
+            alphabet: Alphabet::Base62,
+            precision: Precision::default(),
+            environment: None,
+            shard: None,
+            bit_layout: BitLayout::default(),
+            length: None,
+            is_random: false,
+            is_monotonic: false,
+            is_safe: false,
+            clock_regression: ClockRegression::default(),
+            sequence_overflow: SequenceOverflow::default(),
+            clock: Box::new(SystemClock),
+            entropy: BufferedEntropy::new(Box::new(entropy)),
+            last_issued: None,
+            last_sequence: None,
+            latest_reading: 0,
+            clock_regressions: 0,
+            sequence_overflows: 0,
+            drawn: None,
+        })
+    }
+
+    /// Use `format` for chronological identifiers (default [`Format::V1`])
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Encode chronological identifiers with `alphabet` (default
+    /// [`Alphabet::Base62`])
+    ///
+    /// Alphabets other than base62 always use the [`Format::V2`] layout, and
+    /// random identifiers are always base62.
+    pub fn alphabet(mut self, alphabet: Alphabet) -> Self {
+        self.alphabet = alphabet;
+        self
+    }
+
+    /// Record timestamps at `precision` (default 10ms)
+    ///
+    /// Any other precision uses the [`Format::V3`] layout unless the
+    /// configured [`format`](Self::format) is [`Format::V4`]. Its timestamp field is wider at
+    /// millisecond and microsecond precision, so with Crockford base32 the
+    /// identifiers must be longer than [`MIN_LENGTH`](crate::MIN_LENGTH)
+    /// (see [`BcidError::LayoutDoesNotFit`]).
+    ///
+    /// ```
+    /// let mut generator = bcid::BcidGenerator::new("TEST", 1)?.precision(bcid::Precision::Microseconds);
+    /// let id = generator.next();
+    /// assert_eq!(id.format(), bcid::Format::V3);
+    /// assert_eq!(id.precision(), Some(bcid::Precision::Microseconds));
+    /// # Ok::<(), bcid::BcidError>(())
+    /// ```
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Record `environment` after the machine ID of chronological
+    /// identifiers, using the [`Format::V3`] layout unless the configured
+    /// [`format`](Self::format) is [`Format::V4`] (see [`Environment`])
+    ///
+    /// The environment shares 3 characters with the
+    /// [`shard`](Self::shard), so the random value starts later and the
+    /// padding is shorter. Generating fails with [`BcidError::InvalidShard`]
+    /// if the format is [`Format::V5`].
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// Write `shard` (e.g. a region such as `e1`) after the machine ID of
+    /// chronological identifiers, using the [`Format::V3`] layout unless the
+    /// configured [`format`](Self::format) is [`Format::V4`]
+    ///
+    /// The shard shares 3 characters with the
+    /// [`environment`](Self::environment), so the random value starts later
+    /// and the padding is shorter. Generating fails with
+    /// [`BcidError::InvalidShard`] unless the shard is 1 or 2 digits of the
+    /// [`alphabet`](Self::alphabet) not starting with its zero digit (a
+    /// 1-character shard is padded with it), or if the format is
+    /// [`Format::V5`].
+    ///
+    /// ```
+    /// let mut generator = bcid::BcidGenerator::new("TEST", 1)?.shard("e1");
+    /// let id = generator.next();
+    /// assert_eq!(id.format(), bcid::Format::V3);
+    /// assert_eq!(id.shard(), Some("e1"));
+    /// assert_eq!(bcid::Bcid::parse(id.as_str())?.shard(), Some("e1"));
+    /// # Ok::<(), bcid::BcidError>(())
+    /// ```
+    pub fn shard(mut self, shard: &str) -> Self {
+        self.shard = Some(shard.to_string());
+        self
+    }
+
+    /// Split the 128 bits of identifiers between timestamp, machine ID,
+    /// sequence and random value as `layout` says, using the
+    /// [`Format::V5`] layout
+    ///
+    /// With sequence bits, identifiers within the same millisecond are
+    /// numbered from 0, so one generator never repeats a `(timestamp,
+    /// sequence)` pair; once the sequence is used up, the
+    /// [`sequence_overflow`](Self::sequence_overflow) policy applies. v5
+    /// identifiers have millisecond
+    /// [`precision`](Self::precision) whatever is configured. Fails with
+    /// [`BcidError::ValueOutOfRange`] if the machine ID does not fit the
+    /// layout.
+    ///
+    /// ```
+    /// let layout = "ts=44,machine=12,seq=10".parse()?;
+    /// let mut generator = bcid::BcidGenerator::new("TEST", 1)?.bit_layout(layout)?;
+    /// let (first, second) = (generator.next(), generator.next());
+    /// assert_eq!(first.format(), bcid::Format::V5);
+    /// assert_eq!(first.bit_layout(), Some(layout));
+    /// assert!(first < second);
+    /// assert!(bcid::BcidGenerator::new("TEST", 4096)?.bit_layout(layout).is_err());
+    /// # Ok::<(), bcid::BcidError>(())
+    /// ```
+    pub fn bit_layout(mut self, layout: BitLayout) -> Result<Self, BcidError> {
+        if self.machine_id > layout.max_machine_id() {
+            return Err(BcidError::ValueOutOfRange { field: "machine ID", value: self.machine_id as u64 });
+        }
+        self.format = Format::V5;
+        self.bit_layout = layout;
+        Ok(self)
+    }
+
+    /// Generate identifiers of `length` characters (default
+    /// [`DEFAULT_LENGTH`], or as many as the fields need, e.g. those of v5
+    /// identifiers after a prefix of 6 to 8 characters), prefix and separator
+    /// included;
+    /// the random
+    /// padding, or the random data of random identifiers, grows or shrinks
+    /// to fit
+    ///
+    /// Fails if `length` is not between [`MIN_LENGTH`](crate::MIN_LENGTH)
+    /// and [`MAX_LENGTH`](crate::MAX_LENGTH). Prefixes longer than 4
+    /// characters need longer identifiers (see
+    /// [`min_length`](crate::min_length)), and generating fails with
+    /// [`BcidError::LayoutDoesNotFit`] if `length` is shorter.
+    pub fn length(mut self, length: usize) -> Result<Self, BcidError> {
+        validate_length(length)?;
+        self.length = Some(length);
+        Ok(self)
+    }
+
+    /// Generate fully random identifiers (see [`generate_random_identifier`](crate::generate_random_identifier))
+    pub fn random(mut self, is_random: bool) -> Self {
+        self.is_random = is_random;
+        self
+    }
+
+    /// Make each identifier greater than the previous one from this generator
+    ///
+    /// Within the same 10ms window (or step of a finer
+    /// [`precision`](Self::precision)) the previous random value is incremented,
+    /// as with [`generate_monotonic_identifier`](crate::generate_monotonic_identifier),
+    /// but the sequence is per generator rather than per process.
+    pub fn monotonic(mut self, is_monotonic: bool) -> Self {
+        self.is_monotonic = is_monotonic;
+        self
+    }
+
+    /// Never put a denylisted word in the random characters of an identifier
+    /// (see [`is_safe_identifier`](crate::is_safe_identifier))
+    ///
+    /// An identifier whose random value or padding would spell one is
+    /// generated again with new random values, keeping its timestamp. Words
+    /// entirely within the prefix, timestamp and machine ID are let through,
+    /// as are words that a check digit or signature added later happens to
+    /// form.
+    pub fn safe(mut self, is_safe: bool) -> Self {
+        self.is_safe = is_safe;
+        self
+    }
+
+    /// What to do when the clock reads earlier than it did before (default
+    /// [`ClockRegression::Tolerate`])
+    pub fn clock_regression(mut self, clock_regression: ClockRegression) -> Self {
+        self.clock_regression = clock_regression;
+        self
+    }
+
+    /// What to do when the sequence of a millisecond is used up (default
+    /// [`SequenceOverflow::Spin`])
+    pub fn sequence_overflow(mut self, sequence_overflow: SequenceOverflow) -> Self {
+        self.sequence_overflow = sequence_overflow;
+        self
+    }
+
+    /// Continue from `state`, saved with [`state`](Self::state) by an
+    /// earlier generator
+    pub fn resume(mut self, state: GeneratorState) -> Self {
+        self.last_issued = state
+            .last_issued
+            .map(|(timestamp, random)| ((timestamp, state.last_issued_micros), random));
+        self.latest_reading = state.latest_reading;
+        self
+    }
+
+    /// The sequence state to save for [`resume`](Self::resume)
+    pub fn state(&self) -> GeneratorState {
+        GeneratorState {
+            last_issued: self.last_issued.map(|((timestamp, _), random)| (timestamp, random)),
+            last_issued_micros: self.last_issued.map_or(0, |((_, micros), _)| micros),
+            latest_reading: self.latest_reading,
+        }
+    }
+
+    /// Times this generator read the clock earlier than its latest reading,
+    /// whatever the [`ClockRegression`] policy then did
+    pub fn clock_regressions(&self) -> u64 {
+        self.clock_regressions
+    }
+
+    /// Times this generator ran out of values for a time: the random values
+    /// of a step in monotonic mode, or the sequence of a millisecond,
+    /// whatever the [`SequenceOverflow`] policy then did
+    pub fn sequence_overflows(&self) -> u64 {
+        self.sequence_overflows
+    }
+
+    /// Read the time from `clock` (e.g. a [`MockClock`](crate::MockClock)
+    /// or a closure) instead of the system clock
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// The configured prefix
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// The configured machine ID
+    pub fn machine_id(&self) -> u16 {
+        self.machine_id
+    }
+
+    /// Generate the next identifier
+    ///
+    /// Panics if the clock went backwards with [`ClockRegression::Error`],
+    /// the sequence of a millisecond is used up with
+    /// [`SequenceOverflow::Error`], or the fields do not fit the configured
+    /// length; use
+    /// [`try_next`](Self::try_next) to handle that.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Bcid {
+        self.try_next().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Generate the next identifier, failing with
+    /// [`BcidError::ClockMovedBackwards`] if the clock went backwards with
+    /// [`ClockRegression::Error`], with [`BcidError::SequenceOverflow`] if
+    /// the sequence of a millisecond is used up with
+    /// [`SequenceOverflow::Error`], and with [`BcidError::LayoutDoesNotFit`]
+    /// if the fields do not fit the configured length
+    pub fn try_next(&mut self) -> Result<Bcid, BcidError> {
+        let (layout, length) = self.layout()?;
+        let now = self.read_clock()?;
+        self.next_at(layout, length, now)
+    }
+
+    /// Generate `count` identifiers
+    ///
+    /// For bulk use: the clock is read once every 256 identifiers instead of
+    /// for each one, so timestamps within a batch can lag the clock by the
+    /// time it takes to generate 256 identifiers (microseconds). Panics like
+    /// [`next`](Self::next).
+    pub fn next_batch(&mut self, count: usize) -> Vec<Bcid> {
+        self.try_next_batch(count).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// [`next_batch`](Self::next_batch), failing like
+    /// [`try_next`](Self::try_next)
+    pub fn try_next_batch(&mut self, count: usize) -> Result<Vec<Bcid>, BcidError> {
+        let (layout, length) = self.layout()?;
+        let mut now = (0, 0);
+        (0..count)
+            .map(|i| {
+                if i % CLOCK_READ_INTERVAL == 0 {
+                    now = self.read_clock()?;
+                }
+                self.next_at(layout, length, now)
+            })
+            .collect()
+    }
+
+    /// The layout of chronological identifiers (`None` for v1 and v5) and
+    /// the length of identifiers, failing if the fields do not fit the
+    /// configured length or the shard is invalid
+    fn layout(&self) -> Result<(Option<FixedLayout>, usize), BcidError> {
+        let format = match self.format {
+            Format::V1 | Format::V2 if self.precision != Precision::default() || self.has_tags() => Format::V3,
+            format => format,
+        };
+        let tags = match format {
+            _ if self.is_random || !self.has_tags() => None,
+            Format::V5 => {
+                return Err(BcidError::InvalidShard("v5 identifiers have no shard or environment field".to_string()))
+            }
+            _ => Some(encode_tags(self.environment, self.shard.as_deref(), self.alphabet)?),
+        };
+        let layout = FixedLayout::of(format, self.alphabet, self.precision)
+            .filter(|_| !self.is_random)
+            .map(|layout| layout.with_tags(tags));
+        let fields_len = match layout {
+            _ if self.is_random => MIN_FIELDS_LENGTH,
+            Some(layout) => layout.fields_len(),
+            None if format == Format::V5 => BitLayout::fields_len(self.alphabet),
+            None => MIN_FIELDS_LENGTH,
+        };
+        let needed = fields_start(self.prefix.len()) + fields_len.max(MIN_FIELDS_LENGTH);
+        match self.length {
+            Some(length) if needed > length => Err(BcidError::LayoutDoesNotFit { needed, length }),
+            Some(length) => Ok((layout, length)),
+            None => Ok((layout, DEFAULT_LENGTH.max(needed))),
+        }
+    }
+
+    /// Whether identifiers record an environment or shard after the machine
+    /// ID
+    fn has_tags(&self) -> bool {
+        self.environment.is_some() || self.shard.is_some()
+    }
+
+    /// Precision of the timestamps of generated identifiers: milliseconds
+    /// for v5
+    fn clock_precision(&self) -> Precision {
+        match self.format {
+            Format::V5 => Precision::Milliseconds,
+            _ => self.precision,
+        }
+    }
+
+    /// Components of the last chronological identifier generated
+    pub(crate) fn drawn(&self) -> Option<Drawn> {
+        self.drawn
+    }
+
+    /// Packed timestamp and the microseconds after it for the current time,
+    /// unless generating random identifiers, applying the clock regression
+    /// policy
+    fn read_clock(&mut self) -> Result<(u64, u16), BcidError> {
+        if self.is_random {
+            return Ok((0, 0));
+        }
+        let precision = self.clock_precision();
+        let mut now = precision.pack(&self.clock.now());
+        if now.0 < self.latest_reading {
+            self.clock_regressions += 1;
+        }
+        while now.0 < self.latest_reading {
+            match self.clock_regression {
+                ClockRegression::Tolerate => break,
+                ClockRegression::Wait => {
+                    thread::sleep(CLOCK_WAIT_INTERVAL);
+                    now = precision.pack(&self.clock.now());
+                }
+                ClockRegression::Error => {
+                    return Err(BcidError::ClockMovedBackwards { latest: self.latest_reading, now: now.0 })
+                }
+            }
+        }
+        self.latest_reading = self.latest_reading.max(now.0);
+        Ok(now)
+    }
+
+    /// Generate an identifier of `length` characters with `layout` for the
+    /// time `now`, drawing it again while it spells a denylisted word in
+    /// safe mode
+    fn next_at(&mut self, layout: Option<FixedLayout>, length: usize, now: (u64, u16)) -> Result<Bcid, BcidError> {
+        let mut generated = self.draw_at(layout, length, now)?;
+        // Bounded, for lengths whose random characters are too few to avoid every word
+        for _ in 0..MAX_SAFE_DRAWS {
+            if !self.is_safe || !has_avoidable_word(&generated) {
+                break;
+            }
+            generated = self.draw_at(layout, length, now)?;
+        }
+        Ok(generated)
+    }
+
+    /// Generate an identifier of `length` characters with `layout` for the
+    /// time `now`, failing if the time does not fit the timestamp of a v5
+    /// layout
+    fn draw_at(&mut self, layout: Option<FixedLayout>, length: usize, now: (u64, u16)) -> Result<Bcid, BcidError> {
+        let precision = self.clock_precision();
+        let entropy = &mut self.entropy;
+        let mut out = [0u8; MAX_ENCODED_LENGTH];
+        let out = &mut out[..length];
+        if self.is_random {
+            self.drawn = None;
+            encode_random_into(out, &self.prefix, self.machine_id, entropy);
+            return Ok(Bcid::try_from(identifier_string(out)).expect("generated identifiers are valid"));
+        }
+        let (time, random_value) = if self.is_monotonic {
+            if matches!(self.last_issued, Some((last_time, u16::MAX)) if now <= last_time) {
+                self.sequence_overflows += 1;
+            }
+            next_monotonic(now, &mut self.last_issued, precision, entropy)
+        } else {
+            (now, random_value(entropy))
+        };
+        let mut drawn = Drawn { time, millis: None, random_value, sequence: None };
+        match layout {
+            Some(layout) => layout.encode_into(out, &self.prefix, time, self.machine_id, random_value, entropy),
+            None if self.format == Format::V5 => {
+                let bits = self.bit_layout;
+                let millis = unix_millis(time).ok_or(BcidError::ValueOutOfRange { field: "timestamp", value: time.0 })?;
+                let (millis, sequence) = match bits.sequence_bits() {
+                    0 => (millis, 0),
+                    _ => self.sequence_at(time.0, millis)?,
+                };
+                let entropy = &mut self.entropy;
+                let value = bits.pack(millis, self.machine_id, sequence, random_value, entropy)?;
+                bits.encode_into(out, self.alphabet, &self.prefix, value, entropy);
+                drawn.millis = Some(millis);
+                drawn.sequence = Some(sequence).filter(|_| bits.sequence_bits() > 0);
+            }
+            None => encode_chronological_into(out, Format::V1, &self.prefix, time.0, self.machine_id, random_value, entropy),
+        }
+        self.drawn = Some(drawn);
+        Ok(Bcid::parse_with(&identifier_string(out), self.alphabet).expect("generated identifiers are valid"))
+    }
+
+    /// The millisecond and sequence number of the next v5 identifier at
+    /// `millis` or later, applying the sequence overflow policy
+    ///
+    /// `timestamp` is the packed form of `millis`, for errors.
+    fn sequence_at(&mut self, timestamp: u64, mut millis: u64) -> Result<(u64, u32), BcidError> {
+        let max = self.bit_layout.max_sequence();
+        let mut has_overflowed = false;
+        loop {
+            if let Some(next) = next_sequence(millis, &mut self.last_sequence, max) {
+                return Ok(next);
+            }
+            if !has_overflowed {
+                self.sequence_overflows += 1;
+                has_overflowed = true;
+            }
+            let last_millis = self.last_sequence.map_or(millis, |(last_millis, _)| last_millis);
+            match self.sequence_overflow {
+                SequenceOverflow::Spin => {
+                    std::hint::spin_loop();
+                    let now = self.read_clock()?;
+                    millis = unix_millis(now).unwrap_or(millis);
+                }
+                SequenceOverflow::Borrow => millis = last_millis + 1,
+                SequenceOverflow::Error => return Err(BcidError::SequenceOverflow { timestamp, max }),
+            }
+        }
+    }
+}
+
+/// An endless sequence of identifiers, e.g. `generator.by_ref().take(n)`
+impl Iterator for BcidGenerator {
+    type Item = Bcid;
+
+    fn next(&mut self) -> Option<Bcid> {
+        Some(BcidGenerator::next(self))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+/// An endless stream of identifiers, built with the `async` feature
+///
+/// Every poll is ready with the next identifier, so the stream never
+/// yields to the executor by itself. Like [`Iterator::next`] it panics where
+/// [`BcidGenerator::next`] does, and [`ClockRegression::Wait`] and
+/// [`SequenceOverflow::Spin`] block the executor thread while they wait.
+///
+/// ```
+/// use std::pin::pin;
+/// use std::task::{Context, Poll, Waker};
+///
+/// use futures_core::Stream;
+///
+/// let mut stream = pin!(bcid::BcidGenerator::new("TEST", 1)?);
+/// let mut cx = Context::from_waker(Waker::noop());
+/// let Poll::Ready(Some(id)) = stream.as_mut().poll_next(&mut cx) else { unreachable!() };
+/// assert_eq!(id.prefix(), "TEST");
+/// # Ok::<(), bcid::BcidError>(())
+/// ```
+#[cfg(feature = "async")]
+impl futures_core::Stream for BcidGenerator {
+    type Item = Bcid;
+
+    fn poll_next(self: std::pin::Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<Bcid>> {
+        std::task::Poll::Ready(Some(BcidGenerator::next(self.get_mut())))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::MockClock;
+
+    const FORMATS: [Format; 5] = [Format::V1, Format::V2, Format::V3, Format::V4, Format::V5];
+    const ALPHABETS: [Alphabet; 4] = [Alphabet::Base62, Alphabet::Crockford32, Alphabet::Base58, Alphabet::NoVowels];
+    const PRECISIONS: [Precision; 4] = [Precision::Seconds, Precision::Centiseconds, Precision::Milliseconds, Precision::Microseconds];
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 6, 7, 8, 9).unwrap()
+    }
+
+    fn generator(machine_id: u16) -> BcidGenerator {
+        BcidGenerator::with_rng("TEST", machine_id, StdRng::seed_from_u64(7)).unwrap()
+    }
+
+    #[test]
+    fn clock_regression_error_fails_until_the_clock_catches_up() {
+        let clock = MockClock::new(start());
+        let mut generator = generator(1).clock_regression(ClockRegression::Error).clock(clock.clone());
+        generator.try_next().unwrap();
+        clock.advance(Duration::seconds(-5));
+        assert!(matches!(generator.try_next(), Err(BcidError::ClockMovedBackwards { latest, now }) if now < latest));
+        clock.advance(Duration::seconds(5));
+        assert_eq!(generator.try_next().unwrap().datetime(), Some(start()));
+    }
+
+    #[test]
+    fn clock_regression_tolerate_keeps_monotonic_identifiers_increasing() {
+        let clock = MockClock::new(start());
+        let mut generator = generator(1).format(Format::V2).monotonic(true).clock(clock.clone());
+        let first = generator.next();
+        clock.advance(Duration::seconds(-5));
+        let second = generator.next();
+        assert!(second.as_str() > first.as_str());
+        assert_eq!(second.timestamp(), first.timestamp());
+        assert_eq!(generator.clock_regressions(), 1);
+    }
+
+    #[test]
+    fn clock_regression_tolerate_uses_the_earlier_time() {
+        let clock = MockClock::new(start());
+        let mut generator = generator(1).format(Format::V2).clock(clock.clone());
+        generator.next();
+        clock.advance(Duration::seconds(-5));
+        assert_eq!(generator.next().datetime(), Some(start() - Duration::seconds(5)));
+    }
+
+    #[test]
+    fn clock_regression_wait_rereads_the_clock() {
+        let clock = MockClock::stepping(start(), Duration::seconds(1));
+        let mut generator = generator(1).format(Format::V2).clock_regression(ClockRegression::Wait).clock(clock.clone());
+        generator.next();
+        clock.set(start() - Duration::seconds(3));
+        assert!(generator.next().datetime() >= Some(start()));
+    }
+
+    #[test]
+    fn clock_regression_survives_a_resume() {
+        let clock = MockClock::new(start());
+        let mut generator = generator(1).clock_regression(ClockRegression::Error).clock(clock.clone());
+        generator.next();
+        let state = generator.state();
+        clock.advance(Duration::seconds(-1));
+        let mut resumed = self::generator(1).clock_regression(ClockRegression::Error).clock(clock).resume(state);
+        assert!(matches!(resumed.try_next(), Err(BcidError::ClockMovedBackwards { .. })));
+    }
+
+    #[test]
+    fn sequence_overflow_error_fails_within_the_millisecond() {
+        let clock = MockClock::new(start());
+        let mut generator = generator(0)
+            .bit_layout("ts=44,seq=1".parse().unwrap())
+            .unwrap()
+            .sequence_overflow(SequenceOverflow::Error)
+            .clock(clock.clone());
+        assert_eq!(generator.next().sequence(), Some(0));
+        assert_eq!(generator.next().sequence(), Some(1));
+        assert!(matches!(generator.try_next(), Err(BcidError::SequenceOverflow { max: 1, .. })));
+        clock.advance(Duration::milliseconds(1));
+        assert_eq!(generator.next().sequence(), Some(0));
+    }
+
+    #[test]
+    fn sequence_overflow_borrow_runs_ahead_of_the_clock() {
+        let clock = MockClock::new(start());
+        let mut generator = generator(0)
+            .bit_layout("ts=44,seq=1".parse().unwrap())
+            .unwrap()
+            .sequence_overflow(SequenceOverflow::Borrow)
+            .clock(clock);
+        let ids = generator.next_batch(6);
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(id.sequence(), Some(i as u32 % 2));
+            assert_eq!(id.datetime(), Some(start() + Duration::milliseconds(i as i64 / 2)));
+        }
+        assert!(ids.windows(2).all(|pair| pair[0].as_str() < pair[1].as_str()));
+        assert_eq!(generator.sequence_overflows(), 2);
+    }
+
+    #[test]
+    fn sequence_overflow_spin_waits_for_the_next_millisecond() {
+        let clock = MockClock::stepping(start(), Duration::microseconds(250));
+        let mut generator = generator(0).bit_layout("ts=44,seq=1".parse().unwrap()).unwrap().clock(clock);
+        let ids = generator.next_batch(3);
+        assert_eq!(ids[2].sequence(), Some(0));
+        assert!(ids[2].datetime() > ids[1].datetime());
+        assert_eq!(generator.sequence_overflows(), 1);
+    }
+
+    #[test]
+    fn monotonic_identifiers_count_the_steps_that_run_out_of_random_values() {
+        let mut generator = generator(1).monotonic(true).clock(MockClock::new(start()));
+        // A step starts below 32768 random values, so two steps hold more than this
+        let ids = generator.next_batch(1 << 16);
+        assert_eq!(generator.sequence_overflows(), 1);
+        assert_eq!(ids.last().unwrap().datetime(), Some(start() + Duration::milliseconds(10)));
+        assert_eq!(generator.clock_regressions(), 0);
+    }
+
+    #[test]
+    fn chronological_identifiers_round_trip() {
+        for format in FORMATS {
+            for alphabet in ALPHABETS {
+                for precision in PRECISIONS {
+                    let mut generator = generator(513)
+                        .format(format)
+                        .alphabet(alphabet)
+                        .precision(precision)
+                        .length(40)
+                        .unwrap()
+                        .clock(MockClock::new(start()));
+                    let id = generator.try_next().unwrap();
+                    let parsed = Bcid::parse_with(id.as_str(), alphabet).unwrap();
+                    assert_eq!(parsed, id, "{format:?} {alphabet:?} {precision:?}");
+                    assert_eq!(parsed.as_str().len(), 40);
+                    assert_eq!(parsed.machine_id(), 513);
+                    assert_eq!(parsed.datetime(), Some(start()), "{}", id);
+                    assert_eq!(parsed.random(), generator.drawn.map(|drawn| drawn.random_value));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_identifiers_round_trip() {
+        let mut generator = generator(1000).random(true);
+        for id in generator.next_batch(100) {
+            let parsed = Bcid::parse(id.as_str()).unwrap();
+            assert_eq!(parsed, id);
+            assert_eq!(parsed.machine_id(), 1000);
+            assert_eq!(parsed.timestamp(), None);
+        }
+    }
+
+    #[test]
+    fn random_identifiers_keep_machine_ids_that_read_as_a_year() {
+        // Without the random marker, machine IDs 558-619 and 34596-38439
+        // would read as the start of a timestamp
+        for machine_id in [0, 600, 36000] {
+            let id = generator(machine_id).random(true).next();
+            let parsed = Bcid::parse(id.as_str()).unwrap();
+            assert_eq!(parsed.identifier_type(), crate::IdentifierType::Random);
+            assert_eq!(parsed.machine_id(), machine_id);
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn stream_yields_what_the_iterator_would() {
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Waker};
+
+        use futures_core::Stream;
+
+        let mut stream = generator(1).monotonic(true).clock(MockClock::new(start()));
+        let mut cx = Context::from_waker(Waker::noop());
+        let expected: Vec<Bcid> = generator(1).monotonic(true).clock(MockClock::new(start())).take(3).collect();
+        for id in expected {
+            assert_eq!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Ready(Some(id)));
+        }
+    }
+
+    #[test]
+    fn default_length_fits_v5_fields_after_long_prefixes() {
+        for prefix in ["TEST", "ORDERS", "ABCDEFGH"] {
+            let id = BcidGenerator::new(prefix, 1).unwrap().format(Format::V5).next();
+            assert_eq!(id.as_str().len(), crate::chronological_length(prefix, Format::V5));
+            assert_eq!(Bcid::parse(id.as_str()).unwrap(), id);
+        }
+    }
+
+    #[test]
+    fn long_prefixes_need_longer_identifiers() {
+        let mut generator = BcidGenerator::new("ABCDEFGH", 1).unwrap().format(Format::V2).length(24).unwrap();
+        assert!(matches!(generator.try_next(), Err(BcidError::LayoutDoesNotFit { length: 24, .. })));
+    }
+}
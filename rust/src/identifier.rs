@@ -0,0 +1,822 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use chrono::{DateTime, Utc};
+
+use crate::base62::{BASE62_DIGITS, INVALID_DIGIT};
+use crate::format::{self, FixedLayout, Format, TAGS_WIDTH};
+use crate::{
+    base62_decode, fields_start, json, split_prefix, unpack_timestamp, Alphabet, BcidError, BitLayout, Environment,
+    Precision, SEPARATOR,
+};
+
+/// Kind of identifier, detected from its encoded form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdentifierType {
+    /// Timestamp, machine ID and random value followed by random padding
+    Chronological,
+    /// The `0` marker and the machine ID followed by fully random data
+    /// (identifiers generated before the marker start at the machine ID)
+    Random,
+}
+
+impl IdentifierType {
+    /// Lowercase name used in CLI output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IdentifierType::Chronological => "chronological",
+            IdentifierType::Random => "random",
+        }
+    }
+}
+
+impl fmt::Display for IdentifierType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A named character range within an encoded identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Field {
+    /// Component name: `prefix`, `separator`, `version`, `precision`,
+    /// `timestamp`, `machine_id`, `random` or `padding`, `marker` for the
+    /// marker of random identifiers, or for v5 identifiers `layout` and
+    /// `bits` (the packed fields)
+    pub name: &'static str,
+    /// Byte offset of the first character
+    pub start: usize,
+    /// Byte offset one past the last character
+    pub end: usize,
+}
+
+/// A decoded BCID
+///
+/// Holds the canonical string alongside its decoded components.
+/// `Display` renders the identifier exactly as it was parsed or generated.
+///
+/// Identifiers are ordered by their decoded components rather than their
+/// strings, whose order only follows time for v2 identifiers: chronological
+/// identifiers by timestamp, then machine ID, then sequence (v5 only), then
+/// random value, followed by random identifiers, which carry no timestamp,
+/// by machine ID. Remaining ties are broken by the canonical string, so that
+/// only equal identifiers compare equal.
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// let mut ids: Vec<bcid::Bcid> = ["2024-01-03", "2024-01-01", "2024-01-02"]
+///     .iter()
+///     .map(|day| bcid::Bcid::parse(&bcid::generate_identifier("TEST", 1, Some(day), false)?))
+///     .collect::<Result<_, _>>()?;
+/// ids.sort();
+/// assert!(ids.windows(2).all(|pair| pair[0].timestamp() < pair[1].timestamp()));
+/// # }
+/// # Ok::<(), bcid::BcidError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "diesel", derive(diesel::expression::AsExpression, diesel::deserialize::FromSqlRow))]
+#[cfg_attr(feature = "diesel", diesel(sql_type = diesel::sql_types::Text))]
+pub struct Bcid {
+    raw: String,
+    parts: Parts,
+}
+
+/// The decoded components of a [`Bcid`] as plain fields, for embedding in
+/// API request and response types
+///
+/// With the `serde` feature it implements `Serialize` and `Deserialize`,
+/// with the enums written as their names and `datetime` in RFC 3339.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodedBcid {
+    pub prefix: String,
+    pub identifier_type: IdentifierType,
+    pub format: Format,
+    pub alphabet: Alphabet,
+    /// Packed `YYYYMMDDHHmmSScc` timestamp (chronological only)
+    pub timestamp: Option<u64>,
+    /// Resolution of the timestamp (chronological only)
+    pub precision: Option<Precision>,
+    /// UTC date/time of the timestamp, at its precision (chronological only)
+    pub datetime: Option<DateTime<Utc>>,
+    pub machine_id: u16,
+    /// Environment the identifier was generated in (v3 and v4 only, when
+    /// generated with one)
+    pub environment: Option<Environment>,
+    /// Shard after the machine ID (v3 and v4 only, when generated with one)
+    pub shard: Option<String>,
+    /// Bit allocation of the fields (v5 only)
+    pub bit_layout: Option<BitLayout>,
+    /// Sequence number within the millisecond (v5 with sequence bits only)
+    pub sequence: Option<u32>,
+    /// 16-bit random value (chronological only)
+    pub random: Option<u16>,
+    /// Random data following the machine ID (random only)
+    pub random_part: Option<String>,
+    /// The canonical string form
+    pub raw: String,
+}
+
+/// Decoded components of an identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Parts {
+    /// Characters in the prefix, which is followed by the separator unless
+    /// it has 4
+    prefix_len: u8,
+    format: Format,
+    alphabet: Alphabet,
+    identifier_type: IdentifierType,
+    timestamp: Option<u64>,
+    precision: Precision,
+    /// Microseconds after the packed timestamp, for precisions finer than
+    /// 10ms
+    micros: u16,
+    machine_id: u16,
+    environment: Option<Environment>,
+    /// Offset from the start of the fields and length of the shard, after
+    /// the padding digit of a 1-character shard
+    shard: Option<(u8, u8)>,
+    bit_layout: Option<BitLayout>,
+    sequence: Option<u32>,
+    random: Option<u16>,
+    /// Encoded widths of the timestamp and random value (v1 fields are
+    /// variable-width)
+    widths: (u8, u8),
+    /// Whether a random identifier starts with the random marker
+    has_random_marker: bool,
+}
+
+impl Bcid {
+    /// Parse and decode an identifier, detecting its format and type
+    /// automatically
+    ///
+    /// Fails for the same reasons as [`Bcid::validate`].
+    pub fn parse(identifier: &str) -> Result<Bcid, BcidError> {
+        Bcid::parse_with(identifier, Alphabet::Base62)
+    }
+
+    /// Parse and decode an identifier generated with `alphabet`
+    ///
+    /// Accepts the packed and separated forms of identifiers with a
+    /// 4-character prefix (`USERjqTZ...` or `USER_jqTZ...`), and the
+    /// separated form for other prefix lengths. The result holds the
+    /// canonical form: packed for 4-character prefixes, separated otherwise.
+    /// Identifiers in a case-insensitive alphabet are also normalized to its
+    /// canonical digits (e.g. uppercase for Crockford base32). Fails for the
+    /// same reasons as [`Bcid::validate`], and for identifiers in an
+    /// alphabet other than [`Alphabet::Base62`] that do not have the v2
+    /// layout.
+    pub fn parse_with(identifier: &str, alphabet: Alphabet) -> Result<Bcid, BcidError> {
+        let parts = decode_parts(identifier, alphabet)?;
+        let (prefix, fields) = split_prefix(identifier)?;
+        let mut raw = String::with_capacity(fields_start(prefix.len()) + fields.len());
+        raw.push_str(prefix);
+        if prefix.len() != 4 {
+            raw.push(SEPARATOR);
+        }
+        if alphabet.is_case_insensitive() {
+            let chars = alphabet.chars();
+            let digits = alphabet.digits();
+            let markers = format::markers_len(fields.as_bytes()[0]);
+            raw.push_str(&fields[..markers]);
+            raw.extend(fields.bytes().skip(markers).map(|byte| chars[digits[byte as usize] as usize] as char));
+        } else {
+            raw.push_str(fields);
+        }
+        Ok(Bcid { raw, parts })
+    }
+
+    /// Check that an identifier is well-formed without building a [`Bcid`]
+    ///
+    /// Fails if the identifier is not between [`MIN_LENGTH`](crate::MIN_LENGTH)
+    /// and [`MAX_LENGTH`](crate::MAX_LENGTH) characters long, contains
+    /// characters outside the base62 alphabet after the prefix, carries an
+    /// unknown version marker, holds a timestamp that is not a valid date, or
+    /// has fields that fit neither the chronological nor the random layout.
+    pub fn validate(identifier: &str) -> Result<(), BcidError> {
+        decode_parts(identifier, Alphabet::Base62).map(|_| ())
+    }
+
+    /// Whether [`Bcid::validate`] accepts the identifier
+    pub fn is_valid(identifier: &str) -> bool {
+        Bcid::validate(identifier).is_ok()
+    }
+
+    /// The prefix, without the separator
+    pub fn prefix(&self) -> &str {
+        &self.raw[..self.parts.prefix_len as usize]
+    }
+
+    /// Layout the identifier was encoded with
+    pub fn format(&self) -> Format {
+        self.parts.format
+    }
+
+    /// Alphabet the identifier was parsed with
+    pub fn alphabet(&self) -> Alphabet {
+        self.parts.alphabet
+    }
+
+    /// Whether this is a chronological or random identifier
+    pub fn identifier_type(&self) -> IdentifierType {
+        self.parts.identifier_type
+    }
+
+    /// Packed `YYYYMMDDHHmmSScc` timestamp of a chronological identifier,
+    /// truncated to 10ms at finer precisions
+    pub fn timestamp(&self) -> Option<u64> {
+        self.parts.timestamp
+    }
+
+    /// Resolution of the timestamp of a chronological identifier: 10ms
+    /// unless it has the [`Format::V3`] or [`Format::V4`] layout, and
+    /// milliseconds for [`Format::V5`]
+    pub fn precision(&self) -> Option<Precision> {
+        self.parts.timestamp.map(|_| self.parts.precision)
+    }
+
+    /// UTC date/time of a chronological identifier, at its
+    /// [`precision`](Self::precision)
+    pub fn datetime(&self) -> Option<DateTime<Utc>> {
+        let datetime = self.parts.timestamp.and_then(unpack_timestamp)?;
+        Some(datetime + chrono::Duration::microseconds(self.parts.micros as i64))
+    }
+
+    /// 16-bit machine identifier
+    pub fn machine_id(&self) -> u16 {
+        self.parts.machine_id
+    }
+
+    /// Environment of a [`Format::V3`] or [`Format::V4`] identifier
+    /// generated with one (see
+    /// [`BcidGenerator::environment`](crate::BcidGenerator::environment))
+    pub fn environment(&self) -> Option<Environment> {
+        self.parts.environment
+    }
+
+    /// Fail with [`BcidError::WrongEnvironment`] unless this identifier was
+    /// generated in `expected`, e.g. to reject staging identifiers in
+    /// production; identifiers without an environment fail too
+    pub fn check_environment(&self, expected: Environment) -> Result<(), BcidError> {
+        match self.parts.environment {
+            Some(environment) if environment == expected => Ok(()),
+            found => Err(BcidError::WrongEnvironment { expected, found }),
+        }
+    }
+
+    /// Shard of a [`Format::V3`] or [`Format::V4`] identifier generated
+    /// with one (see [`BcidGenerator::shard`](crate::BcidGenerator::shard))
+    pub fn shard(&self) -> Option<&str> {
+        let (start, len) = self.parts.shard?;
+        let start = self.fields_start() + start as usize;
+        Some(&self.raw[start..start + len as usize])
+    }
+
+    /// Bit allocation of a [`Format::V5`] identifier
+    pub fn bit_layout(&self) -> Option<BitLayout> {
+        self.parts.bit_layout
+    }
+
+    /// Sequence number within the millisecond of a [`Format::V5`]
+    /// identifier whose layout has sequence bits
+    pub fn sequence(&self) -> Option<u32> {
+        self.parts.sequence
+    }
+
+    /// 16-bit random value of a chronological identifier (the top 16 bits
+    /// of the random field of a [`Format::V5`] identifier)
+    pub fn random(&self) -> Option<u16> {
+        self.parts.random
+    }
+
+    /// Random data following the machine ID of a random identifier
+    pub fn random_part(&self) -> Option<&str> {
+        match self.parts.identifier_type {
+            IdentifierType::Random => Some(&self.raw[self.fields_start() + self.parts.has_random_marker as usize + 3..]),
+            IdentifierType::Chronological => None,
+        }
+    }
+
+    /// Character ranges of each encoded component, in order
+    pub fn fields(&self) -> Vec<Field> {
+        let (timestamp_width, random_width) = (self.parts.widths.0 as usize, self.parts.widths.1 as usize);
+        let prefix_len = self.parts.prefix_len as usize;
+        let mut layout = vec![("prefix", prefix_len)];
+        if fields_start(prefix_len) > prefix_len {
+            layout.push(("separator", 1));
+        }
+        if self.parts.format != Format::V1 {
+            layout.push(("version", 1));
+        }
+        if self.parts.has_random_marker {
+            layout.push(("marker", 1));
+        }
+        if matches!(self.parts.format, Format::V3 | Format::V4) {
+            layout.push(("precision", 1));
+        }
+        if self.parts.format == Format::V5 {
+            // The fields are bit ranges of one number, not character ranges
+            layout.push(("layout", 3));
+            layout.push(("bits", timestamp_width));
+        } else {
+            if self.parts.identifier_type == IdentifierType::Chronological {
+                layout.push(("timestamp", timestamp_width));
+            }
+            let machine_width = if self.parts.format == Format::V1 { 3 } else { random_width };
+            layout.push(("machine_id", machine_width));
+            // An uppercase precision marker says tags follow the machine ID
+            if matches!(self.parts.format, Format::V3 | Format::V4)
+                && self.raw.as_bytes()[self.fields_start() + 1].is_ascii_uppercase()
+            {
+                layout.push(("environment", 1));
+                layout.push(("shard", TAGS_WIDTH - 1));
+            }
+            layout.push(("random", random_width));
+        }
+
+        let mut fields = Vec::with_capacity(layout.len() + 1);
+        let mut start = 0;
+        for (name, width) in layout {
+            fields.push(Field { name, start, end: start + width });
+            start += width;
+        }
+        if start < self.raw.len() {
+            fields.push(Field { name: "padding", start, end: self.raw.len() });
+        }
+        fields
+    }
+
+    /// The canonical string form
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// The canonical string form, for database drivers that bind a `String`
+    #[cfg(feature = "sqlx")]
+    pub(crate) fn raw(&self) -> &String {
+        &self.raw
+    }
+
+    /// The string form with a separator after the prefix, e.g.
+    /// `USER_jqTZNAWSyaab...`, which is also the canonical form for
+    /// prefixes other than 4 characters
+    pub fn to_separated(&self) -> String {
+        format!("{}{}{}", self.prefix(), SEPARATOR, &self.raw[self.fields_start()..])
+    }
+
+    /// Offset of the encoded fields in the canonical form
+    fn fields_start(&self) -> usize {
+        fields_start(self.parts.prefix_len as usize)
+    }
+
+    /// Copy the string form into `buf` without allocating
+    ///
+    /// Panics unless `buf` is exactly as long as the identifier.
+    pub fn encode_into(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(self.raw.as_bytes());
+    }
+
+    /// Write the string form to `out` (e.g. a
+    /// [`fmt::Formatter`] or a reused `String`) without allocating
+    pub fn write_to<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        out.write_str(&self.raw)
+    }
+
+    /// The decoded components as a [`DecodedBcid`]
+    pub fn decoded(&self) -> DecodedBcid {
+        DecodedBcid {
+            prefix: self.prefix().to_string(),
+            identifier_type: self.parts.identifier_type,
+            format: self.parts.format,
+            alphabet: self.parts.alphabet,
+            timestamp: self.parts.timestamp,
+            precision: self.precision(),
+            datetime: self.datetime(),
+            machine_id: self.parts.machine_id,
+            environment: self.parts.environment,
+            shard: self.shard().map(str::to_string),
+            bit_layout: self.parts.bit_layout,
+            sequence: self.parts.sequence,
+            random: self.parts.random,
+            random_part: self.random_part().map(str::to_string),
+            raw: self.raw.clone(),
+        }
+    }
+
+    /// Render the decoded components as a single-line JSON object
+    ///
+    /// Fields: `prefix`, `type`, `format`, `layout`, `alphabet`, `timestamp`,
+    /// `precision`, `iso_datetime`, `machine_id`, `environment`, `shard`,
+    /// `bit_layout`, `sequence`, `random`, `random_part` and `raw`. Fields that do not apply to the identifier type are
+    /// `null`.
+    pub fn to_json(&self) -> String {
+        let iso_datetime = self.datetime().map(|dt| dt.to_rfc3339_opts(self.parts.precision.seconds_format(), true));
+        json::Object::new()
+            .string("prefix", Some(self.prefix()))
+            .string("type", Some(self.parts.identifier_type.as_str()))
+            .string("format", Some(&self.parts.format.to_string()))
+            .string("layout", Some(self.parts.format.layout()))
+            .string("alphabet", Some(self.parts.alphabet.name()))
+            .number("timestamp", self.parts.timestamp)
+            .string("precision", self.precision().map(|precision| precision.name()))
+            .string("iso_datetime", iso_datetime.as_deref())
+            .number("machine_id", Some(self.parts.machine_id))
+            .string("environment", self.parts.environment.map(|environment| environment.name()))
+            .string("shard", self.shard())
+            .string("bit_layout", self.parts.bit_layout.map(|layout| layout.to_string()).as_deref())
+            .number("sequence", self.parts.sequence)
+            .number("random", self.parts.random)
+            .string("random_part", self.random_part())
+            .string("raw", Some(&self.raw))
+            .finish()
+    }
+}
+
+impl fmt::Display for Bcid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_to(f)
+    }
+}
+
+impl Ord for Bcid {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        let key = |bcid: &Bcid| {
+            let parts = &bcid.parts;
+            let time = (parts.timestamp, parts.micros);
+            (parts.identifier_type == IdentifierType::Random, time, parts.machine_id, parts.sequence, parts.random)
+        };
+        key(self)
+            .cmp(&key(other))
+            .then_with(|| self.raw.cmp(&other.raw))
+            // The same string can decode differently in another alphabet
+            .then_with(|| (self.parts.alphabet as u8).cmp(&(other.parts.alphabet as u8)))
+    }
+}
+
+impl PartialOrd for Bcid {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for Bcid {
+    type Err = BcidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Bcid::parse(s)
+    }
+}
+
+impl From<Bcid> for String {
+    fn from(bcid: Bcid) -> String {
+        bcid.raw
+    }
+}
+
+impl TryFrom<String> for Bcid {
+    type Error = BcidError;
+
+    fn try_from(identifier: String) -> Result<Self, Self::Error> {
+        let parts = decode_parts(&identifier, Alphabet::Base62)?;
+        // Only a 4-character prefix can be followed by a separator that the
+        // canonical form leaves out
+        if parts.prefix_len == 4 && identifier.contains(SEPARATOR) {
+            return Bcid::parse(&identifier);
+        }
+        Ok(Bcid { raw: identifier, parts })
+    }
+}
+
+impl TryFrom<DecodedBcid> for Bcid {
+    type Error = BcidError;
+
+    /// Re-parse the `raw` form with `alphabet`; the other fields are ignored
+    fn try_from(decoded: DecodedBcid) -> Result<Self, Self::Error> {
+        Bcid::parse_with(&decoded.raw, decoded.alphabet)
+    }
+}
+
+impl AsRef<str> for Bcid {
+    fn as_ref(&self) -> &str {
+        &self.raw
+    }
+}
+
+/// Decode the components of an identifier in `alphabet`, detecting its
+/// format and type
+fn decode_parts(identifier: &str, alphabet: Alphabet) -> Result<Parts, BcidError> {
+    // Reject non-ASCII input up front, so everything below can index bytes
+    if !identifier.is_ascii() {
+        let (position, character) = identifier.chars().enumerate().find(|(_, c)| !c.is_ascii()).unwrap_or_default();
+        return Err(BcidError::InvalidCharacter { character, position });
+    }
+    let (prefix, fields) = split_prefix(identifier)?;
+    let start = identifier.len() - fields.len();
+    let parts = if alphabet == Alphabet::Base62 {
+        decode_fields(fields)
+    } else {
+        decode_alphabet_fields(fields, alphabet)
+    };
+    let parts = parts.map_err(|e| e.offset(start))?;
+    Ok(Parts { prefix_len: prefix.len() as u8, ..parts })
+}
+
+/// Decode the base62 fields following the prefix
+///
+/// The prefix length of the result is left at 0 for the caller to fill in.
+fn decode_fields(fields: &str) -> Result<Parts, BcidError> {
+    // Padding and random data are never decoded, so check every character
+    check_base62_chars(fields)?;
+
+    match Format::detect(fields)? {
+        Format::V1 if fields.as_bytes()[0] == format::RANDOM_MARKER => return decode_random_parts(&fields[1..], true),
+        Format::V1 => {}
+        _ => return decode_fixed_parts(fields, Alphabet::Base62),
+    }
+
+    // Try to decode as chronological identifier first
+    // Find the timestamp (it will be the largest number and should represent a valid date)
+    let (timestamp, timestamp_width) = decode_longest(fields.as_bytes(), 9999999999999999);
+
+    // Check if this looks like a valid timestamp (year should be reasonable)
+    let year = timestamp / 1_000_000_000_000;
+    let is_valid_timestamp = (1970..=2100).contains(&year);
+
+    if is_valid_timestamp {
+        // A plausible year with an impossible month/day/time is corrupt
+        if unpack_timestamp(timestamp).is_none() {
+            return Err(BcidError::InvalidTimestamp(timestamp));
+        }
+
+        // Try to decode as chronological identifier
+        let mut pos = timestamp_width;
+
+        // Decode machine_id (exactly 3 characters)
+        let machine_id = decode_machine_id(&fields[pos..pos + 3]).map_err(|e| e.offset(pos))?;
+        pos += 3;
+
+        // Find random value
+        let (random, random_width) = decode_longest(&fields.as_bytes()[pos..], 65535);
+        let random = random as u16;
+
+        Ok(Parts {
+            prefix_len: 0,
+            format: Format::V1,
+            alphabet: Alphabet::Base62,
+            identifier_type: IdentifierType::Chronological,
+            timestamp: Some(timestamp),
+            precision: Precision::Centiseconds,
+            micros: 0,
+            machine_id,
+            environment: None,
+            shard: None,
+            bit_layout: None,
+            sequence: None,
+            random: Some(random),
+            widths: (timestamp_width as u8, random_width as u8),
+            has_random_marker: false,
+        })
+    } else {
+        decode_random_parts(fields, false)
+    }
+}
+
+/// Decode the fields of a random identifier after the random marker, if it
+/// has one
+fn decode_random_parts(fields: &str, has_random_marker: bool) -> Result<Parts, BcidError> {
+    // Machine ID is always encoded as exactly 3 characters (padded with 'a' if needed)
+    let offset = has_random_marker as usize;
+    let machine_id = decode_machine_id(&fields[..3]).map_err(|e| e.offset(offset))?;
+
+    Ok(Parts {
+        prefix_len: 0,
+        format: Format::V1,
+        alphabet: Alphabet::Base62,
+        identifier_type: IdentifierType::Random,
+        timestamp: None,
+        precision: Precision::Centiseconds,
+        micros: 0,
+        machine_id,
+        environment: None,
+        shard: None,
+        bit_layout: None,
+        sequence: None,
+        random: None,
+        widths: (0, (fields.len() - 3) as u8),
+        has_random_marker,
+    })
+}
+
+/// Decode the fields of an identifier in an alphabet other than base62,
+/// which must have a fixed-width layout
+fn decode_alphabet_fields(fields: &str, alphabet: Alphabet) -> Result<Parts, BcidError> {
+    // The v3 and v4 precision marker and the v5 layout are base62
+    // characters, which are not digits of every alphabet
+    let markers = match fields.as_bytes()[0] {
+        marker @ (format::V2_MARKER | format::V3_MARKER | format::V4_MARKER | format::V5_MARKER) => {
+            format::markers_len(marker)
+        }
+        // Random identifiers are always base62
+        format::RANDOM_MARKER => return Err(BcidError::UnknownType),
+        marker if marker.is_ascii_digit() => return Err(BcidError::UnknownFormat(marker as char)),
+        _ => return Err(BcidError::UnknownType),
+    };
+    let digits = alphabet.digits();
+    if let Some(position) = fields.bytes().skip(markers).position(|byte| digits[byte as usize] == INVALID_DIGIT) {
+        let character = fields.as_bytes()[markers + position] as char;
+        return Err(BcidError::InvalidCharacter { character, position: markers + position });
+    }
+    decode_fixed_parts(fields, alphabet)
+}
+
+/// Decode fixed-width fields whose characters after the markers are all in
+/// `alphabet`
+fn decode_fixed_parts(fields: &str, alphabet: Alphabet) -> Result<Parts, BcidError> {
+    let format = Format::detect(fields)?;
+    if format == Format::V5 {
+        return decode_bit_parts(fields, alphabet);
+    }
+    // An uppercase precision marker says tags follow the machine ID
+    let (precision, has_tags) = match format {
+        Format::V2 => (Precision::Centiseconds, false),
+        _ => {
+            let marker = fields.as_bytes()[1];
+            let precision = Precision::from_marker(marker.to_ascii_lowercase())
+                .ok_or(BcidError::InvalidCharacter { character: marker as char, position: 1 })?;
+            (precision, marker.is_ascii_uppercase())
+        }
+    };
+    let zero = alphabet.chars()[0];
+    let layout = FixedLayout::of(format, alphabet, precision)
+        .expect("markers name fixed-width layouts")
+        .with_tags(Some([zero; TAGS_WIDTH]).filter(|_| has_tags));
+    let (timestamp, micros, machine_id, random) = layout.decode(fields)?;
+    let (timestamp_width, field_width) = layout.widths();
+    let (mut environment, mut shard) = (None, None);
+    if let Some(start) = layout.tags_offset() {
+        let tags = &fields.as_bytes()[start..start + TAGS_WIDTH];
+        if tags[0] != zero {
+            let invalid = BcidError::InvalidCharacter { character: tags[0] as char, position: start };
+            environment = Some(Environment::from_marker(tags[0], alphabet).ok_or(invalid)?);
+        }
+        // Leading zero digits pad a 1-character shard and stand for no shard
+        let padding = tags[1..].iter().take_while(|&&byte| byte == zero).count();
+        if padding < TAGS_WIDTH - 1 {
+            shard = Some(((start + 1 + padding) as u8, (TAGS_WIDTH - 1 - padding) as u8));
+        }
+    }
+    Ok(Parts {
+        prefix_len: 0,
+        format,
+        alphabet,
+        identifier_type: IdentifierType::Chronological,
+        timestamp: Some(timestamp),
+        precision,
+        micros,
+        machine_id,
+        environment,
+        shard,
+        bit_layout: None,
+        sequence: None,
+        random: Some(random),
+        widths: (timestamp_width as u8, field_width as u8),
+        has_random_marker: false,
+    })
+}
+
+/// Decode the packed fields of a v5 identifier whose characters after the
+/// markers are all in `alphabet`
+fn decode_bit_parts(fields: &str, alphabet: Alphabet) -> Result<Parts, BcidError> {
+    let bits = BitLayout::decode(fields, alphabet)?;
+    Ok(Parts {
+        prefix_len: 0,
+        format: Format::V5,
+        alphabet,
+        identifier_type: IdentifierType::Chronological,
+        timestamp: Some(bits.time.0),
+        precision: Precision::Milliseconds,
+        micros: bits.time.1,
+        machine_id: bits.machine_id,
+        environment: None,
+        shard: None,
+        bit_layout: Some(bits.layout),
+        sequence: Some(bits.sequence).filter(|_| bits.layout.sequence_bits() > 0),
+        random: Some(bits.random_value),
+        widths: ((BitLayout::fields_len(alphabet) - 4) as u8, 0),
+        has_random_marker: false,
+    })
+}
+
+/// Decode the longest run of digits at the start of `encoded` whose value
+/// does not exceed `max`, returning the value and the number of digits
+///
+/// `encoded` must hold only [`BASE62`](crate::BASE62) characters.
+fn decode_longest(encoded: &[u8], max: u64) -> (u64, usize) {
+    let mut value = 0u64;
+    for (width, &byte) in encoded.iter().enumerate() {
+        let digit = BASE62_DIGITS[byte as usize];
+        match value.checked_mul(62).map(|value| value + digit as u64) {
+            Some(next) if next <= max => value = next,
+            _ => return (value, width),
+        }
+    }
+    (value, encoded.len())
+}
+
+/// Decode a 3-character v1 machine ID, which must fit in 16 bits
+fn decode_machine_id(encoded: &str) -> Result<u16, BcidError> {
+    let value = base62_decode(encoded)?;
+    u16::try_from(value).map_err(|_| BcidError::UnknownType)
+}
+
+/// Check that every byte of an ASCII string is in the
+/// [`BASE62`](crate::BASE62) alphabet
+fn check_base62_chars(encoded: &str) -> Result<(), BcidError> {
+    match encoded.bytes().position(|byte| BASE62_DIGITS[byte as usize] == INVALID_DIGIT) {
+        Some(position) => Err(BcidError::InvalidCharacter { character: encoded.as_bytes()[position] as char, position }),
+        None => Ok(()),
+    }
+}
+
+// The samples come from generators, which need the clock
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::{BcidGenerator, MockClock, Precision};
+
+    /// Identifiers of every format and alphabet, with the alphabet to parse
+    /// them with
+    fn samples() -> Vec<(Bcid, Alphabet)> {
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 5, 6, 7, 8, 9).unwrap());
+        let generator = |prefix: &str| {
+            BcidGenerator::with_rng(prefix, 513, StdRng::seed_from_u64(7)).unwrap().clock(clock.clone())
+        };
+        let mut samples = Vec::new();
+        for prefix in ["TEST", "AB", "ORDERS"] {
+            for format in [Format::V1, Format::V2, Format::V3, Format::V4, Format::V5] {
+                samples.push((generator(prefix).format(format).next(), Alphabet::Base62));
+            }
+            for alphabet in [Alphabet::Crockford32, Alphabet::Base58, Alphabet::NoVowels] {
+                samples.push((generator(prefix).alphabet(alphabet).next(), alphabet));
+                samples.push((generator(prefix).alphabet(alphabet).precision(Precision::Milliseconds).length(40).unwrap().next(), alphabet));
+            }
+            samples.push((generator(prefix).shard("e1").next(), Alphabet::Base62));
+            samples.push((generator(prefix).random(true).next(), Alphabet::Base62));
+        }
+        samples
+    }
+
+    #[test]
+    fn samples_round_trip() {
+        for (id, alphabet) in samples() {
+            let parsed = Bcid::parse_with(id.as_str(), alphabet).unwrap();
+            assert_eq!(parsed, id);
+            assert_eq!(parsed.machine_id(), 513);
+            assert_eq!(Bcid::parse_with(&id.to_separated(), alphabet).unwrap(), id);
+        }
+    }
+
+    #[test]
+    fn truncated_identifiers_fail_or_keep_their_fields() {
+        for (id, alphabet) in samples() {
+            let min = crate::min_length(id.prefix().len());
+            for length in 0..id.as_str().len() {
+                let truncated = &id.as_str()[..length];
+                match Bcid::parse_with(truncated, alphabet) {
+                    Ok(parsed) => {
+                        assert!(length >= min, "{truncated}");
+                        assert_eq!(parsed.format(), id.format(), "{truncated}");
+                        assert_eq!(parsed.timestamp(), id.timestamp(), "{truncated}");
+                        assert_eq!(parsed.machine_id(), id.machine_id(), "{truncated}");
+                        assert_eq!(parsed.random(), id.random(), "{truncated}");
+                    }
+                    Err(error) => assert_eq!(error, BcidError::InvalidIdentifierLength(length), "{truncated}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt_identifiers_report_the_position() {
+        for (id, alphabet) in samples() {
+            let raw = id.as_str();
+            let start = crate::fields_start(id.prefix().len()) + format::markers_len(raw.as_bytes()[crate::fields_start(id.prefix().len())]);
+            for position in start..raw.len() {
+                let corrupt = format!("{}!{}", &raw[..position], &raw[position + 1..]);
+                assert_eq!(
+                    Bcid::parse_with(&corrupt, alphabet),
+                    Err(BcidError::InvalidCharacter { character: '!', position }),
+                    "{corrupt}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! Minimal JSON rendering and request body parsing helpers for decoded and server output
+//!
+//! Shared with the command line tool; not part of the supported API.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// Append `value` to `out` as a quoted, escaped JSON string
+pub fn push_str(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Builder for a flat JSON object
+pub struct Object {
+    out: String,
+}
+
+impl Default for Object {
+    fn default() -> Self {
+        Object::new()
+    }
+}
+
+impl Object {
+    pub fn new() -> Self {
+        Object { out: String::from("{") }
+    }
+
+    fn key(&mut self, key: &str) {
+        if self.out.len() > 1 {
+            self.out.push(',');
+        }
+        push_str(&mut self.out, key);
+        self.out.push(':');
+    }
+
+    /// Add a string field, or `null` for `None`
+    pub fn string(mut self, key: &str, value: Option<&str>) -> Self {
+        self.key(key);
+        match value {
+            Some(value) => push_str(&mut self.out, value),
+            None => self.out.push_str("null"),
+        }
+        self
+    }
+
+    /// Add an array of strings
+    pub fn strings<S: AsRef<str>>(mut self, key: &str, values: &[S]) -> Self {
+        self.key(key);
+        self.out.push('[');
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            push_str(&mut self.out, value.as_ref());
+        }
+        self.out.push(']');
+        self
+    }
+
+    /// Add a boolean field
+    pub fn boolean(mut self, key: &str, value: bool) -> Self {
+        self.key(key);
+        self.out.push_str(if value { "true" } else { "false" });
+        self
+    }
+
+    /// Add a numeric field, or `null` for `None`
+    pub fn number<N: core::fmt::Display>(mut self, key: &str, value: Option<N>) -> Self {
+        self.key(key);
+        match value {
+            Some(value) => {
+                let _ = write!(self.out, "{}", value);
+            }
+            None => self.out.push_str("null"),
+        }
+        self
+    }
+
+    /// Add a field whose value is already rendered JSON, such as a nested object
+    pub fn raw(mut self, key: &str, json: &str) -> Self {
+        self.key(key);
+        self.out.push_str(json);
+        self
+    }
+
+    pub fn finish(mut self) -> String {
+        self.out.push('}');
+        self.out
+    }
+}
+
+/// Parse a flat JSON object into its fields, in order
+///
+/// String values are unescaped; numbers and booleans are returned as
+/// written and `null` fields are skipped. Nested objects and arrays are
+/// rejected, which is all request bodies need.
+pub fn parse_object(input: &str) -> Result<Vec<(String, String)>, String> {
+    let mut parser = Parser { chars: input.trim().chars().peekable() };
+    let mut fields = Vec::new();
+    parser.expect('{')?;
+    if parser.skip_whitespace() == Some('}') {
+        parser.chars.next();
+    } else {
+        loop {
+            parser.skip_whitespace();
+            let key = parser.string()?;
+            parser.skip_whitespace();
+            parser.expect(':')?;
+            match parser.skip_whitespace() {
+                Some('"') => fields.push((key, parser.string()?)),
+                Some('{' | '[') => return Err(format!("field '{}' must be a string, number or boolean", key)),
+                _ => {
+                    let literal = parser.literal();
+                    if !matches!(literal.as_str(), "true" | "false" | "null") && literal.parse::<f64>().is_err() {
+                        return Err(format!("invalid value for '{}'", key));
+                    }
+                    if literal != "null" {
+                        fields.push((key, literal));
+                    }
+                }
+            }
+            match parser.skip_whitespace() {
+                Some(',') => {
+                    parser.chars.next();
+                }
+                Some('}') => {
+                    parser.chars.next();
+                    break;
+                }
+                _ => return Err("expected ',' or '}'".to_string()),
+            }
+        }
+    }
+    match parser.chars.next() {
+        None => Ok(fields),
+        Some(_) => Err("unexpected data after the object".to_string()),
+    }
+}
+
+/// The value at `path` in a JSON object, e.g. `["result", "TTL"]` for
+/// `{"result":{"TTL":"30"}}`
+///
+/// Strings are unescaped and numbers and booleans returned as written;
+/// `None` if the path is missing, leads to `null`, an array or an object,
+/// or the input is not valid JSON.
+pub fn find(input: &str, path: &[&str]) -> Option<String> {
+    let mut parser = Parser { chars: input.trim().chars().peekable() };
+    parser.find(path).ok().flatten()
+}
+
+struct Parser<'a> {
+    chars: core::iter::Peekable<core::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    /// Skip whitespace and peek at the next character
+    fn skip_whitespace(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(format!("expected '{}'", expected)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.chars.next().ok_or("unterminated string")? {
+                '"' => return Ok(out),
+                '\\' => match self.chars.next().ok_or("unterminated string")? {
+                    'n' => out.push('\n'),
+                    'r' => out.push('\r'),
+                    't' => out.push('\t'),
+                    'b' => out.push('\u{8}'),
+                    'f' => out.push('\u{c}'),
+                    'u' => {
+                        let hex: String = self.chars.by_ref().take(4).collect();
+                        let c = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+                        out.push(c.ok_or("invalid \\u escape")?);
+                    }
+                    c @ ('"' | '\\' | '/') => out.push(c),
+                    c => return Err(format!("invalid escape '\\{}'", c)),
+                },
+                c => out.push(c),
+            }
+        }
+    }
+
+    /// Find `path` in the object starting at the next character
+    fn find(&mut self, path: &[&str]) -> Result<Option<String>, String> {
+        let (name, rest) = path.split_first().ok_or("empty path")?;
+        self.skip_whitespace();
+        self.expect('{')?;
+        if self.skip_whitespace() == Some('}') {
+            return Ok(None);
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let next = self.skip_whitespace();
+            match (key == *name, next) {
+                (true, Some('{')) if !rest.is_empty() => return self.find(rest),
+                (true, Some('"')) if rest.is_empty() => return self.string().map(Some),
+                (true, Some('{' | '[')) => return Ok(None),
+                (true, _) if rest.is_empty() => return Ok(Some(self.literal()).filter(|value| value != "null")),
+                (true, _) => return Ok(None),
+                (false, _) => self.skip_value()?,
+            }
+            match self.skip_whitespace() {
+                Some(',') => {
+                    self.chars.next();
+                }
+                Some('}') => return Ok(None),
+                _ => return Err("expected ',' or '}'".to_string()),
+            }
+        }
+    }
+
+    /// Skip the value starting at the next character
+    fn skip_value(&mut self) -> Result<(), String> {
+        match self.skip_whitespace() {
+            Some('"') => self.string().map(|_| ()),
+            Some(open @ ('{' | '[')) => {
+                let close = if open == '{' { '}' } else { ']' };
+                self.chars.next();
+                if self.skip_whitespace() == Some(close) {
+                    self.chars.next();
+                    return Ok(());
+                }
+                loop {
+                    if open == '{' {
+                        self.skip_whitespace();
+                        self.string()?;
+                        self.skip_whitespace();
+                        self.expect(':')?;
+                    }
+                    self.skip_value()?;
+                    match self.skip_whitespace() {
+                        Some(',') => {
+                            self.chars.next();
+                        }
+                        Some(c) if c == close => {
+                            self.chars.next();
+                            return Ok(());
+                        }
+                        _ => return Err(format!("expected ',' or '{}'", close)),
+                    }
+                }
+            }
+            _ => match self.literal() {
+                literal if literal.is_empty() => Err("missing value".to_string()),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// A number, `true`, `false` or `null`
+    fn literal(&mut self) -> String {
+        let mut out = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')) {
+            out.push(c);
+        }
+        out
+    }
+}
@@ -0,0 +1,301 @@
+use alloc::format;
+use alloc::string::ToString;
+use core::fmt;
+use core::str::FromStr;
+
+use chrono::{DateTime, Utc};
+
+use crate::base62::{decode_wide_with, encode_fixed_wide, INVALID_DIGIT, SORTABLE_BASE62, SORTABLE_BASE62_DIGITS};
+use crate::entropy::{fill_random_chars, random_u128};
+use crate::format::V5_MARKER;
+use crate::{unpack_timestamp, write_prefix, Alphabet, BcidError, EntropySource, Precision};
+
+/// Bits in the fields of a v5 identifier
+pub(crate) const LAYOUT_BITS: u32 = 128;
+
+/// Fewest timestamp bits, which count milliseconds until the year 2109
+const MIN_TIMESTAMP_BITS: u8 = 42;
+
+/// Most timestamp bits, the largest width one layout character can record
+const MAX_TIMESTAMP_BITS: u8 = 61;
+
+/// Most machine ID bits, the width of a machine ID
+const MAX_MACHINE_BITS: u8 = 16;
+
+/// Most sequence bits
+const MAX_SEQUENCE_BITS: u8 = 32;
+
+/// Bits of the random value at the top of the random field, which
+/// monotonic generation increments
+const RANDOM_VALUE_BITS: u32 = 16;
+
+/// How the 128 bits of a [`Format::V5`](crate::Format::V5) identifier are
+/// split between timestamp, machine ID, sequence and random value
+///
+/// The timestamp counts milliseconds since the Unix epoch in 42 to 61 bits,
+/// the machine ID takes up to 16 bits, the sequence (a per-millisecond
+/// counter, see [`BcidGenerator::bit_layout`](crate::BcidGenerator::bit_layout))
+/// up to 32 bits, and the random value the rest. Parse one from the
+/// `ts=44,machine=12,seq=10,rand=62` form that `Display` writes, where
+/// `seq` defaults to 0 and `rand` to the remaining bits. The
+/// [`Default`] layout is `ts=48,machine=16,seq=0,rand=64`.
+///
+/// ```
+/// let layout: bcid::BitLayout = "ts=44,machine=12,seq=10".parse()?;
+/// assert_eq!(layout.random_bits(), 62);
+/// assert_eq!(layout.to_string(), "ts=44,machine=12,seq=10,rand=62");
+/// assert!("ts=44,machine=20".parse::<bcid::BitLayout>().is_err());
+/// # Ok::<(), bcid::BcidError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitLayout {
+    timestamp_bits: u8,
+    machine_bits: u8,
+    sequence_bits: u8,
+}
+
+impl BitLayout {
+    /// A layout with the given timestamp, machine ID and sequence bits, and
+    /// the rest of the 128 bits random
+    ///
+    /// Fails with [`BcidError::InvalidLayout`] if a field is outside its
+    /// range or the fields take more than 128 bits.
+    pub fn new(timestamp_bits: u8, machine_bits: u8, sequence_bits: u8) -> Result<BitLayout, BcidError> {
+        if !(MIN_TIMESTAMP_BITS..=MAX_TIMESTAMP_BITS).contains(&timestamp_bits) {
+            return Err(BcidError::InvalidLayout(format!(
+                "timestamp must take {} to {} bits (got {})",
+                MIN_TIMESTAMP_BITS, MAX_TIMESTAMP_BITS, timestamp_bits
+            )));
+        }
+        if machine_bits > MAX_MACHINE_BITS {
+            return Err(BcidError::InvalidLayout(format!(
+                "machine ID must take at most {} bits (got {})",
+                MAX_MACHINE_BITS, machine_bits
+            )));
+        }
+        if sequence_bits > MAX_SEQUENCE_BITS {
+            return Err(BcidError::InvalidLayout(format!(
+                "sequence must take at most {} bits (got {})",
+                MAX_SEQUENCE_BITS, sequence_bits
+            )));
+        }
+        // Each field is at most 61 bits, so the sum cannot overflow
+        let total = timestamp_bits as u32 + machine_bits as u32 + sequence_bits as u32;
+        if total > LAYOUT_BITS {
+            return Err(BcidError::InvalidLayout(format!("fields take {} of {} bits", total, LAYOUT_BITS)));
+        }
+        Ok(BitLayout { timestamp_bits, machine_bits, sequence_bits })
+    }
+
+    /// Bits of milliseconds since the Unix epoch
+    pub fn timestamp_bits(&self) -> u8 {
+        self.timestamp_bits
+    }
+
+    /// Bits of machine ID
+    pub fn machine_bits(&self) -> u8 {
+        self.machine_bits
+    }
+
+    /// Bits of per-millisecond sequence number
+    pub fn sequence_bits(&self) -> u8 {
+        self.sequence_bits
+    }
+
+    /// Bits of random value: the rest of the 128
+    pub fn random_bits(&self) -> u8 {
+        (LAYOUT_BITS - self.timestamp_bits as u32 - self.machine_bits as u32 - self.sequence_bits as u32) as u8
+    }
+
+    /// Largest machine ID that fits
+    pub fn max_machine_id(&self) -> u16 {
+        ((1u32 << self.machine_bits) - 1) as u16
+    }
+
+    /// Largest sequence number that fits
+    pub fn max_sequence(&self) -> u32 {
+        ((1u64 << self.sequence_bits) - 1) as u32
+    }
+
+    /// Largest millisecond timestamp that fits
+    fn max_millis(&self) -> u64 {
+        (1u64 << self.timestamp_bits) - 1
+    }
+
+    /// Characters from the marker to the end of the packed fields of v5
+    /// identifiers in `alphabet`
+    pub(crate) fn fields_len(alphabet: Alphabet) -> usize {
+        4 + alphabet.wide_width()
+    }
+
+    /// Pack the fields into 128 bits, most significant first
+    ///
+    /// The 16-bit `random_value` takes the top of the random field, and the
+    /// rest is drawn from `entropy`. Fails if `millis` (since the Unix epoch)
+    /// does not fit the timestamp field; the machine ID and sequence must
+    /// fit theirs.
+    pub(crate) fn pack(
+        &self,
+        millis: u64,
+        machine_id: u16,
+        sequence: u32,
+        random_value: u16,
+        entropy: &mut dyn EntropySource,
+    ) -> Result<u128, BcidError> {
+        if millis > self.max_millis() {
+            return Err(BcidError::ValueOutOfRange { field: "timestamp", value: millis });
+        }
+        let random_bits = self.random_bits() as u32;
+        let random = (random_value as u128) << (random_bits - RANDOM_VALUE_BITS)
+            | random_u128(entropy) >> (LAYOUT_BITS - random_bits + RANDOM_VALUE_BITS);
+        let value = millis as u128;
+        let value = value << self.machine_bits | machine_id as u128;
+        let value = value << self.sequence_bits | sequence as u128;
+        Ok(value << random_bits | random)
+    }
+
+    /// Encode an identifier from its packed fields into all of `out`: the
+    /// prefix, the marker and layout characters, the fields and random
+    /// padding
+    pub(crate) fn encode_into(
+        &self,
+        out: &mut [u8],
+        alphabet: Alphabet,
+        prefix: &str,
+        value: u128,
+        entropy: &mut dyn EntropySource,
+    ) {
+        let start = write_prefix(out, prefix);
+        let fields = &mut out[start..];
+        fields[0] = V5_MARKER;
+        for (marker, bits) in fields[1..4].iter_mut().zip([self.timestamp_bits, self.machine_bits, self.sequence_bits]) {
+            *marker = SORTABLE_BASE62[bits as usize];
+        }
+        let padding = BitLayout::fields_len(alphabet);
+        encode_fixed_wide(alphabet.chars(), value, &mut fields[4..padding]);
+        fill_random_chars(entropy, alphabet.chars(), &mut fields[padding..]);
+    }
+
+    /// Decode the encoded fields of a v5 identifier, whose characters after
+    /// the markers must all be in `alphabet`
+    ///
+    /// Fails with [`BcidError::InvalidIdentifierLength`] if the fields are
+    /// too short to hold the packed value.
+    pub(crate) fn decode(fields: &str, alphabet: Alphabet) -> Result<BitFields, BcidError> {
+        let end = BitLayout::fields_len(alphabet);
+        if fields.len() < end {
+            return Err(BcidError::InvalidIdentifierLength(fields.len()));
+        }
+        let mut bits = [0u8; 3];
+        for (position, (bits, &marker)) in bits.iter_mut().zip(&fields.as_bytes()[1..4]).enumerate() {
+            *bits = SORTABLE_BASE62_DIGITS[marker as usize];
+            if *bits == INVALID_DIGIT {
+                return Err(BcidError::InvalidCharacter { character: marker as char, position: position + 1 });
+            }
+        }
+        let layout = BitLayout::new(bits[0], bits[1], bits[2])?;
+        let value = decode_wide_with(alphabet.digits(), alphabet.radix(), &fields[4..end]).map_err(|e| e.offset(4))?;
+
+        let field = |shift: u32, bits: u32| match bits {
+            0 => 0,
+            bits => (value >> shift) & (u128::MAX >> (LAYOUT_BITS - bits)),
+        };
+        let random_bits = layout.random_bits() as u32;
+        let machine_shift = random_bits + layout.sequence_bits as u32;
+        let timestamp_shift = machine_shift + layout.machine_bits as u32;
+        let millis = (value >> timestamp_shift) as u64;
+        let machine_id = field(machine_shift, layout.machine_bits as u32) as u16;
+        let sequence = field(random_bits, layout.sequence_bits as u32) as u32;
+        let random_value = field(random_bits - RANDOM_VALUE_BITS, RANDOM_VALUE_BITS) as u16;
+        Ok(BitFields { layout, time: from_unix_millis(millis)?, machine_id, sequence, random_value })
+    }
+}
+
+/// The decoded fields of a v5 identifier
+pub(crate) struct BitFields {
+    pub(crate) layout: BitLayout,
+    /// Packed timestamp and the microseconds after it
+    pub(crate) time: (u64, u16),
+    pub(crate) machine_id: u16,
+    pub(crate) sequence: u32,
+    /// Top 16 bits of the random field
+    pub(crate) random_value: u16,
+}
+
+impl Default for BitLayout {
+    fn default() -> Self {
+        BitLayout { timestamp_bits: 48, machine_bits: 16, sequence_bits: 0 }
+    }
+}
+
+/// Milliseconds since the Unix epoch of a packed timestamp and the
+/// microseconds after it, or `None` before 1970
+pub(crate) fn unix_millis((timestamp, micros): (u64, u16)) -> Option<u64> {
+    let datetime = unpack_timestamp(timestamp)? + chrono::Duration::microseconds(micros as i64);
+    u64::try_from(datetime.timestamp_millis()).ok()
+}
+
+/// The packed timestamp and the microseconds after it of a time in
+/// milliseconds since the Unix epoch
+fn from_unix_millis(millis: u64) -> Result<(u64, u16), BcidError> {
+    let datetime = i64::try_from(millis)
+        .ok()
+        .and_then(DateTime::<Utc>::from_timestamp_millis)
+        .ok_or(BcidError::ValueOutOfRange { field: "timestamp", value: millis })?;
+    let time = Precision::Milliseconds.pack(&datetime);
+    match unpack_timestamp(time.0) {
+        Some(_) => Ok(time),
+        None => Err(BcidError::InvalidTimestamp(time.0)),
+    }
+}
+
+impl FromStr for BitLayout {
+    type Err = BcidError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (mut timestamp, mut machine, mut sequence, mut random) = (None, None, None, None);
+        for part in spec.split(',').map(str::trim) {
+            let invalid = || BcidError::InvalidLayout(format!("expected `field=bits`, got {:?}", part));
+            let (field, bits) = part.split_once('=').ok_or_else(invalid)?;
+            let bits: u8 = bits.trim().parse().map_err(|_| invalid())?;
+            let slot = match field.trim() {
+                "ts" | "timestamp" => &mut timestamp,
+                "machine" => &mut machine,
+                "seq" | "sequence" => &mut sequence,
+                "rand" | "random" => &mut random,
+                other => {
+                    return Err(BcidError::InvalidLayout(format!(
+                        "unknown field {:?} (expected ts, machine, seq or rand)",
+                        other
+                    )))
+                }
+            };
+            if slot.replace(bits).is_some() {
+                return Err(BcidError::InvalidLayout(format!("{} is given twice", field.trim())));
+            }
+        }
+        let timestamp = timestamp.ok_or_else(|| BcidError::InvalidLayout("ts is required".to_string()))?;
+        let layout = BitLayout::new(timestamp, machine.unwrap_or(0), sequence.unwrap_or(0))?;
+        match random {
+            Some(random) if random != layout.random_bits() => Err(BcidError::InvalidLayout(format!(
+                "fields must take {} bits in total (got {})",
+                LAYOUT_BITS,
+                LAYOUT_BITS - layout.random_bits() as u32 + random as u32
+            ))),
+            _ => Ok(layout),
+        }
+    }
+}
+
+impl fmt::Display for BitLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ts={},machine={},seq={},rand={}",
+            self.timestamp_bits,
+            self.machine_bits,
+            self.sequence_bits,
+            self.random_bits()
+        )
+    }
+}
@@ -0,0 +1,138 @@
+//! Machine IDs claimed as Consul keys locked by a Consul session
+
+use std::io;
+use std::time::Duration;
+
+use super::http::{self, status_error};
+use super::{holder, unreachable, LeaseUrl, MachineIdProvider};
+use crate::{json, BcidError};
+
+/// Key prefix of claims unless the URL gives `key`
+const DEFAULT_KEY: &str = "bcid/machine-id/";
+
+/// Shortest session TTL Consul accepts
+const MIN_TTL: Duration = Duration::from_secs(10);
+
+pub(super) struct Consul {
+    address: String,
+    /// ACL token from `?token=`
+    token: Option<String>,
+    key: String,
+    ttl: Duration,
+    /// The session holding our claims; renewing it renews them and
+    /// destroying it deletes them
+    session: String,
+}
+
+impl Consul {
+    pub(super) fn connect(url: &LeaseUrl) -> Result<Consul, BcidError> {
+        url.check_params(&["token"])?;
+        if url.ttl < MIN_TTL {
+            return Err(BcidError::MachineIdUnavailable("Consul sessions need a ttl of at least 10 seconds".to_string()));
+        }
+        let mut consul = Consul {
+            address: url.address(8500),
+            token: url.params.iter().find(|(name, _)| name == "token").map(|(_, token)| token.clone()),
+            key: url.key.clone().unwrap_or_else(|| DEFAULT_KEY.to_string()),
+            ttl: url.ttl,
+            session: String::new(),
+        };
+        let body = json::Object::new()
+            .string("Name", Some(&format!("bcid machine ID for {}", holder())))
+            .string("TTL", Some(&format!("{}s", url.ttl.as_secs())))
+            .string("Behavior", Some("delete"))
+            .string("LockDelay", Some("0s"))
+            .finish();
+        let response = match consul.put("/v1/session/create", &body).map_err(|err| unreachable("Consul", err))? {
+            (200, response) => response,
+            (status, response) => return Err(unreachable("Consul", status_error(status, &response))),
+        };
+        consul.session = json::find(&response, &["ID"])
+            .ok_or_else(|| BcidError::MachineIdUnavailable("Consul created no session".to_string()))?;
+        Ok(consul)
+    }
+
+    /// PUT `body` and return the status and body of the response
+    fn put(&self, path: &str, body: &str) -> io::Result<(u16, String)> {
+        let mut headers = Vec::new();
+        if let Some(token) = &self.token {
+            headers.push(("X-Consul-Token", token.as_str()));
+        }
+        http::request(&self.address, "PUT", path, &headers, body)
+    }
+}
+
+impl MachineIdProvider for Consul {
+    fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn claim(&mut self, id: u16) -> io::Result<bool> {
+        let path = format!("/v1/kv/{}{}?acquire={}", self.key, id, self.session);
+        match self.put(&path, &holder())? {
+            (200, response) => Ok(response.trim() == "true"),
+            (status, response) => Err(status_error(status, &response)),
+        }
+    }
+
+    fn renew(&mut self, _id: u16) -> io::Result<bool> {
+        match self.put(&format!("/v1/session/renew/{}", self.session), "")? {
+            (200, _) => Ok(true),
+            // The session has expired or was destroyed
+            (404, _) => Ok(false),
+            (status, response) => Err(status_error(status, &response)),
+        }
+    }
+
+    fn release(&mut self, _id: u16) -> io::Result<()> {
+        self.put(&format!("/v1/session/destroy/{}", self.session), "").map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lease::tests::{fake_server, http_exchange, Script};
+
+    const CREATED: &str = "HTTP/1.1 200 OK\r\n\r\n{\"ID\":\"adf4238a-882b-9ddc-4a9d-5b6758e4159e\"}";
+
+    fn connect(scripts: Vec<Script>) -> Result<Consul, BcidError> {
+        Consul::connect(&LeaseUrl::parse(&format!("consul://{}?token=wrong", fake_server(scripts))).unwrap())
+    }
+
+    #[test]
+    fn refused_tokens_fail_to_connect() {
+        let scripts = vec![http_exchange("PUT /v1/session/create ", "HTTP/1.1 403 Forbidden\r\n\r\nPermission denied")];
+        match connect(scripts) {
+            Err(BcidError::MachineIdUnavailable(reason)) => assert!(reason.contains("403"), "{reason}"),
+            _ => panic!("connected with a refused token"),
+        }
+    }
+
+    #[test]
+    fn claims_follow_the_lock_result() {
+        let scripts = vec![
+            http_exchange("PUT /v1/session/create ", CREATED),
+            http_exchange("PUT /v1/kv/bcid/machine-id/7?acquire=adf4238a-", "HTTP/1.1 200 OK\r\n\r\nfalse"),
+            http_exchange("PUT /v1/kv/bcid/machine-id/8?acquire=adf4238a-", "HTTP/1.1 200 OK\r\n\r\ntrue\n"),
+            http_exchange("PUT /v1/kv/bcid/machine-id/9?acquire=adf4238a-", "HTTP/1.1 200 OK\r\n\r\n"),
+        ];
+        let mut consul = connect(scripts).unwrap();
+        assert!(!consul.claim(7).unwrap());
+        assert!(consul.claim(8).unwrap());
+        assert!(!consul.claim(9).unwrap());
+    }
+
+    #[test]
+    fn expired_sessions_are_not_renewed() {
+        let scripts = vec![
+            http_exchange("PUT /v1/session/create ", CREATED),
+            http_exchange("PUT /v1/session/renew/adf4238a-", "HTTP/1.1 404 Not Found\r\n\r\nSession id not found"),
+            http_exchange("PUT /v1/session/renew/adf4238a-", "HTTP/1.1 500 Internal Server Error\r\n\r\nNo cluster leader"),
+        ];
+        let mut consul = connect(scripts).unwrap();
+        assert!(!consul.renew(7).unwrap());
+        let err = consul.renew(7).unwrap_err();
+        assert!(err.to_string().contains("No cluster leader"), "{err}");
+    }
+}
@@ -0,0 +1,147 @@
+//! Machine IDs claimed as etcd keys attached to an etcd lease, through the
+//! JSON gateway of the etcd v3 API
+
+use std::io;
+use std::time::Duration;
+
+use super::http::{self, base64, status_error};
+use super::{holder, unreachable, LeaseUrl, MachineIdProvider};
+use crate::{json, BcidError};
+
+/// Key prefix of claims unless the URL gives `key`
+const DEFAULT_KEY: &str = "bcid/machine-id/";
+
+pub(super) struct Etcd {
+    address: String,
+    /// Authentication token from `USER:PASSWORD` in the URL
+    token: Option<String>,
+    key: String,
+    ttl: Duration,
+    /// The etcd lease our claims are attached to; keeping it alive renews
+    /// them and revoking it deletes them
+    lease: String,
+}
+
+impl Etcd {
+    pub(super) fn connect(url: &LeaseUrl) -> Result<Etcd, BcidError> {
+        url.check_params(&[])?;
+        let mut etcd = Etcd {
+            address: url.address(2379),
+            token: None,
+            key: url.key.clone().unwrap_or_else(|| DEFAULT_KEY.to_string()),
+            ttl: url.ttl,
+            lease: String::new(),
+        };
+        if let (Some(user), Some(password)) = (&url.user, &url.password) {
+            let body = json::Object::new().string("name", Some(user)).string("password", Some(password)).finish();
+            let response = etcd.post("/v3/auth/authenticate", &body).map_err(|err| unreachable("etcd", err))?;
+            etcd.token = json::find(&response, &["token"]);
+        }
+        let body = json::Object::new().number("TTL", Some(url.ttl.as_secs())).finish();
+        let response = etcd.post("/v3/lease/grant", &body).map_err(|err| unreachable("etcd", err))?;
+        etcd.lease = json::find(&response, &["ID"])
+            .ok_or_else(|| BcidError::MachineIdUnavailable("etcd granted no lease".to_string()))?;
+        Ok(etcd)
+    }
+
+    /// POST a JSON body and return the JSON response
+    fn post(&self, path: &str, body: &str) -> io::Result<String> {
+        let mut headers = vec![("Content-Type", "application/json")];
+        if let Some(token) = &self.token {
+            headers.push(("Authorization", token));
+        }
+        match http::request(&self.address, "POST", path, &headers, body)? {
+            (200, response) => Ok(response),
+            (status, response) => Err(status_error(status, &response)),
+        }
+    }
+}
+
+impl MachineIdProvider for Etcd {
+    fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn claim(&mut self, id: u16) -> io::Result<bool> {
+        let key = base64(format!("{}{}", self.key, id).as_bytes());
+        // Put the key only if it does not exist
+        let compare = json::Object::new()
+            .string("key", Some(&key))
+            .string("result", Some("EQUAL"))
+            .string("target", Some("CREATE"))
+            .string("create_revision", Some("0"))
+            .finish();
+        let put = json::Object::new()
+            .string("key", Some(&key))
+            .string("value", Some(&base64(holder().as_bytes())))
+            .string("lease", Some(&self.lease))
+            .finish();
+        let success = json::Object::new().raw("request_put", &put).finish();
+        let body = json::Object::new()
+            .raw("compare", &format!("[{}]", compare))
+            .raw("success", &format!("[{}]", success))
+            .finish();
+        let response = self.post("/v3/kv/txn", &body)?;
+        Ok(json::find(&response, &["succeeded"]).as_deref() == Some("true"))
+    }
+
+    fn renew(&mut self, _id: u16) -> io::Result<bool> {
+        let body = json::Object::new().string("ID", Some(&self.lease)).finish();
+        let response = self.post("/v3/lease/keepalive", &body)?;
+        // An expired lease is kept alive with no TTL
+        let ttl = json::find(&response, &["result", "TTL"]).and_then(|ttl| ttl.parse::<i64>().ok());
+        Ok(ttl.is_some_and(|ttl| ttl > 0))
+    }
+
+    fn release(&mut self, _id: u16) -> io::Result<()> {
+        let body = json::Object::new().string("ID", Some(&self.lease)).finish();
+        self.post("/v3/lease/revoke", &body).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lease::tests::{fake_server, http_exchange, reset, Script};
+
+    const GRANTED: &str = "HTTP/1.1 200 OK\r\n\r\n{\"ID\":\"7587\",\"TTL\":\"30\"}";
+
+    fn connect(credentials: &str, scripts: Vec<Script>) -> Result<Etcd, BcidError> {
+        Etcd::connect(&LeaseUrl::parse(&format!("etcd://{}{}", credentials, fake_server(scripts))).unwrap())
+    }
+
+    #[test]
+    fn refused_credentials_fail_to_connect() {
+        let refused = "HTTP/1.1 401 Unauthorized\r\n\r\n{\"error\":\"authentication failed\"}";
+        let scripts = vec![http_exchange("POST /v3/auth/authenticate ", refused)];
+        match connect("bcid:wrong@", scripts) {
+            Err(BcidError::MachineIdUnavailable(reason)) => assert!(reason.contains("401"), "{reason}"),
+            _ => panic!("connected with refused credentials"),
+        }
+    }
+
+    #[test]
+    fn truncated_and_reset_responses_fail() {
+        let scripts = vec![
+            http_exchange("POST /v3/lease/grant ", GRANTED),
+            http_exchange("POST /v3/kv/txn ", "HTTP/1.1 200"),
+            reset(),
+        ];
+        let mut etcd = connect("", scripts).unwrap();
+        assert_eq!(etcd.lease, "7587");
+        assert_eq!(etcd.claim(7).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(etcd.claim(7).is_err());
+    }
+
+    #[test]
+    fn expired_leases_are_not_renewed() {
+        let scripts = vec![
+            http_exchange("POST /v3/lease/grant ", GRANTED),
+            http_exchange("POST /v3/lease/keepalive ", "HTTP/1.1 200 OK\r\n\r\n{\"result\":{\"ID\":\"7587\"}}"),
+            http_exchange("POST /v3/lease/keepalive ", "HTTP/1.1 200 OK\r\n\r\n{\"result\":{\"ID\":\"7587\",\"TTL\":\"20\"}}"),
+        ];
+        let mut etcd = connect("", scripts).unwrap();
+        assert!(!etcd.renew(7).unwrap());
+        assert!(etcd.renew(7).unwrap());
+    }
+}
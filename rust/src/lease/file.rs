@@ -0,0 +1,101 @@
+//! Machine IDs claimed by locking files in a directory shared by the
+//! processes of one host
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::{holder, MachineIdProvider, DEFAULT_TTL};
+use crate::BcidError;
+
+/// Directory of lock files unless the URL gives one
+pub(super) const DEFAULT_DIR: &str = "/var/lib/bcid/machine-ids";
+
+/// Lock files named after their machine ID, e.g. `17.lock`
+///
+/// The operating system drops a lock when the process holding it exits, so
+/// claims need no time to live; renewal only checks that the lock file has
+/// not been deleted or replaced. Released lock files are left in place, as
+/// deleting them would race with another process locking the same file.
+pub(super) struct LockDir {
+    dir: PathBuf,
+    /// Open, locked files of our claims
+    locked: HashMap<u16, File>,
+}
+
+impl LockDir {
+    pub(super) fn open(dir: &str) -> Result<LockDir, BcidError> {
+        let dir = PathBuf::from(if dir.is_empty() { DEFAULT_DIR } else { dir });
+        fs::create_dir_all(&dir).map_err(|err| {
+            BcidError::MachineIdUnavailable(format!("cannot create {}: {}", dir.display(), err))
+        })?;
+        Ok(LockDir { dir, locked: HashMap::new() })
+    }
+
+    fn path(&self, id: u16) -> PathBuf {
+        self.dir.join(format!("{}.lock", id))
+    }
+}
+
+impl MachineIdProvider for LockDir {
+    fn ttl(&self) -> Duration {
+        DEFAULT_TTL
+    }
+
+    fn claim(&mut self, id: u16) -> io::Result<bool> {
+        let path = self.path(id);
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(false),
+            Err(TryLockError::Error(err)) => return Err(err),
+        }
+        // Record the holder for operators; the lock, not the content, is the claim
+        file.set_len(0)?;
+        writeln!(file, "{}", holder())?;
+        self.locked.insert(id, file);
+        Ok(true)
+    }
+
+    fn renew(&mut self, id: u16) -> io::Result<bool> {
+        match self.locked.get(&id) {
+            Some(file) => is_same_file(file, &self.path(id)),
+            None => Ok(false),
+        }
+    }
+
+    fn release(&mut self, id: u16) -> io::Result<()> {
+        match self.locked.remove(&id) {
+            Some(file) => file.unlock(),
+            None => Ok(()),
+        }
+    }
+
+    /// Claims are atomic on one host, so counting from 0 keeps IDs small and
+    /// reuses the lock files of exited processes
+    fn first_candidate(&self) -> u16 {
+        0
+    }
+}
+
+/// Whether `path` still names the open `file`, rather than nothing or a
+/// file created after ours was deleted
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let ours = file.metadata()?;
+    match fs::metadata(path) {
+        Ok(current) => Ok(current.dev() == ours.dev() && current.ino() == ours.ino()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Whether `path` still exists; open files cannot be deleted on Windows
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, path: &Path) -> io::Result<bool> {
+    Ok(path.exists())
+}
@@ -0,0 +1,122 @@
+//! Plain HTTP requests to the JSON APIs of etcd and Consul
+
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+use super::IO_TIMEOUT;
+
+/// Longest response accepted; lease requests get short JSON answers
+const MAX_RESPONSE_BYTES: u64 = 1024 * 1024;
+
+/// Send one HTTP/1.0 request with a JSON or text body and return the
+/// status and body of the response
+pub(super) fn request(
+    address: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> io::Result<(u16, String)> {
+    let socket = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} does not resolve", address)))?;
+    let mut stream = TcpStream::connect_timeout(&socket, IO_TIMEOUT)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    let mut request = format!("{} {} HTTP/1.0\r\nHost: {}\r\nContent-Length: {}\r\n", method, path, address, body.len());
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    stream.write_all(request.as_bytes())?;
+
+    // HTTP/1.0 responses end when the server closes the connection
+    let mut response = String::new();
+    stream.take(MAX_RESPONSE_BYTES + 1).read_to_string(&mut response)?;
+    if response.len() as u64 > MAX_RESPONSE_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "HTTP response too large"));
+    }
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed HTTP response");
+    let (head, body) = response.split_once("\r\n\r\n").ok_or_else(invalid)?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .filter(|_| head.starts_with("HTTP/1."))
+        .ok_or_else(invalid)?;
+    Ok((status, body.to_string()))
+}
+
+/// An error for a response with an unexpected status
+pub(super) fn status_error(status: u16, body: &str) -> io::Error {
+    io::Error::other(format!("HTTP {}: {}", status, body.trim()))
+}
+
+/// Standard base64 with padding, as etcd's JSON gateway encodes keys and values
+pub(super) fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let bytes = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lease::tests::{fake_server, http_exchange, read_request, reset};
+
+    #[test]
+    fn responses_give_their_status_and_body() {
+        let address = fake_server(vec![http_exchange("GET /v1/ok ", "HTTP/1.1 404 Not Found\r\nX: y\r\n\r\n{}")]);
+        assert_eq!(request(&address, "GET", "/v1/ok", &[], "").unwrap(), (404, "{}".to_string()));
+    }
+
+    #[test]
+    fn malformed_and_truncated_responses_fail() {
+        let responses = ["", "HTTP/1.1 200 OK\r\nContent-Le", "SSH-2.0-OpenSSH\r\n\r\n", "HTTP/1.1 2xx OK\r\n\r\n"];
+        let address = fake_server(responses.iter().map(|&response| http_exchange("PUT /", response)).collect());
+        for response in responses {
+            let err = request(&address, "PUT", "/", &[], "body").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{response:?}");
+        }
+    }
+
+    #[test]
+    fn oversized_responses_fail() {
+        let address = fake_server(vec![Box::new(|stream| {
+            read_request(&stream);
+            let body = vec![b'x'; MAX_RESPONSE_BYTES as usize];
+            let _ = (&stream).write_all(b"HTTP/1.1 200 OK\r\n\r\n").and_then(|_| (&stream).write_all(&body));
+        })]);
+        let err = request(&address, "GET", "/", &[], "").unwrap_err();
+        assert!(err.to_string().contains("too large"), "{err}");
+    }
+
+    #[test]
+    fn reset_connections_fail() {
+        let address = fake_server(vec![reset()]);
+        assert!(request(&address, "PUT", "/", &[], "body").is_err());
+    }
+
+    #[test]
+    fn base64_pads_partial_groups() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"bcid/machine-id/7"), "YmNpZC9tYWNoaW5lLWlkLzc=");
+    }
+}
@@ -0,0 +1,282 @@
+//! Machine IDs claimed as expiring Redis keys, one per machine ID
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use super::{holder, unreachable, LeaseUrl, MachineIdProvider, IO_TIMEOUT};
+use crate::BcidError;
+
+/// Key prefix of claims unless the URL gives `key`
+const DEFAULT_KEY: &str = "bcid:machine-id:";
+
+/// Longest bulk string accepted in a reply; lease commands get short ones
+const MAX_BULK_BYTES: usize = 64 * 1024;
+
+/// Deepest nesting of arrays accepted in a reply
+const MAX_ARRAY_DEPTH: usize = 4;
+
+/// Renew the claim only if it still holds our token
+const RENEW_SCRIPT: &str =
+    "if redis.call('get', KEYS[1]) == ARGV[1] then return redis.call('pexpire', KEYS[1], ARGV[2]) else return 0 end";
+
+/// Delete the claim only if it still holds our token
+const RELEASE_SCRIPT: &str =
+    "if redis.call('get', KEYS[1]) == ARGV[1] then return redis.call('del', KEYS[1]) else return 0 end";
+
+/// Connection settings from a `redis://` URL
+struct RedisConfig {
+    address: String,
+    user: Option<String>,
+    password: Option<String>,
+    database: Option<u32>,
+}
+
+pub(super) struct Redis {
+    config: RedisConfig,
+    connection: Option<RedisConnection>,
+    key: String,
+    ttl: Duration,
+    /// Value of our claims, which renewal and release check
+    token: String,
+}
+
+impl Redis {
+    pub(super) fn connect(url: &LeaseUrl) -> Result<Redis, BcidError> {
+        url.check_params(&[])?;
+        let database = match url.path.as_str() {
+            "" => None,
+            database => Some(database.parse().map_err(|_| {
+                BcidError::MachineIdUnavailable(format!("invalid Redis database '{}' - must be a number", database))
+            })?),
+        };
+        let config =
+            RedisConfig { address: url.address(6379), user: url.user.clone(), password: url.password.clone(), database };
+        let mut redis = Redis {
+            config,
+            connection: None,
+            key: url.key.clone().unwrap_or_else(|| DEFAULT_KEY.to_string()),
+            ttl: url.ttl,
+            token: format!("{}:{:016x}", holder(), rand::random::<u64>()),
+        };
+        redis.connection().map_err(|err| unreachable("Redis", err))?;
+        Ok(redis)
+    }
+
+    /// The open connection, reconnecting if the last one failed
+    fn connection(&mut self) -> io::Result<&mut RedisConnection> {
+        if self.connection.is_none() {
+            self.connection = Some(RedisConnection::open(&self.config)?);
+        }
+        Ok(self.connection.as_mut().expect("connected above"))
+    }
+
+    /// Run a command, dropping the connection if it fails
+    fn command(&mut self, args: &[&str]) -> io::Result<Reply> {
+        let reply = self.connection()?.command(args);
+        if reply.is_err() {
+            self.connection = None;
+        }
+        reply
+    }
+
+    fn key(&self, id: u16) -> String {
+        format!("{}{}", self.key, id)
+    }
+}
+
+impl MachineIdProvider for Redis {
+    fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn claim(&mut self, id: u16) -> io::Result<bool> {
+        let (key, token, ttl) = (self.key(id), self.token.clone(), self.ttl.as_millis().to_string());
+        match self.command(&["SET", &key, &token, "NX", "PX", &ttl])? {
+            Reply::Status => Ok(true),
+            Reply::Nil => Ok(false),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn renew(&mut self, id: u16) -> io::Result<bool> {
+        let (key, token, ttl) = (self.key(id), self.token.clone(), self.ttl.as_millis().to_string());
+        match self.command(&["EVAL", RENEW_SCRIPT, "1", &key, &token, &ttl])? {
+            Reply::Integer(renewed) => Ok(renewed == 1),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn release(&mut self, id: u16) -> io::Result<()> {
+        let (key, token) = (self.key(id), self.token.clone());
+        self.command(&["EVAL", RELEASE_SCRIPT, "1", &key, &token]).map(|_| ())
+    }
+}
+
+/// The kind of a reply in the Redis serialization protocol, with the value
+/// of integers; lease commands need no other values
+#[derive(Debug)]
+enum Reply {
+    Status,
+    Integer(i64),
+    Bulk,
+    Nil,
+    Array,
+}
+
+fn unexpected(reply: Reply) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("unexpected reply {:?}", reply))
+}
+
+struct RedisConnection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl RedisConnection {
+    /// Connect, authenticate and select the database
+    fn open(config: &RedisConfig) -> io::Result<RedisConnection> {
+        let address = config.address.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{} does not resolve", config.address))
+        })?;
+        let stream = TcpStream::connect_timeout(&address, IO_TIMEOUT)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        let mut connection = RedisConnection { reader: BufReader::new(stream.try_clone()?), writer: stream };
+        match (&config.user, &config.password) {
+            (Some(user), Some(password)) => connection.command(&["AUTH", user, password])?,
+            (None, Some(password)) => connection.command(&["AUTH", password])?,
+            _ => Reply::Nil,
+        };
+        if let Some(database) = config.database {
+            connection.command(&["SELECT", &database.to_string()])?;
+        }
+        Ok(connection)
+    }
+
+    /// Send a command and read its reply, turning error replies into errors
+    fn command(&mut self, args: &[&str]) -> io::Result<Reply> {
+        let mut request = format!("*{}\r\n", args.len());
+        for arg in args {
+            request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+        }
+        self.writer.write_all(request.as_bytes())?;
+        self.read_reply(0)
+    }
+
+    /// Read a reply nested in `depth` arrays
+    fn read_reply(&mut self, depth: usize) -> io::Result<Reply> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"));
+        }
+        let Some(line) = line.strip_suffix("\r\n") else {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed within a reply"));
+        };
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("malformed reply '{}'", line));
+        let (kind, rest) = line.split_at_checked(1).ok_or_else(invalid)?;
+        match kind {
+            "+" => Ok(Reply::Status),
+            "-" => Err(io::Error::other(rest.to_string())),
+            ":" => rest.parse().map(Reply::Integer).map_err(|_| invalid()),
+            "$" => match rest.parse::<i64>().map_err(|_| invalid())? {
+                -1 => Ok(Reply::Nil),
+                length => {
+                    let length = usize::try_from(length).ok().filter(|&length| length <= MAX_BULK_BYTES);
+                    // The data and its trailing CRLF
+                    let mut data = vec![0; length.ok_or_else(invalid)? + 2];
+                    io::Read::read_exact(&mut self.reader, &mut data)?;
+                    Ok(Reply::Bulk)
+                }
+            },
+            "*" => match rest.parse::<i64>().map_err(|_| invalid())? {
+                -1 => Ok(Reply::Nil),
+                _ if depth == MAX_ARRAY_DEPTH => Err(invalid()),
+                length => {
+                    for _ in 0..length {
+                        self.read_reply(depth + 1)?;
+                    }
+                    Ok(Reply::Array)
+                }
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+
+    use super::*;
+    use crate::lease::tests::{fake_server, reset, Script};
+
+    /// Read one command sent by the client
+    fn read_command(reader: &mut impl BufRead) -> Vec<String> {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let count: usize = line.trim_end().strip_prefix('*').unwrap().parse().unwrap();
+        let mut read_line = || {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            line.trim_end().to_string()
+        };
+        (0..count).map(|_| { read_line(); read_line() }).collect()
+    }
+
+    /// Answer each command with the next of `replies`, then close the
+    /// connection
+    fn replies(replies: &'static [&'static str]) -> Script {
+        Box::new(move |stream| {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            for reply in replies {
+                read_command(&mut reader);
+                (&stream).write_all(reply.as_bytes()).unwrap();
+            }
+        })
+    }
+
+    fn connect(address: &str, credentials: &str) -> Result<Redis, BcidError> {
+        Redis::connect(&LeaseUrl::parse(&format!("redis://{}{}", credentials, address)).unwrap())
+    }
+
+    #[test]
+    fn refused_credentials_fail_to_connect() {
+        let address = fake_server(vec![replies(&["-WRONGPASS invalid username-password pair\r\n"])]);
+        match connect(&address, "bcid:wrong@") {
+            Err(BcidError::MachineIdUnavailable(reason)) => assert!(reason.contains("WRONGPASS"), "{reason}"),
+            _ => panic!("connected with refused credentials"),
+        }
+    }
+
+    #[test]
+    fn partial_replies_fail_and_reconnect() {
+        let partials: [&'static [&'static str]; 3] = [&[":1"], &["$5\r\nab"], &["*2\r\n:1\r\n"]];
+        let mut scripts: Vec<Script> = partials.into_iter().map(replies).collect();
+        scripts.push(replies(&[":1\r\n"]));
+        let mut redis = connect(&fake_server(scripts), "").unwrap();
+        for _ in partials {
+            assert_eq!(redis.renew(7).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        }
+        assert!(redis.renew(7).unwrap());
+    }
+
+    #[test]
+    fn reset_connections_fail_and_reconnect() {
+        let mut redis = connect(&fake_server(vec![reset(), replies(&["+OK\r\n"])]), "").unwrap();
+        assert!(redis.claim(7).is_err());
+        assert!(redis.claim(7).unwrap());
+    }
+
+    #[test]
+    fn oversized_and_unexpected_replies_fail() {
+        let replies = replies(&["$1000000000\r\n", "*1\r\n*1\r\n*1\r\n*1\r\n*1\r\n:1\r\n", ":1\r\n"]);
+        let mut redis = connect(&fake_server(vec![replies]), "").unwrap();
+        let mut connection = redis.connection.take().unwrap();
+        for _ in 0..2 {
+            assert_eq!(connection.command(&["GET", "k"]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+        redis.connection = Some(connection);
+        assert_eq!(redis.claim(7).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
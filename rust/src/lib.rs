@@ -0,0 +1,650 @@
+//! BCID (Base62 Chronological Identifier) generation and decoding.
+//!
+//! Identifiers are base62 strings, 32 characters long by default, made of a
+//! 4-character prefix followed by either a timestamp, machine ID and random
+//! component (chronological) or a machine ID and fully random data (random).
+//! Prefixes of other lengths (1 to 8 characters) are followed by a `_`
+//! separator, e.g. `user_jqTZNAWSyaab...`.
+//!
+//! ```
+//! # #[cfg(feature = "std")] {
+//! let id = bcid::generate_identifier("TEST", 1, None, false)?;
+//! assert_eq!(id.len(), 32);
+//!
+//! let decoded = bcid::Bcid::parse(&id)?;
+//! assert_eq!(decoded.prefix(), "TEST");
+//! assert_eq!(decoded.machine_id(), 1);
+//! assert_eq!(decoded.identifier_type(), bcid::IdentifierType::Chronological);
+//! assert_eq!(decoded.to_string(), id);
+//! # }
+//! # Ok::<(), bcid::BcidError>(())
+//! ```
+//!
+//! Without the default `std` feature the crate is `no_std` with `alloc`:
+//! [`encode_identifier`] and [`encode_random_identifier`] take the time in
+//! Unix milliseconds and an [`EntropySource`] from the caller, and decoding
+//! works as with `std`. The clock, operating system randomness, generators
+//! and machine IDs need `std`.
+//!
+//! ```
+//! // Any `rand::RngCore` will do, e.g. one over a hardware random number
+//! // generator; this one only steps through values
+//! let mut entropy = rand::rngs::mock::StepRng::new(42, 0x9e37_79b9_7f4a_7c15);
+//! let id = bcid::encode_identifier("TEST", 1, 1_704_067_200_000, bcid::Format::V2, &mut entropy)?;
+//! assert_eq!(bcid::Bcid::parse(&id)?.datetime(), chrono::DateTime::from_timestamp_millis(1_704_067_200_000));
+//! # Ok::<(), bcid::BcidError>(())
+//! ```
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::string::ToString;
+
+use chrono::{Datelike, DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc};
+
+use base62::{encode_fixed, encode_into};
+#[cfg(feature = "std")]
+use entropy::{os_entropy, random_u16};
+use entropy::{fill_random_chars, random_value};
+
+mod alphabet;
+mod base62;
+mod check_digit;
+#[cfg(feature = "std")]
+mod clock;
+mod content;
+mod entropy;
+mod environment;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod format;
+#[cfg(feature = "std")]
+mod generator;
+mod identifier;
+mod layout;
+#[cfg(feature = "std")]
+mod lease;
+#[doc(hidden)]
+pub mod json;
+#[doc(hidden)]
+pub mod __private {
+    //! Dependencies of the [`define_bcid!`](crate::define_bcid) expansion
+    pub use alloc::string::String;
+    #[cfg(feature = "serde")]
+    pub use serde;
+}
+#[cfg(feature = "std")]
+mod machine_id;
+#[cfg(feature = "std")]
+mod monotonic;
+mod opaque;
+mod precision;
+mod prefix;
+mod safe;
+#[cfg(feature = "std")]
+mod selftest;
+#[cfg(feature = "serde")]
+mod serde_impls;
+#[cfg(feature = "std")]
+mod shared;
+mod signature;
+#[cfg(any(feature = "sqlx", feature = "diesel"))]
+mod sql;
+#[cfg(any(feature = "arbitrary", feature = "proptest"))]
+pub mod strategy;
+mod typed;
+mod typeid;
+mod ulid;
+mod uuid;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use alphabet::Alphabet;
+pub use base62::{base62_decode, base62_encode, BASE62, SORTABLE_BASE62};
+pub use check_digit::{append_check_digit, verify_check_digit};
+#[cfg(feature = "std")]
+pub use clock::{Clock, MockClock, SystemClock};
+pub use entropy::EntropySource;
+pub use environment::Environment;
+pub use error::BcidError;
+pub use format::Format;
+#[cfg(feature = "std")]
+pub use generator::{BcidGenerator, ClockRegression, GeneratorState, SequenceOverflow};
+pub use identifier::{Bcid, DecodedBcid, Field, IdentifierType};
+pub use layout::BitLayout;
+#[cfg(feature = "std")]
+pub use lease::{MachineIdLease, MachineIdProvider};
+#[cfg(feature = "std")]
+pub use machine_id::{derive_machine_id, hash_machine_id, kubernetes_machine_id, DerivedMachineId, MachineIdSource};
+#[cfg(feature = "std")]
+pub use monotonic::generate_monotonic_identifier;
+pub use opaque::{decrypt_identifier, encrypt_identifier};
+pub use precision::Precision;
+pub use prefix::Prefix;
+pub use safe::is_safe_identifier;
+#[cfg(feature = "std")]
+pub use selftest::{selftest, SelftestFailure, SelftestReport};
+#[cfg(feature = "std")]
+pub use shared::SharedGenerator;
+pub use signature::{sign_identifier, verify_signature, DEFAULT_SIGNATURE_LENGTH, MAX_SIGNATURE_LENGTH};
+
+/// Length of identifiers unless configured otherwise (see
+/// [`BcidGenerator::length`])
+///
+/// Lengths count every character of the canonical form, the prefix and
+/// separator included, so identifiers with longer prefixes have less random
+/// padding. Only the separator that [`Bcid::to_separated`] writes after a
+/// 4-character prefix is left out.
+pub const DEFAULT_LENGTH: usize = 32;
+
+/// Shortest identifier length, which fits the widest fixed-width fields
+/// (prefix, marker and 19 characters of Crockford base32 fields)
+///
+/// Prefixes of 5 to 8 characters and their separator take more room, so
+/// identifiers with them must be longer (see [`min_length`]).
+pub const MIN_LENGTH: usize = 24;
+
+/// Longest identifier length
+pub const MAX_LENGTH: usize = 64;
+
+/// Fewest characters of encoded fields, which every identifier has
+pub(crate) const MIN_FIELDS_LENGTH: usize = MIN_LENGTH - 4;
+
+/// Check that an identifier length is between [`MIN_LENGTH`] and [`MAX_LENGTH`]
+#[cfg(feature = "std")]
+pub(crate) fn validate_length(length: usize) -> Result<(), BcidError> {
+    if !(MIN_LENGTH..=MAX_LENGTH).contains(&length) {
+        return Err(BcidError::InvalidIdentifierLength(length));
+    }
+    Ok(())
+}
+
+/// Shortest identifier length with a prefix of `prefix_len` characters:
+/// [`MIN_LENGTH`], or more for prefixes longer than 4 characters
+///
+/// ```
+/// assert_eq!(bcid::min_length(4), 24);
+/// assert_eq!(bcid::min_length(8), 29);
+/// ```
+pub fn min_length(prefix_len: usize) -> usize {
+    MIN_LENGTH.max(fields_start(prefix_len) + MIN_FIELDS_LENGTH)
+}
+
+/// Shortest prefix
+pub const MIN_PREFIX_LENGTH: usize = 1;
+
+/// Longest prefix
+pub const MAX_PREFIX_LENGTH: usize = 8;
+
+/// Separator between the prefix and the encoded fields
+///
+/// Identifiers with a 4-character prefix are written without it (the packed
+/// form) unless asked for with [`Bcid::to_separated`]; every other prefix
+/// length needs it, since nothing else marks where the prefix ends.
+pub const SEPARATOR: char = '_';
+
+/// Longest encoded identifier
+pub(crate) const MAX_ENCODED_LENGTH: usize = MAX_LENGTH;
+
+/// Check that a prefix is 1 to 8 printable ASCII characters other than the
+/// separator
+pub(crate) fn validate_prefix(prefix: &str) -> Result<(), BcidError> {
+    let len = prefix.chars().count();
+    if !(MIN_PREFIX_LENGTH..=MAX_PREFIX_LENGTH).contains(&len) {
+        return Err(BcidError::InvalidPrefixLength(len));
+    }
+    match prefix.chars().find(|&c| !c.is_ascii_graphic() || c == SEPARATOR) {
+        Some(character) => Err(BcidError::InvalidPrefixCharacter(character)),
+        None => Ok(()),
+    }
+}
+
+/// Offset of the encoded fields in the canonical form of an identifier whose
+/// prefix has `prefix_len` characters: right after a 4-character prefix,
+/// otherwise after the separator
+pub(crate) fn fields_start(prefix_len: usize) -> usize {
+    if prefix_len == 4 {
+        4
+    } else {
+        prefix_len + 1
+    }
+}
+
+/// Write the prefix, and the separator if it needs one, to the start of
+/// `out`, returning where the encoded fields start
+pub(crate) fn write_prefix(out: &mut [u8], prefix: &str) -> usize {
+    out[..prefix.len()].copy_from_slice(prefix.as_bytes());
+    let start = fields_start(prefix.len());
+    if start > prefix.len() {
+        out[prefix.len()] = SEPARATOR as u8;
+    }
+    start
+}
+
+/// Split an ASCII identifier into its prefix and encoded fields: at the
+/// separator if it has one, otherwise after 4 characters
+///
+/// Fails if a separated prefix is invalid, or if the identifier is not
+/// [`MIN_LENGTH`] to [`MAX_LENGTH`] characters long (leaving out a
+/// separator after a 4-character prefix) with at least
+/// [`MIN_FIELDS_LENGTH`] characters of fields.
+pub(crate) fn split_prefix(identifier: &str) -> Result<(&str, &str), BcidError> {
+    let (prefix, fields) = match identifier.split_once(SEPARATOR) {
+        Some((prefix, fields)) => {
+            validate_prefix(prefix)?;
+            (prefix, fields)
+        }
+        None if identifier.len() >= 4 => identifier.split_at(4),
+        None => return Err(BcidError::InvalidIdentifierLength(identifier.len())),
+    };
+    let length = fields_start(prefix.len()) + fields.len();
+    if !(MIN_LENGTH..=MAX_LENGTH).contains(&length) || fields.len() < MIN_FIELDS_LENGTH {
+        return Err(BcidError::InvalidIdentifierLength(identifier.len()));
+    }
+    Ok((prefix, fields))
+}
+
+/// Unix timestamps with more digits than this are in milliseconds (10
+/// digits of seconds last until the year 2286)
+const MAX_EPOCH_SECONDS_DIGITS: usize = 10;
+
+/// Parse user-supplied date/time string
+///
+/// Accepts RFC 3339 date/times with a `Z` or `+09:00` offset, which are
+/// converted to UTC; `2023-12-25T10:30:00`, `2023-12-25 10:30:00` or
+/// `2023-12-25`, interpreted as UTC; fractional seconds in any of the
+/// date/times (`10:30:00.123`, truncated to 10ms); and Unix timestamps: `@1703500200` (seconds), or
+/// bare digits, which are seconds up to 10 digits and milliseconds beyond
+/// (`1703500200` or `1703500200123`). Relative times are resolved against
+/// the current time (see [`parse_user_datetime_at`]).
+#[cfg(feature = "std")]
+pub fn parse_user_datetime(datetime_str: &str) -> Result<DateTime<Utc>, BcidError> {
+    parse_user_datetime_at(datetime_str, Utc::now())
+}
+
+/// [`parse_user_datetime`], resolving relative times against `now`
+///
+/// Relative times are `now`, `yesterday` and `tomorrow` (24 hours either
+/// way), an amount and unit `ago` or after `in` (`2 hours ago`, `in 3 days`),
+/// or an amount and unit with a sign (`+30m`, `-1w`). Units are seconds
+/// (`s`, `sec`, `second`), minutes (`m`, `min`, `minute`), hours (`h`, `hr`,
+/// `hour`), days (`d`, `day`) and weeks (`w`, `week`), with or without a
+/// space and a plural `s`.
+///
+/// ```
+/// let now = "2024-01-02T12:00:00Z".parse().unwrap();
+/// let parse = |input| bcid::parse_user_datetime_at(input, now);
+/// assert_eq!(parse("2 hours ago")?, parse("2024-01-02T10:00:00Z")?);
+/// assert_eq!(parse("yesterday")?, parse("2024-01-01T12:00:00Z")?);
+/// assert_eq!(parse("+30m")?, parse("2024-01-02T12:30:00Z")?);
+/// # Ok::<(), bcid::BcidError>(())
+/// ```
+pub fn parse_user_datetime_at(datetime_str: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, BcidError> {
+    if let Some(datetime) = parse_epoch(datetime_str).or_else(|| parse_relative(datetime_str, now)) {
+        return datetime.ok_or_else(|| BcidError::InvalidDatetime(datetime_str.to_string()));
+    }
+
+    // RFC 3339, with an offset
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(datetime_str) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+
+    // Try ISO 8601 format without an offset
+    if let Ok(naive) = NaiveDateTime::parse_from_str(datetime_str, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Ok(Utc.from_utc_datetime(&naive));
+    }
+    
+    // Try space-separated format
+    if let Ok(naive) = NaiveDateTime::parse_from_str(datetime_str, "%Y-%m-%d %H:%M:%S%.f") {
+        return Ok(Utc.from_utc_datetime(&naive));
+    }
+    
+    // Try date only (default to 00:00:00)
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(datetime_str, "%Y-%m-%d") {
+        let naive = date
+            .and_hms_opt(0, 0, 0)
+            .ok_or_else(|| BcidError::InvalidDatetime(datetime_str.to_string()))?;
+        return Ok(Utc.from_utc_datetime(&naive));
+    }
+    
+    Err(BcidError::InvalidDatetime(datetime_str.to_string()))
+}
+
+/// Resolve a relative time against `now`, if `input` is one: `None` if it is
+/// not, and `Some(None)` if the result is out of range
+fn parse_relative(input: &str, now: DateTime<Utc>) -> Option<Option<DateTime<Utc>>> {
+    let input = input.trim().to_ascii_lowercase();
+    let (is_past, amount) = match input.as_str() {
+        "now" => return Some(Some(now)),
+        "yesterday" => (true, "1d"),
+        "tomorrow" => (false, "1d"),
+        _ => match (input.strip_suffix(" ago"), input.strip_prefix("in "), input.strip_prefix(['+', '-'])) {
+            (Some(amount), None, None) => (true, amount),
+            (None, Some(amount), None) => (false, amount),
+            (None, None, Some(amount)) => (input.starts_with('-'), amount),
+            _ => return None,
+        },
+    };
+    let duration = parse_relative_duration(amount.trim())?;
+    Some(if is_past { now.checked_sub_signed(duration) } else { now.checked_add_signed(duration) })
+}
+
+/// An amount and unit such as `30m` or `2 hours`
+fn parse_relative_duration(input: &str) -> Option<chrono::Duration> {
+    let unit_start = input.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = input.split_at(unit_start);
+    let amount: i64 = amount.parse().ok()?;
+    let unit_seconds = match unit.trim_start() {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 3600,
+        "d" | "day" | "days" => 86400,
+        "w" | "week" | "weeks" => 604800,
+        _ => return None,
+    };
+    chrono::Duration::try_seconds(amount.checked_mul(unit_seconds)?)
+}
+
+/// Parse a Unix timestamp, if `input` is one: `None` if it is not, and
+/// `Some(None)` if it is out of range
+fn parse_epoch(input: &str) -> Option<Option<DateTime<Utc>>> {
+    let (digits, is_seconds) = match input.strip_prefix('@') {
+        Some(digits) => (digits, true),
+        None => (input, input.len() <= MAX_EPOCH_SECONDS_DIGITS),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let value: i64 = digits.parse().ok()?;
+    Some(if is_seconds { DateTime::from_timestamp(value, 0) } else { DateTime::from_timestamp_millis(value) })
+}
+
+/// Generate a time-orderable, base62 string identifier with a prefix
+///
+/// `user_datetime` overrides the current time (see [`parse_user_datetime`]),
+/// and `is_random` delegates to [`generate_random_identifier`].
+///
+/// Fails if the prefix is not 1 to 8 printable ASCII characters other than
+/// `_`, or the datetime cannot be parsed.
+#[cfg(feature = "std")]
+pub fn generate_identifier(prefix: &str, machine_id: u16, user_datetime: Option<&str>, is_random: bool) -> Result<String, BcidError> {
+    generate_identifier_with(prefix, machine_id, user_datetime, is_random, &mut os_entropy())
+}
+
+/// [`generate_identifier`] drawing randomness from `entropy` instead of the
+/// operating system
+#[cfg(feature = "std")]
+pub fn generate_identifier_with(
+    prefix: &str,
+    machine_id: u16,
+    user_datetime: Option<&str>,
+    is_random: bool,
+    entropy: &mut dyn EntropySource,
+) -> Result<String, BcidError> {
+    validate_prefix(prefix)?;
+
+    if is_random {
+        return Ok(encode_random(prefix, machine_id, entropy));
+    }
+
+    let timestamp = resolve_timestamp(user_datetime)?;
+
+    // Generate random value
+    let random_value = random_value(entropy);
+
+    Ok(encode_chronological(Format::V1, prefix, timestamp, machine_id, random_value, entropy))
+}
+
+/// Generate a v2 identifier whose string order matches chronological order
+///
+/// Uses fixed-width fields and the [`SORTABLE_BASE62`] alphabet (see
+/// [`Format::V2`]), so `ORDER BY id` sorts identifiers with the same prefix by
+/// time, then machine ID, then random value.
+#[cfg(feature = "std")]
+pub fn generate_sortable_identifier(prefix: &str, machine_id: u16, user_datetime: Option<&str>) -> Result<String, BcidError> {
+    validate_prefix(prefix)?;
+    let timestamp = resolve_timestamp(user_datetime)?;
+    let mut entropy = os_entropy();
+    let random_value = random_u16(&mut entropy);
+    Ok(encode_chronological(Format::V2, prefix, timestamp, machine_id, random_value, &mut entropy))
+}
+
+/// Packed `YYYYMMDDHHmmSScc` timestamp for a user-supplied or the current time
+#[cfg(feature = "std")]
+pub(crate) fn resolve_timestamp(user_datetime: Option<&str>) -> Result<u64, BcidError> {
+    // Use user-supplied datetime or current UTC time
+    let datetime = if let Some(datetime_str) = user_datetime {
+        parse_user_datetime(datetime_str)?
+    } else {
+        Utc::now()
+    };
+    Ok(pack_timestamp(&datetime))
+}
+
+/// Pack a UTC datetime into the `YYYYMMDDHHmmSScc` timestamp (10ms resolution)
+pub(crate) fn pack_timestamp(datetime: &DateTime<Utc>) -> u64 {
+    // Digits of YYYYMMDDHHmmSS (UTC) followed by hundredths of a second; a
+    // leap second counts as second 60
+    let second = datetime.second() + u32::from(datetime.nanosecond() >= 1_000_000_000);
+    let centis = datetime.timestamp_subsec_millis().min(999) / 10;
+    [datetime.month(), datetime.day(), datetime.hour(), datetime.minute(), second, centis]
+        .iter()
+        .fold(datetime.year() as u64, |packed, &part| packed * 100 + part as u64)
+}
+
+/// Unpack a `YYYYMMDDHHmmSScc` timestamp into a UTC datetime
+pub(crate) fn unpack_timestamp(timestamp: u64) -> Option<DateTime<Utc>> {
+    if timestamp > 9999999999999999 {
+        return None;
+    }
+    let part = |position: u32| (timestamp / 10u64.pow(position) % 100) as u32;
+    let date = NaiveDate::from_ymd_opt((timestamp / 1_000_000_000_000) as i32, part(10), part(8))?;
+    let time = match part(2) {
+        60 => NaiveTime::from_hms_nano_opt(part(6), part(4), 59, 1_000_000_000)?,
+        second => NaiveTime::from_hms_opt(part(6), part(4), second)?,
+    };
+    Some(Utc.from_utc_datetime(&date.and_time(time)) + chrono::Duration::milliseconds(part(0) as i64 * 10))
+}
+
+/// Largest Unix timestamp in milliseconds UUIDv7 and ULID can hold (48 bits)
+const MAX_UNIX_MILLIS: u64 = (1 << 48) - 1;
+
+/// Unix timestamp in milliseconds of a chronological identifier, for
+/// formats with a 48-bit millisecond timestamp
+pub(crate) fn unix_millis(bcid: &Bcid) -> Result<u64, BcidError> {
+    let datetime = bcid.datetime().ok_or(BcidError::NotChronological)?;
+    u64::try_from(datetime.timestamp_millis())
+        .ok()
+        .filter(|ms| *ms <= MAX_UNIX_MILLIS)
+        .ok_or(BcidError::ValueOutOfRange { field: "timestamp", value: bcid.timestamp().unwrap_or_default() })
+}
+
+/// Packed timestamp for a Unix timestamp in milliseconds, truncated to 10ms
+pub(crate) fn pack_unix_millis(unix_ms: u64) -> Result<u64, BcidError> {
+    let datetime = i64::try_from(unix_ms)
+        .ok()
+        .and_then(DateTime::<Utc>::from_timestamp_millis)
+        .ok_or(BcidError::ValueOutOfRange { field: "timestamp", value: unix_ms })?;
+    Ok(pack_timestamp(&datetime))
+}
+
+/// 64-bit FNV-1a hash
+pub(crate) fn fnv1a(input: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in input.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Length of identifiers in `format` with `prefix` from the functions that
+/// take no length: [`DEFAULT_LENGTH`], unless the packed fields of a v5
+/// identifier need more after a prefix of 6 to 8 characters
+pub(crate) fn chronological_length(prefix: &str, format: Format) -> usize {
+    match format {
+        Format::V5 => DEFAULT_LENGTH.max(fields_start(prefix.len()) + BitLayout::fields_len(Alphabet::Base62)),
+        _ => DEFAULT_LENGTH,
+    }
+}
+
+/// Encode a chronological identifier from its components, adding random
+/// padding up to [`chronological_length`]
+pub(crate) fn encode_chronological(
+    format: Format,
+    prefix: &str,
+    timestamp: u64,
+    machine_id: u16,
+    random_value: u16,
+    entropy: &mut dyn EntropySource,
+) -> String {
+    let mut out = [0u8; MAX_ENCODED_LENGTH];
+    let out = &mut out[..chronological_length(prefix, format)];
+    encode_chronological_into(out, format, prefix, timestamp, machine_id, random_value, entropy);
+    identifier_string(out)
+}
+
+/// [`encode_chronological`] into a caller-provided buffer, filling all of it
+pub(crate) fn encode_chronological_into(
+    out: &mut [u8],
+    format: Format,
+    prefix: &str,
+    timestamp: u64,
+    machine_id: u16,
+    random_value: u16,
+    entropy: &mut dyn EntropySource,
+) {
+    if format == Format::V5 {
+        let layout = BitLayout::default();
+        let millis = layout::unix_millis((timestamp, 0)).expect("callers check that v5 times are after 1970");
+        let value = layout.pack(millis, machine_id, 0, random_value, entropy).expect("years to 9999 fit 48 bits");
+        return layout.encode_into(out, Alphabet::Base62, prefix, value, entropy);
+    }
+    if let Some(layout) = format::FixedLayout::of(format, Alphabet::Base62, Precision::default()) {
+        return layout.encode_into(out, prefix, (timestamp, 0), machine_id, random_value, entropy);
+    }
+
+    // Convert components to base62 (the machine ID always takes 3 characters)
+    let start = write_prefix(out, prefix);
+    let mut len = start + encode_into(timestamp, &mut out[start..]);
+    encode_fixed(BASE62, machine_id as u64, &mut out[len..len + 3]);
+    len += 3;
+    len += encode_into(random_value as u64, &mut out[len..]);
+
+    // Fill the rest with random padding
+    fill_random_chars(entropy, BASE62, &mut out[len..]);
+}
+
+/// An encoded identifier as a `String`
+pub(crate) fn identifier_string(out: &[u8]) -> String {
+    String::from_utf8(out.to_vec()).expect("identifiers are ASCII")
+}
+
+/// Generate a fully random (non-chronological) base62 string identifier with a prefix
+///
+/// Fails if the prefix is not 1 to 8 printable ASCII characters other than
+/// `_`.
+#[cfg(feature = "std")]
+pub fn generate_random_identifier(prefix: &str, machine_id: u16) -> Result<String, BcidError> {
+    validate_prefix(prefix)?;
+    Ok(encode_random(prefix, machine_id, &mut os_entropy()))
+}
+
+/// Encode a random identifier: the random marker and the machine ID
+/// followed by random data
+pub(crate) fn encode_random(prefix: &str, machine_id: u16, entropy: &mut dyn EntropySource) -> String {
+    let mut out = [0u8; MAX_ENCODED_LENGTH];
+    let out = &mut out[..DEFAULT_LENGTH];
+    encode_random_into(out, prefix, machine_id, entropy);
+    identifier_string(out)
+}
+
+/// [`encode_random`] into a caller-provided buffer, filling all of it
+pub(crate) fn encode_random_into(out: &mut [u8], prefix: &str, machine_id: u16, entropy: &mut dyn EntropySource) {
+    // The marker tells random identifiers from v1 ones, and the machine ID
+    // always takes 3 characters, padded with 'a' (zero)
+    let start = write_prefix(out, prefix);
+    out[start] = format::RANDOM_MARKER;
+    encode_fixed(BASE62, machine_id as u64, &mut out[start + 1..start + 4]);
+
+    // Generate the remaining characters as fully random data
+    fill_random_chars(entropy, BASE62, &mut out[start + 4..]);
+}
+
+/// Encode a chronological identifier in `format` for a Unix timestamp in
+/// milliseconds (truncated to 10ms), drawing randomness from `entropy`
+///
+/// Needs neither a clock nor operating system randomness, so it is
+/// available without the `std` feature. Fails if the prefix is not 1 to 8
+/// printable ASCII characters other than `_`, or the timestamp is past the
+/// year 9999.
+pub fn encode_identifier(
+    prefix: &str,
+    machine_id: u16,
+    unix_ms: u64,
+    format: Format,
+    entropy: &mut dyn EntropySource,
+) -> Result<String, BcidError> {
+    validate_prefix(prefix)?;
+    let timestamp = pack_unix_millis(unix_ms)?;
+    let random_value = random_value(entropy);
+    Ok(encode_chronological(format, prefix, timestamp, machine_id, random_value, entropy))
+}
+
+/// Encode a fully random identifier, drawing randomness from `entropy`
+///
+/// The `no_std` counterpart of [`generate_random_identifier`], with the
+/// same errors.
+pub fn encode_random_identifier(prefix: &str, machine_id: u16, entropy: &mut dyn EntropySource) -> Result<String, BcidError> {
+    validate_prefix(prefix)?;
+    Ok(encode_random(prefix, machine_id, entropy))
+}
+
+/// Decode a BCID into its component parts
+///
+/// Equivalent to [`Bcid::parse`]; the identifier type is detected
+/// automatically.
+pub fn decode_identifier(identifier: &str) -> Result<Bcid, BcidError> {
+    Bcid::parse(identifier)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+
+    #[test]
+    fn min_length_counts_the_prefix_and_separator() {
+        assert_eq!(min_length(1), MIN_LENGTH);
+        assert_eq!(min_length(4), MIN_LENGTH);
+        assert_eq!(min_length(5), MIN_LENGTH + 2);
+        assert_eq!(min_length(8), 29);
+    }
+
+    #[test]
+    fn split_prefix_reports_the_input_length() {
+        let fields = "a".repeat(MIN_FIELDS_LENGTH);
+        assert_eq!(split_prefix(&format!("TEST{fields}")), Ok(("TEST", fields.as_str())));
+        assert_eq!(split_prefix(&format!("TEST_{fields}")), Ok(("TEST", fields.as_str())));
+        assert_eq!(split_prefix(&format!("ABCDEFGH_{fields}")), Ok(("ABCDEFGH", fields.as_str())));
+        assert_eq!(split_prefix(&format!("ABCDEFGH_{}", &fields[1..])), Err(BcidError::InvalidIdentifierLength(28)));
+        assert_eq!(split_prefix("AB_aaaa"), Err(BcidError::InvalidIdentifierLength(7)));
+        assert_eq!(split_prefix("AB"), Err(BcidError::InvalidIdentifierLength(2)));
+        let long = format!("TEST{}", "a".repeat(MAX_LENGTH - 3));
+        assert_eq!(split_prefix(&long), Err(BcidError::InvalidIdentifierLength(MAX_LENGTH + 1)));
+    }
+
+    #[test]
+    fn chronological_length_fits_v5_fields() {
+        assert_eq!(chronological_length("ORDERS", Format::V2), DEFAULT_LENGTH);
+        for prefix in ["T", "TEST", "ORDERS", "ABCDEFGH"] {
+            let length = chronological_length(prefix, Format::V5);
+            assert!(length >= DEFAULT_LENGTH);
+            assert!(length >= fields_start(prefix.len()) + BitLayout::fields_len(Alphabet::Base62));
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use crate::{fnv1a, BcidError};
+
+/// Link-local address of the EC2, GCE and Azure instance metadata services
+const METADATA_ADDR: ([u8; 4], u16) = ([169, 254, 169, 254], 80);
+
+/// How long to wait for the metadata service before assuming we are not in a cloud
+const METADATA_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Where an automatically derived machine ID came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MachineIdSource {
+    /// Instance ID from a cloud metadata service (`ec2`, `gce` or `azure`)
+    CloudMetadata(&'static str),
+    /// Hardware address of the primary network interface
+    MacAddress,
+    /// The host name
+    Hostname,
+    /// Ordinal of a Kubernetes StatefulSet pod, from its pod name or index label
+    StatefulSetOrdinal,
+    /// UID of a Kubernetes pod, from the downward API
+    PodUid,
+}
+
+impl fmt::Display for MachineIdSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MachineIdSource::CloudMetadata(provider) => write!(f, "{} instance metadata", provider),
+            MachineIdSource::MacAddress => f.write_str("MAC address"),
+            MachineIdSource::Hostname => f.write_str("hostname"),
+            MachineIdSource::StatefulSetOrdinal => f.write_str("StatefulSet pod"),
+            MachineIdSource::PodUid => f.write_str("pod UID"),
+        }
+    }
+}
+
+/// A machine ID derived from the host, with the value it was derived from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedMachineId {
+    pub id: u16,
+    pub source: MachineIdSource,
+    /// The instance ID, MAC address or host name that was hashed, or the
+    /// pod name or UID in Kubernetes
+    pub input: String,
+}
+
+/// Derive a stable 16-bit machine ID from the host
+///
+/// Tries, in order, the cloud instance ID (EC2, GCE, Azure), the MAC address
+/// of the primary network interface, and the host name, and hashes the first
+/// one found with [`hash_machine_id`]. The same host always maps to the same
+/// ID, but distinct hosts can collide (65536 possible values).
+pub fn derive_machine_id() -> Result<DerivedMachineId, BcidError> {
+    let (source, input) = cloud_instance_id()
+        .map(|(provider, id)| (MachineIdSource::CloudMetadata(provider), id))
+        .or_else(|| primary_mac_address().map(|mac| (MachineIdSource::MacAddress, mac)))
+        .or_else(|| hostname().map(|name| (MachineIdSource::Hostname, name)))
+        .ok_or_else(|| {
+            BcidError::MachineIdUnavailable("no cloud metadata, MAC address or hostname found".to_string())
+        })?;
+    Ok(DerivedMachineId { id: hash_machine_id(&input), source, input })
+}
+
+/// Derive a machine ID for a Kubernetes pod
+///
+/// Pods of a StatefulSet use their ordinal, so that its first 65536 replicas
+/// never collide. The ordinal comes from `POD_INDEX` (the
+/// `apps.kubernetes.io/pod-index` label exposed through the downward API) or
+/// the `-N` suffix of the pod name (`POD_NAME`, or the host name inside a
+/// pod). Other pods hash `POD_UID`, the pod UID exposed through the downward
+/// API, with [`hash_machine_id`], which can collide like any derived ID.
+///
+/// Pods of different StatefulSets share ordinals; give them distinct machine
+/// IDs, or coordinator leases, if they generate identifiers with the same
+/// prefix.
+pub fn kubernetes_machine_id() -> Result<DerivedMachineId, BcidError> {
+    let env = |name: &str| std::env::var(name).ok().map(|value| value.trim().to_string()).filter(|v| !v.is_empty());
+    let ordinal = |input: String, value: &str| -> Result<DerivedMachineId, BcidError> {
+        let id = value.parse().map_err(|_| {
+            BcidError::MachineIdUnavailable(format!("StatefulSet ordinal {} is over 65535", value))
+        })?;
+        Ok(DerivedMachineId { id, source: MachineIdSource::StatefulSetOrdinal, input })
+    };
+
+    // The host name of a pod is its name, but only trust it inside a cluster
+    let pod_name = env("POD_NAME").or_else(|| env("KUBERNETES_SERVICE_HOST").and(hostname()));
+    if let Some(index) = env("POD_INDEX") {
+        if !is_ordinal(&index) {
+            return Err(BcidError::MachineIdUnavailable(format!("POD_INDEX '{}' is not a pod ordinal", index)));
+        }
+        return ordinal(pod_name.unwrap_or_else(|| format!("with POD_INDEX {}", index)), &index);
+    }
+    if let Some(name) = pod_name {
+        if let Some((_, suffix)) = name.rsplit_once('-').filter(|(_, suffix)| is_ordinal(suffix) && !is_generated(suffix)) {
+            return ordinal(name.clone(), suffix);
+        }
+    }
+    match env("POD_UID") {
+        Some(uid) => Ok(DerivedMachineId { id: hash_machine_id(&uid), source: MachineIdSource::PodUid, input: uid }),
+        None => Err(BcidError::MachineIdUnavailable(
+            "not a StatefulSet pod and POD_UID is not set (expose metadata.uid through the downward API)".to_string(),
+        )),
+    }
+}
+
+/// A decimal number without leading zeros, as Kubernetes writes ordinals
+fn is_ordinal(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) && (value == "0" || !value.starts_with('0'))
+}
+
+/// Whether a pod name suffix could be the random one Kubernetes appends to
+/// pods of a ReplicaSet or Job: five characters from an alphabet without
+/// vowels or the digits 0, 1 and 3
+fn is_generated(suffix: &str) -> bool {
+    suffix.len() == 5 && suffix.bytes().all(|b| b"bcdfghjklmnpqrstvwxz2456789".contains(&b))
+}
+
+/// Hash an arbitrary host identifier down to a 16-bit machine ID
+///
+/// Uses 64-bit FNV-1a folded to 16 bits; the mapping is part of the format
+/// and will not change between releases.
+pub fn hash_machine_id(input: &str) -> u16 {
+    let hash = fnv1a(input);
+    ((hash >> 48) ^ (hash >> 32) ^ (hash >> 16) ^ hash) as u16
+}
+
+/// Instance ID from the first cloud metadata service that answers
+fn cloud_instance_id() -> Option<(&'static str, String)> {
+    let addr = SocketAddr::from(METADATA_ADDR);
+    // Bail out quickly when there is no metadata service at all
+    TcpStream::connect_timeout(&addr, METADATA_TIMEOUT).ok()?;
+
+    let ec2 = || {
+        let token = metadata_request(
+            "PUT",
+            "/latest/api/token",
+            &[("X-aws-ec2-metadata-token-ttl-seconds", "60")],
+        )?;
+        metadata_request("GET", "/latest/meta-data/instance-id", &[("X-aws-ec2-metadata-token", &token)])
+    };
+    let gce = || metadata_request("GET", "/computeMetadata/v1/instance/id", &[("Metadata-Flavor", "Google")]);
+    let azure = || {
+        metadata_request(
+            "GET",
+            "/metadata/instance/compute/vmId?api-version=2021-02-01&format=text",
+            &[("Metadata", "true")],
+        )
+    };
+
+    ec2()
+        .map(|id| ("ec2", id))
+        .or_else(|| gce().map(|id| ("gce", id)))
+        .or_else(|| azure().map(|id| ("azure", id)))
+}
+
+/// Body of a successful HTTP/1.0 request to the metadata service
+fn metadata_request(method: &str, path: &str, headers: &[(&str, &str)]) -> Option<String> {
+    let mut stream = TcpStream::connect_timeout(&SocketAddr::from(METADATA_ADDR), METADATA_TIMEOUT).ok()?;
+    stream.set_read_timeout(Some(METADATA_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(METADATA_TIMEOUT)).ok()?;
+
+    let mut request = format!("{} {} HTTP/1.0\r\nHost: 169.254.169.254\r\nContent-Length: 0\r\n", method, path);
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).ok()?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+    let (head, body) = response.split_once("\r\n\r\n")?;
+    if !head.starts_with("HTTP/1.") || head.split_whitespace().nth(1) != Some("200") {
+        return None;
+    }
+    let body = body.trim();
+    (!body.is_empty()).then(|| body.to_string())
+}
+
+/// MAC address of the primary network interface (Linux)
+///
+/// Prefers interfaces backed by a physical device, then the first by name,
+/// skipping loopback and all-zero addresses.
+fn primary_mac_address() -> Option<String> {
+    let mut interfaces: Vec<(bool, String, String)> = fs::read_dir("/sys/class/net")
+        .ok()?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?.to_string();
+            let mac = fs::read_to_string(path.join("address")).ok()?.trim().to_lowercase();
+            if name == "lo" || mac.is_empty() || mac.chars().all(|c| c == '0' || c == ':') {
+                return None;
+            }
+            let is_physical = path.join("device").exists();
+            Some((!is_physical, name, mac))
+        })
+        .collect();
+    interfaces.sort();
+    interfaces.into_iter().next().map(|(_, _, mac)| mac)
+}
+
+/// The host name, from the kernel or the environment
+pub(crate) fn hostname() -> Option<String> {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .chain(["HOSTNAME", "COMPUTERNAME"].iter().filter_map(|var| std::env::var(var).ok()))
+        .map(|name| name.trim().to_string())
+        .find(|name| !name.is_empty())
+}
@@ -0,0 +1,84 @@
+use std::sync::Mutex;
+
+use crate::entropy::{os_entropy, random_u16, MIN_RANDOM_VALUE};
+use crate::layout::unix_millis;
+use crate::EntropySource;
+
+use crate::{encode_chronological, resolve_timestamp, validate_prefix, BcidError, Format, Precision};
+
+/// Last timestamp and random value issued by this process
+static LAST_ISSUED: Mutex<Option<((u64, u16), u16)>> = Mutex::new(None);
+
+/// Generate a chronological identifier that is strictly greater than the
+/// previous one issued by this process
+///
+/// Identifiers generated within the same 10ms window reuse the previous
+/// timestamp and increment its random value instead of drawing a new one
+/// (like ULID's monotonic mode). If the clock reads earlier than the last
+/// issued timestamp, the last timestamp is reused. When the random value
+/// would overflow, the timestamp advances to the next 10ms window.
+///
+/// Ordering is by the encoded `(timestamp, random)` pair; the padding stays
+/// random. With [`Format::V2`] this is also the string order.
+pub fn generate_monotonic_identifier(prefix: &str, machine_id: u16, user_datetime: Option<&str>, format: Format) -> Result<String, BcidError> {
+    validate_prefix(prefix)?;
+    let now = resolve_timestamp(user_datetime)?;
+    // v5 timestamps count milliseconds since the Unix epoch
+    if format == Format::V5 && unix_millis((now, 0)).is_none() {
+        return Err(BcidError::ValueOutOfRange { field: "timestamp", value: now });
+    }
+    let mut entropy = os_entropy();
+    let mut last = LAST_ISSUED.lock().unwrap_or_else(|e| e.into_inner());
+    let ((timestamp, _), random_value) = next_monotonic((now, 0), &mut last, Precision::default(), &mut entropy);
+    drop(last);
+    Ok(encode_chronological(format, prefix, timestamp, machine_id, random_value, &mut entropy))
+}
+
+/// Next `(time, random)` pair at or after `now` and after `last`, which is
+/// updated to it
+///
+/// Times are a packed timestamp and the microseconds after it, at
+/// `precision`; the random value is incremented within one step of it.
+pub(crate) fn next_monotonic(
+    now: (u64, u16),
+    last: &mut Option<((u64, u16), u16)>,
+    precision: Precision,
+    entropy: &mut dyn EntropySource,
+) -> ((u64, u16), u16) {
+    let next = match *last {
+        Some((last_time, last_random)) if now <= last_time => match last_random.checked_add(1) {
+            Some(random_value) => (last_time, random_value),
+            None => (precision.next_step(last_time), fresh_random(entropy)),
+        },
+        _ => (now, fresh_random(entropy)),
+    };
+    *last = Some(next);
+    next
+}
+
+/// Next `(millis, sequence)` pair at or after `millis` and after `last`,
+/// which is updated to it, or `None` once the sequence of the last
+/// millisecond would pass `max`
+///
+/// The sequence counts identifiers within a millisecond from 0. If the clock
+/// reads earlier than the last millisecond, the last one is reused.
+pub(crate) fn next_sequence(millis: u64, last: &mut Option<(u64, u32)>, max: u32) -> Option<(u64, u32)> {
+    let next = match *last {
+        Some((last_millis, last_sequence)) if millis <= last_millis => {
+            (last_millis, last_sequence.checked_add(1).filter(|&sequence| sequence <= max)?)
+        }
+        _ => (millis, 0),
+    };
+    *last = Some(next);
+    Some(next)
+}
+
+/// Random starting value for a new window, leaving at least 32768 increments
+pub(crate) fn fresh_random(entropy: &mut dyn EntropySource) -> u16 {
+    loop {
+        let value = random_u16(entropy) & 0x7fff;
+        if value >= MIN_RANDOM_VALUE {
+            return value;
+        }
+    }
+}
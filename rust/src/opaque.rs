@@ -0,0 +1,111 @@
+//! Opaque identifiers: the structured fields encrypted under a key
+//!
+//! The first 16 characters after the prefix (characters 4 to 19 with a
+//! 4-character prefix), which hold every decoded field of both layouts
+//! (timestamp, machine ID and random value, or the machine ID and the start
+//! of the random data), are encrypted with a 10-round Feistel network over
+//! base62 digits keyed by HMAC-SHA256. The result has the same length and
+//! prefix, but reveals neither the creation time nor the machine. The
+//! characters after them are left alone, so check digits and signatures can
+//! be added after encryption.
+
+use alloc::string::String;
+
+use crate::signature::hmac_sha256;
+use crate::{split_prefix, BcidError, BASE62};
+
+/// Encrypted characters, from the start of the encoded fields
+const ENCRYPTED: usize = 16;
+const HALF: usize = ENCRYPTED / 2;
+const ROUNDS: u8 = 10;
+
+/// Encrypt the structured fields of an identifier with `key`
+///
+/// Fails if the identifier's length is out of range or it has
+/// characters outside the base62 alphabet after the prefix.
+pub fn encrypt_identifier(identifier: &str, key: &[u8]) -> Result<String, BcidError> {
+    let (start, mut left, mut right) = split(identifier)?;
+    for round in 0..ROUNDS {
+        let f = round_function(key, round, &right);
+        let next = add(&left, &f);
+        left = right;
+        right = next;
+    }
+    Ok(join(identifier, start, &left, &right))
+}
+
+/// Decrypt an identifier produced by [`encrypt_identifier`] with the same key
+///
+/// A wrong key yields a different, usually invalid, identifier.
+pub fn decrypt_identifier(identifier: &str, key: &[u8]) -> Result<String, BcidError> {
+    let (start, mut left, mut right) = split(identifier)?;
+    for round in (0..ROUNDS).rev() {
+        let f = round_function(key, round, &left);
+        let previous = subtract(&right, &f);
+        right = left;
+        left = previous;
+    }
+    Ok(join(identifier, start, &left, &right))
+}
+
+type Half = [u8; HALF];
+
+/// Where the encrypted range starts, and the base62 digits of its two halves
+fn split(identifier: &str) -> Result<(usize, Half, Half), BcidError> {
+    if !identifier.is_ascii() {
+        return Err(BcidError::InvalidIdentifierLength(identifier.chars().count()));
+    }
+    let (_, fields) = split_prefix(identifier)?;
+    let start = identifier.len() - fields.len();
+    let mut digits = [0u8; ENCRYPTED];
+    for (i, byte) in identifier.bytes().enumerate().skip(start).take(ENCRYPTED) {
+        digits[i - start] = BASE62
+            .iter()
+            .position(|&c| c == byte)
+            .ok_or(BcidError::InvalidCharacter { character: byte as char, position: i })? as u8;
+    }
+    let mut left = [0u8; HALF];
+    let mut right = [0u8; HALF];
+    left.copy_from_slice(&digits[..HALF]);
+    right.copy_from_slice(&digits[HALF..]);
+    Ok((start, left, right))
+}
+
+/// Replace the encrypted range of `identifier`, from `start`, with the two
+/// halves
+fn join(identifier: &str, start: usize, left: &Half, right: &Half) -> String {
+    let mut result = String::with_capacity(identifier.len());
+    result.push_str(&identifier[..start]);
+    result.extend(left.iter().chain(right).map(|&digit| BASE62[digit as usize] as char));
+    result.push_str(&identifier[start + ENCRYPTED..]);
+    result
+}
+
+/// Keyed pseudo-random digits for one round
+fn round_function(key: &[u8], round: u8, half: &Half) -> Half {
+    let mut message = b"bcid-opaque".to_vec();
+    message.push(round);
+    message.extend_from_slice(half);
+    let mac = hmac_sha256(key, &message);
+    let mut digits = [0u8; HALF];
+    for (digit, bytes) in digits.iter_mut().zip(mac.chunks_exact(2)) {
+        *digit = (u16::from_be_bytes([bytes[0], bytes[1]]) % 62) as u8;
+    }
+    digits
+}
+
+fn add(a: &Half, b: &Half) -> Half {
+    let mut sum = [0u8; HALF];
+    for i in 0..HALF {
+        sum[i] = (a[i] + b[i]) % 62;
+    }
+    sum
+}
+
+fn subtract(a: &Half, b: &Half) -> Half {
+    let mut difference = [0u8; HALF];
+    for i in 0..HALF {
+        difference[i] = (a[i] + 62 - b[i]) % 62;
+    }
+    difference
+}
@@ -0,0 +1,142 @@
+use core::fmt;
+
+use chrono::{DateTime, SecondsFormat, Timelike, Utc};
+
+use crate::pack_timestamp;
+#[cfg(feature = "std")]
+use crate::unpack_timestamp;
+
+/// Microseconds in the 10ms tick of a packed timestamp
+pub(crate) const MICROS_PER_TICK: u16 = 10_000;
+
+/// Resolution of the timestamp of chronological identifiers
+///
+/// v1 and v2 identifiers always have 10ms resolution; every other precision
+/// uses the [`Format::V3`](crate::Format::V3) layout (or
+/// [`Format::V4`](crate::Format::V4)), which records the precision and
+/// widens or narrows the timestamp field to fit it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Precision {
+    /// Whole seconds
+    Seconds,
+    /// Hundredths of a second
+    #[default]
+    Centiseconds,
+    /// Milliseconds
+    Milliseconds,
+    /// Microseconds
+    Microseconds,
+}
+
+impl Precision {
+    /// Name used on the command line: `s`, `10ms`, `ms` or `us`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Precision::Seconds => "s",
+            Precision::Centiseconds => "10ms",
+            Precision::Milliseconds => "ms",
+            Precision::Microseconds => "us",
+        }
+    }
+
+    /// The precision called `name` (see [`Precision::name`])
+    pub fn from_name(name: &str) -> Option<Precision> {
+        match name {
+            "s" => Some(Precision::Seconds),
+            "10ms" => Some(Precision::Centiseconds),
+            "ms" => Some(Precision::Milliseconds),
+            "us" => Some(Precision::Microseconds),
+            _ => None,
+        }
+    }
+
+    /// How `decode` and JSON output write date/times at this precision
+    pub(crate) fn seconds_format(&self) -> SecondsFormat {
+        match self {
+            Precision::Microseconds => SecondsFormat::Micros,
+            _ => SecondsFormat::Millis,
+        }
+    }
+
+    /// The character after the marker of v3 identifiers
+    pub(crate) fn marker(&self) -> u8 {
+        match self {
+            Precision::Seconds => b's',
+            Precision::Centiseconds => b'c',
+            Precision::Milliseconds => b'm',
+            Precision::Microseconds => b'u',
+        }
+    }
+
+    /// The precision marked by `marker` (see [`Precision::marker`])
+    pub(crate) fn from_marker(marker: u8) -> Option<Precision> {
+        match marker {
+            b's' => Some(Precision::Seconds),
+            b'c' => Some(Precision::Centiseconds),
+            b'm' => Some(Precision::Milliseconds),
+            b'u' => Some(Precision::Microseconds),
+            _ => None,
+        }
+    }
+
+    /// Microseconds in one step of this precision
+    pub(crate) fn unit_micros(&self) -> u64 {
+        match self {
+            Precision::Seconds => 1_000_000,
+            Precision::Centiseconds => 10_000,
+            Precision::Milliseconds => 1000,
+            Precision::Microseconds => 1,
+        }
+    }
+
+    /// Microseconds in one step of the part of the timestamp finer than
+    /// 10ms, and the largest value of that part (0 without one)
+    pub(crate) fn sub_tick(&self) -> (u16, u16) {
+        match self {
+            Precision::Seconds | Precision::Centiseconds => (MICROS_PER_TICK, 0),
+            Precision::Milliseconds => (1000, 9),
+            Precision::Microseconds => (1, MICROS_PER_TICK - 1),
+        }
+    }
+
+    /// The packed timestamp and the microseconds after it of `datetime`,
+    /// truncated to this precision
+    pub(crate) fn pack(&self, datetime: &DateTime<Utc>) -> (u64, u16) {
+        // A leap second's nanoseconds run past 1e9; keep them within the tick
+        let micros = (datetime.nanosecond() / 1000).min(999_999) % MICROS_PER_TICK as u32;
+        self.truncate((pack_timestamp(datetime), micros as u16))
+    }
+
+    /// Truncate a packed timestamp and the microseconds after it to this
+    /// precision
+    pub(crate) fn truncate(&self, (timestamp, micros): (u64, u16)) -> (u64, u16) {
+        match self {
+            Precision::Seconds => (timestamp / 100 * 100, 0),
+            _ => (timestamp, micros / self.sub_tick().0 * self.sub_tick().0),
+        }
+    }
+
+    /// The time one step of this precision after a packed timestamp and the
+    /// microseconds after it
+    #[cfg(feature = "std")]
+    pub(crate) fn next_step(&self, (timestamp, micros): (u64, u16)) -> (u64, u16) {
+        let step = self.sub_tick().0;
+        if *self != Precision::Seconds && micros + step < MICROS_PER_TICK {
+            return (timestamp, micros + step);
+        }
+        let step = match self {
+            Precision::Seconds => chrono::Duration::seconds(1),
+            _ => chrono::Duration::milliseconds(10),
+        };
+        match unpack_timestamp(timestamp) {
+            Some(datetime) => (pack_timestamp(&(datetime + step)), 0),
+            None => (timestamp + 1, 0),
+        }
+    }
+}
+
+impl fmt::Display for Precision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
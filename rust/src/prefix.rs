@@ -0,0 +1,81 @@
+use core::fmt;
+
+use crate::{validate_prefix, BcidError, MAX_PREFIX_LENGTH, MIN_PREFIX_LENGTH, SEPARATOR};
+
+/// A prefix checked when the program is compiled
+///
+/// [`Prefix::new`] is a `const fn` that panics on an invalid prefix, so a
+/// prefix held in a `const` (or written with [`prefix!`](crate::prefix!))
+/// that is not 1 to 8 printable ASCII characters other than `_` fails the
+/// build instead of every call at runtime:
+///
+/// ```
+/// const USER: bcid::Prefix = bcid::Prefix::new("USER");
+/// assert_eq!(bcid::prefix!("inv").as_str(), "inv");
+///
+/// # #[cfg(feature = "std")] {
+/// let mut generator = bcid::BcidGenerator::from_prefix(USER, 1);
+/// assert_eq!(generator.next().prefix(), "USER");
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// let prefix = bcid::prefix!("USER_ID");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Prefix(&'static str);
+
+impl Prefix {
+    /// A prefix, panicking (at compile time in a `const`) if it is invalid
+    pub const fn new(prefix: &'static str) -> Prefix {
+        let bytes = prefix.as_bytes();
+        if bytes.len() < MIN_PREFIX_LENGTH || bytes.len() > MAX_PREFIX_LENGTH {
+            panic!("prefix must be between 1 and 8 characters long");
+        }
+        let mut i = 0;
+        while i < bytes.len() {
+            if !bytes[i].is_ascii_graphic() || bytes[i] == SEPARATOR as u8 {
+                panic!("prefix must be printable ASCII without '_'");
+            }
+            i += 1;
+        }
+        Prefix(prefix)
+    }
+
+    /// The prefix as a string
+    pub const fn as_str(&self) -> &'static str {
+        self.0
+    }
+}
+
+/// Check a prefix that is only known at runtime
+impl TryFrom<&'static str> for Prefix {
+    type Error = BcidError;
+
+    fn try_from(prefix: &'static str) -> Result<Self, Self::Error> {
+        validate_prefix(prefix)?;
+        Ok(Prefix(prefix))
+    }
+}
+
+impl AsRef<str> for Prefix {
+    fn as_ref(&self) -> &str {
+        self.0
+    }
+}
+
+impl fmt::Display for Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// A [`Prefix`] checked at compile time: `bcid::prefix!("USER")` fails to
+/// build if the prefix is invalid
+#[macro_export]
+macro_rules! prefix {
+    ($prefix:expr) => {{
+        const PREFIX: $crate::Prefix = $crate::Prefix::new($prefix);
+        PREFIX
+    }};
+}
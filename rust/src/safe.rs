@@ -0,0 +1,80 @@
+//! Identifiers screened for offensive words
+//!
+//! Random base62 characters occasionally spell words that customers should
+//! not see in a URL. [`BcidGenerator::safe`](crate::BcidGenerator::safe)
+//! draws new random values for an identifier whose random characters form a
+//! denylisted word; [`Alphabet::NoVowels`](crate::Alphabet::NoVowels) avoids
+//! most words by construction instead, at the cost of a different encoding.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::Bcid;
+
+/// Words that must not appear in customer-facing identifiers, lowercase
+///
+/// Matched case-insensitively, with digits read as the letters they
+/// resemble (see [`normalize`]).
+const DENYLIST: &[&str] = &[
+    "anal", "anus", "arse", "ass", "bitch", "boob", "cock", "coon", "crap", "cum", "cunt", "dick", "dildo", "dyke",
+    "fag", "fuck", "jizz", "kike", "nazi", "nigg", "paki", "penis", "piss", "porn", "pussy", "rape", "retard", "sex",
+    "shit", "slut", "spic", "tit", "twat", "wank", "whore",
+];
+
+/// Whether `identifier` is free of denylisted words
+///
+/// Words are matched case-insensitively anywhere after the prefix, with the
+/// digits 0, 1, 3, 4, 5 and 7 read as o, i, e, a, s and t (so `5h1t` counts).
+/// The prefix is chosen by the caller and is not checked.
+///
+/// ```
+/// assert!(bcid::is_safe_identifier("TESTjqTZNAWSyaabkXENIJ3mg9rTtkWk"));
+/// assert!(!bcid::is_safe_identifier("TESTjqTZNAWSyaabkXENIJ3mgFuCkWkA"));
+/// ```
+pub fn is_safe_identifier(identifier: &str) -> bool {
+    let prefix_len = Bcid::parse(identifier).map_or(0, |bcid| bcid.fields()[0].end);
+    denylisted(identifier).all(|word| word.end <= prefix_len)
+}
+
+/// Whether a denylisted word in `identifier` overlaps one of its random
+/// characters, so that generating it again could avoid the word
+///
+/// Words entirely within the prefix, timestamp and machine ID cannot be
+/// avoided by drawing new random values and are let through.
+#[cfg(feature = "std")]
+pub(crate) fn has_avoidable_word(identifier: &Bcid) -> bool {
+    let random: Vec<Range<usize>> = identifier
+        .fields()
+        .into_iter()
+        .filter(|field| matches!(field.name, "random" | "padding"))
+        .map(|field| field.start..field.end)
+        .collect();
+    denylisted(identifier.as_str()).any(|word| random.iter().any(|field| word.start < field.end && field.start < word.end))
+}
+
+/// Character ranges of the denylisted words in `text`
+fn denylisted(text: &str) -> impl Iterator<Item = Range<usize>> {
+    let normalized: Vec<u8> = text.bytes().map(normalize).collect();
+    let mut words = Vec::new();
+    for start in 0..normalized.len() {
+        for word in DENYLIST {
+            if normalized[start..].starts_with(word.as_bytes()) {
+                words.push(start..start + word.len());
+            }
+        }
+    }
+    words.into_iter()
+}
+
+/// A character lowercased, with digits that resemble letters read as them
+fn normalize(c: u8) -> u8 {
+    match c {
+        b'0' => b'o',
+        b'1' => b'i',
+        b'3' => b'e',
+        b'4' => b'a',
+        b'5' => b's',
+        b'7' => b't',
+        c => c.to_ascii_lowercase(),
+    }
+}
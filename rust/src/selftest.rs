@@ -0,0 +1,297 @@
+//! Round-trip check of generation and decoding
+//!
+//! [`selftest`] generates identifiers in every mode the generator supports
+//! and decodes them again, comparing each component with the value it was
+//! encoded from. It needs no setup, so it verifies an installed build, e.g.
+//! with `bcid selftest` after an upgrade.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+
+use crate::{
+    unpack_timestamp, Alphabet, Bcid, BcidError, BcidGenerator,
+    BitLayout, Environment, Format, IdentifierType, Precision, SequenceOverflow, MAX_LENGTH, MIN_LENGTH,
+};
+
+/// Identifiers generated in a row by each generator, so that monotonic
+/// random values and v5 sequence numbers advance
+const BURST: usize = 4;
+
+/// Prefixes drawn: with a separator, without one (4 characters), and the
+/// shortest and longest
+const PREFIXES: [&str; 4] = ["T", "TEST", "usr", "Ab3$-x.Z"];
+
+/// Milliseconds since the Unix epoch of 2100-01-01, the latest timestamp drawn
+const MAX_MILLIS: i64 = 4_102_444_800_000;
+
+const ALPHABETS: [Alphabet; 4] = [Alphabet::Base62, Alphabet::Crockford32, Alphabet::Base58, Alphabet::NoVowels];
+const PRECISIONS: [Precision; 4] =
+    [Precision::Seconds, Precision::Centiseconds, Precision::Milliseconds, Precision::Microseconds];
+
+/// Outcome of [`selftest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelftestReport {
+    /// Identifiers generated and decoded
+    pub checked: usize,
+    /// Generator modes they were spread over
+    pub modes: usize,
+    /// Components that did not round-trip, empty if all did
+    pub failures: Vec<SelftestFailure>,
+}
+
+impl SelftestReport {
+    /// Whether every component of every identifier round-tripped
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// A component of a generated identifier that decoded to another value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelftestFailure {
+    /// Generator settings, e.g. `v3 base58 us env+shard`
+    pub mode: String,
+    /// The identifier, empty if generating it failed
+    pub identifier: String,
+    /// Name of the component, e.g. `random`, or `decode` if the identifier
+    /// did not decode at all
+    pub component: &'static str,
+    pub expected: String,
+    pub found: String,
+}
+
+impl fmt::Display for SelftestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): {} decodes as {}, expected {}",
+            self.identifier, self.mode, self.component, self.found, self.expected
+        )
+    }
+}
+
+/// Settings of the generators one mode draws from
+#[derive(Debug, Clone, Copy)]
+struct Mode {
+    format: Format,
+    alphabet: Alphabet,
+    precision: Precision,
+    environment: Option<Environment>,
+    /// Whether to write a 2-digit shard
+    has_shard: bool,
+    bit_layout: Option<BitLayout>,
+    is_random: bool,
+}
+
+impl Mode {
+    fn chronological(format: Format, alphabet: Alphabet) -> Mode {
+        Mode {
+            format,
+            alphabet,
+            precision: Precision::default(),
+            environment: None,
+            has_shard: false,
+            bit_layout: None,
+            is_random: false,
+        }
+    }
+
+    /// Every mode: random identifiers, v1, v2 in each alphabet, v3 and v4
+    /// in each alphabet and precision with and without tags, and v5 in each
+    /// alphabet with several layouts
+    fn all() -> Vec<Mode> {
+        let mut modes = vec![Mode { is_random: true, ..Mode::chronological(Format::V1, Alphabet::Base62) }];
+        modes.push(Mode::chronological(Format::V1, Alphabet::Base62));
+        for alphabet in ALPHABETS {
+            modes.push(Mode::chronological(Format::V2, alphabet));
+            for format in [Format::V3, Format::V4] {
+                for (i, precision) in PRECISIONS.into_iter().enumerate() {
+                    modes.push(Mode {
+                        precision,
+                        environment: [None, Some(Environment::Production), None, Some(Environment::Staging)][i],
+                        has_shard: i >= 2,
+                        ..Mode::chronological(format, alphabet)
+                    });
+                }
+            }
+            for layout in [(44, 12, 10), (42, 16, 0), (61, 0, 1), (48, 8, 32)] {
+                let bit_layout = BitLayout::new(layout.0, layout.1, layout.2).expect("layouts are valid");
+                modes.push(Mode { bit_layout: Some(bit_layout), ..Mode::chronological(Format::V5, alphabet) });
+            }
+        }
+        modes
+    }
+
+    /// A generator in this mode, with the clock stopped at `at`
+    fn generator(&self, prefix: &str, machine_id: u16, length: usize, at: DateTime<Utc>) -> Result<BcidGenerator, BcidError> {
+        let mut generator = BcidGenerator::new(prefix, machine_id)?
+            .clock(move || at)
+            .length(length)?
+            .format(self.format)
+            .alphabet(self.alphabet)
+            .precision(self.precision)
+            .random(self.is_random)
+            .sequence_overflow(SequenceOverflow::Borrow);
+        if let Some(environment) = self.environment {
+            generator = generator.environment(environment);
+        }
+        if self.has_shard {
+            let digits = self.alphabet.chars();
+            generator = generator.shard(std::str::from_utf8(&[digits[7], digits[1]]).expect("digits are ASCII"));
+        }
+        if let Some(layout) = self.bit_layout {
+            generator = generator.bit_layout(layout)?;
+        }
+        Ok(generator)
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_random {
+            return f.write_str("random");
+        }
+        write!(f, "{} {}", self.format, self.alphabet)?;
+        if matches!(self.format, Format::V3 | Format::V4) {
+            write!(f, " {}", self.precision)?;
+        }
+        match (self.environment.is_some(), self.has_shard) {
+            (true, true) => f.write_str(" env+shard")?,
+            (true, false) => f.write_str(" env")?,
+            (false, true) => f.write_str(" shard")?,
+            (false, false) => {}
+        }
+        if let Some(layout) = self.bit_layout {
+            write!(f, " {}", layout)?;
+        }
+        Ok(())
+    }
+}
+
+/// Generate `count` identifiers across every generator mode, decode each
+/// one again and compare its components with the values it was encoded from
+///
+/// Modes cover random identifiers, every format, alphabet and precision,
+/// environment and shard tags, several v5 layouts, and monotonic and safe
+/// generation. Prefixes, machine IDs, lengths and timestamps (1970 to 2100)
+/// are drawn at random. Each identifier is checked for its prefix, type,
+/// format, alphabet, precision, timestamp, machine ID, tags, layout,
+/// sequence number, random value and length, and must parse back to an
+/// equal [`Bcid`].
+///
+/// ```
+/// let report = bcid::selftest(500);
+/// assert!(report.is_ok(), "{:?}", report.failures);
+/// assert_eq!(report.checked, 500);
+/// ```
+pub fn selftest(count: usize) -> SelftestReport {
+    let modes = Mode::all();
+    let mut rng = rand::thread_rng();
+    let mut failures = Vec::new();
+    let mut checked = 0;
+    for burst in 0.. {
+        if checked >= count {
+            break;
+        }
+        let mode = modes[burst % modes.len()];
+        let prefix = PREFIXES[rng.gen_range(0..PREFIXES.len())];
+        let mut machine_id: u16 = rng.gen();
+        if let Some(layout) = mode.bit_layout {
+            machine_id &= layout.max_machine_id();
+        }
+        let at = DateTime::from_timestamp_micros(rng.gen_range(0..MAX_MILLIS * 1_000)).expect("within range");
+        let mut length = rng.gen_range(MIN_LENGTH..=MAX_LENGTH);
+        let mut generator = match mode.generator(prefix, machine_id, length, at) {
+            Ok(generator) => generator.monotonic(burst % 2 == 1).safe(burst % 3 == 0),
+            Err(e) => {
+                failures.push(failure(&mode, "", "generate", "an identifier", e));
+                checked += BURST.min(count - checked);
+                continue;
+            }
+        };
+        for _ in 0..BURST.min(count - checked) {
+            checked += 1;
+            let generated = match generator.try_next() {
+                // Short lengths do not fit every layout; use the shortest that does
+                Err(BcidError::LayoutDoesNotFit { needed, .. }) if needed > length => {
+                    length = needed;
+                    generator = match generator.length(length) {
+                        Ok(generator) => generator,
+                        Err(e) => {
+                            failures.push(failure(&mode, "", "generate", "an identifier", e));
+                            break;
+                        }
+                    };
+                    generator.try_next()
+                }
+                generated => generated,
+            };
+            match generated {
+                Ok(id) => check(&mut failures, &mode, &id, &generator, length),
+                Err(e) => failures.push(failure(&mode, "", "generate", "an identifier", e)),
+            }
+        }
+    }
+    SelftestReport { checked, modes: modes.len(), failures }
+}
+
+/// Compare the components `id` decodes to with the ones `generator` drew
+fn check(failures: &mut Vec<SelftestFailure>, mode: &Mode, id: &Bcid, generator: &BcidGenerator, length: usize) {
+    let raw = id.as_str();
+    let decoded = match Bcid::parse_with(raw, id.alphabet()) {
+        Ok(decoded) => decoded,
+        Err(e) => return failures.push(failure(mode, raw, "decode", "a valid identifier", e)),
+    };
+    let mut compare = |component: &'static str, expected: String, found: String| {
+        if expected != found {
+            failures.push(SelftestFailure { mode: mode.to_string(), identifier: raw.to_string(), component, expected, found });
+        }
+    };
+    compare("identifier", format!("{:?}", id), format!("{:?}", decoded));
+    compare("prefix", generator.prefix().to_string(), decoded.prefix().to_string());
+    compare("length", length.to_string(), raw.len().to_string());
+    compare("machine_id", generator.machine_id().to_string(), decoded.machine_id().to_string());
+    if mode.is_random {
+        compare("type", format!("{:?}", IdentifierType::Random), format!("{:?}", decoded.identifier_type()));
+        return;
+    }
+    compare("type", format!("{:?}", IdentifierType::Chronological), format!("{:?}", decoded.identifier_type()));
+    compare("format", mode.format.to_string(), decoded.format().to_string());
+    compare("alphabet", mode.alphabet.to_string(), decoded.alphabet().to_string());
+    let precision = match mode.format {
+        Format::V3 | Format::V4 => mode.precision,
+        Format::V5 => Precision::Milliseconds,
+        _ => Precision::default(),
+    };
+    compare("precision", format!("{:?}", Some(precision)), format!("{:?}", decoded.precision()));
+    compare("environment", format!("{:?}", mode.environment), format!("{:?}", decoded.environment()));
+    compare("bit_layout", format!("{:?}", mode.bit_layout), format!("{:?}", decoded.bit_layout()));
+    let expected_shard = mode.has_shard.then(|| {
+        let digits = mode.alphabet.chars();
+        [digits[7] as char, digits[1] as char].iter().collect::<String>()
+    });
+    compare("shard", format!("{:?}", expected_shard), format!("{:?}", decoded.shard()));
+
+    let Some(drawn) = generator.drawn() else {
+        return compare("components", "recorded".to_string(), "none".to_string());
+    };
+    let datetime = match drawn.millis {
+        Some(millis) => i64::try_from(millis).ok().and_then(DateTime::from_timestamp_millis),
+        None => unpack_timestamp(drawn.time.0).map(|datetime| datetime + chrono::Duration::microseconds(drawn.time.1 as i64)),
+    };
+    compare("timestamp", format!("{:?}", datetime), format!("{:?}", decoded.datetime()));
+    compare("sequence", format!("{:?}", drawn.sequence), format!("{:?}", decoded.sequence()));
+    compare("random", format!("{:?}", Some(drawn.random_value)), format!("{:?}", decoded.random()));
+}
+
+fn failure(mode: &Mode, identifier: &str, component: &'static str, expected: &str, error: BcidError) -> SelftestFailure {
+    SelftestFailure {
+        mode: mode.to_string(),
+        identifier: identifier.to_string(),
+        component,
+        expected: expected.to_string(),
+        found: error.to_string(),
+    }
+}
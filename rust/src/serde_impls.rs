@@ -0,0 +1,147 @@
+//! `serde::Serialize` and `serde::Deserialize`, built with the `serde`
+//! feature
+//!
+//! A [`Bcid`] is written as its canonical string and read back with
+//! [`Bcid::try_from`], so identifiers in an alphabet other than base62 need
+//! a [`DecodedBcid`](crate::DecodedBcid), which records the alphabet. The
+//! enums and [`BitLayout`] are written as the names used in the JSON output
+//! of [`Bcid::to_json`] (e.g. `"v2"`, `"crockford32"`, `"10ms"`), and
+//! [`DecodedBcid`](crate::DecodedBcid) derives both traits. Types from
+//! [`define_bcid!`](crate::define_bcid) are written like a [`Bcid`].
+//!
+//! ```
+//! # #[cfg(feature = "std")] {
+//! let id = bcid::Bcid::parse(&bcid::generate_identifier("TEST", 1, Some("2024-01-01"), false)?)?;
+//! let json = serde_json::to_string(&id).unwrap();
+//! assert_eq!(json, format!("\"{}\"", id));
+//! assert_eq!(serde_json::from_str::<bcid::Bcid>(&json).unwrap(), id);
+//!
+//! let decoded = serde_json::to_value(id.decoded()).unwrap();
+//! assert_eq!(decoded["format"], "v1");
+//! assert_eq!(decoded["datetime"], "2024-01-01T00:00:00Z");
+//! # }
+//! # Ok::<(), bcid::BcidError>(())
+//! ```
+
+use alloc::string::String;
+use core::fmt;
+use core::str::FromStr;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{Alphabet, Bcid, BitLayout, Environment, Format, IdentifierType, Precision};
+
+/// Implement both traits for a type written as a string: `to_str` renders a
+/// value and `from_str` parses one, returning `None` for an invalid string
+macro_rules! string_serde {
+    ($type:ty, $expecting:literal, |$value:ident| $to_str:expr, |$name:ident| $from_str:expr) => {
+        impl Serialize for $type {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let $value = self;
+                serializer.collect_str(&$to_str)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct StringVisitor;
+
+                impl Visitor<'_> for StringVisitor {
+                    type Value = $type;
+
+                    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                        f.write_str($expecting)
+                    }
+
+                    fn visit_str<E: de::Error>(self, $name: &str) -> Result<$type, E> {
+                        $from_str.ok_or_else(|| E::invalid_value(de::Unexpected::Str($name), &self))
+                    }
+                }
+
+                deserializer.deserialize_str(StringVisitor)
+            }
+        }
+    };
+}
+
+string_serde!(IdentifierType, "`chronological` or `random`", |value| value.as_str(), |name| match name {
+    "chronological" => Some(IdentifierType::Chronological),
+    "random" => Some(IdentifierType::Random),
+    _ => None,
+});
+string_serde!(Format, "a format version such as `v2`", |value| value, |name| Format::parse_version(name));
+string_serde!(Alphabet, "an alphabet name such as `base62`", |value| value.name(), |name| Alphabet::from_name(name));
+string_serde!(Precision, "`s`, `10ms`, `ms` or `us`", |value| value.name(), |name| Precision::from_name(name));
+string_serde!(Environment, "an environment name such as `production`", |value| value.name(), |name| {
+    Environment::from_name(name)
+});
+string_serde!(BitLayout, "a bit layout such as `ts=44,machine=12,seq=10`", |value| value, |name| {
+    BitLayout::from_str(name).ok()
+});
+
+impl Serialize for Bcid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Bcid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let identifier = String::deserialize(deserializer)?;
+        Bcid::try_from(identifier).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Bcid, Format, Precision};
+    #[cfg(feature = "std")]
+    use crate::{Alphabet, BcidGenerator, DecodedBcid, Environment};
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn decoded_identifiers_round_trip() {
+        let generators = [
+            BcidGenerator::new("TEST", 1).unwrap().alphabet(Alphabet::Crockford32),
+            BcidGenerator::new("ORDERS", 2).unwrap().precision(Precision::Milliseconds).environment(Environment::Staging).shard("e1"),
+            BcidGenerator::new("AB", 3).unwrap().bit_layout("ts=44,machine=12,seq=10".parse().unwrap()).unwrap(),
+            BcidGenerator::new("TEST", 4).unwrap().random(true),
+        ];
+        for mut generator in generators {
+            let decoded = generator.next().decoded();
+            let json = serde_json::to_string(&decoded).unwrap();
+            let read: DecodedBcid = serde_json::from_str(&json).unwrap();
+            assert_eq!(read, decoded);
+            assert_eq!(Bcid::try_from(read).unwrap().decoded(), decoded);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[allow(dead_code)]
+    mod user {
+        crate::define_bcid!(pub UserId, "USER");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn typed_identifiers_check_their_prefix() {
+        use user::UserId;
+
+        let user = UserId::generate(1);
+        let json = serde_json::to_string(&user).unwrap();
+        assert_eq!(json, format!("\"{}\"", user));
+        assert_eq!(serde_json::from_str::<UserId>(&json).unwrap(), user);
+        let other = serde_json::to_string(&Bcid::parse(&crate::generate_identifier("ORDR", 1, None, false).unwrap()).unwrap()).unwrap();
+        let error = serde_json::from_str::<UserId>(&other).unwrap_err();
+        assert!(error.to_string().contains("USER"), "{error}");
+    }
+
+    #[test]
+    fn invalid_strings_are_rejected() {
+        assert!(serde_json::from_str::<Bcid>("\"TEST\"").is_err());
+        assert!(serde_json::from_str::<Format>("\"v9\"").is_err());
+        assert!(serde_json::from_str::<Precision>("\"ns\"").is_err());
+        assert_eq!(serde_json::from_str::<Format>("\"v5\"").unwrap(), Format::V5);
+    }
+}
@@ -0,0 +1,143 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::DateTime;
+
+use crate::monotonic::fresh_random;
+use crate::{
+    chronological_length, encode_chronological_into, pack_timestamp, validate_prefix, BcidError, Clock, Format, Prefix,
+    SystemClock, MAX_ENCODED_LENGTH,
+};
+
+thread_local! {
+    /// The last 10ms tick this thread packed, and its packed timestamp
+    static PACKED_TICK: Cell<(u64, u64)> = const { Cell::new((u64::MAX, 0)) };
+}
+
+/// A monotonic generator that can be shared between threads
+///
+/// Unlike [`BcidGenerator`](crate::BcidGenerator), generating takes `&self`:
+/// the last issued timestamp and random value live in a single atomic, which
+/// is advanced with compare-and-swap instead of a lock, and randomness comes
+/// from each thread's own buffered CSPRNG ([`rand::thread_rng`]). Identifiers
+/// from all threads are strictly increasing in issue order.
+///
+/// ```
+/// use std::sync::OnceLock;
+///
+/// static IDS: OnceLock<bcid::SharedGenerator> = OnceLock::new();
+///
+/// fn ids() -> &'static bcid::SharedGenerator {
+///     IDS.get_or_init(|| bcid::SharedGenerator::new("TEST", 1).expect("valid prefix"))
+/// }
+///
+/// let handles: Vec<_> = (0..4).map(|_| std::thread::spawn(|| ids().next())).collect();
+/// let mut issued: Vec<_> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+/// issued.dedup();
+/// assert_eq!(issued.len(), 4);
+/// ```
+pub struct SharedGenerator {
+    prefix: String,
+    machine_id: u16,
+    format: Format,
+    clock: Box<dyn Clock>,
+    /// 10ms ticks since the Unix epoch in the high bits, random value in the low 16
+    last_issued: AtomicU64,
+}
+
+impl SharedGenerator {
+    /// A shared generator for the current time
+    ///
+    /// Fails if the prefix is not 1 to 8 printable ASCII characters other
+    /// than `_`.
+    pub fn new(prefix: &str, machine_id: u16) -> Result<Self, BcidError> {
+        validate_prefix(prefix)?;
+        Ok(SharedGenerator {
+            prefix: prefix.to_string(),
+            machine_id,
+            format: Format::V1,
+            clock: Box::new(SystemClock),
+            last_issued: AtomicU64::new(0),
+        })
+    }
+
+    /// A shared generator for a prefix checked at compile time
+    pub fn from_prefix(prefix: Prefix, machine_id: u16) -> Self {
+        Self::new(prefix.as_str(), machine_id).expect("prefixes are valid")
+    }
+
+    /// Use `format` (default [`Format::V1`])
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Read the time from `clock` (e.g. a [`MockClock`](crate::MockClock))
+    /// instead of the system clock
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Generate the next identifier
+    pub fn next(&self) -> String {
+        let mut buf = [0u8; MAX_ENCODED_LENGTH];
+        self.next_into(&mut buf).to_string()
+    }
+
+    /// Generate the next identifier into `buf` without allocating, for hot
+    /// loops that write identifiers straight to an output buffer
+    ///
+    /// Panics if `buf` is shorter than the identifier:
+    /// [`DEFAULT_LENGTH`](crate::DEFAULT_LENGTH) (32) bytes, or up to 35 for [`Format::V5`] with
+    /// prefixes of 6 to 8 characters, whose packed fields need more.
+    ///
+    /// ```
+    /// let generator = bcid::SharedGenerator::new("TEST", 1)?;
+    /// let mut buf = [0u8; 32];
+    /// let id: &str = generator.next_into(&mut buf);
+    /// assert!(id.starts_with("TEST"));
+    /// # Ok::<(), bcid::BcidError>(())
+    /// ```
+    pub fn next_into<'a>(&self, buf: &'a mut [u8]) -> &'a str {
+        let mut rng = rand::thread_rng();
+        let now = self.current_tick();
+        let mut last = self.last_issued.load(Ordering::Relaxed);
+        let next = loop {
+            let (last_tick, last_random) = (last >> 16, last as u16);
+            let next = match (now > last_tick, last_random.checked_add(1)) {
+                (false, Some(random)) => last_tick << 16 | random as u64,
+                (false, None) => (last_tick + 1) << 16 | fresh_random(&mut rng) as u64,
+                (true, _) => now << 16 | fresh_random(&mut rng) as u64,
+            };
+            match self.last_issued.compare_exchange_weak(last, next, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => break next,
+                Err(current) => last = current,
+            }
+        };
+
+        let timestamp = packed_tick(next >> 16);
+        let buf = &mut buf[..chronological_length(&self.prefix, self.format)];
+        encode_chronological_into(buf, self.format, &self.prefix, timestamp, self.machine_id, next as u16, &mut rng);
+        std::str::from_utf8(buf).expect("identifiers are ASCII")
+    }
+
+    /// 10ms ticks since the Unix epoch
+    fn current_tick(&self) -> u64 {
+        u64::try_from(self.clock.now().timestamp_millis()).map(|millis| millis / 10).unwrap_or(0)
+    }
+}
+
+/// Packed timestamp for a tick, cached per thread since ticks change every 10ms
+fn packed_tick(tick: u64) -> u64 {
+    PACKED_TICK.with(|cache| {
+        let (cached_tick, packed) = cache.get();
+        if cached_tick == tick {
+            return packed;
+        }
+        let datetime = DateTime::from_timestamp_millis(tick as i64 * 10).unwrap_or_default();
+        let packed = pack_timestamp(&datetime);
+        cache.set((tick, packed));
+        packed
+    })
+}
@@ -0,0 +1,143 @@
+//! HMAC-signed identifiers
+//!
+//! The trailing characters of a signed identifier are a truncated
+//! HMAC-SHA256 of the characters before them, encoded with [`BASE62`]. They
+//! replace random padding (or random data), so a signed identifier still
+//! decodes normally; verifying it needs the key. The separator is not
+//! signed, so the packed and separated forms carry the same signature. A signature of `length`
+//! characters holds about `5.95 * length` bits, so the default of 8 gives
+//! roughly 47 bits against forgery.
+
+use alloc::format;
+use alloc::string::String;
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::{split_prefix, BcidError, BASE62};
+
+/// Default number of signature characters
+pub const DEFAULT_SIGNATURE_LENGTH: usize = 8;
+
+/// Most signature characters, which never overlap a decoded field of a
+/// [`DEFAULT_LENGTH`](crate::DEFAULT_LENGTH) identifier with a prefix of up
+/// to 4 characters
+pub const MAX_SIGNATURE_LENGTH: usize = 12;
+
+/// End of the last decoded field of any base62 layout, from the start of the
+/// encoded fields
+const FIELDS_END: usize = 16;
+
+/// Replace the last `length` characters of an identifier with a truncated
+/// HMAC-SHA256 of the rest, keyed with `key`
+///
+/// Fails if the identifier's length is out of range, or `length` is not
+/// between 1 and [`MAX_SIGNATURE_LENGTH`] or would overlap a decoded field
+/// (the fields take the first 16 characters after the prefix and
+/// separator).
+pub fn sign_identifier(identifier: &str, key: &[u8], length: usize) -> Result<String, BcidError> {
+    let message = body(identifier, length)?;
+    Ok(format!("{}{}", &identifier[..identifier.len() - length], signature(&message, key, length)))
+}
+
+/// Check the trailing signature of an identifier signed with
+/// [`sign_identifier`]
+///
+/// Fails with [`BcidError::InvalidSignature`] if it does not match.
+pub fn verify_signature(identifier: &str, key: &[u8], length: usize) -> Result<(), BcidError> {
+    let message = body(identifier, length)?;
+    let expected = signature(&message, key, length);
+    // Compare without an early exit, so timing does not reveal the prefix that matched
+    let difference = expected.bytes().zip(identifier[identifier.len() - length..].bytes()).fold(0, |acc, (a, b)| acc | (a ^ b));
+    if difference != 0 {
+        return Err(BcidError::InvalidSignature);
+    }
+    Ok(())
+}
+
+/// The characters covered by the signature: the prefix and the encoded
+/// fields before it
+fn body(identifier: &str, length: usize) -> Result<String, BcidError> {
+    if !identifier.is_ascii() {
+        return Err(BcidError::InvalidIdentifierLength(identifier.chars().count()));
+    }
+    let (prefix, fields) = split_prefix(identifier)?;
+    if !(1..=MAX_SIGNATURE_LENGTH.min(fields.len() - FIELDS_END)).contains(&length) {
+        return Err(BcidError::ValueOutOfRange { field: "signature length", value: length as u64 });
+    }
+    Ok([prefix, &fields[..fields.len() - length]].concat())
+}
+
+/// `length` base62 characters of HMAC-SHA256(key, body)
+fn signature(body: &str, key: &[u8], length: usize) -> String {
+    let mac = hmac_sha256(key, body.as_bytes());
+    let mut value = u128::from_be_bytes(mac[..16].try_into().unwrap_or_default());
+    (0..length)
+        .map(|_| {
+            let c = BASE62[(value % 62) as usize] as char;
+            value /= 62;
+            c
+        })
+        .collect()
+}
+
+/// HMAC-SHA256 (RFC 2104)
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+/// SHA-256 (FIPS 180-4)
+pub(crate) fn sha256(message: &[u8]) -> [u8; 32] {
+    Sha256::digest(message).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn sha256_known_answers() {
+        // FIPS 180-4 examples
+        assert_eq!(hex(&sha256(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hex(&sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(
+            hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn hmac_sha256_known_answers() {
+        // RFC 4231 test cases 1, 2 and 6 (a key longer than the block)
+        assert_eq!(
+            hex(&hmac_sha256(&[0x0b; 20], b"Hi There")),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First")),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn signed_identifiers_verify() {
+        let key = b"key";
+        let signed = sign_identifier("TESTjqTZNAWSyaabkXENIJ3mg9rTtkWk", key, DEFAULT_SIGNATURE_LENGTH).unwrap();
+        assert_eq!(signed.len(), 32);
+        assert!(verify_signature(&signed, key, DEFAULT_SIGNATURE_LENGTH).is_ok());
+        assert_eq!(verify_signature(&signed, b"other", DEFAULT_SIGNATURE_LENGTH), Err(BcidError::InvalidSignature));
+        // The separated form carries the same signature
+        let separated = format!("TEST_{}", &signed[4..]);
+        assert!(verify_signature(&separated, key, DEFAULT_SIGNATURE_LENGTH).is_ok());
+    }
+}
@@ -0,0 +1,128 @@
+//! Database column impls for [`Bcid`], built with the `sqlx` or `diesel`
+//! feature
+//!
+//! Identifiers are stored as text (`TEXT`, `VARCHAR` or `CHAR(N)` columns)
+//! in their canonical form and decoded with [`Bcid::try_from`] when read, so
+//! a column holding something other than a base62 identifier fails to
+//! decode with the [`BcidError`](crate::BcidError). Both impls are generic
+//! over the database and apply to any driver whose `String` maps to text.
+
+use crate::Bcid;
+
+#[cfg(feature = "sqlx")]
+mod sqlx_impls {
+    use sqlx::encode::IsNull;
+    use sqlx::error::BoxDynError;
+    use sqlx::{Database, Decode, Encode, Type};
+
+    use super::Bcid;
+
+    impl<DB: Database> Type<DB> for Bcid
+    where
+        String: Type<DB>,
+    {
+        fn type_info() -> DB::TypeInfo {
+            <String as Type<DB>>::type_info()
+        }
+
+        fn compatible(ty: &DB::TypeInfo) -> bool {
+            <String as Type<DB>>::compatible(ty)
+        }
+    }
+
+    impl<'q, DB: Database> Encode<'q, DB> for Bcid
+    where
+        String: Encode<'q, DB>,
+    {
+        fn encode_by_ref(&self, buf: &mut <DB as Database>::ArgumentBuffer<'q>) -> Result<IsNull, BoxDynError> {
+            self.raw().encode_by_ref(buf)
+        }
+
+        fn size_hint(&self) -> usize {
+            self.raw().size_hint()
+        }
+    }
+
+    impl<'r, DB: Database> Decode<'r, DB> for Bcid
+    where
+        String: Decode<'r, DB>,
+    {
+        fn decode(value: <DB as Database>::ValueRef<'r>) -> Result<Self, BoxDynError> {
+            Ok(Bcid::try_from(String::decode(value)?)?)
+        }
+    }
+}
+
+#[cfg(feature = "diesel")]
+mod diesel_impls {
+    use diesel::backend::Backend;
+    use diesel::deserialize::{self, FromSql};
+    use diesel::serialize::{self, Output, ToSql};
+    use diesel::sql_types::Text;
+
+    use super::Bcid;
+
+    impl<DB: Backend> ToSql<Text, DB> for Bcid
+    where
+        str: ToSql<Text, DB>,
+    {
+        fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+            self.as_str().to_sql(out)
+        }
+    }
+
+    impl<DB: Backend> FromSql<Text, DB> for Bcid
+    where
+        String: FromSql<Text, DB>,
+    {
+        fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+            Ok(Bcid::try_from(String::from_sql(bytes)?)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Bcid, BcidGenerator};
+
+    fn samples() -> Vec<Bcid> {
+        vec![
+            BcidGenerator::new("TEST", 1).unwrap().next(),
+            BcidGenerator::new("ORDERS", 2).unwrap().random(true).next(),
+        ]
+    }
+
+    #[cfg(feature = "diesel")]
+    #[test]
+    fn diesel_round_trips_through_text_columns() {
+        use diesel::prelude::*;
+        use diesel::sql_types::Text;
+
+        let mut connection = SqliteConnection::establish(":memory:").unwrap();
+        for id in samples() {
+            let read: Bcid = diesel::select(id.clone().into_sql::<Text>()).get_result(&mut connection).unwrap();
+            assert_eq!(read, id);
+        }
+        let error = diesel::select("TEST".into_sql::<Text>()).get_result::<Bcid>(&mut connection).unwrap_err();
+        assert!(matches!(error, diesel::result::Error::DeserializationError(_)), "{error}");
+    }
+
+    #[cfg(feature = "sqlx")]
+    #[tokio::test(flavor = "current_thread")]
+    async fn sqlx_round_trips_through_text_columns() {
+        use sqlx::Connection;
+
+        use crate::BcidError;
+
+        let mut connection = sqlx::SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE ids (id CHAR(32) PRIMARY KEY)").execute(&mut connection).await.unwrap();
+        for id in samples() {
+            sqlx::query("INSERT INTO ids VALUES (?)").bind(&id).execute(&mut connection).await.unwrap();
+            let read: Bcid = sqlx::query_scalar("SELECT id FROM ids WHERE id = ?").bind(&id).fetch_one(&mut connection).await.unwrap();
+            assert_eq!(read, id);
+        }
+        let error = sqlx::query_scalar::<_, Bcid>("SELECT 'TEST'").fetch_one(&mut connection).await.unwrap_err();
+        let source = std::error::Error::source(&error).and_then(|source| source.downcast_ref::<BcidError>());
+        assert_eq!(source, Some(&BcidError::InvalidIdentifierLength(4)));
+    }
+}
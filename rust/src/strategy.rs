@@ -0,0 +1,212 @@
+//! Arbitrary identifiers for fuzzing and property tests, built with the
+//! `arbitrary` or `proptest` feature
+//!
+//! With `arbitrary`, [`Bcid`] implements `arbitrary::Arbitrary`, for
+//! cargo-fuzz targets. With `proptest`, `bcid()` is a strategy for valid
+//! identifiers and `near_bcid()` one for strings a single edit away from a
+//! valid identifier, which a parser must reject or accept without panicking.
+//!
+//! Both draw every format, alphabet, precision, prefix length and identifier
+//! length, with timestamps between 1970 and 2100, and are reproducible from
+//! their input.
+
+use chrono::DateTime;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::{Alphabet, Bcid, BcidGenerator, BitLayout, Environment, Format, Precision, MAX_LENGTH, MIN_LENGTH};
+
+/// Milliseconds since the Unix epoch of 2100-01-01, the latest timestamp drawn
+const MAX_MILLIS: i64 = 4_102_444_800_000;
+
+/// Characters of drawn prefixes: printable ASCII other than the separator
+const PREFIX_CHARS: &[u8] = b"!\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^`abcdefghijklmnopqrstuvwxyz{|}~";
+
+const FORMATS: [Format; 5] = [Format::V1, Format::V2, Format::V3, Format::V4, Format::V5];
+const ALPHABETS: [Alphabet; 4] = [Alphabet::Base62, Alphabet::Crockford32, Alphabet::Base58, Alphabet::NoVowels];
+const PRECISIONS: [Precision; 4] =
+    [Precision::Seconds, Precision::Centiseconds, Precision::Milliseconds, Precision::Microseconds];
+const ENVIRONMENTS: [Environment; 3] = [Environment::Production, Environment::Staging, Environment::Development];
+
+/// The choices that make up one drawn identifier, each an index or value
+/// that [`Recipe::build`] maps onto a valid setting
+#[derive(Debug, Clone)]
+struct Recipe {
+    prefix: Vec<u8>,
+    machine_id: u16,
+    format: usize,
+    alphabet: usize,
+    precision: usize,
+    /// Index into [`ENVIRONMENTS`], or none past its end
+    environment: usize,
+    /// Timestamp, machine ID and sequence bits of a v5 layout
+    bit_layout: (u8, u8, u8),
+    length: usize,
+    is_random: bool,
+    millis: i64,
+    seed: u64,
+}
+
+impl Recipe {
+    fn build(&self) -> Bcid {
+        let prefix: String =
+            self.prefix.iter().map(|&index| PREFIX_CHARS[index as usize % PREFIX_CHARS.len()] as char).collect();
+        let prefix = if prefix.is_empty() { "TEST".to_string() } else { prefix };
+        let length = MIN_LENGTH + self.length % (MAX_LENGTH - MIN_LENGTH + 1);
+        let at = DateTime::from_timestamp_millis(self.millis.rem_euclid(MAX_MILLIS)).expect("within range");
+        let generator = |machine_id: u16| -> Result<BcidGenerator, crate::BcidError> {
+            Ok(BcidGenerator::with_rng(&prefix, machine_id, StdRng::seed_from_u64(self.seed))?.clock(move || at))
+        };
+
+        let (timestamp_bits, machine_bits, sequence_bits) = self.bit_layout;
+        let layout =
+            BitLayout::new(42 + timestamp_bits % 20, machine_bits % 17, sequence_bits % 33).unwrap_or_default();
+        let format = FORMATS[self.format % FORMATS.len()];
+        // The machine ID of a v5 layout must fit its bits
+        let machine_id = match format {
+            Format::V5 => self.machine_id & layout.max_machine_id(),
+            _ => self.machine_id,
+        };
+        let configured = generator(machine_id).and_then(|generator| {
+            let mut generator = generator
+                .length(length)?
+                .format(format)
+                .alphabet(ALPHABETS[self.alphabet % ALPHABETS.len()])
+                .precision(PRECISIONS[self.precision % PRECISIONS.len()])
+                .random(self.is_random);
+            if let Some(&environment) = ENVIRONMENTS.get(self.environment % (ENVIRONMENTS.len() + 1)) {
+                generator = generator.environment(environment);
+            }
+            if format == Format::V5 {
+                generator = generator.bit_layout(layout)?;
+            }
+            generator.try_next()
+        });
+        // Settings that do not combine, e.g. a layout longer than the
+        // length, fall back to a plain identifier
+        configured.unwrap_or_else(|_| generator(self.machine_id).expect("the prefix is valid").next())
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Bcid {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let prefix_len = u.int_in_range(1..=8)?;
+        let recipe = Recipe {
+            prefix: (0..prefix_len).map(|_| u.arbitrary()).collect::<arbitrary::Result<_>>()?,
+            machine_id: u.arbitrary()?,
+            format: u.arbitrary()?,
+            alphabet: u.arbitrary()?,
+            precision: u.arbitrary()?,
+            environment: u.arbitrary()?,
+            bit_layout: u.arbitrary()?,
+            length: u.arbitrary()?,
+            is_random: u.arbitrary()?,
+            millis: u.arbitrary()?,
+            seed: u.arbitrary()?,
+        };
+        Ok(recipe.build())
+    }
+}
+
+#[cfg(feature = "proptest")]
+pub use self::proptest_strategies::{bcid, near_bcid};
+
+#[cfg(feature = "proptest")]
+mod proptest_strategies {
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    use super::Recipe;
+    use crate::Bcid;
+
+    /// Valid identifiers of every format, alphabet and length
+    ///
+    /// ```
+    /// use proptest::prelude::*;
+    ///
+    /// proptest!(|(id in bcid::strategy::bcid())| {
+    ///     let stored = id.to_string();
+    ///     prop_assert_eq!(bcid::Bcid::parse_with(&stored, id.alphabet())?, id);
+    /// });
+    /// ```
+    pub fn bcid() -> impl Strategy<Value = Bcid> {
+        let settings = (vec(any::<u8>(), 1..=8), any::<u16>(), any::<usize>(), any::<usize>(), any::<usize>(), any::<usize>());
+        let rest = (any::<(u8, u8, u8)>(), any::<usize>(), any::<bool>(), any::<i64>(), any::<u64>());
+        (settings, rest).prop_map(
+            |(
+                (prefix, machine_id, format, alphabet, precision, environment),
+                (bit_layout, length, is_random, millis, seed),
+            )| {
+                Recipe {
+                    prefix,
+                    machine_id,
+                    format,
+                    alphabet,
+                    precision,
+                    environment,
+                    bit_layout,
+                    length,
+                    is_random,
+                    millis,
+                    seed,
+                }
+                .build()
+            },
+        )
+    }
+
+    /// Valid identifiers with one edit: a character replaced, removed,
+    /// inserted or swapped with the next, a change of case, or truncation
+    ///
+    /// Replacement and inserted characters include the separator, other
+    /// punctuation, whitespace and non-ASCII characters. Some edits leave
+    /// the identifier valid, e.g. swapping two equal characters, so the
+    /// strategy suits checks that parsing never panics and that whatever it
+    /// accepts round-trips, rather than checks that every input is rejected.
+    ///
+    /// ```
+    /// use proptest::prelude::*;
+    ///
+    /// proptest!(|(input in bcid::strategy::near_bcid())| {
+    ///     if let Ok(id) = bcid::Bcid::parse(&input) {
+    ///         prop_assert_eq!(bcid::Bcid::parse(id.as_str())?, id);
+    ///     }
+    /// });
+    /// ```
+    pub fn near_bcid() -> impl Strategy<Value = String> {
+        let characters = prop_oneof![
+            proptest::char::range('0', 'z'),
+            Just('_'),
+            Just('-'),
+            Just(' '),
+            Just('\n'),
+            Just('é'),
+            Just('\u{0}'),
+            any::<char>(),
+        ];
+        (bcid(), 0..6usize, any::<usize>(), characters).prop_map(|(id, edit, position, character)| {
+            let mut chars: Vec<char> = id.as_str().chars().collect();
+            let position = position % chars.len();
+            match edit {
+                0 => chars[position] = character,
+                1 => {
+                    chars.remove(position);
+                }
+                2 => chars.insert(position, character),
+                3 if position + 1 < chars.len() => chars.swap(position, position + 1),
+                4 => chars[position] = flip_case(chars[position]),
+                _ => chars.truncate(position),
+            }
+            chars.into_iter().collect()
+        })
+    }
+
+    fn flip_case(c: char) -> char {
+        if c.is_ascii_lowercase() {
+            c.to_ascii_uppercase()
+        } else {
+            c.to_ascii_lowercase()
+        }
+    }
+}
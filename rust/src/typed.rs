@@ -0,0 +1,184 @@
+/// Define a newtype around [`Bcid`](crate::Bcid) for the identifiers of one
+/// entity
+///
+/// `define_bcid!(pub UserId, "USER")` defines `UserId`, which only holds
+/// identifiers with the `USER` prefix: parsing it (`FromStr`,
+/// `TryFrom<String>` and `TryFrom<Bcid>`) fails with
+/// [`UnexpectedPrefix`](crate::BcidError::UnexpectedPrefix) for any other.
+/// Since each entity gets its own type, passing a `UserId` where an `OrderId`
+/// is expected does not compile. The prefix is checked at compile time, as
+/// with [`Prefix::new`](crate::Prefix::new).
+///
+/// ```
+/// bcid::define_bcid!(pub UserId, "USER");
+/// bcid::define_bcid!(pub OrderId, "ORDR");
+///
+/// # #[cfg(feature = "std")] {
+/// let user = UserId::generate(1);
+/// let parsed: UserId = user.to_string().parse()?;
+/// assert_eq!(parsed, user);
+/// assert_eq!(parsed.as_bcid().machine_id(), 1);
+///
+/// // Identifiers of other entities are rejected
+/// let order = OrderId::generate(1);
+/// assert!(order.as_str().parse::<UserId>().is_err());
+/// # }
+/// # Ok::<(), bcid::BcidError>(())
+/// ```
+///
+/// `generate` and `generator` need the default `std` feature.
+///
+/// With the `serde` feature the type implements `Serialize` and
+/// `Deserialize` as its canonical string, and deserializing fails for
+/// identifiers with another prefix.
+#[macro_export]
+macro_rules! define_bcid {
+    ($(#[$meta:meta])* $vis:vis $name:ident, $prefix:expr) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        $vis struct $name($crate::Bcid);
+
+        // Fail the build for an invalid prefix even if `PREFIX` is unused
+        const _: $crate::Prefix = $crate::Prefix::new($prefix);
+
+        impl $name {
+            /// The prefix of every identifier of this type
+            pub const PREFIX: $crate::Prefix = $crate::Prefix::new($prefix);
+
+            /// The decoded identifier
+            pub fn as_bcid(&self) -> &$crate::Bcid {
+                &self.0
+            }
+
+            /// The canonical string form
+            pub fn as_str(&self) -> &str {
+                self.0.as_str()
+            }
+        }
+
+        impl ::core::convert::TryFrom<$crate::Bcid> for $name {
+            type Error = $crate::BcidError;
+
+            fn try_from(bcid: $crate::Bcid) -> ::core::result::Result<Self, Self::Error> {
+                if bcid.prefix() != Self::PREFIX.as_str() {
+                    return Err($crate::BcidError::UnexpectedPrefix {
+                        expected: Self::PREFIX.as_str(),
+                        found: bcid.prefix().to_string(),
+                    });
+                }
+                Ok($name(bcid))
+            }
+        }
+
+        impl ::core::convert::TryFrom<$crate::__private::String> for $name {
+            type Error = $crate::BcidError;
+
+            fn try_from(identifier: $crate::__private::String) -> ::core::result::Result<Self, Self::Error> {
+                <$name as ::core::convert::TryFrom<$crate::Bcid>>::try_from($crate::Bcid::try_from(identifier)?)
+            }
+        }
+
+        impl ::core::str::FromStr for $name {
+            type Err = $crate::BcidError;
+
+            fn from_str(identifier: &str) -> ::core::result::Result<Self, Self::Err> {
+                <$name as ::core::convert::TryFrom<$crate::Bcid>>::try_from($crate::Bcid::parse(identifier)?)
+            }
+        }
+
+        impl ::core::convert::From<$name> for $crate::Bcid {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl ::core::convert::From<$name> for $crate::__private::String {
+            fn from(id: $name) -> Self {
+                id.0.into()
+            }
+        }
+
+        impl ::core::convert::AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                self.0.as_str()
+            }
+        }
+
+        impl ::core::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                ::core::fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        $crate::__define_bcid_std!($name);
+        $crate::__define_bcid_serde!($name);
+    };
+}
+
+/// The constructors of a [`define_bcid!`] type that read the clock, chosen
+/// by the features of this crate rather than those of the crate expanding
+/// the macro
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_bcid_std {
+    ($name:ident) => {
+        impl $name {
+            /// A new chronological identifier for the current time
+            pub fn generate(machine_id: u16) -> Self {
+                let identifier = $crate::generate_identifier(Self::PREFIX.as_str(), machine_id, None, false)
+                    .expect("prefixes are valid");
+                $name($crate::Bcid::parse(&identifier).expect("generated identifiers are valid"))
+            }
+
+            /// A generator for identifiers of this type, to configure
+            /// builder-style; convert its output with `TryFrom<Bcid>`
+            pub fn generator(machine_id: u16) -> $crate::BcidGenerator {
+                $crate::BcidGenerator::from_prefix(Self::PREFIX, machine_id)
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_bcid_std {
+    ($name:ident) => {};
+}
+
+/// The serde impls of a [`define_bcid!`] type, chosen by the features of
+/// this crate rather than those of the crate expanding the macro
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_bcid_serde {
+    ($name:ident) => {
+        impl $crate::__private::serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+            where
+                S: $crate::__private::serde::Serializer,
+            {
+                $crate::__private::serde::Serialize::serialize(&self.0, serializer)
+            }
+        }
+
+        impl<'de> $crate::__private::serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+            where
+                D: $crate::__private::serde::Deserializer<'de>,
+            {
+                let bcid = <$crate::Bcid as $crate::__private::serde::Deserialize>::deserialize(deserializer)?;
+                <$name as ::core::convert::TryFrom<$crate::Bcid>>::try_from(bcid)
+                    .map_err(<D::Error as $crate::__private::serde::de::Error>::custom)
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_bcid_serde {
+    ($name:ident) => {};
+}
@@ -0,0 +1,57 @@
+//! TypeID rendering of chronological BCIDs
+//!
+//! A TypeID is a lowercase type prefix, an underscore and a UUIDv7 encoded
+//! as 26 lowercase Crockford base32 characters, e.g.
+//! `test_01jgjfgr4828t4059e8aqjb60j`. The suffix carries the same bits as
+//! [`Bcid::to_uuid7`], so the timestamp, machine ID, random value and format
+//! survive a round trip. The prefix is case-folded: it is written in
+//! lowercase and read back in uppercase.
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::ulid::encode_crockford;
+#[cfg(feature = "std")]
+use crate::ulid::decode_crockford;
+use crate::uuid::uuid7_bits;
+#[cfg(feature = "std")]
+use crate::uuid::from_uuid7_bits;
+use crate::{Bcid, BcidError};
+#[cfg(feature = "std")]
+use crate::validate_prefix;
+
+impl Bcid {
+    /// Render a chronological identifier as a TypeID
+    ///
+    /// Fails for random identifiers, which carry no timestamp, and for
+    /// prefixes that are not all ASCII letters.
+    pub fn to_typeid(&self) -> Result<String, BcidError> {
+        let prefix = self.prefix();
+        if !prefix.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return Err(BcidError::InvalidTypeId(format!("prefix '{}' is not all letters", prefix)));
+        }
+        let suffix = encode_crockford(uuid7_bits(self)?);
+        Ok(format!("{}_{}", prefix.to_ascii_lowercase(), suffix.to_ascii_lowercase()))
+    }
+
+    /// Build a chronological identifier from a TypeID with a 1 to 8-letter
+    /// prefix
+    ///
+    /// The prefix is uppercased, and the suffix must be a lowercase UUIDv7 as
+    /// the TypeID specification requires.
+    #[cfg(feature = "std")]
+    pub fn from_typeid(typeid: &str) -> Result<Bcid, BcidError> {
+        let (prefix, suffix) = typeid
+            .rsplit_once('_')
+            .ok_or_else(|| BcidError::InvalidTypeId(format!("'{}' has no '_' separator", typeid)))?;
+        if !prefix.bytes().all(|b| b.is_ascii_lowercase()) {
+            return Err(BcidError::InvalidTypeId(format!("prefix '{}' is not all lowercase letters", prefix)));
+        }
+        validate_prefix(prefix)?;
+        if suffix.bytes().any(|b| b.is_ascii_uppercase() || matches!(b, b'i' | b'l' | b'o' | b'u')) {
+            return Err(BcidError::InvalidTypeId(format!("suffix '{}' is not lowercase Crockford base32", suffix)));
+        }
+        let value = decode_crockford(suffix).map_err(BcidError::InvalidTypeId)?;
+        from_uuid7_bits(value, &prefix.to_ascii_uppercase())
+    }
+}
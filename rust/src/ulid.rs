@@ -0,0 +1,95 @@
+//! Conversion between chronological BCIDs and ULIDs
+//!
+//! A ULID is a 48-bit Unix timestamp in milliseconds followed by 80 bits of
+//! randomness, written as 26 Crockford base32 characters. BCIDs map onto it
+//! as:
+//!
+//! ```text
+//! unix_ts_ms (48) | machine_id (16) | random (16) | v2 flag (1) | hash (47)
+//! ```
+//!
+//! The timestamp, machine ID, random value and format survive a round trip;
+//! the prefix is not stored and the random padding is re-drawn. The low 47
+//! bits are a hash of the BCID, so the same BCID always maps to the same ULID.
+
+#[cfg(feature = "std")]
+use alloc::format;
+use alloc::string::String;
+
+#[cfg(feature = "std")]
+use crate::entropy::os_entropy;
+#[cfg(feature = "std")]
+use crate::{encode_chronological, pack_unix_millis, validate_prefix};
+use crate::{fnv1a, unix_millis, Bcid, BcidError, Format};
+
+/// Crockford's base32 alphabet used by ULIDs
+pub(crate) const CROCKFORD_BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Bits of the ULID below the v2 flag
+const HASH_BITS: u32 = 47;
+
+impl Bcid {
+    /// Map a chronological identifier onto a ULID
+    ///
+    /// Fails for random identifiers, which carry no timestamp, and for
+    /// timestamps before 1970.
+    pub fn to_ulid(&self) -> Result<String, BcidError> {
+        let unix_ms = unix_millis(self)? as u128;
+        let machine_id = self.machine_id() as u128;
+        let random = self.random().unwrap_or_default() as u128;
+        let is_v2 = (self.format() != Format::V1) as u128;
+
+        let value = unix_ms << 80
+            | machine_id << 64
+            | random << 48
+            | is_v2 << HASH_BITS
+            | (fnv1a(self.as_str()) & ((1 << HASH_BITS) - 1)) as u128;
+        Ok(encode_crockford(value))
+    }
+
+    /// Build a chronological identifier from a ULID
+    ///
+    /// Decoding is case-insensitive and accepts Crockford's `I`, `L` and `O`
+    /// aliases. The millisecond timestamp is truncated to BCID's 10ms
+    /// resolution; ULIDs not produced by [`Bcid::to_ulid`] yield an arbitrary
+    /// but deterministic machine ID and random value.
+    #[cfg(feature = "std")]
+    pub fn from_ulid(ulid: &str, prefix: &str) -> Result<Bcid, BcidError> {
+        validate_prefix(prefix)?;
+        let value = decode_crockford(ulid).map_err(BcidError::InvalidUlid)?;
+        let timestamp = pack_unix_millis((value >> 80) as u64)?;
+        let machine_id = (value >> 64) as u16;
+        let random = (value >> 48) as u16;
+        let format = if (value >> HASH_BITS) & 1 == 1 { Format::V2 } else { Format::V1 };
+
+        Bcid::parse(&encode_chronological(format, prefix, timestamp, machine_id, random, &mut os_entropy()))
+    }
+}
+
+/// Encode 128 bits as 26 uppercase Crockford base32 characters
+pub(crate) fn encode_crockford(value: u128) -> String {
+    (0..26).rev().map(|i| CROCKFORD_BASE32[((value >> (i * 5)) & 0x1f) as usize] as char).collect()
+}
+
+/// Decode 26 Crockford base32 characters into 128 bits
+#[cfg(feature = "std")]
+pub(crate) fn decode_crockford(encoded: &str) -> Result<u128, String> {
+    if encoded.len() != 26 {
+        return Err(format!("'{}' is not 26 characters long", encoded));
+    }
+    encoded.chars().enumerate().try_fold(0u128, |value, (position, c)| {
+        let digit = match c.to_ascii_uppercase() {
+            'I' | 'L' => 1,
+            'O' => 0,
+            upper => CROCKFORD_BASE32
+                .iter()
+                .position(|&b| b as char == upper)
+                .ok_or_else(|| format!("invalid character {:?} at position {}", c, position))?,
+        };
+        // The first character only holds 3 bits, so values top out at 7ZZZ...
+        if position == 0 && digit > 7 {
+            return Err(format!("'{}' overflows 128 bits", encoded));
+        }
+        Ok(value << 5 | digit as u128)
+    })
+}
@@ -0,0 +1,143 @@
+//! Conversion between chronological BCIDs and UUIDv7
+//!
+//! A UUIDv7 holds a 48-bit Unix timestamp in milliseconds followed by 74
+//! bits of free data (RFC 9562). BCIDs map onto it as:
+//!
+//! ```text
+//! unix_ts_ms (48) | ver 7 (4) | machine_id[15..4] (12) | var 10 (2)
+//!     | machine_id[3..0] (4) | random (16) | v2 flag (1) | hash (41)
+//! ```
+//!
+//! The timestamp, machine ID, random value and format survive a round trip;
+//! the prefix is not stored and the random padding is re-drawn. The low 41
+//! bits are a hash of the BCID, so the same BCID always maps to the same UUID.
+//!
+//! The same 128 bits, big-endian, are the binary form of an identifier
+//! ([`Bcid::to_bytes`]); compared as bytes or as `u128`s they order by
+//! timestamp, then machine ID, then random value.
+
+use alloc::format;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::string::ToString;
+
+#[cfg(feature = "std")]
+use crate::entropy::os_entropy;
+#[cfg(feature = "std")]
+use crate::{encode_chronological, pack_unix_millis, validate_prefix};
+use crate::{fnv1a, unix_millis, Bcid, BcidError, Format};
+
+/// Bits of the UUID below the v2 flag
+const HASH_BITS: u32 = 41;
+
+impl Bcid {
+    /// Map a chronological identifier onto a UUIDv7, rendered in the
+    /// lowercase hyphenated form
+    ///
+    /// Fails for random identifiers, which carry no timestamp, and for
+    /// timestamps before 1970.
+    pub fn to_uuid7(&self) -> Result<String, BcidError> {
+        uuid7_bits(self).map(format_uuid)
+    }
+
+    /// Build a chronological identifier from a UUIDv7
+    ///
+    /// Accepts the hyphenated or plain 32-digit hexadecimal form. The
+    /// millisecond timestamp is truncated to BCID's 10ms resolution; UUIDs
+    /// not produced by [`Bcid::to_uuid7`] yield an arbitrary but
+    /// deterministic machine ID and random value.
+    #[cfg(feature = "std")]
+    pub fn from_uuid7(uuid: &str, prefix: &str) -> Result<Bcid, BcidError> {
+        validate_prefix(prefix)?;
+        from_uuid7_bits(parse_uuid(uuid)?, prefix)
+    }
+
+    /// The 16-byte binary form of a chronological identifier, for binary
+    /// database columns: the bits of [`Bcid::to_uuid7`], big-endian
+    ///
+    /// Byte arrays (and their `u128::from_be_bytes` values) compare by
+    /// timestamp, then machine ID, then random value. Fails for random
+    /// identifiers and timestamps before 1970, like [`Bcid::to_uuid7`].
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// let first = bcid::Bcid::parse(&bcid::generate_identifier("TEST", 1, Some("2024-01-01"), false)?)?;
+    /// let second = bcid::Bcid::parse(&bcid::generate_identifier("TEST", 1, Some("2024-01-02"), false)?)?;
+    /// assert!(first.to_bytes()? < second.to_bytes()?);
+    ///
+    /// let restored = bcid::Bcid::from_bytes(first.to_bytes()?, "TEST")?;
+    /// assert_eq!(restored.timestamp(), first.timestamp());
+    /// assert_eq!(restored.random(), first.random());
+    /// # }
+    /// # Ok::<(), bcid::BcidError>(())
+    /// ```
+    pub fn to_bytes(&self) -> Result<[u8; 16], BcidError> {
+        uuid7_bits(self).map(u128::to_be_bytes)
+    }
+
+    /// Build a chronological identifier from its binary form (see
+    /// [`Bcid::to_bytes`])
+    ///
+    /// As with [`Bcid::from_uuid7`], the prefix is not stored and the random
+    /// padding is re-drawn.
+    #[cfg(feature = "std")]
+    pub fn from_bytes(bytes: [u8; 16], prefix: &str) -> Result<Bcid, BcidError> {
+        validate_prefix(prefix)?;
+        from_uuid7_bits(u128::from_be_bytes(bytes), prefix)
+    }
+}
+
+/// The 128 bits of the UUIDv7 a chronological identifier maps to
+pub(crate) fn uuid7_bits(bcid: &Bcid) -> Result<u128, BcidError> {
+    let unix_ms = unix_millis(bcid)?;
+    let random = bcid.random().unwrap_or_default();
+    let machine_id = bcid.machine_id() as u64;
+    let is_v2 = (bcid.format() != Format::V1) as u64;
+
+    let high = unix_ms << 16 | 0x7 << 12 | machine_id >> 4;
+    let low = 0b10 << 62
+        | (machine_id & 0xf) << 58
+        | (random as u64) << 42
+        | is_v2 << HASH_BITS
+        | fnv1a(bcid.as_str()) & ((1 << HASH_BITS) - 1);
+    Ok(((high as u128) << 64) | low as u128)
+}
+
+/// Build a chronological identifier from the 128 bits of a UUIDv7
+#[cfg(feature = "std")]
+pub(crate) fn from_uuid7_bits(value: u128, prefix: &str) -> Result<Bcid, BcidError> {
+    let (high, low) = ((value >> 64) as u64, value as u64);
+    if (high >> 12) & 0xf != 7 {
+        return Err(BcidError::InvalidUuid(format!("version {} is not 7", (high >> 12) & 0xf)));
+    }
+    if low >> 62 != 0b10 {
+        return Err(BcidError::InvalidUuid("not an RFC 9562 variant".to_string()));
+    }
+
+    let timestamp = pack_unix_millis(high >> 16)?;
+    let machine_id = ((high & 0xfff) << 4 | (low >> 58) & 0xf) as u16;
+    let random = (low >> 42) as u16;
+    let format = if (low >> HASH_BITS) & 1 == 1 { Format::V2 } else { Format::V1 };
+
+    Bcid::parse(&encode_chronological(format, prefix, timestamp, machine_id, random, &mut os_entropy()))
+}
+
+/// Render a 128-bit value as `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`
+fn format_uuid(value: u128) -> String {
+    let hex = format!("{:032x}", value);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// Parse a hyphenated or plain hexadecimal UUID
+#[cfg(feature = "std")]
+fn parse_uuid(uuid: &str) -> Result<u128, BcidError> {
+    let hex: String = match uuid.len() {
+        36 if [8, 13, 18, 23].iter().all(|&i| uuid.as_bytes()[i] == b'-') => uuid.replace('-', ""),
+        32 => uuid.to_string(),
+        _ => return Err(BcidError::InvalidUuid(format!("'{}' is not 32 hexadecimal digits", uuid))),
+    };
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(BcidError::InvalidUuid(format!("'{}' is not 32 hexadecimal digits", uuid)));
+    }
+    u128::from_str_radix(&hex, 16).map_err(|_| BcidError::InvalidUuid(format!("'{}' is not 32 hexadecimal digits", uuid)))
+}
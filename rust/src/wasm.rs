@@ -0,0 +1,46 @@
+//! JavaScript interface, built with the `wasm` feature
+//!
+//! `generate` and `decode` are exported with `wasm-bindgen` for browsers
+//! and other JavaScript hosts, so client-side code mints identifiers the
+//! server decodes like its own. In `wasm32-unknown-unknown` builds the
+//! clock is JavaScript's `Date` and randomness comes from
+//! `crypto.getRandomValues`. Failures throw an `Error` with the message of
+//! the [`BcidError`](crate::BcidError).
+
+use wasm_bindgen::prelude::*;
+
+use crate::{Bcid, BcidGenerator, Format};
+
+/// Generate an identifier with `prefix` and `machine_id` for the current
+/// time: v1 unless `sortable` selects the time-sortable v2 layout, or fully
+/// random if `random`
+#[wasm_bindgen]
+pub fn generate(prefix: &str, machine_id: u16, sortable: Option<bool>, random: Option<bool>) -> Result<String, JsError> {
+    let format = if sortable.unwrap_or(false) { Format::V2 } else { Format::V1 };
+    let mut generator = BcidGenerator::new(prefix, machine_id)?.format(format).random(random.unwrap_or(false));
+    Ok(generator.try_next()?.into())
+}
+
+/// Decode an identifier into an object with the fields of
+/// [`Bcid::to_json`] (`prefix`, `type`, `format`, `timestamp`,
+/// `iso_datetime`, `machine_id`, ...)
+#[wasm_bindgen]
+pub fn decode(identifier: &str) -> Result<JsValue, JsError> {
+    let bcid = Bcid::parse(identifier)?;
+    js_sys::JSON::parse(&bcid.to_json()).map_err(|_| JsError::new("decoded identifier is not valid JSON"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IdentifierType;
+
+    // Errors and `decode` build JavaScript values, which only exist in wasm
+    #[test]
+    fn generated_identifiers_decode() {
+        let sortable = Bcid::parse(&generate("USER", 7, Some(true), None).unwrap()).unwrap();
+        assert_eq!((sortable.format(), sortable.machine_id()), (Format::V2, 7));
+        let random = Bcid::parse(&generate("USER", 7, None, Some(true)).unwrap()).unwrap();
+        assert_eq!(random.identifier_type(), IdentifierType::Random);
+    }
+}